@@ -0,0 +1,437 @@
+use std::collections::HashMap;
+use std::fs;
+use std::io::{self, Read, Write};
+use std::path::{Path, PathBuf};
+use std::process::ExitCode;
+use clap::{Parser, Subcommand, ValueEnum};
+use serde_json::{json, Value};
+use parser::diff::diff;
+use parser::merge::merge;
+use parser::patch::{apply_patch, Patch};
+use parser::units::Quantity;
+use parser::validation::{validate, Severity};
+use parser::writer::write_inp;
+use parser::INP;
+
+/// Inspect, validate and convert EPANET input files.
+#[derive(Parser)]
+#[command(name = "inp", version)]
+struct Cli {
+    #[command(subcommand)]
+    command: Command,
+}
+
+#[derive(Subcommand)]
+enum Command {
+    /// Element counts, pipe length by diameter and options.
+    Summary {
+        /// Model to read, standard input when missing or `-`.
+        file: Option<PathBuf>,
+        #[arg(long, value_enum)]
+        from: Option<Format>,
+        #[arg(long)]
+        json: bool,
+    },
+    /// Diagnostics of the model, exits with 1 when there are errors.
+    Validate {
+        file: Option<PathBuf>,
+        #[arg(long, value_enum)]
+        from: Option<Format>,
+        #[arg(long)]
+        json: bool,
+    },
+    /// Writes the model in another format.
+    Convert {
+        file: Option<PathBuf>,
+        #[arg(long, value_enum)]
+        from: Option<Format>,
+        #[arg(long, value_enum)]
+        to: Format,
+        /// File to write, standard output when missing. Shapefiles are written to a directory.
+        #[arg(short, long)]
+        output: Option<PathBuf>,
+    },
+    /// Elements added, removed, renamed or modified between two models. Exits with 1 when they differ.
+    Diff {
+        old: PathBuf,
+        new: PathBuf,
+        #[arg(long)]
+        json: bool,
+        /// Compare the formatted lines, section by section, instead of the elements.
+        #[arg(long, conflicts_with = "json")]
+        lines: bool,
+    },
+    /// Three-way merge of two models edited from `base`. Conflicts keep our side, are
+    /// reported on standard error and exit with 1, as git expects from a merge driver.
+    Merge {
+        base: PathBuf,
+        ours: PathBuf,
+        theirs: PathBuf,
+        /// File to write, standard output when missing.
+        #[arg(short, long)]
+        output: Option<PathBuf>,
+        /// Report the conflicts as JSON.
+        #[arg(long)]
+        json: bool,
+    },
+    /// Applies a JSON patch to the model. Nothing is written when an operation fails.
+    Patch {
+        file: Option<PathBuf>,
+        /// Patch to apply, see `parser::patch::Operation` for its operations.
+        #[arg(long)]
+        patch: PathBuf,
+        /// File to write, standard output when missing.
+        #[arg(short, long)]
+        output: Option<PathBuf>,
+    },
+    /// Rewrites the model with the sections in order and the fields in columns.
+    Fmt {
+        file: Option<PathBuf>,
+        /// Replace the file instead of writing to standard output.
+        #[arg(short, long)]
+        write: bool,
+        /// Only check whether the file is formatted, exiting with 1 when it isn't.
+        #[arg(long)]
+        check: bool,
+    },
+}
+
+#[derive(Clone, Copy, PartialEq, Debug, ValueEnum)]
+enum Format {
+    Inp,
+    Json,
+    Geojson,
+    Gpkg,
+    Shp,
+    Svg,
+    Png,
+}
+
+impl Format {
+    fn of_path(path: &Path) -> Format {
+        match path.extension().and_then(|extension| extension.to_str()).map(|extension| extension.to_lowercase()).as_deref() {
+            Some("json") => Format::Json,
+            Some("geojson") => Format::Geojson,
+            Some("gpkg") => Format::Gpkg,
+            Some("shp") => Format::Shp,
+            Some("svg") => Format::Svg,
+            Some("png") => Format::Png,
+            _ => Format::Inp,
+        }
+    }
+}
+
+fn is_stdin(file: &Option<PathBuf>) -> bool {
+    file.as_deref().is_none_or(|file| file == Path::new("-"))
+}
+
+fn read_text(file: &Option<PathBuf>) -> Result<String, String> {
+    match file {
+        Some(path) if !is_stdin(file) => fs::read_to_string(path).map_err(|error| format!("{}: {}", path.display(), error)),
+        _ => {
+            let mut text = String::new();
+            io::stdin().read_to_string(&mut text).map_err(|error| error.to_string())?;
+            Ok(text)
+        },
+    }
+}
+
+/// Reads a model in the format of `from`, or of the extension of the file.
+fn read_model(file: &Option<PathBuf>, from: Option<Format>) -> Result<INP, String> {
+    let format = from.unwrap_or_else(|| file.as_deref().filter(|_| !is_stdin(file)).map(Format::of_path).unwrap_or(Format::Inp));
+    match format {
+        Format::Inp => Ok(INP::read(read_text(file)?)),
+        Format::Json => serde_json::from_str(&read_text(file)?).map_err(|error| error.to_string()),
+        Format::Geojson => parser::geojson::from_geojson(&read_text(file)?, &Default::default()).map_err(|error| error.message),
+        Format::Gpkg => match file {
+            Some(path) if !is_stdin(file) => parser::geopackage::read_geopackage(path).map_err(|error| error.message),
+            _ => Err("GeoPackages can't be read from standard input".to_string()),
+        },
+        Format::Shp | Format::Svg | Format::Png => Err(format!("Models can't be read from {:?}", format).to_lowercase()),
+    }
+}
+
+fn write_output(output: &Option<PathBuf>, content: &[u8]) -> Result<(), String> {
+    match output {
+        Some(path) => fs::write(path, content).map_err(|error| format!("{}: {}", path.display(), error)),
+        // Output piped to a command that stops reading early, like `head`, is fine.
+        None => match io::stdout().write_all(content) {
+            Err(error) if error.kind() == io::ErrorKind::BrokenPipe => Ok(()),
+            result => result.map_err(|error| error.to_string()),
+        },
+    }
+}
+
+fn summary(inp: &INP) -> Value {
+    let flow_units = inp.flow_units();
+    let length_unit = Quantity::Length.unit(flow_units, inp.headloss_formula());
+    let distinct = |ids: Vec<&String>| ids.into_iter().collect::<std::collections::HashSet<_>>().len();
+
+    let mut lengths: HashMap<u64, (f64, f64, usize)> = HashMap::new();
+    for pipe in inp.pipes.iter() {
+        let entry = lengths.entry(pipe.diameter.to_bits()).or_insert((pipe.diameter, 0.0, 0));
+        entry.1 += pipe.length;
+        entry.2 += 1;
+    }
+    let mut lengths = lengths.into_values().collect::<Vec<(f64, f64, usize)>>();
+    lengths.sort_by(|a, b| a.0.total_cmp(&b.0));
+
+    json!({
+        "title": inp.title,
+        "counts": {
+            "junctions": inp.junctions.len(),
+            "reservoirs": inp.reservoirs.len(),
+            "tanks": inp.tanks.len(),
+            "pipes": inp.pipes.len(),
+            "pumps": inp.pumps.len(),
+            "valves": inp.valves.len(),
+            "patterns": distinct(inp.patterns.iter().map(|pattern| &pattern.id).collect()),
+            "curves": distinct(inp.curves.iter().map(|curve| &curve.id).collect()),
+            "controls": inp.controls.len(),
+            "rules": inp.rules().map(|rules| rules.len()).unwrap_or(0),
+            "errors": inp.errors.len(),
+        },
+        "length_unit": length_unit,
+        "diameter_unit": Quantity::Diameter.unit(flow_units, inp.headloss_formula()),
+        "pipe_length": inp.pipes.iter().map(|pipe| pipe.length).sum::<f64>(),
+        "pipe_length_by_diameter": lengths.iter()
+            .map(|(diameter, length, count)| json!({ "diameter": diameter, "length": length, "pipes": count }))
+            .collect::<Vec<Value>>(),
+        "options": inp.options.iter().map(|setting| (setting.key.clone(), Value::from(setting.value.clone()))).collect::<serde_json::Map<String, Value>>(),
+    })
+}
+
+fn format_summary(summary: &Value) -> String {
+    let mut text = String::new();
+    if let Some(title) = summary["title"].as_str().filter(|title| !title.is_empty()) {
+        text.push_str(&format!("{}\n\n", title));
+    }
+    for name in ["junctions", "reservoirs", "tanks", "pipes", "pumps", "valves", "patterns", "curves", "controls", "rules", "errors"] {
+        text.push_str(&format!("{:<12}{:>10}\n", name, summary["counts"][name].as_u64().unwrap_or_default()));
+    }
+    let unit = |name: &str| summary[name].as_str().unwrap_or_default().to_string();
+    text.push_str(&format!("\nPipe length ({}) by diameter ({})\n", unit("length_unit"), unit("diameter_unit")));
+    for row in summary["pipe_length_by_diameter"].as_array().unwrap() {
+        let (diameter, length) = (row["diameter"].as_f64().unwrap_or_default(), row["length"].as_f64().unwrap_or_default());
+        text.push_str(&format!("{:>12}{:>14.1}{:>8} pipes\n", diameter, length, row["pipes"].as_u64().unwrap_or_default()));
+    }
+    text.push_str(&format!("{:>12}{:>14.1}\n", "Total", summary["pipe_length"].as_f64().unwrap_or_default()));
+    text.push_str("\nOptions\n");
+    for (key, value) in summary["options"].as_object().unwrap() {
+        text.push_str(&format!("{:<24}{}\n", key, value.as_str().unwrap_or_default()));
+    }
+    text
+}
+
+/// Lines only in `old` and lines only in `new` of the formatted models, under their section.
+fn line_diff(old: &str, new: &str) -> Vec<(String, char, String)> {
+    let sections = |text: &str| {
+        let mut lines: Vec<(String, String)> = Vec::new();
+        let mut section = String::new();
+        for line in text.lines().filter(|line| !line.trim().is_empty() && !line.starts_with(';')) {
+            if line.starts_with('[') {
+                section = line.to_string();
+            } else {
+                lines.push((section.clone(), line.to_string()));
+            }
+        }
+        lines
+    };
+    let (old, new) = (sections(old), sections(new));
+
+    let mut counts: HashMap<&(String, String), i64> = HashMap::new();
+    for line in old.iter() {
+        *counts.entry(line).or_default() += 1;
+    }
+    for line in new.iter() {
+        *counts.entry(line).or_default() -= 1;
+    }
+
+    let mut changes = Vec::new();
+    let mut take = |lines: &Vec<(String, String)>, sign: char, wanted: i64| {
+        for line in lines.iter() {
+            let count = counts.get_mut(line).unwrap();
+            if *count * wanted > 0 {
+                *count -= wanted;
+                changes.push((line.0.clone(), sign, line.1.clone()));
+            }
+        }
+    };
+    take(&old, '-', 1);
+    take(&new, '+', -1);
+    changes.sort_by_key(|(section, sign, _)| (old.iter().chain(new.iter()).position(|(s, _)| s == section), *sign == '+'));
+    changes
+}
+
+fn run(cli: Cli) -> Result<ExitCode, String> {
+    match cli.command {
+        Command::Summary { file, from, json } => {
+            let summary = summary(&read_model(&file, from)?);
+            let text = if json { format!("{}\n", serde_json::to_string_pretty(&summary).unwrap()) } else { format_summary(&summary) };
+            write_output(&None, text.as_bytes())?;
+            Ok(ExitCode::SUCCESS)
+        },
+        Command::Validate { file, from, json } => {
+            let validation = validate(&read_model(&file, from)?);
+            let mut text = String::new();
+            if json {
+                text.push_str(&format!("{}\n", serde_json::to_string_pretty(&validation.to_json()).unwrap()));
+            } else {
+                for diagnostic in validation.diagnostics.iter() {
+                    let severity = if diagnostic.severity == Severity::Error { "error" } else { "warning" };
+                    text.push_str(&match (diagnostic.line, diagnostic.section.is_empty()) {
+                        (Some(line), _) => format!("{}: line {}: {}\n", severity, line, diagnostic.message),
+                        (None, false) => format!("{}: [{}] {}\n", severity, diagnostic.section, diagnostic.message),
+                        (None, true) => format!("{}: {}\n", severity, diagnostic.message),
+                    });
+                }
+                text.push_str(&format!("{} errors, {} warnings\n", validation.errors().count(), validation.warnings().count()));
+            }
+            write_output(&None, text.as_bytes())?;
+            Ok(if validation.has_errors() { ExitCode::FAILURE } else { ExitCode::SUCCESS })
+        },
+        Command::Convert { file, from, to, output } => {
+            let inp = read_model(&file, from)?;
+            let render = parser::render::RenderOptions::default();
+            match to {
+                Format::Inp => write_output(&output, write_inp(&inp).as_bytes())?,
+                Format::Json => write_output(&output, serde_json::to_string_pretty(&inp).unwrap().as_bytes())?,
+                Format::Geojson => write_output(&output, parser::geojson::to_geojson(&inp).as_bytes())?,
+                Format::Svg => write_output(&output, parser::render::render_svg(&inp, &render).map_err(|error| error.message)?.as_bytes())?,
+                Format::Png => write_output(&output, &parser::render::render_png(&inp, &render).map_err(|error| error.message)?)?,
+                Format::Gpkg => {
+                    let path = output.ok_or("GeoPackages need an output file")?;
+                    parser::geopackage::write_geopackage(&inp, &path).map_err(|error| error.message)?;
+                },
+                Format::Shp => {
+                    let directory = output.ok_or("Shapefiles need an output directory")?;
+                    fs::create_dir_all(&directory).map_err(|error| error.to_string())?;
+                    parser::shapefile::write_shapefiles(&inp, &directory).map_err(|error| error.to_string())?;
+                },
+            }
+            Ok(ExitCode::SUCCESS)
+        },
+        Command::Diff { old, new, json, lines: false } => {
+            let diff = diff(&read_model(&Some(old), None)?, &read_model(&Some(new), None)?);
+            let text = if json { format!("{}\n", serde_json::to_string_pretty(&diff.to_json()).unwrap()) } else { diff.report() };
+            write_output(&None, text.as_bytes())?;
+            Ok(if diff.is_empty() { ExitCode::SUCCESS } else { ExitCode::FAILURE })
+        },
+        Command::Diff { old, new, lines: true, .. } => {
+            let old_text = write_inp(&read_model(&Some(old), None)?);
+            let new_text = write_inp(&read_model(&Some(new), None)?);
+            let changes = line_diff(&old_text, &new_text);
+            let (mut text, mut section) = (String::new(), "");
+            for (line_section, sign, line) in changes.iter() {
+                if line_section != section {
+                    text.push_str(&format!("{}\n", line_section));
+                    section = line_section;
+                }
+                text.push_str(&format!("{}{}\n", sign, line));
+            }
+            write_output(&None, text.as_bytes())?;
+            Ok(if changes.is_empty() { ExitCode::SUCCESS } else { ExitCode::FAILURE })
+        },
+        Command::Merge { base, ours, theirs, output, json } => {
+            let mut models = Vec::new();
+            for path in [base, ours, theirs] {
+                let inp = read_model(&Some(path.clone()), None)?;
+                if let Some(error) = inp.errors.first() {
+                    return Err(format!("{}: line {}: {}, merging would drop it", path.display(), error.line_number, error.message));
+                }
+                models.push(inp);
+            }
+            let merge = merge(&models[0], &models[1], &models[2]).map_err(|error| error.message)?;
+            write_output(&output, write_inp(&merge.inp).as_bytes())?;
+            if json {
+                eprintln!("{}", serde_json::to_string_pretty(&merge.to_json()).unwrap());
+            } else {
+                eprint!("{}", merge.report());
+            }
+            Ok(if merge.has_conflicts() { ExitCode::FAILURE } else { ExitCode::SUCCESS })
+        },
+        Command::Patch { file, patch, output } => {
+            let mut inp = read_model(&file, None)?;
+            let text = fs::read_to_string(&patch).map_err(|error| format!("{}: {}", patch.display(), error))?;
+            let patch = Patch::from_json(&text).map_err(|error| format!("{}: {}", patch.display(), error))?;
+            apply_patch(&mut inp, &patch).map_err(|error| error.message)?;
+            write_output(&output, write_inp(&inp).as_bytes())?;
+            Ok(ExitCode::SUCCESS)
+        },
+        Command::Fmt { file, write, check } => {
+            let text = read_text(&file)?;
+            let inp = INP::read(text.clone());
+            if let Some(error) = inp.errors.first() {
+                return Err(format!("line {}: {}, formatting would drop it", error.line_number, error.message));
+            }
+            let formatted = write_inp(&inp);
+            if check {
+                return Ok(if formatted == text { ExitCode::SUCCESS } else { ExitCode::FAILURE });
+            }
+            match file {
+                Some(path) if write && !is_stdin(&Some(path.clone())) => write_output(&Some(path), formatted.as_bytes())?,
+                _ => write_output(&None, formatted.as_bytes())?,
+            }
+            Ok(ExitCode::SUCCESS)
+        },
+    }
+}
+
+fn main() -> ExitCode {
+    match run(Cli::parse()) {
+        Ok(code) => code,
+        Err(message) => {
+            eprintln!("inp: {}", message);
+            ExitCode::from(2)
+        },
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::fs;
+    use std::path::Path;
+    use super::{line_diff, summary, Format};
+    use parser::writer::write_inp;
+    use parser::INP;
+
+    #[test]
+    fn summarize_a_model() {
+        let inp = INP::read(fs::read_to_string("../parser/tests/MagneticIslandEnhanced.inp").unwrap());
+
+        let summary = summary(&inp);
+
+        assert_eq!(summary["counts"]["pipes"], 1648);
+        assert_eq!(summary["counts"]["patterns"], inp.patterns.iter().map(|pattern| &pattern.id).collect::<std::collections::HashSet<_>>().len());
+        assert_eq!(summary["length_unit"], "m");
+        let by_diameter = summary["pipe_length_by_diameter"].as_array().unwrap();
+        assert!(by_diameter.windows(2).all(|pair| pair[0]["diameter"].as_f64() < pair[1]["diameter"].as_f64()));
+        let total = by_diameter.iter().map(|row| row["length"].as_f64().unwrap()).sum::<f64>();
+        assert!((total - summary["pipe_length"].as_f64().unwrap()).abs() < 1e-6);
+        assert_eq!(summary["options"]["UNITS"], "LPS");
+    }
+
+    #[test]
+    fn diff_lines_by_section() {
+        let old = write_inp(&INP::read("[JUNCTIONS]\nJ1 10\nJ2 20\n[PIPES]\nP1 J1 J2 100 150 100\n".to_string()));
+        let new = write_inp(&INP::read("[JUNCTIONS]\nJ2 20\nJ1 10\nJ3 5\n[PIPES]\nP1 J1 J2 100 200 100\n".to_string()));
+
+        let changes = line_diff(&old, &new);
+
+        let changes = changes.iter().map(|(section, sign, line)| (section.as_str(), *sign, line.split_whitespace().take(5).collect::<Vec<&str>>().join(" "))).collect::<Vec<_>>();
+        assert_eq!(changes, vec![
+            ("[JUNCTIONS]", '+', "J3 5".to_string()),
+            ("[PIPES]", '-', "P1 J1 J2 100 150".to_string()),
+            ("[PIPES]", '+', "P1 J1 J2 100 200".to_string()),
+        ]);
+        assert!(line_diff(&old, &old).is_empty());
+    }
+
+    #[test]
+    fn guess_formats_from_extensions() {
+        assert_eq!(Format::of_path(Path::new("net.inp")), Format::Inp);
+        assert_eq!(Format::of_path(Path::new("net.GeoJSON")), Format::Geojson);
+        assert_eq!(Format::of_path(Path::new("net.gpkg")), Format::Gpkg);
+        assert_eq!(Format::of_path(Path::new("net")), Format::Inp);
+    }
+}
@@ -19,3 +19,77 @@ pub fn deserialize_inp(content: String) -> JsValue {
     serde_wasm_bindgen::to_value(&INP::read(content)).unwrap()
 }
 
+
+#[wasm_bindgen]
+pub fn solve_inp(content: String) -> Result<JsValue, JsValue> {
+    let results = parser::hydraulics::solve(&INP::read(content)).map_err(|error| JsValue::from_str(&error.message))?;
+    Ok(serde_wasm_bindgen::to_value(&results).unwrap())
+}
+
+#[wasm_bindgen]
+pub fn simulate_inp(content: String) -> Result<JsValue, JsValue> {
+    let simulation = parser::hydraulics::simulate(&INP::read(content)).map_err(|error| JsValue::from_str(&error.message))?;
+    Ok(serde_wasm_bindgen::to_value(&simulation).unwrap())
+}
+
+#[wasm_bindgen]
+pub fn simulate_quality_inp(content: String) -> Result<JsValue, JsValue> {
+    let inp = INP::read(content);
+    let analysis = parser::quality::QualityAnalysis::from_inp(&inp).ok_or_else(|| JsValue::from_str("No water quality analysis in the options"))?;
+    let quality = parser::quality::simulate_quality(&inp, &analysis).map_err(|error| JsValue::from_str(&error.message))?;
+    Ok(serde_wasm_bindgen::to_value(&quality).unwrap())
+}
+
+#[wasm_bindgen]
+pub fn energy_report_inp(content: String) -> Result<JsValue, JsValue> {
+    let report = parser::energy::energy_report(&INP::read(content)).map_err(|error| JsValue::from_str(&error.message))?;
+    Ok(serde_wasm_bindgen::to_value(&report).unwrap())
+}
+
+#[wasm_bindgen]
+pub fn read_out_period(content: Vec<u8>, period: usize) -> Result<JsValue, JsValue> {
+    let mut out = parser::output::OutFile::new(std::io::Cursor::new(content)).map_err(|error| JsValue::from_str(&error.message))?;
+    let results = out.period(period).map_err(|error| JsValue::from_str(&error.message))?;
+    Ok(serde_wasm_bindgen::to_value(&results).unwrap())
+}
+
+#[wasm_bindgen]
+pub fn geojson_inp(content: String) -> String {
+    parser::geojson::to_geojson(&INP::read(content))
+}
+
+#[wasm_bindgen]
+pub fn reproject_inp(content: String, from: String, to: String) -> Result<JsValue, JsValue> {
+    let mut inp = INP::read(content);
+    parser::crs::reproject(&mut inp, &from, &to).map_err(|error| JsValue::from_str(&error.message))?;
+    Ok(serde_wasm_bindgen::to_value(&inp).unwrap())
+}
+
+#[wasm_bindgen]
+pub fn svg_inp(content: String, width: u32, height: u32) -> Result<String, JsValue> {
+    let options = parser::render::RenderOptions { width, height, ..Default::default() };
+    parser::render::render_svg(&INP::read(content), &options).map_err(|error| JsValue::from_str(&error.message))
+}
+
+#[wasm_bindgen]
+pub fn format_inp(content: String) -> String {
+    parser::writer::write_inp(&INP::read(content))
+}
+
+#[wasm_bindgen]
+pub fn validate_inp(content: String) -> JsValue {
+    serde_wasm_bindgen::to_value(&parser::validation::validate(&INP::read(content))).unwrap()
+}
+
+#[wasm_bindgen]
+pub fn diff_inp(old: String, new: String) -> JsValue {
+    serde_wasm_bindgen::to_value(&parser::diff::diff(&INP::read(old), &INP::read(new))).unwrap()
+}
+
+#[wasm_bindgen]
+pub fn patch_inp(content: String, patch: String) -> Result<String, JsValue> {
+    let mut inp = INP::read(content);
+    let patch = parser::patch::Patch::from_json(&patch).map_err(|error| JsValue::from_str(&error.message))?;
+    parser::patch::apply_patch(&mut inp, &patch).map_err(|error| JsValue::from_str(&error.message))?;
+    Ok(parser::writer::write_inp(&inp))
+}
@@ -0,0 +1,143 @@
+use serde::{Serialize, Deserialize};
+use crate::INP;
+use crate::network::{DanglingLink, Network, NodeKind, Traversal};
+
+#[derive(Serialize, Deserialize, Debug, PartialEq, Clone, Copy, Default)]
+pub struct ConnectivityOptions {
+    /// Ignore the links closed by `Pipe::status` or by the [STATUS] section.
+    pub respect_initial_status: bool,
+    /// Only follow pumps and check valves from their start to their end node.
+    pub directed_check_valves: bool,
+}
+
+#[derive(Serialize, Deserialize, Debug, PartialEq)]
+pub struct ConnectivityReport {
+    /// Node ids of every connected component, the biggest one first.
+    pub components: Vec<Vec<String>>,
+    /// Nodes that can't be reached from any reservoir or tank.
+    pub unsupplied_nodes: Vec<String>,
+    /// Junctions connected to a single link.
+    pub dead_ends: Vec<String>,
+    /// Links whose start or end node doesn't exist, left out of the analysis.
+    pub dangling_links: Vec<DanglingLink>,
+}
+
+pub fn analyze_connectivity(inp: &INP, options: &ConnectivityOptions) -> ConnectivityReport {
+    let network = Network::new(inp);
+    let undirected = Traversal { respect_status: options.respect_initial_status, ..Traversal::default() };
+    let supply = Traversal { directed: options.directed_check_valves, ..undirected };
+
+    let mut component_of = vec![None; network.nodes.len()];
+    let mut components: Vec<Vec<String>> = Vec::new();
+    for node in 0..network.nodes.len() {
+        if component_of[node].is_some() {
+            continue;
+        }
+        let reachable = network.reachable(&[node], &undirected);
+        let members = (0..network.nodes.len()).filter(|&i| reachable[i]).collect::<Vec<usize>>();
+        for &member in members.iter() {
+            component_of[member] = Some(components.len());
+        }
+        components.push(members.iter().map(|&i| network.nodes[i].id.clone()).collect());
+    }
+    components.sort_by_key(|component| std::cmp::Reverse(component.len()));
+
+    let sources = (0..network.nodes.len()).filter(|&i| network.is_source(i)).collect::<Vec<usize>>();
+    let supplied = network.reachable(&sources, &supply);
+    let unsupplied_nodes = network.nodes.iter().enumerate()
+        .filter(|(i, _)| !supplied[*i])
+        .map(|(_, node)| node.id.clone())
+        .collect();
+
+    let dead_ends = network.nodes.iter().enumerate()
+        .filter(|(_, node)| node.kind == NodeKind::Junction)
+        .filter(|(i, _)| network.neighbours(*i, &undirected).len() == 1)
+        .map(|(_, node)| node.id.clone())
+        .collect();
+
+    ConnectivityReport {
+        components,
+        unsupplied_nodes,
+        dead_ends,
+        dangling_links: network.dangling_links.clone(),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::fs;
+    use super::{analyze_connectivity, ConnectivityOptions};
+    use crate::INP;
+
+    fn a_model() -> INP {
+        let input = r#"
+[JUNCTIONS]
+J1  10
+J2  10
+J3  10
+J4  10
+J5  10
+[RESERVOIRS]
+R1  100
+[PIPES]
+P1  R1  J1  100  100  100
+P2  J1  J2  100  100  100  0  CV
+P3  J3  J2  100  100  100
+P4  J4  J5  100  100  100
+P5  J1  J4  100  100  100  0  CLOSED
+"#;
+        INP::read(input.to_string())
+    }
+
+    #[test]
+    fn report_components_and_dead_ends() {
+        let report = analyze_connectivity(&a_model(), &ConnectivityOptions::default());
+
+        assert_eq!(report.components.len(), 1);
+        assert!(report.unsupplied_nodes.is_empty());
+        assert_eq!(report.dead_ends, vec!["J3", "J5"]);
+    }
+
+    #[test]
+    fn closed_links_disconnect_the_network() {
+        let options = ConnectivityOptions { respect_initial_status: true, ..ConnectivityOptions::default() };
+
+        let report = analyze_connectivity(&a_model(), &options);
+
+        assert_eq!(report.components, vec![vec!["J1", "J2", "J3", "R1"], vec!["J4", "J5"]]);
+        assert_eq!(report.unsupplied_nodes, vec!["J4", "J5"]);
+    }
+
+    #[test]
+    fn check_valves_only_supply_downstream() {
+        let mut inp = a_model();
+        inp.pipes[1].node1 = "J2".to_string();
+        inp.pipes[1].node2 = "J1".to_string();
+        let options = ConnectivityOptions { respect_initial_status: true, directed_check_valves: true };
+
+        let report = analyze_connectivity(&inp, &options);
+
+        assert_eq!(report.unsupplied_nodes, vec!["J2", "J3", "J4", "J5"]);
+    }
+
+    #[test]
+    fn report_links_to_missing_nodes() {
+        let mut inp = a_model();
+        inp.pipes[3].node2 = "J9".to_string();
+
+        let report = analyze_connectivity(&inp, &ConnectivityOptions::default());
+
+        assert_eq!(report.dangling_links.len(), 1);
+        assert_eq!((report.dangling_links[0].id.as_str(), &report.dangling_links[0].missing_nodes), ("P4", &vec!["J9".to_string()]));
+        assert_eq!(report.unsupplied_nodes, vec!["J5"]);
+    }
+
+    #[test]
+    fn every_node_of_magnetic_island_is_supplied() {
+        let input = fs::read_to_string("tests/MagneticIslandEnhanced.inp").unwrap();
+
+        let report = analyze_connectivity(&INP::read(input), &ConnectivityOptions::default());
+
+        assert!(report.unsupplied_nodes.is_empty(), "{:?}", report.unsupplied_nodes);
+    }
+}
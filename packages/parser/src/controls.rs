@@ -0,0 +1,475 @@
+use std::collections::HashMap;
+use std::error::Error;
+use std::fmt;
+use serde::{Serialize, Deserialize};
+use crate::{INP, SectionError};
+use crate::hydraulics::{HydraulicResults, LinkStatus};
+use crate::sections::{Control, ControlAction, ControlCondition, Premise, Relation, Rule};
+use crate::sections::time::parse_time;
+
+/// Hydraulic state of a node, in the units of the INP.
+#[derive(Serialize, Deserialize, Debug, PartialEq, Clone)]
+pub struct NodeState {
+    pub head: f64,
+    pub pressure: f64,
+    pub demand: f64,
+}
+
+/// Hydraulic state of a link, in the units of the INP.
+#[derive(Serialize, Deserialize, Debug, PartialEq, Clone)]
+pub struct LinkState {
+    pub status: LinkStatus,
+    pub flow: f64,
+    pub setting: Option<f64>,
+}
+
+/// State of the network at an instant of a simulation.
+#[derive(Serialize, Deserialize, Debug, PartialEq, Clone)]
+pub struct Snapshot {
+    /// Seconds since the start of the simulation.
+    pub time: u64,
+    /// Time of the previous snapshot, so that time conditions reached in between fire.
+    pub previous: Option<u64>,
+    /// Seconds since midnight.
+    pub clocktime: u64,
+    pub nodes: HashMap<String, NodeState>,
+    pub links: HashMap<String, LinkState>,
+}
+
+#[derive(Debug, PartialEq)]
+pub struct SnapshotError {
+    pub message: String,
+}
+
+impl fmt::Display for SnapshotError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.message)
+    }
+}
+
+impl Error for SnapshotError {}
+
+impl Snapshot {
+    /// Empty snapshot `time` seconds after the start of the simulation of an INP.
+    pub fn at(inp: &INP, time: u64) -> Snapshot {
+        Snapshot {
+            time,
+            previous: None,
+            clocktime: (inp.time("START CLOCKTIME").unwrap_or(0) + time) % 86400,
+            nodes: HashMap::new(),
+            links: HashMap::new(),
+        }
+    }
+
+    /// Snapshot of the results of a hydraulic solution.
+    pub fn from_results(inp: &INP, time: u64, results: &HydraulicResults) -> Snapshot {
+        let mut snapshot = Snapshot::at(inp, time);
+        for node in results.nodes.iter() {
+            snapshot.nodes.insert(node.id.clone(), NodeState { head: node.head, pressure: node.pressure, demand: node.demand });
+        }
+        for link in results.links.iter() {
+            snapshot.links.insert(link.id.clone(), LinkState { status: link.status, flow: link.flow, setting: None });
+        }
+        snapshot
+    }
+
+    /// Seconds since the previous snapshot, if any.
+    pub fn elapsed(&self) -> Result<Option<u64>, SnapshotError> {
+        match self.previous {
+            Some(previous) => self.time.checked_sub(previous)
+                .map(Some)
+                .ok_or_else(|| SnapshotError { message: format!("Previous time {} is after the time {} of the snapshot", previous, self.time) }),
+            None => Ok(None),
+        }
+    }
+}
+
+/// What made a link change.
+#[derive(Serialize, Deserialize, Debug, PartialEq, Clone)]
+pub enum Trigger {
+    /// Position of the control in [CONTROLS].
+    Control(usize),
+    /// Id of the rule.
+    Rule(String),
+}
+
+#[derive(Serialize, Deserialize, Debug, PartialEq, Clone)]
+pub struct ControlChange {
+    pub link_id: String,
+    pub action: ControlAction,
+    pub trigger: Trigger,
+}
+
+pub fn compare(value: f64, relation: Relation, target: f64) -> bool {
+    const TOLERANCE: f64 = 0.001;
+    match relation {
+        Relation::Equal => (value - target).abs() < TOLERANCE,
+        Relation::NotEqual => (value - target).abs() >= TOLERANCE,
+        Relation::Below => value < target,
+        Relation::Above => value > target,
+        Relation::BelowOrEqual => value <= target + TOLERANCE,
+        Relation::AboveOrEqual => value >= target - TOLERANCE,
+    }
+}
+
+pub fn parse_value(value: &str) -> Option<f64> {
+    value.split_whitespace().next()?.parse::<f64>().ok()
+}
+
+/// Compares a time with the time of a premise, e.g. `8 AM`. An equality holds when the
+/// time has been reached since the previous step.
+pub fn compare_time(time: u64, previous: Option<u64>, relation: Relation, value: &str) -> Option<bool> {
+    let mut words = value.split_whitespace();
+    let target = parse_time(words.next()?, words.next()).ok()?;
+    Some(match (relation, previous) {
+        (Relation::Equal, Some(previous)) => previous < target && target <= time,
+        _ => compare(time as f64, relation, target as f64),
+    })
+}
+
+/// Whether the clock reached `target` seconds after midnight in the `elapsed` seconds
+/// before `clocktime`, wrapping around midnight.
+pub fn clocktime_reached(clocktime: u64, elapsed: u64, target: u64) -> bool {
+    if elapsed >= 86400 {
+        return true;
+    }
+    let previous = (clocktime + 86400 - elapsed) % 86400;
+    if previous <= clocktime {
+        previous < target && target <= clocktime
+    } else {
+        previous < target || target <= clocktime
+    }
+}
+
+/// Compares a time of day with the time of a premise, e.g. `8 AM`. An equality holds
+/// when the time has been reached in the `elapsed` seconds since the previous step.
+pub fn compare_clocktime(clocktime: u64, elapsed: Option<u64>, relation: Relation, value: &str) -> Option<bool> {
+    let mut words = value.split_whitespace();
+    let target = parse_time(words.next()?, words.next()).ok()? % 86400;
+    Some(match (relation, elapsed) {
+        (Relation::Equal, Some(elapsed)) => clocktime_reached(clocktime, elapsed, target),
+        _ => compare(clocktime as f64, relation, target as f64),
+    })
+}
+
+/// Whether the premises of a rule hold. Premises are evaluated in order, like
+/// EPANET: a failed AND fails the whole rule, whatever the premises after it.
+pub fn rule_holds(premises: &[Premise], evaluate: impl Fn(&Premise) -> bool) -> bool {
+    let mut holds = false;
+    for (i, premise) in premises.iter().enumerate() {
+        if i > 0 && premise.or {
+            holds = holds || evaluate(premise);
+        } else if i > 0 && !holds {
+            break;
+        } else {
+            holds = evaluate(premise);
+        }
+    }
+    holds
+}
+
+/// Keeps a single action per link: the one of the rule with the highest priority,
+/// or of the first rule among rules of equal priority.
+pub fn resolve_actions<L: PartialEq, A>(mut actions: Vec<(f64, L, A)>) -> Vec<(L, A)> {
+    actions.sort_by(|a, b| b.0.partial_cmp(&a.0).unwrap_or(std::cmp::Ordering::Equal));
+    let mut resolved: Vec<(L, A)> = Vec::new();
+    for (_, link, action) in actions {
+        if !resolved.iter().any(|(other, _)| *other == link) {
+            resolved.push((link, action));
+        }
+    }
+    resolved
+}
+
+#[derive(Debug, PartialEq, Clone, Copy)]
+enum NodeType {
+    Junction,
+    Reservoir,
+    Tank,
+}
+
+/// Evaluates [CONTROLS] and [RULES] against snapshots of the network, without solving it.
+pub struct ControlEngine {
+    controls: Vec<Control>,
+    rules: Vec<Rule>,
+    nodes: HashMap<String, (NodeType, f64)>,
+}
+
+impl ControlEngine {
+    pub fn new(inp: &INP) -> Result<ControlEngine, SectionError> {
+        let mut nodes = HashMap::new();
+        for junction in inp.junctions.iter() {
+            nodes.insert(junction.id.clone(), (NodeType::Junction, junction.elevation));
+        }
+        for reservoir in inp.reservoirs.iter() {
+            nodes.insert(reservoir.id.clone(), (NodeType::Reservoir, 0.0));
+        }
+        for tank in inp.tanks.iter() {
+            nodes.insert(tank.id.clone(), (NodeType::Tank, tank.elevation));
+        }
+
+        Ok(ControlEngine { controls: inp.controls.clone(), rules: inp.rules()?, nodes })
+    }
+
+    /// Level of a tank, pressure of a junction or head of a reservoir: the value
+    /// compared by a simple control on the node.
+    fn control_value(&self, id: &str, state: &NodeState) -> f64 {
+        match self.nodes.get(id) {
+            Some((NodeType::Tank, elevation)) => state.head - elevation,
+            Some((NodeType::Reservoir, _)) => state.head,
+            _ => state.pressure,
+        }
+    }
+
+    fn time_reached(snapshot: &Snapshot, target: u64) -> bool {
+        match snapshot.previous {
+            Some(previous) => previous < target && target <= snapshot.time,
+            None => snapshot.time == target,
+        }
+    }
+
+    /// Whether the condition of a simple control holds.
+    pub fn control_fires(&self, control: &Control, snapshot: &Snapshot) -> Result<bool, SnapshotError> {
+        let elapsed = snapshot.elapsed()?;
+        Ok(match &control.condition {
+            ControlCondition::Above { node_id, value } => snapshot.nodes.get(node_id)
+                .is_some_and(|state| self.control_value(node_id, state) > *value),
+            ControlCondition::Below { node_id, value } => snapshot.nodes.get(node_id)
+                .is_some_and(|state| self.control_value(node_id, state) < *value),
+            ControlCondition::Time(time) => ControlEngine::time_reached(snapshot, *time),
+            ControlCondition::ClockTime(time) => match elapsed {
+                Some(elapsed) => clocktime_reached(snapshot.clocktime, elapsed, time % 86400),
+                None => snapshot.clocktime == time % 86400,
+            },
+        })
+    }
+
+    /// Whether a premise holds for a snapshot. Premises on missing objects do not hold.
+    pub fn premise_holds(&self, premise: &Premise, snapshot: &Snapshot) -> Result<bool, SnapshotError> {
+        let elapsed = snapshot.elapsed()?;
+        let id = premise.object_id.as_deref().unwrap_or("");
+        let outcome = match (premise.object.as_str(), premise.attribute.as_str()) {
+            ("SYSTEM", "TIME") => compare_time(snapshot.time, snapshot.previous, premise.relation, &premise.value),
+            ("SYSTEM", "CLOCKTIME") => compare_clocktime(snapshot.clocktime, elapsed, premise.relation, &premise.value),
+            ("SYSTEM", "DEMAND") => parse_value(&premise.value).map(|target| {
+                let demand = snapshot.nodes.iter()
+                    .filter(|(id, _)| matches!(self.nodes.get(id.as_str()), Some((NodeType::Junction, _))))
+                    .map(|(_, state)| state.demand)
+                    .sum::<f64>();
+                compare(demand, premise.relation, target)
+            }),
+            ("NODE", _) | ("JUNCTION", _) | ("RESERVOIR", _) | ("TANK", _) => snapshot.nodes.get(id).and_then(|state| {
+                let value = match premise.attribute.as_str() {
+                    "HEAD" => state.head,
+                    "LEVEL" => state.head - self.nodes.get(id).map(|(_, elevation)| *elevation).unwrap_or(0.0),
+                    "PRESSURE" => state.pressure,
+                    "DEMAND" => state.demand,
+                    _ => return None,
+                };
+                Some(compare(value, premise.relation, parse_value(&premise.value)?))
+            }),
+            ("LINK", _) | ("PIPE", _) | ("PUMP", _) | ("VALVE", _) => snapshot.links.get(id).and_then(|state| {
+                match premise.attribute.as_str() {
+                    "STATUS" => {
+                        let status = match state.status {
+                            LinkStatus::Open => "OPEN",
+                            LinkStatus::Closed => "CLOSED",
+                            LinkStatus::Active => "ACTIVE",
+                        };
+                        let equal = status == premise.value.to_uppercase();
+                        match premise.relation {
+                            Relation::Equal => Some(equal),
+                            Relation::NotEqual => Some(!equal),
+                            _ => None,
+                        }
+                    },
+                    "FLOW" => Some(compare(state.flow, premise.relation, parse_value(&premise.value)?)),
+                    "SETTING" => Some(compare(state.setting?, premise.relation, parse_value(&premise.value)?)),
+                    _ => None,
+                }
+            }),
+            _ => None,
+        };
+        Ok(outcome.unwrap_or(false))
+    }
+
+    /// Whether an action would change the status or setting of a link.
+    fn changes(action: &ControlAction, state: Option<&LinkState>) -> bool {
+        let Some(state) = state else { return true };
+        match action {
+            ControlAction::Open => state.status != LinkStatus::Open,
+            ControlAction::Closed => state.status != LinkStatus::Closed,
+            ControlAction::Setting(value) => state.setting != Some(*value) || state.status == LinkStatus::Closed,
+        }
+    }
+
+    /// Status and setting changes that fire for a snapshot. Simple controls act in
+    /// their order in [CONTROLS], the last one on a link winning; rules then override
+    /// them, resolving conflicts between rules by priority. At most one change is
+    /// returned per link, and only when it alters the state of the link.
+    pub fn evaluate(&self, snapshot: &Snapshot) -> Result<Vec<ControlChange>, SnapshotError> {
+        snapshot.elapsed()?;
+        let mut actions: Vec<(String, ControlAction, Trigger)> = Vec::new();
+        for (i, control) in self.controls.iter().enumerate() {
+            if self.control_fires(control, snapshot)? {
+                actions.retain(|(link, _, _)| *link != control.link_id);
+                actions.push((control.link_id.clone(), control.action.clone(), Trigger::Control(i)));
+            }
+        }
+
+        let mut rule_actions = Vec::new();
+        for rule in self.rules.iter() {
+            // The times of the snapshot were checked above, so premises can't fail.
+            let holds = rule_holds(&rule.premises, |premise| self.premise_holds(premise, snapshot).unwrap_or(false));
+            for action in if holds { &rule.then_actions } else { &rule.else_actions } {
+                rule_actions.push((rule.priority, action.link_id.as_str(), (&action.action, &rule.id)));
+            }
+        }
+        for (link, (action, rule)) in resolve_actions(rule_actions) {
+            actions.retain(|(other, _, _)| other != link);
+            actions.push((link.to_string(), action.clone(), Trigger::Rule(rule.clone())));
+        }
+
+        Ok(actions.into_iter()
+            .filter(|(link, action, _)| ControlEngine::changes(action, snapshot.links.get(link)))
+            .map(|(link_id, action, trigger)| ControlChange { link_id, action, trigger })
+            .collect())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{clocktime_reached, ControlChange, ControlEngine, LinkState, NodeState, Snapshot, Trigger};
+    use crate::INP;
+    use crate::hydraulics::LinkStatus;
+    use crate::sections::ControlAction;
+
+    fn a_model(rules: &str) -> INP {
+        INP::read(format!(r#"
+[JUNCTIONS]
+J1  10  5
+[RESERVOIRS]
+R1  50
+[TANKS]
+T1  20  2  0  10  10  0
+[PIPES]
+P1  J1  T1  100  300  130
+[PUMPS]
+PU1  R1  J1  HEAD C1
+[CONTROLS]
+LINK PU1 CLOSED IF NODE T1 ABOVE 8
+LINK PU1 OPEN IF NODE T1 BELOW 2
+LINK P1 CLOSED AT TIME 2
+[TIMES]
+Start ClockTime  6 AM
+{}
+"#, rules))
+    }
+
+    fn a_snapshot(inp: &INP, time: u64, tank_head: f64) -> Snapshot {
+        let mut snapshot = Snapshot::at(inp, time);
+        snapshot.nodes.insert("T1".to_string(), NodeState { head: tank_head, pressure: tank_head - 20.0, demand: 0.0 });
+        snapshot.nodes.insert("J1".to_string(), NodeState { head: 40.0, pressure: 30.0, demand: 5.0 });
+        snapshot.links.insert("PU1".to_string(), LinkState { status: LinkStatus::Open, flow: 5.0, setting: Some(1.0) });
+        snapshot.links.insert("P1".to_string(), LinkState { status: LinkStatus::Open, flow: 5.0, setting: None });
+        snapshot
+    }
+
+    #[test]
+    fn simple_controls_compare_tank_levels() {
+        let inp = a_model("");
+        let engine = ControlEngine::new(&inp).unwrap();
+
+        assert_eq!(engine.evaluate(&a_snapshot(&inp, 0, 25.0)).unwrap(), vec![]);
+        assert_eq!(engine.evaluate(&a_snapshot(&inp, 0, 29.0)).unwrap(), vec![ControlChange {
+            link_id: "PU1".to_string(),
+            action: ControlAction::Closed,
+            trigger: Trigger::Control(0),
+        }]);
+        // The pump is already open.
+        assert_eq!(engine.evaluate(&a_snapshot(&inp, 0, 21.0)).unwrap(), vec![]);
+    }
+
+    #[test]
+    fn time_controls_fire_once_reached() {
+        let inp = a_model("");
+        let engine = ControlEngine::new(&inp).unwrap();
+
+        assert!(engine.evaluate(&a_snapshot(&inp, 3600, 25.0)).unwrap().is_empty());
+        assert_eq!(engine.evaluate(&a_snapshot(&inp, 7200, 25.0)).unwrap()[0].link_id, "P1");
+        let mut snapshot = a_snapshot(&inp, 9000, 25.0);
+        snapshot.previous = Some(5400);
+        assert_eq!(engine.evaluate(&snapshot).unwrap()[0].link_id, "P1");
+    }
+
+    #[test]
+    fn rules_override_controls_by_priority() {
+        let inp = a_model(r#"
+[RULES]
+RULE 1
+IF TANK T1 LEVEL ABOVE 8
+THEN PUMP PU1 STATUS IS OPEN
+PRIORITY 1
+RULE 2
+IF SYSTEM CLOCKTIME >= 6 AM
+AND JUNCTION J1 PRESSURE > 20
+THEN PUMP PU1 SETTING = 0.8
+ELSE PIPE P1 STATUS IS CLOSED
+PRIORITY 5
+RULE 3
+IF SYSTEM DEMAND > 1
+THEN PUMP PU1 STATUS IS CLOSED
+PRIORITY 5
+"#);
+        let engine = ControlEngine::new(&inp).unwrap();
+
+        let changes = engine.evaluate(&a_snapshot(&inp, 0, 29.0)).unwrap();
+
+        assert_eq!(changes, vec![ControlChange {
+            link_id: "PU1".to_string(),
+            action: ControlAction::Setting(0.8),
+            trigger: Trigger::Rule("2".to_string()),
+        }]);
+    }
+
+    #[test]
+    fn rules_take_their_else_actions() {
+        let inp = a_model("[RULES]\nRULE 1\nIF JUNCTION J1 PRESSURE < 20\nTHEN PIPE P1 STATUS IS CLOSED\nELSE PUMP PU1 STATUS IS CLOSED\n");
+        let engine = ControlEngine::new(&inp).unwrap();
+
+        let changes = engine.evaluate(&a_snapshot(&inp, 0, 25.0)).unwrap();
+
+        assert_eq!(changes.len(), 1);
+        assert_eq!(changes[0].link_id, "PU1");
+        assert_eq!(changes[0].trigger, Trigger::Rule("1".to_string()));
+    }
+
+    #[test]
+    fn clock_times_are_reached_across_midnight() {
+        assert!(clocktime_reached(1800, 3600, 0));
+        assert!(clocktime_reached(1800, 3600, 86400 - 600));
+        assert!(!clocktime_reached(1800, 3600, 3600));
+        assert!(!clocktime_reached(1800, 3600, 86400 - 3600));
+        assert!(clocktime_reached(7200, 3600, 7200));
+
+        // From 11:30 PM to 0:30 AM, starting at 6 AM.
+        let inp = a_model("[RULES]\nRULE 1\nIF SYSTEM CLOCKTIME = 11:45 PM\nTHEN PIPE P1 STATUS IS CLOSED\n");
+        let engine = ControlEngine::new(&inp).unwrap();
+        let mut snapshot = a_snapshot(&inp, 66600, 25.0);
+        snapshot.previous = Some(63000);
+
+        assert_eq!(snapshot.clocktime, 1800);
+        assert_eq!(engine.evaluate(&snapshot).unwrap()[0].link_id, "P1");
+    }
+
+    #[test]
+    fn reject_a_previous_time_after_the_snapshot() {
+        let inp = a_model("[RULES]\nRULE 1\nIF SYSTEM CLOCKTIME = 11:45 PM\nTHEN PIPE P1 STATUS IS CLOSED\n");
+        let engine = ControlEngine::new(&inp).unwrap();
+        let mut snapshot = a_snapshot(&inp, 3600, 25.0);
+        snapshot.previous = Some(7200);
+
+        assert_eq!(engine.evaluate(&snapshot).unwrap_err().message, "Previous time 7200 is after the time 3600 of the snapshot");
+        assert!(engine.control_fires(&inp.controls[0], &snapshot).is_err());
+    }
+}
@@ -0,0 +1,224 @@
+use std::collections::HashMap;
+use serde::{Serialize, Deserialize};
+use crate::{INP, ToJson};
+use crate::hydraulics::{HydraulicModel, Solver};
+use crate::network::{Network, NodeKind, Traversal};
+use crate::units::Quantity;
+
+#[derive(Serialize, Deserialize, Debug, PartialEq, Clone, Copy)]
+pub struct CriticalityOptions {
+    /// Junctions below this pressure, in the units of the INP, are reported.
+    pub pressure_threshold: f64,
+    /// Solve the hydraulics of the network with every link closed. Without it, or when
+    /// the network can't be solved, only the nodes cut from every source are found.
+    pub hydraulic: bool,
+}
+
+/// Effect of closing a pipe or a valve.
+#[derive(Serialize, Deserialize, Debug, PartialEq, Clone)]
+pub struct LinkCriticality {
+    pub link_id: String,
+    /// Demand that can't be delivered, in the flow units of the INP.
+    pub unmet_demand: f64,
+    /// Junctions that drop below the pressure threshold.
+    pub low_pressure_nodes: Vec<String>,
+    /// Nodes cut from every reservoir and tank.
+    pub disconnected_nodes: Vec<String>,
+    /// Whether the effect comes from a hydraulic solution rather than connectivity alone.
+    pub hydraulic: bool,
+}
+
+#[derive(Serialize, Deserialize, Debug, PartialEq, Clone)]
+pub struct CriticalityReport {
+    /// Closed links, the most critical first.
+    pub links: Vec<LinkCriticality>,
+}
+
+impl CriticalityReport {
+    pub fn link(&self, id: &str) -> Option<&LinkCriticality> {
+        self.links.iter().find(|link| link.link_id == id)
+    }
+}
+
+impl ToJson for CriticalityReport {}
+
+/// Base demand of every junction, the [DEMANDS] of a junction replacing its own demand.
+fn base_demands(inp: &INP) -> HashMap<&str, f64> {
+    let mut demands = inp.junctions.iter()
+        .map(|junction| (junction.id.as_str(), junction.base_demand_flow.unwrap_or(0.0)))
+        .collect::<HashMap<&str, f64>>();
+    let mut replaced = Vec::new();
+    for demand in inp.demands.iter() {
+        let id = demand.junction_id.as_str();
+        if !replaced.contains(&id) {
+            replaced.push(id);
+            demands.insert(id, 0.0);
+        }
+        *demands.entry(id).or_insert(0.0) += demand.base_demand;
+    }
+    demands
+}
+
+/// Nodes supplied by a reservoir or a tank once `closed` is closed.
+fn supplied(network: &Network, closed: Option<usize>) -> Vec<bool> {
+    let mut network = network.clone();
+    if let Some(link) = closed {
+        network.links[link].initially_closed = true;
+    }
+    let sources = (0..network.nodes.len()).filter(|&i| network.is_source(i)).collect::<Vec<usize>>();
+    network.reachable(&sources, &Traversal { respect_status: true, directed: true, upstream: false })
+}
+
+/// Pressure of every node and unmet demand of every junction, in the units of the
+/// INP, with a link closed. `None` when the network can't be solved.
+fn solve(model: &mut HydraulicModel, closed: Option<usize>) -> Option<(Vec<f64>, Vec<f64>)> {
+    let initially_closed = closed.map(|link| std::mem::replace(&mut model.initial_closed[link], true));
+    let model_ref = &*model;
+    let mut solver = Solver::new(model_ref);
+    let solution = match solver.run() {
+        Ok(convergence) if convergence.converged => {
+            let pressure_factor = Quantity::Pressure.si_factor(model_ref.flow_units, model_ref.formula);
+            let flow_factor = Quantity::Flow.si_factor(model_ref.flow_units, model_ref.formula);
+            let pressures = (0..model_ref.network.nodes.len())
+                .map(|i| (solver.heads[i] - model_ref.elevations[i]) / pressure_factor)
+                .collect();
+            let unmet = (0..model_ref.network.nodes.len())
+                .map(|i| if model_ref.is_junction(i) { (solver.demands[i] - solver.demand_flows[i]) / flow_factor } else { 0.0 })
+                .collect();
+            Some((pressures, unmet))
+        },
+        _ => None,
+    };
+    if let (Some(link), Some(status)) = (closed, initially_closed) {
+        model.initial_closed[link] = status;
+    }
+    solution
+}
+
+/// N-1 analysis: closes each pipe and valve of `link_ids`, or all of them, in turn and
+/// ranks them by the demand left unmet beyond the demand already unmet with every link
+/// in place, then by the number of nodes disconnected and of
+/// junctions dropping below the pressure threshold. Only the nodes that were supplied,
+/// and above the threshold, with every link in place are reported.
+pub fn criticality(inp: &INP, link_ids: Option<&[String]>, options: &CriticalityOptions) -> CriticalityReport {
+    let network = Network::new(inp);
+    let demands = base_demands(inp);
+    let candidates = inp.pipes.iter().map(|pipe| &pipe.id)
+        .chain(inp.valves.iter().map(|valve| &valve.id))
+        .filter(|id| link_ids.is_none_or(|ids| ids.contains(id)))
+        .filter_map(|id| network.link(id))
+        .filter(|&link| !network.links[link].initially_closed)
+        .collect::<Vec<usize>>();
+
+    let mut model = if options.hydraulic { HydraulicModel::new(inp).ok() } else { None };
+    let baseline = model.as_mut().and_then(|model| solve(model, None));
+    let supplied_before = supplied(&network, None);
+
+    let mut links = Vec::new();
+    for link in candidates {
+        let supplied_after = supplied(&network, Some(link));
+        let disconnected = (0..network.nodes.len())
+            .filter(|&i| supplied_before[i] && !supplied_after[i])
+            .collect::<Vec<usize>>();
+        let disconnected_demand = disconnected.iter()
+            .map(|&i| demands.get(network.nodes[i].id.as_str()).copied().unwrap_or(0.0).max(0.0))
+            .sum::<f64>();
+
+        let solution = match (&mut model, &baseline) {
+            (Some(model), Some(_)) => solve(model, Some(link)),
+            _ => None,
+        };
+        let (unmet_demand, low_pressure_nodes) = match (&solution, &baseline) {
+            (Some((pressures, unmet)), Some((before, unmet_before))) => {
+                let connected_unmet = (0..network.nodes.len())
+                    .filter(|&i| supplied_after[i])
+                    .map(|i| (unmet[i] - unmet_before[i]).max(0.0))
+                    .sum::<f64>();
+                let low = (0..network.nodes.len())
+                    .filter(|&i| network.nodes[i].kind == NodeKind::Junction && supplied_after[i])
+                    .filter(|&i| pressures[i] < options.pressure_threshold && before[i] >= options.pressure_threshold)
+                    .map(|i| network.nodes[i].id.clone())
+                    .collect();
+                (disconnected_demand + connected_unmet, low)
+            },
+            _ => (disconnected_demand, Vec::new()),
+        };
+
+        links.push(LinkCriticality {
+            link_id: network.links[link].id.clone(),
+            unmet_demand,
+            low_pressure_nodes,
+            disconnected_nodes: disconnected.iter().map(|&i| network.nodes[i].id.clone()).collect(),
+            hydraulic: solution.is_some(),
+        });
+    }
+    links.sort_by(|a, b| {
+        b.unmet_demand.total_cmp(&a.unmet_demand)
+            .then(b.disconnected_nodes.len().cmp(&a.disconnected_nodes.len()))
+            .then(b.low_pressure_nodes.len().cmp(&a.low_pressure_nodes.len()))
+    });
+
+    CriticalityReport { links }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{criticality, CriticalityOptions};
+    use crate::INP;
+
+    // A loop fed by P1, with J3 hanging off it through P4.
+    fn a_model() -> INP {
+        INP::read(r#"
+[JUNCTIONS]
+J1  0  10
+J2  0  10
+J3  0  5
+[RESERVOIRS]
+R1  40
+[PIPES]
+P1  R1  J1  100  300  130
+P2  J1  J2  1000  300  130
+P3  J1  J2  2000  100  130
+P4  J2  J3  100  200  130
+[OPTIONS]
+Units  LPS
+Demand Model  PDA
+Minimum Pressure  0
+Required Pressure  20
+"#.to_string())
+    }
+
+    #[test]
+    fn rank_links_by_their_effect() {
+        let options = CriticalityOptions { pressure_threshold: 38.0, hydraulic: true };
+        let report = criticality(&a_model(), None, &options);
+
+        assert_eq!(report.links.len(), 4);
+        assert_eq!(report.links[0].link_id, "P1");
+        assert_eq!(report.links[3].link_id, "P3");
+        assert!(report.links.windows(2).all(|pair| pair[0].unmet_demand >= pair[1].unmet_demand));
+        let p1 = report.link("P1").unwrap();
+        assert_eq!(p1.disconnected_nodes, vec!["J1", "J2", "J3"]);
+        assert!((p1.unmet_demand - 25.0).abs() < 1e-6);
+        assert!(p1.hydraulic);
+        assert!((report.link("P4").unwrap().unmet_demand - 5.0).abs() < 1e-3);
+        let p2 = report.link("P2").unwrap();
+        assert!(p2.disconnected_nodes.is_empty());
+        assert!(p2.low_pressure_nodes.contains(&"J2".to_string()), "{:?}", p2);
+        assert!(p2.unmet_demand > 0.0);
+    }
+
+    #[test]
+    fn fall_back_to_connectivity() {
+        let options = CriticalityOptions { pressure_threshold: 38.0, hydraulic: false };
+        let report = criticality(&a_model(), Some(&["P2".to_string(), "P4".to_string()]), &options);
+
+        assert_eq!(report.links.len(), 2);
+        assert_eq!(report.links[0].link_id, "P4");
+        assert_eq!(report.links[0].unmet_demand, 5.0);
+        assert_eq!(report.links[0].disconnected_nodes, vec!["J3"]);
+        assert!(!report.links[0].hydraulic);
+        assert_eq!(report.links[1].unmet_demand, 0.0);
+        assert!(report.links[1].low_pressure_nodes.is_empty());
+    }
+}
@@ -0,0 +1,455 @@
+use std::error::Error;
+use std::f64::consts::{FRAC_PI_2, FRAC_PI_4};
+use std::fmt;
+use serde::{Serialize, Deserialize};
+use crate::geometry::Point;
+use crate::INP;
+
+#[derive(Debug, PartialEq, Clone)]
+pub struct CrsError {
+    pub message: String,
+}
+
+impl fmt::Display for CrsError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.message)
+    }
+}
+
+impl Error for CrsError {}
+
+#[derive(Serialize, Deserialize, Debug, PartialEq, Clone, Copy)]
+pub struct Ellipsoid {
+    pub name: &'static str,
+    pub semi_major_axis: f64,
+    pub inverse_flattening: f64,
+}
+
+pub const GRS80: Ellipsoid = Ellipsoid { name: "GRS_1980", semi_major_axis: 6378137.0, inverse_flattening: 298.257222101 };
+pub const WGS84_ELLIPSOID: Ellipsoid = Ellipsoid { name: "WGS_1984", semi_major_axis: 6378137.0, inverse_flattening: 298.257223563 };
+pub const AUSTRALIAN_NATIONAL: Ellipsoid = Ellipsoid { name: "Australian", semi_major_axis: 6378160.0, inverse_flattening: 298.25 };
+
+impl Ellipsoid {
+    pub fn flattening(&self) -> f64 {
+        1.0 / self.inverse_flattening
+    }
+
+    /// Square of the first eccentricity.
+    pub fn eccentricity_squared(&self) -> f64 {
+        let f = self.flattening();
+        f * (2.0 - f)
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, PartialEq, Clone, Copy)]
+pub enum Datum {
+    Wgs84,
+    Gda94,
+    Gda2020,
+    Agd66,
+    Agd84,
+}
+
+impl Datum {
+    pub fn ellipsoid(&self) -> Ellipsoid {
+        match self {
+            Datum::Wgs84 => WGS84_ELLIPSOID,
+            Datum::Gda94 | Datum::Gda2020 => GRS80,
+            Datum::Agd66 | Datum::Agd84 => AUSTRALIAN_NATIONAL,
+        }
+    }
+
+    /// Helmert parameters to WGS 84 as in the `towgs84` of PROJ: translations in metres,
+    /// rotations in arc seconds (position vector convention) and scale in parts per million.
+    pub fn to_wgs84(&self) -> [f64; 7] {
+        match self {
+            Datum::Wgs84 | Datum::Gda94 | Datum::Gda2020 => [0.0; 7],
+            Datum::Agd66 => [-117.808, -51.536, 137.784, 0.303, 0.446, 0.234, -0.29],
+            Datum::Agd84 => [-117.763, -51.51, 139.061, 0.292, 0.443, 0.277, -0.191],
+        }
+    }
+
+    /// Names of the geographic coordinate system and of the datum in ESRI WKT.
+    fn names(&self) -> (&'static str, &'static str) {
+        match self {
+            Datum::Wgs84 => ("GCS_WGS_1984", "D_WGS_1984"),
+            Datum::Gda94 => ("GCS_GDA_1994", "D_GDA_1994"),
+            Datum::Gda2020 => ("GCS_GDA2020", "D_GDA2020"),
+            Datum::Agd66 => ("GCS_Australian_1966", "D_Australian_1966"),
+            Datum::Agd84 => ("GCS_Australian_1984", "D_Australian_1984"),
+        }
+    }
+}
+
+/// Coordinate reference systems known without a projection database: longitude and
+/// latitude on WGS 84, the UTM zones of WGS 84 and of the Australian datums, and
+/// Web Mercator.
+#[derive(Serialize, Deserialize, Debug, PartialEq, Clone, Copy)]
+pub enum Crs {
+    Geographic(Datum),
+    Utm { datum: Datum, zone: u32, south: bool },
+    WebMercator,
+}
+
+impl Crs {
+    /// CRS of an EPSG code such as `EPSG:28355`.
+    pub fn from_projection(projection: &str) -> Option<Crs> {
+        let (authority, code) = projection.trim().split_once(':')?;
+        if !authority.eq_ignore_ascii_case("EPSG") {
+            return None;
+        }
+        let code = code.trim().parse::<u32>().ok()?;
+        let utm = |datum: Datum, first: u32, south: bool| Some(Crs::Utm { datum, zone: code - first, south });
+        match code {
+            4326 => Some(Crs::Geographic(Datum::Wgs84)),
+            3857 | 900913 => Some(Crs::WebMercator),
+            32601..=32660 => utm(Datum::Wgs84, 32600, false),
+            32701..=32760 => utm(Datum::Wgs84, 32700, true),
+            28348..=28358 => utm(Datum::Gda94, 28300, true),
+            7846..=7859 => utm(Datum::Gda2020, 7800, true),
+            20248..=20258 => utm(Datum::Agd66, 20200, true),
+            20348..=20358 => utm(Datum::Agd84, 20300, true),
+            _ => None,
+        }
+    }
+
+    /// Longitude of the central meridian of a UTM zone, in degrees.
+    pub fn central_meridian(zone: u32) -> f64 {
+        6.0 * zone as f64 - 183.0
+    }
+
+    pub fn datum(&self) -> Datum {
+        match *self {
+            Crs::Geographic(datum) | Crs::Utm { datum, .. } => datum,
+            Crs::WebMercator => Datum::Wgs84,
+        }
+    }
+
+    /// Transforms a point of this CRS to another one, through WGS 84 when the datums differ.
+    /// Geographic points are longitude and latitude in degrees.
+    pub fn transform(&self, to: &Crs, point: Point) -> Point {
+        let (lon, lat) = self.unproject(point);
+        let (from_datum, to_datum) = (self.datum(), to.datum());
+        let (lon, lat) = if from_datum.to_wgs84() == to_datum.to_wgs84() && from_datum.ellipsoid() == to_datum.ellipsoid() {
+            (lon, lat)
+        } else {
+            let wgs84 = helmert(geocentric(from_datum.ellipsoid(), lon, lat), from_datum.to_wgs84(), false);
+            geodetic(to_datum.ellipsoid(), helmert(wgs84, to_datum.to_wgs84(), true))
+        };
+        to.project((lon, lat))
+    }
+
+    /// Longitude and latitude in degrees on the datum of the CRS.
+    fn unproject(&self, (x, y): Point) -> Point {
+        match *self {
+            Crs::Geographic(_) => (x, y),
+            Crs::WebMercator => (
+                (x / WGS84_ELLIPSOID.semi_major_axis).to_degrees(),
+                (2.0 * (y / WGS84_ELLIPSOID.semi_major_axis).exp().atan() - FRAC_PI_2).to_degrees(),
+            ),
+            Crs::Utm { datum, zone, south } => {
+                let northing = if south { y - 10000000.0 } else { y };
+                let (lon, lat) = TransverseMercator::new(datum.ellipsoid()).inverse(x - 500000.0, northing);
+                (lon + Crs::central_meridian(zone), lat)
+            },
+        }
+    }
+
+    fn project(&self, (lon, lat): Point) -> Point {
+        match *self {
+            Crs::Geographic(_) => (lon, lat),
+            Crs::WebMercator => {
+                // Latitudes beyond about 85.06 degrees make the map square.
+                let lat = lat.clamp(-85.051_128_78, 85.051_128_78).to_radians();
+                let radius = WGS84_ELLIPSOID.semi_major_axis;
+                (radius * lon.to_radians(), radius * (FRAC_PI_4 + lat / 2.0).tan().ln())
+            },
+            Crs::Utm { datum, zone, south } => {
+                let (x, y) = TransverseMercator::new(datum.ellipsoid()).forward(lon - Crs::central_meridian(zone), lat);
+                (x + 500000.0, if south { y + 10000000.0 } else { y })
+            },
+        }
+    }
+
+    /// Well-known text in the ESRI dialect of .prj files.
+    pub fn wkt(&self) -> String {
+        let geographic = |datum: Datum| {
+            let (name, datum_name) = datum.names();
+            let ellipsoid = datum.ellipsoid();
+            format!(
+                "GEOGCS[\"{}\",DATUM[\"{}\",SPHEROID[\"{}\",{:?},{:?}]],PRIMEM[\"Greenwich\",0.0],UNIT[\"Degree\",0.0174532925199433]]",
+                name, datum_name, ellipsoid.name, ellipsoid.semi_major_axis, ellipsoid.inverse_flattening,
+            )
+        };
+        match *self {
+            Crs::Geographic(datum) => geographic(datum),
+            Crs::WebMercator => format!(
+                "PROJCS[\"WGS_1984_Web_Mercator_Auxiliary_Sphere\",{},PROJECTION[\"Mercator_Auxiliary_Sphere\"],PARAMETER[\"False_Easting\",0.0],PARAMETER[\"False_Northing\",0.0],PARAMETER[\"Central_Meridian\",0.0],PARAMETER[\"Standard_Parallel_1\",0.0],PARAMETER[\"Auxiliary_Sphere_Type\",0.0],UNIT[\"Meter\",1.0]]",
+                geographic(Datum::Wgs84),
+            ),
+            Crs::Utm { datum, zone, south } => {
+                let name = match datum {
+                    Datum::Wgs84 => format!("WGS_1984_UTM_Zone_{}{}", zone, if south { "S" } else { "N" }),
+                    Datum::Gda94 => format!("GDA_1994_MGA_Zone_{}", zone),
+                    Datum::Gda2020 => format!("GDA2020_MGA_Zone_{}", zone),
+                    Datum::Agd66 => format!("AGD_1966_AMG_Zone_{}", zone),
+                    Datum::Agd84 => format!("AGD_1984_AMG_Zone_{}", zone),
+                };
+                format!(
+                    "PROJCS[\"{}\",{},PROJECTION[\"Transverse_Mercator\"],PARAMETER[\"False_Easting\",500000.0],PARAMETER[\"False_Northing\",{:?}],PARAMETER[\"Central_Meridian\",{:?}],PARAMETER[\"Scale_Factor\",0.9996],PARAMETER[\"Latitude_Of_Origin\",0.0],UNIT[\"Meter\",1.0]]",
+                    name, geographic(datum), if south { 10000000.0 } else { 0.0 }, Crs::central_meridian(zone),
+                )
+            },
+        }
+    }
+}
+
+const UTM_SCALE: f64 = 0.9996;
+
+/// Transverse Mercator with the series in the third flattening of Krüger, accurate to
+/// well under a millimetre within a UTM zone.
+struct TransverseMercator {
+    eccentricity: f64,
+    radius: f64,
+    alpha: [f64; 4],
+    beta: [f64; 4],
+}
+
+impl TransverseMercator {
+    fn new(ellipsoid: Ellipsoid) -> TransverseMercator {
+        let f = ellipsoid.flattening();
+        let n = f / (2.0 - f);
+        let (n2, n3, n4) = (n * n, n * n * n, n * n * n * n);
+        TransverseMercator {
+            eccentricity: ellipsoid.eccentricity_squared().sqrt(),
+            radius: ellipsoid.semi_major_axis / (1.0 + n) * (1.0 + n2 / 4.0 + n4 / 64.0),
+            alpha: [
+                n / 2.0 - 2.0 * n2 / 3.0 + 5.0 * n3 / 16.0 + 41.0 * n4 / 180.0,
+                13.0 * n2 / 48.0 - 3.0 * n3 / 5.0 + 557.0 * n4 / 1440.0,
+                61.0 * n3 / 240.0 - 103.0 * n4 / 140.0,
+                49561.0 * n4 / 161280.0,
+            ],
+            beta: [
+                n / 2.0 - 2.0 * n2 / 3.0 + 37.0 * n3 / 96.0 - n4 / 360.0,
+                n2 / 48.0 + n3 / 15.0 - 437.0 * n4 / 1440.0,
+                17.0 * n3 / 480.0 - 37.0 * n4 / 840.0,
+                4397.0 * n4 / 161280.0,
+            ],
+        }
+    }
+
+    /// Easting and northing from the central meridian and the equator, of a longitude
+    /// relative to the central meridian and a latitude in degrees.
+    fn forward(&self, lon: f64, lat: f64) -> Point {
+        let (lon, lat) = (lon.to_radians(), lat.to_radians());
+        let e = self.eccentricity;
+        let t = (lat.sin().atanh() - e * (e * lat.sin()).atanh()).sinh();
+        let xi_prime = t.atan2(lon.cos());
+        let eta_prime = (lon.sin() / (1.0 + t * t).sqrt()).atanh();
+
+        let (mut xi, mut eta) = (xi_prime, eta_prime);
+        for (j, alpha) in self.alpha.iter().enumerate() {
+            let k = 2.0 * (j + 1) as f64;
+            xi += alpha * (k * xi_prime).sin() * (k * eta_prime).cosh();
+            eta += alpha * (k * xi_prime).cos() * (k * eta_prime).sinh();
+        }
+        (UTM_SCALE * self.radius * eta, UTM_SCALE * self.radius * xi)
+    }
+
+    fn inverse(&self, x: f64, y: f64) -> Point {
+        let xi = y / (UTM_SCALE * self.radius);
+        let eta = x / (UTM_SCALE * self.radius);
+
+        let (mut xi_prime, mut eta_prime) = (xi, eta);
+        for (j, beta) in self.beta.iter().enumerate() {
+            let k = 2.0 * (j + 1) as f64;
+            xi_prime -= beta * (k * xi).sin() * (k * eta).cosh();
+            eta_prime -= beta * (k * xi).cos() * (k * eta).sinh();
+        }
+        let conformal = (xi_prime.sin() / eta_prime.cosh()).asin();
+        let lon = eta_prime.sinh().atan2(xi_prime.cos());
+
+        // Latitude of the conformal latitude by fixed point iteration.
+        let e = self.eccentricity;
+        let mut lat = conformal;
+        for _ in 0..15 {
+            let e_sin = e * lat.sin();
+            lat = 2.0 * ((FRAC_PI_4 + conformal / 2.0).tan() * ((1.0 + e_sin) / (1.0 - e_sin)).powf(e / 2.0)).atan() - FRAC_PI_2;
+        }
+        (lon.to_degrees(), lat.to_degrees())
+    }
+}
+
+type Geocentric = (f64, f64, f64);
+
+fn geocentric(ellipsoid: Ellipsoid, lon: f64, lat: f64) -> Geocentric {
+    let (lon, lat) = (lon.to_radians(), lat.to_radians());
+    let e2 = ellipsoid.eccentricity_squared();
+    let normal = ellipsoid.semi_major_axis / (1.0 - e2 * lat.sin().powi(2)).sqrt();
+    (normal * lat.cos() * lon.cos(), normal * lat.cos() * lon.sin(), normal * (1.0 - e2) * lat.sin())
+}
+
+/// Longitude and latitude in degrees of a geocentric point, dropping the height.
+fn geodetic(ellipsoid: Ellipsoid, (x, y, z): Geocentric) -> Point {
+    let e2 = ellipsoid.eccentricity_squared();
+    let p = x.hypot(y);
+    let mut lat = z.atan2(p * (1.0 - e2));
+    for _ in 0..10 {
+        let normal = ellipsoid.semi_major_axis / (1.0 - e2 * lat.sin().powi(2)).sqrt();
+        let height = p / lat.cos() - normal;
+        lat = z.atan2(p * (1.0 - e2 * normal / (normal + height)));
+    }
+    (y.atan2(x).to_degrees(), lat.to_degrees())
+}
+
+/// Seven parameter similarity transformation, or its inverse by negated parameters.
+fn helmert((x, y, z): Geocentric, parameters: [f64; 7], inverse: bool) -> Geocentric {
+    let sign = if inverse { -1.0 } else { 1.0 };
+    let [tx, ty, tz, rx, ry, rz, ds] = parameters.map(|parameter| sign * parameter);
+    let (rx, ry, rz) = (rx.to_radians() / 3600.0, ry.to_radians() / 3600.0, rz.to_radians() / 3600.0);
+    let scale = 1.0 + ds * 1e-6;
+    (
+        tx + scale * (x - rz * y + ry * z),
+        ty + scale * (rz * x + y - rx * z),
+        tz + scale * (-ry * x + rx * y + z),
+    )
+}
+
+/// Reprojects [COORDINATES], [VERTICES], [LABELS] and the [BACKDROP] dimensions between
+/// two EPSG codes, e.g. `EPSG:20255` to `EPSG:4326`, and records the new projection in the title.
+pub fn reproject(inp: &mut INP, from: &str, to: &str) -> Result<(), CrsError> {
+    let crs = |projection: &str| Crs::from_projection(projection)
+        .ok_or_else(|| CrsError { message: format!("Unsupported projection {}", projection) });
+    let (from_crs, to_crs) = (crs(from)?, crs(to)?);
+    let transform = |point: Point| from_crs.transform(&to_crs, point);
+
+    // Read the backdrop dimensions first, so an invalid backdrop leaves the model unchanged.
+    let dimensions = inp.backdrop.iter()
+        .enumerate()
+        .filter(|(_, backdrop)| backdrop.key == "DIMENSIONS")
+        .map(|(index, backdrop)| {
+            let bounds = backdrop.values.iter().map(|value| value.parse::<f64>()).collect::<Result<Vec<f64>, _>>();
+            match bounds.as_deref() {
+                Ok(&[x1, y1, x2, y2]) => Ok((index, (x1, y1, x2, y2))),
+                _ => Err(CrsError { message: format!("Invalid backdrop dimensions {}", backdrop.values.join(" ")) }),
+            }
+        })
+        .collect::<Result<Vec<_>, CrsError>>()?;
+
+    for coordinate in inp.coordinates.iter_mut() {
+        (coordinate.x, coordinate.y) = transform((coordinate.x, coordinate.y));
+    }
+    for vertex in inp.vertices.iter_mut() {
+        (vertex.x, vertex.y) = transform((vertex.x, vertex.y));
+    }
+    for label in inp.labels.iter_mut() {
+        (label.x, label.y) = transform((label.x, label.y));
+    }
+    for (index, (x1, y1, x2, y2)) in dimensions {
+        // A projected rectangle is no rectangle, so take the bounds of its corners.
+        let corners = [(x1, y1), (x1, y2), (x2, y1), (x2, y2)].map(transform);
+        let (mut min, mut max) = (corners[0], corners[0]);
+        for &(x, y) in corners.iter() {
+            min = (min.0.min(x), min.1.min(y));
+            max = (max.0.max(x), max.1.max(y));
+        }
+        inp.backdrop[index].values = [min.0, min.1, max.0, max.1].iter().map(|value| value.to_string()).collect();
+    }
+
+    let token = format!("Q_VAR_PROJECTION={}", to);
+    if inp.title.contains("Q_VAR_PROJECTION=") {
+        inp.title = inp.title.split(' ')
+            .map(|word| if word.starts_with("Q_VAR_PROJECTION=") { token.as_str() } else { word })
+            .collect::<Vec<&str>>()
+            .join(" ");
+    } else if inp.title.is_empty() {
+        inp.title = token;
+    } else {
+        inp.title = format!("{} {}", inp.title, token);
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use std::fs;
+    use super::{reproject, Crs, Datum};
+    use crate::INP;
+
+    #[test]
+    fn recognize_epsg_codes() {
+        assert_eq!(Crs::from_projection("EPSG:20255"), Some(Crs::Utm { datum: Datum::Agd66, zone: 55, south: true }));
+        assert_eq!(Crs::from_projection("epsg:28356"), Some(Crs::Utm { datum: Datum::Gda94, zone: 56, south: true }));
+        assert_eq!(Crs::from_projection("EPSG:32630"), Some(Crs::Utm { datum: Datum::Wgs84, zone: 30, south: false }));
+        assert_eq!(Crs::from_projection("EPSG:3857"), Some(Crs::WebMercator));
+        assert_eq!(Crs::from_projection("EPSG:2193"), None);
+        assert_eq!(Crs::from_projection("20255"), None);
+    }
+
+    #[test]
+    fn write_esri_wkt() {
+        let wkt = Crs::from_projection("EPSG:28355").unwrap().wkt();
+
+        assert!(wkt.starts_with("PROJCS[\"GDA_1994_MGA_Zone_55\",GEOGCS[\"GCS_GDA_1994\""));
+        assert!(wkt.contains("SPHEROID[\"GRS_1980\",6378137.0,298.257222101]"));
+        assert!(wkt.contains("PARAMETER[\"Central_Meridian\",147.0]"));
+        assert!(wkt.contains("PARAMETER[\"False_Northing\",10000000.0]"));
+        assert!(Crs::Geographic(Datum::Wgs84).wkt().starts_with("GEOGCS[\"GCS_WGS_1984\""));
+    }
+
+    #[test]
+    fn project_to_mga() {
+        // Flinders Peak, from the GDA technical manual.
+        let lon = 144.0 + 25.0 / 60.0 + 29.5244 / 3600.0;
+        let lat = -(37.0 + 57.0 / 60.0 + 3.7203 / 3600.0);
+        let mga = Crs::from_projection("EPSG:28355").unwrap();
+
+        let (x, y) = Crs::Geographic(Datum::Gda94).transform(&mga, (lon, lat));
+        assert!((x - 273741.297).abs() < 0.001 && (y - 5796489.777).abs() < 0.001);
+
+        let (back_lon, back_lat) = mga.transform(&Crs::Geographic(Datum::Gda94), (x, y));
+        assert!((back_lon - lon).abs() < 1e-9 && (back_lat - lat).abs() < 1e-9);
+    }
+
+    #[test]
+    fn transform_between_datums() {
+        let amg = Crs::from_projection("EPSG:20255").unwrap();
+        let web_mercator = Crs::from_projection("EPSG:3857").unwrap();
+
+        let (x, y) = amg.transform(&web_mercator, (483000.0, 7882000.0));
+        let (back_x, back_y) = web_mercator.transform(&amg, (x, y));
+        assert!((back_x - 483000.0).abs() < 0.001 && (back_y - 7882000.0).abs() < 0.001);
+
+        // AMG moves about 200 metres to the north east on GDA94.
+        let (x, y) = amg.transform(&Crs::from_projection("EPSG:28355").unwrap(), (483000.0, 7882000.0));
+        assert!((x - 483114.2).abs() < 0.1 && (y - 7882177.6).abs() < 0.1);
+    }
+
+    #[test]
+    fn reproject_inp() {
+        let mut inp = INP::read(fs::read_to_string("tests/MagneticIslandEnhanced.inp").unwrap());
+
+        reproject(&mut inp, "EPSG:20255", "EPSG:4326").unwrap();
+
+        assert_eq!(inp.projection(), Some("EPSG:4326"));
+        assert!(inp.coordinates.iter()
+            .all(|coordinate| (146.7..146.9).contains(&coordinate.x) && (-19.2..-19.1).contains(&coordinate.y)));
+        assert!(inp.vertices.iter().all(|vertex| (146.7..146.9).contains(&vertex.x)));
+        let dimensions = inp.backdrop[0].values.iter().map(|value| value.parse::<f64>().unwrap()).collect::<Vec<f64>>();
+        assert!(dimensions[0] < dimensions[2] && (146.7..146.9).contains(&dimensions[0]));
+
+        assert!(reproject(&mut inp, "EPSG:4326", "EPSG:2193").is_err());
+    }
+
+    #[test]
+    fn leave_the_model_unchanged_on_an_invalid_backdrop() {
+        let mut inp = INP::read(fs::read_to_string("tests/MagneticIslandEnhanced.inp").unwrap());
+        inp.backdrop[0].values = vec!["0".to_string(), "0".to_string(), "wide".to_string()];
+        let original = inp.clone();
+
+        let error = reproject(&mut inp, "EPSG:20255", "EPSG:4326").unwrap_err();
+
+        assert_eq!(error.message, "Invalid backdrop dimensions 0 0 wide");
+        assert_eq!(inp, original);
+    }
+}
@@ -0,0 +1,548 @@
+use std::collections::{HashMap, HashSet};
+use serde::{Serialize, Deserialize};
+use serde::de::DeserializeOwned;
+use serde_json::{json, Map, Value};
+use crate::sections::{Coordinate, Curve, Demand, Emitter, Pattern, RuleLine, Setting, Vertex};
+use crate::shapefile::records;
+use crate::writer::control_line;
+use crate::INP;
+
+pub type Record = Map<String, Value>;
+
+#[derive(Serialize, Deserialize, Debug, PartialEq, Eq, Clone, Copy)]
+pub enum ChangeKind {
+    Added,
+    Removed,
+    Modified,
+    Renamed,
+}
+
+#[derive(Serialize, Deserialize, Debug, PartialEq, Clone)]
+pub struct FieldChange {
+    pub field: String,
+    pub before: Value,
+    pub after: Value,
+}
+
+#[derive(Serialize, Deserialize, Debug, PartialEq, Clone)]
+pub struct Change {
+    /// Kind of element, e.g. `junction`, `pipe`, `pattern` or `option`.
+    pub element: String,
+    pub kind: ChangeKind,
+    pub id: String,
+    /// ID in the new model of a renamed element.
+    pub new_id: Option<String>,
+    /// Changed fields, or every field of an added or removed element.
+    pub fields: Vec<FieldChange>,
+}
+
+#[derive(Serialize, Deserialize, Debug, PartialEq)]
+pub struct ModelDiff {
+    pub changes: Vec<Change>,
+}
+
+/// Elements compared by the diff, in the order of the report, with their heading.
+pub const ELEMENTS: [(&str, &str); 19] = [
+    ("title", "Title"), ("junction", "Junctions"), ("reservoir", "Reservoirs"), ("tank", "Tanks"),
+    ("pipe", "Pipes"), ("pump", "Pumps"), ("valve", "Valves"), ("status", "Status"),
+    ("pattern", "Patterns"), ("curve", "Curves"), ("control", "Controls"), ("rule", "Rules"),
+    ("energy", "Energy"), ("quality", "Quality"), ("source", "Sources"), ("reaction", "Reactions"),
+    ("mixing", "Mixing"), ("time", "Times"), ("option", "Options"),
+];
+
+const NODE_FIELDS: [&str; 4] = ["node1", "node2", "start_node", "end_node"];
+
+pub(crate) fn show(value: &Value) -> String {
+    match value {
+        Value::String(text) => text.clone(),
+        Value::Null => "-".to_string(),
+        Value::Number(number) => number.as_f64().map(|number| number.to_string()).unwrap_or_default(),
+        value => value.to_string(),
+    }
+}
+
+impl ModelDiff {
+    pub fn is_empty(&self) -> bool {
+        self.changes.is_empty()
+    }
+
+    pub fn changes_of<'a>(&'a self, element: &'a str) -> impl Iterator<Item = &'a Change> {
+        self.changes.iter().filter(move |change| change.element == element)
+    }
+
+    pub fn change<'a>(&'a self, element: &'a str, id: &str) -> Option<&'a Change> {
+        self.changes_of(element).find(|change| change.id == id)
+    }
+
+    pub fn to_json(&self) -> Value {
+        serde_json::to_value(self).unwrap()
+    }
+
+    /// Readable report, one line per element under the heading of its kind.
+    pub fn report(&self) -> String {
+        let mut text = String::new();
+        for (element, heading) in ELEMENTS.iter() {
+            let changes = self.changes_of(element).collect::<Vec<&Change>>();
+            if changes.is_empty() {
+                continue;
+            }
+            text.push_str(&format!("{}\n", heading));
+            for change in changes {
+                let fields = change.fields.iter()
+                    .map(|field| format!("{} {} -> {}", field.field, show(&field.before), show(&field.after)))
+                    .collect::<Vec<String>>()
+                    .join(", ");
+                let line = match change.kind {
+                    ChangeKind::Added => format!("  + {}", change.id),
+                    ChangeKind::Removed => format!("  - {}", change.id),
+                    ChangeKind::Modified => format!("  ~ {}: {}", change.id, fields),
+                    ChangeKind::Renamed if fields.is_empty() => format!("  > {} -> {}", change.id, change.new_id.as_deref().unwrap_or_default()),
+                    ChangeKind::Renamed => format!("  > {} -> {}: {}", change.id, change.new_id.as_deref().unwrap_or_default(), fields),
+                };
+                text.push_str(&line);
+                text.push('\n');
+            }
+        }
+        text
+    }
+}
+
+/// Records keyed by ID in the order of the model. A later record with the same ID
+/// replaces the earlier one, as in EPANET.
+#[derive(Debug, Default)]
+pub(crate) struct Table {
+    pub ids: Vec<String>,
+    pub records: HashMap<String, Record>,
+}
+
+impl Table {
+    pub(crate) fn insert(&mut self, id: String, record: Record) {
+        if !self.records.contains_key(&id) {
+            self.ids.push(id.clone());
+        }
+        self.records.insert(id, record);
+    }
+
+    fn keyed(records: Vec<Record>, key: &str) -> Table {
+        let mut table = Table::default();
+        for record in records {
+            let id = record.get(key).map(show).unwrap_or_default();
+            table.insert(id, record);
+        }
+        table
+    }
+}
+
+/// ID of a setting: its key, with the pipe, tank or pump of the settings given per
+/// element, e.g. `BULK P1` or `PUMP P1 PRICE`.
+pub(crate) fn setting_id(key: &str, value: &str) -> String {
+    let key = key.to_uppercase();
+    let mut words = value.split_whitespace();
+    match key.as_str() {
+        "BULK" | "WALL" | "TANK" => format!("{} {}", key, words.next().unwrap_or_default()),
+        "PUMP" => format!("{} {} {}", key, words.next().unwrap_or_default(), words.next().unwrap_or_default().to_uppercase()),
+        _ => key,
+    }
+}
+
+fn settings(settings: &[Setting]) -> Table {
+    let mut table = Table::default();
+    for (setting, record) in settings.iter().zip(records(settings)) {
+        table.insert(setting_id(&setting.key, &setting.value), record);
+    }
+    table
+}
+
+fn object(value: Value) -> Record {
+    match value {
+        Value::Object(record) => record,
+        _ => Record::new(),
+    }
+}
+
+/// Every element of a model as records keyed by ID, in the order of `ELEMENTS`. Nodes
+/// hold their coordinates, junctions their demands and emitter, and links their vertices.
+pub(crate) fn tables(inp: &INP) -> Vec<(&'static str, Table)> {
+    let coordinates = inp.coordinates.iter()
+        .map(|coordinate| (coordinate.node_id.as_str(), (coordinate.x, coordinate.y)))
+        .collect::<HashMap<&str, (f64, f64)>>();
+    let mut vertices: HashMap<&str, Vec<Value>> = HashMap::new();
+    for vertex in inp.vertices.iter() {
+        vertices.entry(vertex.link_id.as_str()).or_default().push(json!([vertex.x, vertex.y]));
+    }
+    let mut demands: HashMap<&str, Vec<Value>> = HashMap::new();
+    for demand in inp.demands.iter() {
+        demands.entry(demand.junction_id.as_str()).or_default().push(json!([demand.base_demand, demand.pattern_id]));
+    }
+    let emitters = inp.emitters.iter()
+        .map(|emitter| (emitter.junction_id.as_str(), emitter.flow_coefficient))
+        .collect::<HashMap<&str, f64>>();
+
+    let node = |mut record: Record| {
+        let id = show(&record["id"]);
+        if let Some(&(x, y)) = coordinates.get(id.as_str()) {
+            record.insert("x".to_string(), json!(x));
+            record.insert("y".to_string(), json!(y));
+        }
+        if let Some(demands) = demands.get(id.as_str()) {
+            record.insert("demands".to_string(), json!(demands));
+        }
+        if let Some(coefficient) = emitters.get(id.as_str()) {
+            record.insert("emitter".to_string(), json!(coefficient));
+        }
+        record
+    };
+    let link = |mut record: Record| {
+        if let Some(vertices) = vertices.get(show(&record["id"]).as_str()) {
+            record.insert("vertices".to_string(), json!(vertices));
+        }
+        record
+    };
+
+    let mut patterns = Table::default();
+    for pattern in inp.patterns.iter() {
+        if !patterns.records.contains_key(&pattern.id) {
+            let multipliers = inp.pattern(&pattern.id).unwrap_or_default();
+            patterns.insert(pattern.id.clone(), object(json!({ "id": pattern.id, "multipliers": multipliers, "comment": pattern.comment })));
+        }
+    }
+    let mut curves = Table::default();
+    for curve in inp.curves.iter() {
+        if !curves.records.contains_key(&curve.id) {
+            let points = inp.curves.iter().filter(|point| point.id == curve.id).map(|point| json!([point.x, point.y])).collect::<Vec<Value>>();
+            curves.insert(curve.id.clone(), object(json!({ "id": curve.id, "points": points, "comment": curve.comment })));
+        }
+    }
+    let mut controls = Table::default();
+    for (control, record) in inp.controls.iter().zip(records(&inp.controls)) {
+        controls.insert(control_line(control), record);
+    }
+    let mut rules = Table::default();
+    let mut rule: Option<(String, Vec<String>)> = None;
+    for line in inp.rules.iter() {
+        let mut text = format!("{} {}", line.keyword, line.words.join(" "));
+        if let Some(comment) = &line.comment {
+            text.push_str(&format!(" ;{}", comment));
+        }
+        match (&mut rule, line.keyword.as_str()) {
+            (_, "RULE") => {
+                if let Some((id, lines)) = rule.take() {
+                    rules.insert(id.clone(), object(json!({ "id": id, "text": lines.join("\n") })));
+                }
+                rule = Some((line.words[0].clone(), vec![text]));
+            },
+            (Some((_, lines)), _) => lines.push(text),
+            (None, _) => {},
+        }
+    }
+    if let Some((id, lines)) = rule {
+        rules.insert(id.clone(), object(json!({ "id": id, "text": lines.join("\n") })));
+    }
+
+    let mut title = Table::default();
+    if !inp.title.is_empty() {
+        title.insert(String::new(), object(json!({ "title": inp.title })));
+    }
+
+    vec![
+        ("title", title),
+        ("junction", Table::keyed(records(&inp.junctions).into_iter().map(node).collect(), "id")),
+        ("reservoir", Table::keyed(records(&inp.reservoirs).into_iter().map(node).collect(), "id")),
+        ("tank", Table::keyed(records(&inp.tanks).into_iter().map(node).collect(), "id")),
+        ("pipe", Table::keyed(records(&inp.pipes).into_iter().map(link).collect(), "id")),
+        ("pump", Table::keyed(records(&inp.pumps).into_iter().map(link).collect(), "id")),
+        ("valve", Table::keyed(records(&inp.valves).into_iter().map(link).collect(), "id")),
+        ("status", Table::keyed(records(&inp.statuses), "link_id")),
+        ("pattern", patterns),
+        ("curve", curves),
+        ("control", controls),
+        ("rule", rules),
+        ("energy", settings(&inp.energy)),
+        ("quality", Table::keyed(records(&inp.quality), "nodeid")),
+        ("source", Table::keyed(records(&inp.sources), "node")),
+        ("reaction", settings(&inp.reactions)),
+        ("mixing", Table::keyed(records(&inp.mixing), "tank_id")),
+        ("time", settings(&inp.times)),
+        ("option", settings(&inp.options)),
+    ]
+}
+
+fn item<T: DeserializeOwned>(element: &str, id: &str, record: &Record) -> Result<T, String> {
+    serde_json::from_value(Value::Object(record.clone()))
+        .map_err(|error| format!("Invalid {} {}: {}", element, id, error))
+}
+
+fn number(value: &Value) -> f64 {
+    value.as_f64().unwrap_or_default()
+}
+
+fn text(value: &Value) -> Option<String> {
+    value.as_str().map(str::to_string)
+}
+
+/// Model of the element tables, the reverse of `tables`. Tags, labels, the backdrop,
+/// full-line comments and unknown sections are not part of the tables and are left empty,
+/// as are the comments of coordinates, vertices, demands and emitters.
+pub(crate) fn build(tables: &[(&str, Table)]) -> Result<INP, String> {
+    let mut inp = INP::read(String::new());
+    for (element, table) in tables.iter() {
+        for (id, record) in table.ids.iter().map(|id| (id, &table.records[id])) {
+            match *element {
+                "title" => inp.title = record.get("title").and_then(text).unwrap_or_default(),
+                "junction" => inp.junctions.push(item(element, id, record)?),
+                "reservoir" => inp.reservoirs.push(item(element, id, record)?),
+                "tank" => inp.tanks.push(item(element, id, record)?),
+                "pipe" => inp.pipes.push(item(element, id, record)?),
+                "pump" => inp.pumps.push(item(element, id, record)?),
+                "valve" => inp.valves.push(item(element, id, record)?),
+                "status" => inp.statuses.push(item(element, id, record)?),
+                "pattern" => {
+                    let multipliers = record.get("multipliers").and_then(Value::as_array).cloned().unwrap_or_default();
+                    // Six multipliers a line, as EPANET writes them.
+                    for (index, chunk) in multipliers.chunks(6).enumerate() {
+                        let comment = if index == 0 { record.get("comment").and_then(text) } else { None };
+                        inp.patterns.push(Pattern { id: id.clone(), multipliers: chunk.iter().map(number).collect(), comment });
+                    }
+                },
+                "curve" => {
+                    let points = record.get("points").and_then(Value::as_array).cloned().unwrap_or_default();
+                    for (index, point) in points.iter().enumerate() {
+                        let comment = if index == 0 { record.get("comment").and_then(text) } else { None };
+                        inp.curves.push(Curve { id: id.clone(), x: number(&point[0]), y: number(&point[1]), comment });
+                    }
+                },
+                "control" => inp.controls.push(item(element, id, record)?),
+                "rule" => {
+                    for line in record.get("text").and_then(Value::as_str).unwrap_or_default().lines() {
+                        let (words, comment) = match line.split_once(';') {
+                            Some((words, comment)) => (words, Some(comment.to_string())),
+                            None => (line, None),
+                        };
+                        let mut words = words.split_whitespace().map(str::to_string);
+                        let keyword = words.next().unwrap_or_default().to_uppercase();
+                        inp.rules.push(RuleLine { keyword, words: words.collect(), comment });
+                    }
+                },
+                "energy" => inp.energy.push(item(element, id, record)?),
+                "quality" => inp.quality.push(item(element, id, record)?),
+                "source" => inp.sources.push(item(element, id, record)?),
+                "reaction" => inp.reactions.push(item(element, id, record)?),
+                "mixing" => inp.mixing.push(item(element, id, record)?),
+                "time" => inp.times.push(item(element, id, record)?),
+                "option" => inp.options.push(item(element, id, record)?),
+                _ => {},
+            }
+            if let (Some(x), Some(y)) = (record.get("x").and_then(Value::as_f64), record.get("y").and_then(Value::as_f64)) {
+                inp.coordinates.push(Coordinate { node_id: id.clone(), x, y, comment: None });
+            }
+            for demand in record.get("demands").and_then(Value::as_array).into_iter().flatten() {
+                inp.demands.push(Demand { junction_id: id.clone(), base_demand: number(&demand[0]), pattern_id: text(&demand[1]), comment: None });
+            }
+            if let Some(flow_coefficient) = record.get("emitter").and_then(Value::as_f64) {
+                inp.emitters.push(Emitter { junction_id: id.clone(), flow_coefficient, comment: None });
+            }
+            for vertex in record.get("vertices").and_then(Value::as_array).into_iter().flatten() {
+                inp.vertices.push(Vertex { link_id: id.clone(), x: number(&vertex[0]), y: number(&vertex[1]), comment: None });
+            }
+        }
+    }
+    Ok(inp)
+}
+
+/// Key of an element that stays the same when it is renamed: the position of a node, or
+/// the end nodes and vertices of a link. Links use the new IDs of renamed nodes.
+fn identity(element: &str, record: &Record, renames: &HashMap<String, String>) -> Option<String> {
+    let rounded = |value: Option<&Value>| value.and_then(Value::as_f64).map(|value| format!("{:.3}", value));
+    match element {
+        "junction" | "reservoir" | "tank" => Some(format!("{} {}", rounded(record.get("x"))?, rounded(record.get("y"))?)),
+        "pipe" | "pump" | "valve" => {
+            let ends = NODE_FIELDS.iter()
+                .filter_map(|field| record.get(*field).map(show))
+                .map(|node| renames.get(&node).cloned().unwrap_or(node))
+                .collect::<Vec<String>>();
+            Some(format!("{} {}", ends.join(" "), record.get("vertices").map(Value::to_string).unwrap_or_default()))
+        },
+        _ => None,
+    }
+}
+
+/// Fields that differ between two versions of an element, ignoring the ID and node
+/// references that only differ by a rename.
+pub(crate) fn field_changes(old: &Record, new: &Record, renames: &HashMap<String, String>) -> Vec<FieldChange> {
+    let mut fields = old.keys().collect::<Vec<&String>>();
+    fields.extend(new.keys().filter(|field| !old.contains_key(*field)));
+    fields.into_iter()
+        .filter(|field| *field != "id")
+        .filter_map(|field| {
+            let before = old.get(field).cloned().unwrap_or(Value::Null);
+            let after = new.get(field).cloned().unwrap_or(Value::Null);
+            let renamed = match (NODE_FIELDS.contains(&field.as_str()), &before) {
+                (true, Value::String(node)) => renames.get(node).map(|node| Value::from(node.clone())),
+                _ => None,
+            };
+            if before == after || renamed.as_ref() == Some(&after) {
+                None
+            } else {
+                Some(FieldChange { field: field.clone(), before, after })
+            }
+        })
+        .collect()
+}
+
+fn all_fields(record: &Record, added: bool) -> Vec<FieldChange> {
+    record.iter()
+        .map(|(field, value)| {
+            let (before, after) = if added { (Value::Null, value.clone()) } else { (value.clone(), Value::Null) };
+            FieldChange { field: field.clone(), before, after }
+        })
+        .collect()
+}
+
+/// Structural diff of two models: elements are matched by ID, or by position and
+/// topology when they were renamed, and compared field by field.
+pub fn diff(old: &INP, new: &INP) -> ModelDiff {
+    let mut changes = Vec::new();
+    let mut renames: HashMap<String, String> = HashMap::new();
+
+    for ((element, old_table), (_, new_table)) in tables(old).into_iter().zip(tables(new)) {
+        let removed = old_table.ids.iter().filter(|id| !new_table.records.contains_key(*id)).collect::<Vec<&String>>();
+        let added = new_table.ids.iter().filter(|id| !old_table.records.contains_key(*id)).collect::<Vec<&String>>();
+
+        // A removed and an added element with the same unique identity are a rename.
+        let unique = |ids: &[&String], table: &Table, renames: &HashMap<String, String>| {
+            let mut keys: HashMap<String, Vec<String>> = HashMap::new();
+            for id in ids {
+                if let Some(key) = identity(element, &table.records[*id], renames) {
+                    keys.entry(key).or_default().push(id.to_string());
+                }
+            }
+            keys.into_iter().filter(|(_, ids)| ids.len() == 1).map(|(key, ids)| (key, ids[0].clone())).collect::<HashMap<String, String>>()
+        };
+        let new_keys = unique(&added, &new_table, &renames);
+        let mut renamed: HashMap<String, String> = HashMap::new();
+        for (key, old_id) in unique(&removed, &old_table, &renames) {
+            if let Some(new_id) = new_keys.get(&key) {
+                renamed.insert(old_id, new_id.clone());
+            }
+        }
+        let renamed_to = renamed.values().cloned().collect::<HashSet<String>>();
+
+        for id in old_table.ids.iter() {
+            let old_record = &old_table.records[id];
+            let (kind, new_id, new_record) = match (new_table.records.get(id), renamed.get(id)) {
+                (Some(record), _) => (ChangeKind::Modified, None, record),
+                (None, Some(new_id)) => (ChangeKind::Renamed, Some(new_id.clone()), &new_table.records[new_id]),
+                (None, None) => {
+                    changes.push(Change { element: element.to_string(), kind: ChangeKind::Removed, id: id.clone(), new_id: None, fields: all_fields(old_record, false) });
+                    continue;
+                },
+            };
+            let fields = field_changes(old_record, new_record, &renames);
+            if kind == ChangeKind::Renamed || !fields.is_empty() {
+                changes.push(Change { element: element.to_string(), kind, id: id.clone(), new_id, fields });
+            }
+        }
+        for id in added.into_iter().filter(|id| !renamed_to.contains(*id)) {
+            changes.push(Change { element: element.to_string(), kind: ChangeKind::Added, id: id.clone(), new_id: None, fields: all_fields(&new_table.records[id], true) });
+        }
+        renames.extend(renamed);
+    }
+
+    ModelDiff { changes }
+}
+
+#[cfg(test)]
+mod test {
+    use std::fs;
+    use serde_json::json;
+    use super::{diff, ChangeKind, FieldChange};
+    use crate::INP;
+
+    fn a_model() -> String {
+        r#"
+[JUNCTIONS]
+J1  10  1
+J2  20  1
+J3  30
+[RESERVOIRS]
+R1  100
+[PIPES]
+P1  R1  J1  100  150  100
+P2  J1  J2  100  150  100
+P3  J2  J3  100  100  100
+[PATTERNS]
+PAT1  1  1.2
+[CONTROLS]
+LINK P3 CLOSED AT TIME 6
+[OPTIONS]
+Units  LPS
+[COORDINATES]
+J1  0  0
+J2  10  0
+J3  20  0
+R1  -10  0
+"#.to_string()
+    }
+
+    #[test]
+    fn compare_elements_by_id() {
+        let old = INP::read(a_model());
+        let new = INP::read(a_model()
+            .replace("J1  10  1", "J1  12  1")
+            .replace("P3  J2  J3  100  100  100", "P4  J3  J2  100  100  100")
+            .replace("[RESERVOIRS]", "J5  5\n[RESERVOIRS]"));
+
+        let diff = diff(&old, &new);
+
+        let elevation = diff.change("junction", "J1").unwrap();
+        assert_eq!(elevation.kind, ChangeKind::Modified);
+        assert_eq!(elevation.fields, vec![FieldChange { field: "elevation".to_string(), before: json!(10.0), after: json!(12.0) }]);
+        assert_eq!(diff.change("junction", "J5").map(|change| change.kind), Some(ChangeKind::Added));
+        assert_eq!(diff.change("pipe", "P3").map(|change| change.kind), Some(ChangeKind::Removed));
+        assert_eq!(diff.change("pipe", "P4").map(|change| change.kind), Some(ChangeKind::Added));
+        assert_eq!(diff.changes.len(), 4);
+    }
+
+    #[test]
+    fn detect_renamed_elements() {
+        let old = INP::read(a_model());
+        let new = INP::read(a_model().replace("J2", "J2b").replace("P2", "P20").replace("J3  30", "J3  31"));
+
+        let diff = diff(&old, &new);
+
+        let node = diff.change("junction", "J2").unwrap();
+        assert_eq!((node.kind, node.new_id.as_deref()), (ChangeKind::Renamed, Some("J2b")));
+        assert!(node.fields.is_empty());
+        let pipe = diff.change("pipe", "P2").unwrap();
+        assert_eq!((pipe.kind, pipe.new_id.as_deref()), (ChangeKind::Renamed, Some("P20")));
+        assert!(pipe.fields.is_empty());
+        // P3 kept its ID, its end J2 was only renamed.
+        assert!(diff.change("pipe", "P3").is_none());
+        assert_eq!(diff.changes.len(), 3);
+        assert!(diff.report().contains("Junctions\n  > J2 -> J2b\n  ~ J3: elevation 30 -> 31\nPipes\n  > P2 -> P20\n"));
+    }
+
+    #[test]
+    fn compare_options_patterns_and_controls() {
+        let old = INP::read(a_model());
+        let new = INP::read(a_model()
+            .replace("PAT1  1  1.2", "PAT1  1  1.3")
+            .replace("AT TIME 6", "AT TIME 7")
+            .replace("Units  LPS", "Units  GPM"));
+
+        let diff = diff(&old, &new);
+
+        assert_eq!(diff.change("pattern", "PAT1").unwrap().fields[0].after, json!([1.0, 1.3]));
+        assert_eq!(diff.change("control", "LINK P3 CLOSED AT TIME 6:00:00").unwrap().kind, ChangeKind::Removed);
+        assert_eq!(diff.change("control", "LINK P3 CLOSED AT TIME 7:00:00").unwrap().kind, ChangeKind::Added);
+        assert_eq!(diff.report().lines().last(), Some("  ~ UNITS: value LPS -> GPM"));
+        assert_eq!(diff.to_json()["changes"][0]["kind"], "Modified");
+    }
+
+    #[test]
+    fn compare_a_model_with_itself() {
+        let text = fs::read_to_string("tests/MagneticIslandEnhanced.inp").unwrap();
+
+        assert!(diff(&INP::read(text.clone()), &INP::read(text)).is_empty());
+    }
+}
@@ -0,0 +1,263 @@
+use serde::{Serialize, Deserialize};
+use crate::{INP, ToJson};
+use crate::hydraulics::{self, interpolate, HydraulicError, HydraulicModel, LinkType, State};
+use crate::units::{Quantity, UnitSystem};
+
+// Unit weight of water, in kN/m3.
+const UNIT_WEIGHT: f64 = 9.81;
+const CUBIC_METRES_PER_MILLION_GALLONS: f64 = 3785.411784;
+
+/// Energy use of a pump over a simulation, like a row of EPANET's energy report.
+#[derive(Serialize, Deserialize, Debug, PartialEq, Clone)]
+pub struct PumpEnergy {
+    pub id: String,
+    /// Percentage of the time the pump is running.
+    pub utilization: f64,
+    /// Average efficiency while running, in percent.
+    pub efficiency: f64,
+    /// Energy per volume pumped, in kWh/m3, or kWh/Mgal for US units.
+    pub energy_per_volume: f64,
+    /// Average power while running, in kW.
+    pub average_power: f64,
+    pub peak_power: f64,
+    pub daily_cost: f64,
+}
+
+#[derive(Serialize, Deserialize, Debug, PartialEq, Clone)]
+pub struct EnergyReport {
+    pub pumps: Vec<PumpEnergy>,
+    /// DEMAND CHARGE times the peak power of all the pumps together.
+    pub demand_charge: f64,
+    /// Daily cost of every pump plus the demand charge.
+    pub total_cost: f64,
+}
+
+impl EnergyReport {
+    pub fn pump(&self, id: &str) -> Option<&PumpEnergy> {
+        self.pumps.iter().find(|pump| pump.id == id)
+    }
+}
+
+impl ToJson for EnergyReport {}
+
+fn energy_pattern(inp: &INP, key: &str) -> Option<String> {
+    inp.energy.iter()
+        .find(|setting| setting.key == key)
+        .and_then(|setting| setting.value.split_whitespace().next().map(|id| id.to_string()))
+}
+
+/// Value of a `PUMP <id> <keyword> <value>` line of [ENERGY].
+fn pump_parameter<'a>(inp: &'a INP, id: &str, keyword: &str) -> Option<&'a str> {
+    inp.energy.iter()
+        .filter(|setting| setting.key == "PUMP")
+        .find_map(|setting| {
+            let words = setting.value.split_whitespace().collect::<Vec<&str>>();
+            match words[..] {
+                [pump, parameter, value, ..] if pump == id && parameter.to_uppercase() == keyword => Some(value),
+                _ => None,
+            }
+        })
+}
+
+/// Energy parameters of a pump, gathered from [ENERGY] and [CURVES].
+struct PumpPricing {
+    link: usize,
+    efficiency_curve: Option<Vec<(f64, f64)>>,
+    price: f64,
+    pattern: Option<usize>,
+}
+
+#[derive(Default)]
+struct PumpTotals {
+    hours: f64,
+    efficiency: f64,
+    energy: f64,
+    volume: f64,
+    peak: f64,
+    cost: f64,
+}
+
+/// Per-pump utilization, efficiency, energy and cost over the extended-period simulation
+/// of an INP, using the efficiency curves, prices, price patterns and demand charge of [ENERGY].
+pub fn energy_report(inp: &INP) -> Result<EnergyReport, HydraulicError> {
+    let model = HydraulicModel::new(inp)?;
+    let pattern = |id: Option<String>| -> Result<Option<usize>, HydraulicError> {
+        match id {
+            Some(id) => model.pattern_ids.iter().position(|pattern| *pattern == id)
+                .map(Some)
+                .ok_or_else(|| HydraulicError { message: format!("Pattern {} not found", id) }),
+            None => Ok(None),
+        }
+    };
+    let global_efficiency = inp.energy_number("GLOBAL EFFICIENCY").unwrap_or(75.0);
+    let global_price = inp.energy_number("GLOBAL PRICE").unwrap_or(0.0);
+    let global_pattern = pattern(energy_pattern(inp, "GLOBAL PATTERN"))?;
+    let specific_gravity = inp.option_number("SPECIFIC GRAVITY").unwrap_or(1.0);
+
+    let mut pumps = Vec::new();
+    for (k, link) in model.links.iter().enumerate() {
+        if !matches!(link.link_type, LinkType::Pump(_)) {
+            continue;
+        }
+        let id = &model.network.links[k].id;
+        let efficiency_curve = match pump_parameter(inp, id, "EFFICIENCY") {
+            Some(curve) => {
+                let points = inp.curves.iter().filter(|point| point.id == curve).map(|point| (point.x, point.y)).collect::<Vec<(f64, f64)>>();
+                if points.is_empty() {
+                    return Err(HydraulicError { message: format!("Curve {} not found", curve) });
+                }
+                Some(points)
+            },
+            None => None,
+        };
+        let price = pump_parameter(inp, id, "PRICE").and_then(|value| value.parse::<f64>().ok()).unwrap_or(global_price);
+        let pump_pattern = match pump_parameter(inp, id, "PATTERN") {
+            Some(id) => pattern(Some(id.to_string()))?,
+            None => global_pattern,
+        };
+        pumps.push(PumpPricing { link: k, efficiency_curve, price, pattern: pump_pattern });
+    }
+
+    let flow_factor = Quantity::Flow.si_factor(model.flow_units, model.formula);
+    let duration = model.times.duration;
+    let mut totals = pumps.iter().map(|_| PumpTotals::default()).collect::<Vec<PumpTotals>>();
+    let mut peak_total: f64 = 0.0;
+    hydraulics::run(&model, |solver, _, time, step| {
+        // A steady-state run counts as one hour.
+        let hours = if duration == 0 { 1.0 } else if time < duration { step as f64 / 3600.0 } else { return };
+        let period = model.period(time);
+        let mut total = 0.0;
+        for (pump, totals) in pumps.iter().zip(totals.iter_mut()) {
+            let k = pump.link;
+            if matches!(solver.states[k], State::Closed | State::TempClosed) {
+                continue;
+            }
+            let link = &model.links[k];
+            let flow = solver.flows[k].abs();
+            let head = (solver.heads[link.end] - solver.heads[link.start]).abs();
+            let speed = solver.settings[k].unwrap_or(1.0);
+            let efficiency = match &pump.efficiency_curve {
+                Some(curve) => {
+                    let efficiency = interpolate(curve, flow / speed / flow_factor);
+                    // Sarbu and Borza adjustment of the efficiency for the pump speed.
+                    100.0 - (100.0 - efficiency) * (1.0 / speed).powf(0.1)
+                },
+                None => global_efficiency,
+            }.clamp(1.0, 100.0) / 100.0;
+            let power = UNIT_WEIGHT * specific_gravity * flow * head / efficiency;
+            let price = pump.price * pump.pattern.map(|pattern| model.multiplier(pattern, period)).unwrap_or(1.0);
+
+            totals.hours += hours;
+            totals.efficiency += efficiency * hours;
+            totals.energy += power * hours;
+            totals.volume += flow * hours * 3600.0;
+            totals.peak = totals.peak.max(power);
+            totals.cost += power * hours * price;
+            total += power;
+        }
+        peak_total = peak_total.max(total);
+    })?;
+
+    let hours = if duration == 0 { 1.0 } else { duration as f64 / 3600.0 };
+    let volume_factor = if model.flow_units.system() == UnitSystem::Us { 1.0 / CUBIC_METRES_PER_MILLION_GALLONS } else { 1.0 };
+    let pumps = pumps.iter().zip(totals.iter())
+        .map(|(pump, totals)| {
+            let running = totals.hours > 0.0;
+            PumpEnergy {
+                id: model.network.links[pump.link].id.clone(),
+                utilization: 100.0 * totals.hours / hours,
+                efficiency: if running { 100.0 * totals.efficiency / totals.hours } else { 0.0 },
+                energy_per_volume: if totals.volume > 0.0 { totals.energy / (totals.volume * volume_factor) } else { 0.0 },
+                average_power: if running { totals.energy / totals.hours } else { 0.0 },
+                peak_power: totals.peak,
+                daily_cost: totals.cost * 24.0 / hours,
+            }
+        })
+        .collect::<Vec<PumpEnergy>>();
+    let demand_charge = inp.energy_number("DEMAND CHARGE").unwrap_or(0.0) * peak_total;
+    let total_cost = pumps.iter().map(|pump| pump.daily_cost).sum::<f64>() + demand_charge;
+
+    Ok(EnergyReport { pumps, demand_charge, total_cost })
+}
+
+#[cfg(test)]
+mod test {
+    use std::fs;
+    use super::energy_report;
+    use crate::INP;
+
+    // 50 L/s lifted by 40 m.
+    fn a_model(energy: &str) -> String {
+        format!(r#"
+[JUNCTIONS]
+J1  0  50
+[RESERVOIRS]
+R1  0
+[PUMPS]
+PU1  R1  J1  HEAD C1
+[CURVES]
+C1  50  40
+E1  0   50
+E1  100 90
+[PATTERNS]
+Price  1  1  0.5  0.5
+[TIMES]
+Duration  4:00
+[OPTIONS]
+Units  LPS
+[ENERGY]
+{}
+"#, energy)
+    }
+
+    #[test]
+    fn report_the_energy_of_a_pump() {
+        let report = energy_report(&INP::read(a_model("Global Price  0.2"))).unwrap();
+
+        let pump = report.pump("PU1").unwrap();
+        let power = 9.81 * 0.05 * 40.0 / 0.75;
+        assert!((pump.utilization - 100.0).abs() < 1e-9);
+        assert!((pump.efficiency - 75.0).abs() < 1e-9);
+        assert!((pump.average_power - power).abs() < 1e-3, "{} {}", pump.average_power, power);
+        assert!((pump.peak_power - power).abs() < 1e-3);
+        assert!((pump.energy_per_volume - power / (0.05 * 3600.0)).abs() < 1e-5);
+        assert!((pump.daily_cost - power * 24.0 * 0.2).abs() < 1e-2);
+        assert_eq!(report.demand_charge, 0.0);
+        assert_eq!(report.total_cost, pump.daily_cost);
+    }
+
+    #[test]
+    fn use_efficiency_curves_price_patterns_and_the_demand_charge() {
+        let report = energy_report(&INP::read(a_model("Pump PU1 Efficiency E1\nPump PU1 Price 0.2\nPump PU1 Pattern Price\nDemand Charge  10"))).unwrap();
+
+        let pump = report.pump("PU1").unwrap();
+        let power = 9.81 * 0.05 * 40.0 / 0.7;
+        assert!((pump.efficiency - 70.0).abs() < 1e-3);
+        assert!((pump.daily_cost - power * 24.0 * 0.2 * 0.75).abs() < 1e-2, "{}", pump.daily_cost);
+        assert!((report.demand_charge - power * 10.0).abs() < 1e-2);
+        assert!((report.total_cost - pump.daily_cost - report.demand_charge).abs() < 1e-9);
+    }
+
+    #[test]
+    fn closed_pumps_use_no_energy() {
+        let input = a_model("Global Price  0.2")
+            .replace("[PUMPS]", "R2  30\n[PIPES]\nP1  R2  J1  100  300  130\n[PUMPS]")
+            + "[CONTROLS]\nLINK PU1 CLOSED AT TIME 2\n";
+        let report = energy_report(&INP::read(input)).unwrap();
+
+        let pump = report.pump("PU1").unwrap();
+        assert!((pump.utilization - 50.0).abs() < 1e-9);
+        assert!((pump.daily_cost - pump.average_power * 12.0 * 0.2).abs() < 1e-6);
+    }
+
+    #[test]
+    fn report_the_energy_of_magnetic_island() {
+        let inp = INP::read(fs::read_to_string("tests/MagneticIslandEnhanced.inp").unwrap());
+
+        let report = energy_report(&inp).unwrap();
+
+        assert_eq!(report.pumps.len(), 5);
+        assert!(report.pumps.iter().all(|pump| pump.utilization >= 0.0 && pump.utilization <= 100.0 + 1e-9));
+        assert!(report.pumps.iter().any(|pump| pump.average_power > 0.0));
+    }
+}
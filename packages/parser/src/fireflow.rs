@@ -0,0 +1,209 @@
+use serde::{Serialize, Deserialize};
+use crate::{INP, ToJson};
+use crate::hydraulics::{DemandModel, HydraulicError, HydraulicModel, Solver};
+use crate::units::Quantity;
+
+/// Parameters of a fire-flow analysis, in the units of the INP.
+#[derive(Serialize, Deserialize, Debug, PartialEq, Clone, Copy)]
+pub struct FireFlowOptions {
+    /// Pressure every junction must keep while the fire flow is drawn.
+    pub residual_pressure: f64,
+    /// Largest fire flow looked for.
+    pub maximum_flow: f64,
+    /// The available flow is found within this flow.
+    pub tolerance: f64,
+}
+
+/// Row of the fire-flow table.
+#[derive(Serialize, Deserialize, Debug, PartialEq, Clone)]
+pub struct FireFlow {
+    pub junction_id: String,
+    /// Largest demand added to the junction that keeps every junction above the
+    /// residual pressure.
+    pub available_flow: f64,
+    /// Pressure at the junction while the available flow is drawn.
+    pub residual_pressure: f64,
+    /// Junction whose pressure first drops below the residual pressure, `None` when
+    /// the maximum flow is available.
+    pub limiting_node: Option<String>,
+}
+
+#[derive(Serialize, Deserialize, Debug, PartialEq, Clone)]
+pub struct FireFlowResults {
+    pub rows: Vec<FireFlow>,
+}
+
+impl FireFlowResults {
+    pub fn row(&self, junction_id: &str) -> Option<&FireFlow> {
+        self.rows.iter().find(|row| row.junction_id == junction_id)
+    }
+}
+
+impl ToJson for FireFlowResults {}
+
+/// Outcome of a hydraulic solve with an extra demand.
+struct Trial {
+    feasible: bool,
+    /// Pressure at the fire junction.
+    pressure: f64,
+    /// Junction with the lowest pressure.
+    lowest: Option<usize>,
+}
+
+fn trial(model: &HydraulicModel, node: usize, flow: f64, options: &FireFlowOptions) -> Trial {
+    let flow_factor = Quantity::Flow.si_factor(model.flow_units, model.formula);
+    let pressure_factor = Quantity::Pressure.si_factor(model.flow_units, model.formula);
+    let mut solver = Solver::new(model);
+    solver.demands[node] += flow * flow_factor;
+    solver.demand_flows[node] += flow * flow_factor;
+    match solver.run() {
+        Ok(convergence) if convergence.converged => {
+            let pressure = |i: usize| (solver.heads[i] - model.elevations[i]) / pressure_factor;
+            let lowest = (0..model.junction_count).min_by(|&a, &b| pressure(a).total_cmp(&pressure(b)));
+            Trial {
+                feasible: lowest.is_none_or(|lowest| pressure(lowest) >= options.residual_pressure),
+                pressure: pressure(node),
+                lowest,
+            }
+        },
+        _ => Trial { feasible: false, pressure: f64::NEG_INFINITY, lowest: None },
+    }
+}
+
+/// Available fire flow at each junction: the largest extra demand, found by bracketing
+/// over repeated steady-state solves at the start of the simulation, for which every
+/// junction stays above the residual pressure. Demands are always delivered in full.
+pub fn fire_flow(inp: &INP, junction_ids: &[String], options: &FireFlowOptions) -> Result<FireFlowResults, HydraulicError> {
+    if options.tolerance <= 0.0 {
+        return Err(HydraulicError { message: format!("Fire-flow tolerance must be positive, got {}", options.tolerance) });
+    }
+    if options.maximum_flow <= 0.0 {
+        return Err(HydraulicError { message: format!("Maximum fire flow must be positive, got {}", options.maximum_flow) });
+    }
+    let mut model = HydraulicModel::new(inp)?;
+    model.options.demand_model = DemandModel::Dda;
+
+    let mut rows = Vec::new();
+    for id in junction_ids.iter() {
+        let node = model.network.node(id)
+            .filter(|&node| model.is_junction(node))
+            .ok_or_else(|| HydraulicError { message: format!("Junction {} not found", id) })?;
+        let node_id = |trial: &Trial| trial.lowest.map(|lowest| model.network.nodes[lowest].id.clone());
+
+        let mut low = trial(&model, node, 0.0, options);
+        let mut high = trial(&model, node, options.maximum_flow, options);
+        let (mut low_flow, mut high_flow) = (0.0, options.maximum_flow);
+        let row = if high.feasible {
+            FireFlow { junction_id: id.clone(), available_flow: high_flow, residual_pressure: high.pressure, limiting_node: None }
+        } else if !low.feasible {
+            FireFlow { junction_id: id.clone(), available_flow: 0.0, residual_pressure: low.pressure, limiting_node: node_id(&low) }
+        } else {
+            while high_flow - low_flow > options.tolerance {
+                let flow = (low_flow + high_flow) / 2.0;
+                let middle = trial(&model, node, flow, options);
+                if middle.feasible {
+                    (low, low_flow) = (middle, flow);
+                } else {
+                    (high, high_flow) = (middle, flow);
+                }
+            }
+            FireFlow { junction_id: id.clone(), available_flow: low_flow, residual_pressure: low.pressure, limiting_node: node_id(&high) }
+        };
+        rows.push(row);
+    }
+
+    Ok(FireFlowResults { rows })
+}
+
+#[cfg(test)]
+mod test {
+    use std::fs;
+    use super::{fire_flow, FireFlowOptions};
+    use crate::INP;
+
+    fn a_model() -> INP {
+        INP::read(r#"
+[JUNCTIONS]
+J1  0  10
+J2  5  10
+[RESERVOIRS]
+R1  60
+[PIPES]
+P1  R1  J1  1000  300  130
+P2  J1  J2  500  150  130
+[OPTIONS]
+Units  LPS
+Headloss  H-W
+"#.to_string())
+    }
+
+    fn options() -> FireFlowOptions {
+        FireFlowOptions { residual_pressure: 20.0, maximum_flow: 1000.0, tolerance: 0.01 }
+    }
+
+    // Hazen-Williams headloss, in m, and its inverse, with flows in L/s.
+    fn headloss(flow: f64, length: f64, diameter: f64) -> f64 {
+        10.67 * length * (flow / 1000.0).powf(1.852) / (130_f64.powf(1.852) * diameter.powf(4.871))
+    }
+
+    fn flow(headloss: f64, length: f64, diameter: f64) -> f64 {
+        (headloss * 130_f64.powf(1.852) * diameter.powf(4.871) / (10.67 * length)).powf(1.0 / 1.852) * 1000.0
+    }
+
+    #[test]
+    fn find_the_available_flow_and_the_limiting_node() {
+        let results = fire_flow(&a_model(), &["J1".to_string(), "J2".to_string()], &options()).unwrap();
+
+        let at_j2 = results.row("J2").unwrap();
+        assert_eq!(at_j2.limiting_node.as_deref(), Some("J2"));
+        assert!(at_j2.residual_pressure >= 20.0 && at_j2.residual_pressure < 20.1);
+        assert!(at_j2.available_flow > 0.0);
+
+        // J2 sits 5 m higher: it limits a fire at J1 once J1 drops to a head of 25 m
+        // plus the headloss of P2.
+        let at_j1 = results.row("J1").unwrap();
+        assert_eq!(at_j1.limiting_node.as_deref(), Some("J2"));
+        let expected = flow(60.0 - 25.0 - headloss(10.0, 500.0, 0.15), 1000.0, 0.3) - 20.0;
+        assert!((at_j1.available_flow - expected).abs() < 0.5, "{} {}", at_j1.available_flow, expected);
+    }
+
+    #[test]
+    fn cap_the_flow_at_the_maximum() {
+        let options = FireFlowOptions { maximum_flow: 10.0, ..options() };
+        let results = fire_flow(&a_model(), &["J1".to_string()], &options).unwrap();
+
+        assert_eq!(results.rows[0].available_flow, 10.0);
+        assert_eq!(results.rows[0].limiting_node, None);
+    }
+
+    #[test]
+    fn no_flow_is_available_below_the_residual_pressure() {
+        let options = FireFlowOptions { residual_pressure: 80.0, ..options() };
+        let results = fire_flow(&a_model(), &["J1".to_string()], &options).unwrap();
+
+        assert_eq!(results.rows[0].available_flow, 0.0);
+        assert!(results.rows[0].limiting_node.is_some());
+        assert!(fire_flow(&a_model(), &["R1".to_string()], &options).is_err());
+    }
+
+    #[test]
+    fn reject_options_that_never_end_the_search() {
+        let zero_tolerance = FireFlowOptions { tolerance: 0.0, ..options() };
+        let no_flow = FireFlowOptions { maximum_flow: -10.0, ..options() };
+
+        let error = fire_flow(&a_model(), &["J1".to_string()], &zero_tolerance).unwrap_err();
+
+        assert_eq!(error.message, "Fire-flow tolerance must be positive, got 0");
+        assert!(fire_flow(&a_model(), &["J1".to_string()], &no_flow).is_err());
+    }
+
+    #[test]
+    fn fire_flow_of_magnetic_island() {
+        let inp = INP::read(fs::read_to_string("tests/MagneticIslandEnhanced.inp").unwrap());
+        let options = FireFlowOptions { residual_pressure: 0.0, maximum_flow: 200.0, tolerance: 1.0 };
+
+        let results = fire_flow(&inp, &[inp.junctions[100].id.clone()], &options).unwrap();
+
+        assert!(results.rows[0].available_flow >= 0.0 && results.rows[0].available_flow <= 200.0);
+    }
+}
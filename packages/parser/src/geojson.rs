@@ -0,0 +1,562 @@
+use std::collections::{HashMap, HashSet};
+use std::error::Error;
+use std::fmt;
+use serde::Serialize;
+use serde::de::DeserializeOwned;
+use serde_json::{json, Map, Value};
+use crate::{INP, Sectionable};
+use crate::geometry::{Geometry, Point};
+use crate::sections::{Coordinate, Junction, Pipe, Pump, Reservoir, Tank, Valve, Vertex};
+
+#[derive(Debug, PartialEq)]
+pub struct GeoJsonError {
+    pub message: String,
+}
+
+impl fmt::Display for GeoJsonError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.message)
+    }
+}
+
+impl Error for GeoJsonError {}
+
+impl From<serde_json::Error> for GeoJsonError {
+    fn from(error: serde_json::Error) -> Self {
+        GeoJsonError { message: error.to_string() }
+    }
+}
+
+/// Name of a coordinate reference system in the `crs` member of GeoJSON, e.g.
+/// `urn:ogc:def:crs:EPSG::20255` for `EPSG:20255`.
+pub fn crs_name(projection: &str) -> String {
+    match projection.split_once(':') {
+        Some((authority, code)) => format!("urn:ogc:def:crs:{}::{}", authority.to_uppercase(), code),
+        None => projection.to_string(),
+    }
+}
+
+/// Properties of a feature: every attribute of the section, plus its element type.
+fn properties<T: Serialize>(element: &str, item: &T) -> Map<String, Value> {
+    let mut properties = match serde_json::to_value(item).unwrap() {
+        Value::Object(properties) => properties,
+        _ => Map::new(),
+    };
+    properties.insert("element".to_string(), Value::from(element));
+    properties
+}
+
+fn feature(id: &str, geometry: Value, properties: Map<String, Value>) -> Value {
+    json!({
+        "type": "Feature",
+        "id": id,
+        "geometry": geometry,
+        "properties": properties,
+    })
+}
+
+fn point(point: Option<Point>) -> Value {
+    match point {
+        Some((x, y)) => json!({ "type": "Point", "coordinates": [x, y] }),
+        None => Value::Null,
+    }
+}
+
+fn line_string(polyline: Option<Vec<Point>>) -> Value {
+    match polyline {
+        Some(polyline) => json!({
+            "type": "LineString",
+            "coordinates": polyline.iter().map(|&(x, y)| vec![x, y]).collect::<Vec<Vec<f64>>>(),
+        }),
+        None => Value::Null,
+    }
+}
+
+/// Junctions, reservoirs and tanks as Points and pipes, pumps and valves as LineStrings
+/// through their vertices. Elements without coordinates have a null geometry.
+pub fn feature_collection(inp: &INP) -> Value {
+    let geometry = Geometry::new(inp);
+    let node = |id: &str, properties: Map<String, Value>| feature(id, point(geometry.node(id)), properties);
+    let link = |id: &str, properties: Map<String, Value>| feature(id, line_string(geometry.link(id)), properties);
+
+    let features = inp.junctions.iter().map(|junction| node(&junction.id, properties("junction", junction)))
+        .chain(inp.reservoirs.iter().map(|reservoir| node(&reservoir.id, properties("reservoir", reservoir))))
+        .chain(inp.tanks.iter().map(|tank| node(&tank.id, properties("tank", tank))))
+        .chain(inp.pipes.iter().map(|pipe| link(&pipe.id, properties("pipe", pipe))))
+        .chain(inp.pumps.iter().map(|pump| link(&pump.id, properties("pump", pump))))
+        .chain(inp.valves.iter().map(|valve| link(&valve.id, properties("valve", valve))))
+        .collect::<Vec<Value>>();
+
+    let mut collection = json!({
+        "type": "FeatureCollection",
+        "features": features,
+    });
+    if let Some(projection) = inp.projection() {
+        collection["crs"] = json!({ "type": "name", "properties": { "name": crs_name(projection) } });
+    }
+    collection
+}
+
+/// GeoJSON FeatureCollection of the nodes and links of an INP.
+pub fn to_geojson(inp: &INP) -> String {
+    serde_json::to_string(&feature_collection(inp)).unwrap()
+}
+
+/// How GeoJSON features become nodes and links.
+#[derive(Debug, PartialEq, Clone, Default)]
+pub struct ImportOptions {
+    /// Line ends within this distance of a node are connected to it.
+    pub snap_tolerance: f64,
+    /// Property of the features to attribute of a section, e.g. `diam_mm` to `diameter`.
+    /// Properties already named like an attribute, or `element`, are used as they are.
+    pub mappings: HashMap<String, String>,
+}
+
+/// Projection of a title line for the `crs` member of GeoJSON, the inverse of `crs_name`.
+fn projection_of_crs(name: &str) -> String {
+    match name.strip_prefix("urn:ogc:def:crs:").and_then(|name| name.split_once("::")) {
+        Some((authority, code)) => format!("{}:{}", authority, code),
+        None => name.to_string(),
+    }
+}
+
+/// Section with every attribute set, giving the kind of each value, the attributes left
+/// unset when a feature doesn't give them and those that keep the default of an INP.
+/// Features have to give every other attribute.
+fn template(element: &str) -> Option<(Value, &'static [&'static str], &'static [&'static str])> {
+    let comment = Some(String::new());
+    let value = match element {
+        "junction" => serde_json::to_value(Junction::from_section(vec!["", "0", "0", ""], comment).ok()?),
+        "reservoir" => serde_json::to_value(Reservoir::from_section(vec!["", "0", ""], comment).ok()?),
+        "tank" => serde_json::to_value(Tank::from_section(vec!["", "0", "0", "0", "0", "0", "0", "", "NO"], comment).ok()?),
+        "pipe" => serde_json::to_value(Pipe::from_section(vec!["", "", "", "0", "12", "100", "0", "OPEN"], comment).ok()?),
+        "pump" => serde_json::to_value(Pump::from_section(vec!["", "", "", "POWER", "0", "HEAD", "", "SPEED", "1", "PATTERN", ""], comment).ok()?),
+        "valve" => serde_json::to_value(Valve::from_section(vec!["", "", "", "12", "PRV", "0", "0"], comment).ok()?),
+        _ => return None,
+    }.ok()?;
+    let unset: &[&str] = match element {
+        "junction" => &["base_demand_flow", "demand_pattern_id", "comment"],
+        "reservoir" => &["pattern", "comment"],
+        "tank" => &["volume_curve_id", "comment"],
+        "pump" => &["power", "head", "speed", "pattern", "comment"],
+        _ => &["comment"],
+    };
+    let defaults: &[&str] = match element {
+        "tank" => &["min_volume", "overflow"],
+        "pipe" => &["minor_loss", "status"],
+        "valve" => &["minor_loss_coefficient"],
+        _ => &[],
+    };
+    Some((value, unset, defaults))
+}
+
+/// Converts a property to the kind of value of an attribute.
+fn coerce(attribute: &str, value: &Value, kind: &Value) -> Result<Value, GeoJsonError> {
+    let invalid = || GeoJsonError { message: format!("Invalid value {} for {}", value, attribute) };
+    Ok(match (kind, value) {
+        (Value::Number(_), Value::String(text)) => Value::from(text.trim().parse::<f64>().map_err(|_| invalid())?),
+        (Value::String(_), Value::Number(number)) => Value::from(number.to_string()),
+        (Value::Bool(_), Value::String(text)) => Value::from(matches!(text.to_uppercase().as_str(), "YES" | "TRUE" | "1")),
+        (Value::Bool(_), Value::Number(number)) => Value::from(number.as_f64() != Some(0.0)),
+        // Valve types are written PRV in an INP and Prv once serialized.
+        (Value::String(_), Value::String(text)) if attribute == "valve_type" => {
+            let text = text.to_lowercase();
+            let mut chars = text.chars();
+            Value::from(chars.next().map(|first| first.to_uppercase().chain(chars).collect::<String>()).unwrap_or_default())
+        },
+        (Value::Number(_), Value::Number(_)) | (Value::String(_), Value::String(_)) | (Value::Bool(_), Value::Bool(_)) => value.clone(),
+        _ => return Err(invalid()),
+    })
+}
+
+/// Section of an element from the attributes of a feature.
+fn build<T: DeserializeOwned>(element: &str, id: &str, attributes: &Map<String, Value>) -> Result<T, GeoJsonError> {
+    let (kinds, unset, defaults) = template(element).ok_or_else(|| GeoJsonError { message: format!("Unknown element {}", element) })?;
+    let given = |attribute: &String| attributes.get(attribute).is_some_and(|property| !property.is_null());
+    let missing = kinds.as_object().unwrap().keys()
+        .find(|attribute| *attribute != "id" && !unset.contains(&attribute.as_str()) && !defaults.contains(&attribute.as_str()) && !given(attribute));
+    if let Some(attribute) = missing {
+        return Err(GeoJsonError { message: format!("The {} {} has no {}", element, id, attribute) });
+    }
+    let mut value = kinds.clone();
+    let section = value.as_object_mut().unwrap();
+    for &attribute in unset {
+        section.insert(attribute.to_string(), Value::Null);
+    }
+    section.insert("id".to_string(), Value::from(id));
+    for (attribute, property) in attributes.iter() {
+        if attribute == "id" || property.is_null() {
+            continue;
+        }
+        if let Some(kind) = kinds.get(attribute) {
+            section.insert(attribute.clone(), coerce(attribute, property, kind)?);
+        }
+    }
+    serde_json::from_value(value).map_err(|error| GeoJsonError { message: format!("Invalid {} {}: {}", element, id, error) })
+}
+
+enum Shape {
+    Point(Point),
+    Line(Vec<Point>),
+    None,
+}
+
+struct ImportedFeature {
+    id: Option<String>,
+    element: String,
+    attributes: Map<String, Value>,
+    shape: Shape,
+}
+
+fn position(value: &Value) -> Result<Point, GeoJsonError> {
+    match value.as_array().map(|position| position.iter().map(|value| value.as_f64()).collect::<Vec<Option<f64>>>()).as_deref() {
+        Some([Some(x), Some(y), ..]) => Ok((*x, *y)),
+        _ => Err(GeoJsonError { message: format!("Invalid position {}", value) }),
+    }
+}
+
+fn shape(geometry: &Value) -> Result<Shape, GeoJsonError> {
+    let coordinates = &geometry["coordinates"];
+    let line = |coordinates: &Value| -> Result<Vec<Point>, GeoJsonError> {
+        let line = coordinates.as_array().into_iter().flatten().map(position).collect::<Result<Vec<Point>, GeoJsonError>>()?;
+        if line.len() < 2 {
+            return Err(GeoJsonError { message: "Lines need two positions".to_string() });
+        }
+        Ok(line)
+    };
+    match geometry["type"].as_str() {
+        None => Ok(Shape::None),
+        Some("Point") => Ok(Shape::Point(position(coordinates)?)),
+        Some("LineString") => Ok(Shape::Line(line(coordinates)?)),
+        Some("MultiLineString") if coordinates.as_array().is_some_and(|lines| lines.len() == 1) => Ok(Shape::Line(line(&coordinates[0])?)),
+        Some(kind) => Err(GeoJsonError { message: format!("Unsupported geometry {}", kind) }),
+    }
+}
+
+fn text(value: &Value) -> Option<String> {
+    match value {
+        Value::String(text) => Some(text.clone()),
+        Value::Number(number) => Some(number.to_string()),
+        _ => None,
+    }
+}
+
+fn read_feature(feature: &Value, options: &ImportOptions) -> Result<ImportedFeature, GeoJsonError> {
+    let properties = feature["properties"].as_object().cloned().unwrap_or_default();
+    let mut attributes = properties.clone();
+    for (property, value) in properties.into_iter() {
+        if let Some(attribute) = options.mappings.get(&property) {
+            attributes.insert(attribute.clone(), value);
+        }
+    }
+    let shape = shape(&feature["geometry"])?;
+    let element = match attributes.remove("element").as_ref().and_then(text) {
+        Some(element) => element.to_lowercase(),
+        None if matches!(shape, Shape::Line(_)) => "pipe".to_string(),
+        None => "junction".to_string(),
+    };
+    let id = attributes.get("id").and_then(text).or_else(|| text(&feature["id"])).filter(|id| !id.is_empty());
+    Ok(ImportedFeature { id, element, attributes, shape })
+}
+
+/// Unused ID made of a prefix and a number.
+fn next_id(prefix: &str, counter: &mut usize, used: &mut HashSet<String>) -> String {
+    loop {
+        *counter += 1;
+        let id = format!("{}{}", prefix, counter);
+        if used.insert(id.clone()) {
+            return id;
+        }
+    }
+}
+
+/// Builds an INP from a GeoJSON FeatureCollection. Points become junctions, reservoirs
+/// or tanks and lines become pipes, pumps or valves, following an `element` property.
+/// Line ends are connected to the nearest node within the snap tolerance, or to a new
+/// junction at elevation 0, and interior positions become [VERTICES]. Missing IDs are
+/// generated, missing attributes without an INP default are errors.
+pub fn import_features(collection: &Value, options: &ImportOptions) -> Result<INP, GeoJsonError> {
+    let features = collection["features"].as_array()
+        .ok_or_else(|| GeoJsonError { message: "Not a GeoJSON FeatureCollection".to_string() })?
+        .iter()
+        .map(|feature| read_feature(feature, options))
+        .collect::<Result<Vec<ImportedFeature>, GeoJsonError>>()?;
+    let (node_features, link_features): (Vec<ImportedFeature>, Vec<ImportedFeature>) = features.into_iter()
+        .partition(|feature| matches!(feature.element.as_str(), "junction" | "reservoir" | "tank"));
+
+    let mut inp = INP::read(String::new());
+    if let Some(name) = collection["crs"]["properties"]["name"].as_str() {
+        inp.title = format!("Q_VAR_PROJECTION={}", projection_of_crs(name));
+    }
+    let mut node_ids = node_features.iter().filter_map(|feature| feature.id.clone()).collect::<HashSet<String>>();
+    let mut link_ids = link_features.iter().filter_map(|feature| feature.id.clone()).collect::<HashSet<String>>();
+    let mut counters = HashMap::new();
+    let mut nodes = Vec::new();
+
+    for feature in node_features.iter() {
+        let prefix = match feature.element.as_str() {
+            "junction" => "J",
+            "reservoir" => "R",
+            _ => "T",
+        };
+        let id = match &feature.id {
+            Some(id) => id.clone(),
+            None => next_id(prefix, counters.entry(prefix).or_insert(0), &mut node_ids),
+        };
+        match feature.element.as_str() {
+            "junction" => inp.junctions.push(build(&feature.element, &id, &feature.attributes)?),
+            "reservoir" => inp.reservoirs.push(build(&feature.element, &id, &feature.attributes)?),
+            _ => inp.tanks.push(build(&feature.element, &id, &feature.attributes)?),
+        }
+        match feature.shape {
+            Shape::Point(point) => {
+                inp.coordinates.push(Coordinate { node_id: id.clone(), x: point.0, y: point.1, comment: None });
+                nodes.push((id, point));
+            },
+            Shape::Line(_) => return Err(GeoJsonError { message: format!("The {} {} is a line", feature.element, id) }),
+            Shape::None => (),
+        }
+    }
+
+    for feature in link_features.iter() {
+        let prefix = match feature.element.as_str() {
+            "pipe" => "P",
+            "pump" => "PU",
+            "valve" => "V",
+            element => return Err(GeoJsonError { message: format!("Unknown element {}", element) }),
+        };
+        let id = match &feature.id {
+            Some(id) => id.clone(),
+            None => next_id(prefix, counters.entry(prefix).or_insert(0), &mut link_ids),
+        };
+        let (start_key, end_key) = if prefix == "P" { ("node1", "node2") } else { ("start_node", "end_node") };
+        let mut attributes = feature.attributes.clone();
+
+        let line = match &feature.shape {
+            Shape::Line(line) => Some(line),
+            Shape::Point(_) => return Err(GeoJsonError { message: format!("The {} {} is a point", feature.element, id) }),
+            Shape::None => None,
+        };
+        for (key, index) in [(start_key, 0), (end_key, 1)] {
+            let given = attributes.get(key).and_then(text).filter(|node| nodes.iter().any(|(id, _)| id == node) || line.is_none());
+            if given.is_some() {
+                continue;
+            }
+            let line = line.ok_or_else(|| GeoJsonError { message: format!("The {} {} has no geometry", feature.element, id) })?;
+            let end = if index == 0 { line[0] } else { line[line.len() - 1] };
+            let distance = |point: &Point| ((point.0 - end.0).powi(2) + (point.1 - end.1).powi(2)).sqrt();
+            let nearest = nodes.iter()
+                .filter(|(_, point)| distance(point) <= options.snap_tolerance)
+                .min_by(|(_, a), (_, b)| distance(a).total_cmp(&distance(b)))
+                .map(|(id, _)| id.clone());
+            let node = match nearest {
+                Some(node) => node,
+                None => {
+                    let node = next_id("J", counters.entry("J").or_insert(0), &mut node_ids);
+                    let mut attributes = Map::new();
+                    attributes.insert("elevation".to_string(), Value::from(0.0));
+                    inp.junctions.push(build("junction", &node, &attributes)?);
+                    inp.coordinates.push(Coordinate { node_id: node.clone(), x: end.0, y: end.1, comment: None });
+                    nodes.push((node.clone(), end));
+                    node
+                },
+            };
+            attributes.insert(key.to_string(), Value::from(node));
+        }
+        if let Some(line) = line {
+            if prefix == "P" && attributes.get("length").is_none_or(|length| length.is_null()) {
+                let length = line.windows(2).map(|pair| ((pair[1].0 - pair[0].0).powi(2) + (pair[1].1 - pair[0].1).powi(2)).sqrt()).sum::<f64>();
+                attributes.insert("length".to_string(), Value::from(length));
+            }
+            for &(x, y) in line[1..line.len() - 1].iter() {
+                inp.vertices.push(Vertex { link_id: id.clone(), x, y, comment: None });
+            }
+        }
+
+        match prefix {
+            "P" => inp.pipes.push(build(&feature.element, &id, &attributes)?),
+            "PU" => {
+                let pump: Pump = build(&feature.element, &id, &attributes)?;
+                if pump.head.is_none() && pump.power.is_none() {
+                    return Err(GeoJsonError { message: format!("The pump {} has no head curve or power", id) });
+                }
+                inp.pumps.push(pump);
+            },
+            _ => inp.valves.push(build(&feature.element, &id, &attributes)?),
+        }
+    }
+    Ok(inp)
+}
+
+/// Builds an INP from the text of a GeoJSON FeatureCollection.
+pub fn from_geojson(content: &str, options: &ImportOptions) -> Result<INP, GeoJsonError> {
+    import_features(&serde_json::from_str(content)?, options)
+}
+
+#[cfg(test)]
+mod test {
+    use std::fs;
+    use serde_json::Value;
+    use std::collections::HashMap;
+    use super::{feature_collection, from_geojson, import_features, to_geojson, ImportOptions};
+    use crate::sections::ValveType;
+    use crate::INP;
+
+    fn a_model() -> INP {
+        INP::read(r#"
+[TITLE]
+A network
+Q_VAR_PROJECTION=EPSG:28355
+[JUNCTIONS]
+J1  10  5  ;corner
+J2  12
+[RESERVOIRS]
+R1  50
+[PIPES]
+P1  R1  J1  100  300  130
+[VALVES]
+V1  J1  J2  200  PRV  30  0
+[COORDINATES]
+R1  0  0
+J1  10  0
+J2  10  10
+[VERTICES]
+P1  5  2
+P1  7  1
+"#.to_string())
+    }
+
+    #[test]
+    fn export_nodes_as_points_and_links_as_line_strings() {
+        let collection = feature_collection(&a_model());
+
+        assert_eq!(collection["type"], "FeatureCollection");
+        let features = collection["features"].as_array().unwrap();
+        assert_eq!(features.len(), 5);
+        let j1 = &features[0];
+        assert_eq!(j1["id"], "J1");
+        assert_eq!(j1["geometry"]["type"], "Point");
+        assert_eq!(j1["geometry"]["coordinates"], serde_json::json!([10.0, 0.0]));
+        assert_eq!(j1["properties"]["element"], "junction");
+        assert_eq!(j1["properties"]["elevation"], 10.0);
+        assert_eq!(j1["properties"]["base_demand_flow"], 5.0);
+        assert_eq!(j1["properties"]["comment"], "corner");
+
+        let p1 = features.iter().find(|feature| feature["id"] == "P1").unwrap();
+        assert_eq!(p1["geometry"]["type"], "LineString");
+        assert_eq!(p1["geometry"]["coordinates"], serde_json::json!([[0.0, 0.0], [5.0, 2.0], [7.0, 1.0], [10.0, 0.0]]));
+        assert_eq!(p1["properties"]["roughness"], 130.0);
+        let v1 = features.iter().find(|feature| feature["id"] == "V1").unwrap();
+        assert_eq!(v1["properties"]["element"], "valve");
+        assert_eq!(v1["properties"]["valve_setting"], 30.0);
+    }
+
+    #[test]
+    fn carry_the_projection_of_the_title() {
+        let collection = feature_collection(&a_model());
+        assert_eq!(collection["crs"]["properties"]["name"], "urn:ogc:def:crs:EPSG::28355");
+
+        let collection = feature_collection(&INP::read("[JUNCTIONS]\nJ1  10\n".to_string()));
+        assert_eq!(collection.get("crs"), None);
+        assert_eq!(collection["features"][0]["geometry"], Value::Null);
+    }
+
+    #[test]
+    fn export_magnetic_island() {
+        let inp = INP::read(fs::read_to_string("tests/MagneticIslandEnhanced.inp").unwrap());
+
+        let geojson = serde_json::from_str::<Value>(&to_geojson(&inp)).unwrap();
+
+        assert_eq!(geojson["crs"]["properties"]["name"], "urn:ogc:def:crs:EPSG::20255");
+        let features = geojson["features"].as_array().unwrap();
+        assert_eq!(features.len(), 2050 + 2 + 4 + 1648 + 5 + 507);
+        assert!(features.iter().all(|feature| !feature["geometry"].is_null()));
+    }
+
+    #[test]
+    fn import_mapped_features() {
+        let geojson = r#"{
+            "type": "FeatureCollection",
+            "crs": { "type": "name", "properties": { "name": "urn:ogc:def:crs:EPSG::28355" } },
+            "features": [
+                { "type": "Feature", "properties": { "asset_id": "H1", "ground": "12.5" }, "geometry": { "type": "Point", "coordinates": [0, 0] } },
+                { "type": "Feature", "properties": { "ground": 8 }, "geometry": { "type": "Point", "coordinates": [100, 0] } },
+                { "type": "Feature", "properties": { "element": "Reservoir", "head": 60 }, "geometry": { "type": "Point", "coordinates": [-50, 0] } },
+                { "type": "Feature", "properties": { "diam_mm": 150, "roughness": 100 }, "geometry": { "type": "LineString", "coordinates": [[0.2, 0.1], [30, 40], [99.9, 0]] } },
+                { "type": "Feature", "id": "M2", "properties": { "diam_mm": "100", "length": 20, "roughness": 120 }, "geometry": { "type": "LineString", "coordinates": [[100, 0], [100, 50]] } },
+                { "type": "Feature", "properties": { "element": "valve", "diam_mm": 150, "valve_type": "PRV", "valve_setting": 30 }, "geometry": { "type": "LineString", "coordinates": [[-50, 0], [0, 0]] } }
+            ]
+        }"#;
+        let options = ImportOptions {
+            snap_tolerance: 0.5,
+            mappings: [("asset_id", "id"), ("ground", "elevation"), ("diam_mm", "diameter")].iter()
+                .map(|(property, attribute)| (property.to_string(), attribute.to_string()))
+                .collect::<HashMap<String, String>>(),
+        };
+
+        let inp = from_geojson(geojson, &options).unwrap();
+
+        assert_eq!(inp.projection(), Some("EPSG:28355"));
+        assert_eq!(inp.junctions.iter().map(|junction| junction.id.as_str()).collect::<Vec<&str>>(), vec!["H1", "J1", "J2"]);
+        assert_eq!(inp.junctions[0].elevation, 12.5);
+        assert_eq!(inp.junctions[1].elevation, 8.0);
+        assert_eq!(inp.reservoirs[0].head, 60.0);
+
+        let p1 = &inp.pipes[0];
+        assert_eq!((p1.id.as_str(), p1.node1.as_str(), p1.node2.as_str()), ("P1", "H1", "J1"));
+        assert_eq!(p1.diameter, 150.0);
+        assert_eq!(p1.roughness, 100.0);
+        assert!((p1.length - (29.8_f64.hypot(39.9) + 69.9_f64.hypot(40.0))).abs() < 1e-9);
+        assert_eq!(inp.vertices.len(), 1);
+        assert_eq!((inp.vertices[0].link_id.as_str(), inp.vertices[0].x), ("P1", 30.0));
+
+        // The end of M2 isn't near a node, so a junction is added.
+        let m2 = &inp.pipes[1];
+        assert_eq!((m2.node1.as_str(), m2.node2.as_str(), m2.length, m2.diameter), ("J1", "J2", 20.0, 100.0));
+        assert_eq!(inp.coordinates.iter().find(|coordinate| coordinate.node_id == "J2").map(|coordinate| coordinate.y), Some(50.0));
+
+        assert_eq!(inp.valves[0].id, "V1");
+        assert_eq!(inp.valves[0].valve_type, ValveType::Prv);
+        assert_eq!((inp.valves[0].start_node.as_str(), inp.valves[0].end_node.as_str()), ("R1", "H1"));
+    }
+
+    #[test]
+    fn import_an_exported_network() {
+        let inp = a_model();
+
+        let imported = import_features(&feature_collection(&inp), &ImportOptions::default()).unwrap();
+
+        assert_eq!(imported.junctions, inp.junctions);
+        assert_eq!(imported.reservoirs, inp.reservoirs);
+        assert_eq!(imported.pipes, inp.pipes);
+        assert_eq!(imported.valves, inp.valves);
+        assert_eq!(imported.vertices.len(), 2);
+        assert_eq!(imported.projection(), inp.projection());
+    }
+
+    #[test]
+    fn reject_invalid_features() {
+        let options = ImportOptions::default();
+        let feature = |properties: &str, geometry: &str| format!(r#"{{ "type": "FeatureCollection", "features": [{{ "type": "Feature", "properties": {}, "geometry": {} }}] }}"#, properties, geometry);
+
+        assert!(from_geojson(&feature(r#"{ "elevation": "high" }"#, r#"{ "type": "Point", "coordinates": [0, 0] }"#), &options).is_err());
+        assert!(from_geojson(&feature(r#"{ "element": "pump" }"#, r#"{ "type": "LineString", "coordinates": [[0, 0], [1, 1]] }"#), &options).is_err());
+        assert!(from_geojson(&feature("{}", r#"{ "type": "Polygon", "coordinates": [] }"#), &options).is_err());
+        assert!(from_geojson(&feature(r#"{ "element": "pump", "head": "C1" }"#, r#"{ "type": "LineString", "coordinates": [[0, 0], [1, 1]] }"#), &options).is_ok());
+    }
+
+    #[test]
+    fn require_attributes_without_a_default() {
+        let options = ImportOptions::default();
+        let feature = |properties: &str, geometry: &str| format!(r#"{{ "type": "FeatureCollection", "features": [{{ "type": "Feature", "id": "A1", "properties": {}, "geometry": {} }}] }}"#, properties, geometry);
+        let line = r#"{ "type": "LineString", "coordinates": [[0, 0], [1, 1]] }"#;
+
+        let error = |properties: &str, geometry: &str| from_geojson(&feature(properties, geometry), &options).unwrap_err().message;
+
+        assert_eq!(error("{}", r#"{ "type": "Point", "coordinates": [0, 0] }"#), "The junction A1 has no elevation");
+        assert_eq!(error(r#"{ "diameter": 100 }"#, line), "The pipe A1 has no roughness");
+        assert_eq!(error(r#"{ "element": "valve", "diameter": 100, "valve_type": "PRV" }"#, line), "The valve A1 has no valve_setting");
+        let pipe = from_geojson(&feature(r#"{ "diameter": 100, "roughness": 130 }"#, line), &options).unwrap().pipes.remove(0);
+        assert_eq!((pipe.minor_loss, pipe.status.as_str()), (0.0, "OPEN"));
+    }
+}
@@ -0,0 +1,93 @@
+use std::collections::HashMap;
+use crate::INP;
+
+pub type Point = (f64, f64);
+
+/// Node positions and link polylines taken from [COORDINATES] and [VERTICES].
+#[derive(Debug, PartialEq, Clone)]
+pub struct Geometry {
+    nodes: HashMap<String, Point>,
+    links: HashMap<String, (String, String)>,
+    vertices: HashMap<String, Vec<Point>>,
+}
+
+impl Geometry {
+    pub fn new(inp: &INP) -> Geometry {
+        let nodes = inp.coordinates.iter()
+            .map(|coordinate| (coordinate.node_id.clone(), (coordinate.x, coordinate.y)))
+            .collect();
+
+        let mut links = HashMap::new();
+        for pipe in inp.pipes.iter() {
+            links.insert(pipe.id.clone(), (pipe.node1.clone(), pipe.node2.clone()));
+        }
+        for pump in inp.pumps.iter() {
+            links.insert(pump.id.clone(), (pump.start_node.clone(), pump.end_node.clone()));
+        }
+        for valve in inp.valves.iter() {
+            links.insert(valve.id.clone(), (valve.start_node.clone(), valve.end_node.clone()));
+        }
+
+        let mut vertices: HashMap<String, Vec<Point>> = HashMap::new();
+        for vertex in inp.vertices.iter() {
+            vertices.entry(vertex.link_id.clone()).or_default().push((vertex.x, vertex.y));
+        }
+
+        Geometry {
+            nodes,
+            links,
+            vertices,
+        }
+    }
+
+    pub fn node(&self, id: &str) -> Option<Point> {
+        self.nodes.get(id).copied()
+    }
+
+    /// Polyline of a link, from its start node through its vertices to its end node.
+    pub fn link(&self, id: &str) -> Option<Vec<Point>> {
+        let (start, end) = self.links.get(id)?;
+        let mut polyline = vec![self.node(start)?];
+        if let Some(vertices) = self.vertices.get(id) {
+            polyline.extend(vertices.iter().copied());
+        }
+        polyline.push(self.node(end)?);
+        Some(polyline)
+    }
+
+    /// Bounding box of every node and vertex as (min x, min y, max x, max y).
+    pub fn bounds(&self) -> Option<(f64, f64, f64, f64)> {
+        self.nodes.values()
+            .chain(self.vertices.values().flatten())
+            .fold(None, |bounds, &(x, y)| match bounds {
+                None => Some((x, y, x, y)),
+                Some((min_x, min_y, max_x, max_y)) => Some((min_x.min(x), min_y.min(y), max_x.max(x), max_y.max(y))),
+            })
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::Geometry;
+    use crate::INP;
+
+    #[test]
+    fn build_link_polylines() {
+        let input = r#"
+[PIPES]
+P1  J1  J2  100  100  100
+P2  J2  J3  100  100  100
+[COORDINATES]
+J1  0  0
+J2  10  0
+[VERTICES]
+P1  5  5
+"#;
+        let geometry = Geometry::new(&INP::read(input.to_string()));
+
+        assert_eq!(geometry.node("J2"), Some((10.0, 0.0)));
+        assert_eq!(geometry.link("P1"), Some(vec![(0.0, 0.0), (5.0, 5.0), (10.0, 0.0)]));
+        assert_eq!(geometry.link("P2"), None);
+        assert_eq!(geometry.bounds(), Some((0.0, 0.0, 10.0, 5.0)));
+    }
+}
@@ -0,0 +1,597 @@
+use std::collections::{HashMap, HashSet};
+use std::convert::TryInto;
+use std::error::Error;
+use std::fmt;
+use std::fs;
+use std::path::Path;
+use rusqlite::{params, params_from_iter, Connection, OpenFlags};
+use rusqlite::types::{Value as SqlValue, ValueRef};
+use serde::de::DeserializeOwned;
+use serde_json::{Map, Value};
+use crate::INP;
+use crate::crs::Crs;
+use crate::geometry::{Geometry, Point};
+use crate::sections::{Coordinate, Vertex};
+use crate::shapefile::records;
+
+const APPLICATION_ID: i32 = 0x4750_4B47;
+const USER_VERSION: i32 = 10201;
+const JSON_MIME_TYPE: &str = "application/json";
+const UNDEFINED_SRS: i32 = -1;
+
+/// Points of the geometry of every record, `None` for a null geometry.
+type Shapes = Vec<Option<Vec<Point>>>;
+
+// Tables of the GeoPackage 1.2.1 core and of its schema extension.
+const SCHEMA: &str = r#"
+CREATE TABLE gpkg_spatial_ref_sys (srs_name TEXT NOT NULL, srs_id INTEGER PRIMARY KEY, organization TEXT NOT NULL, organization_coordsys_id INTEGER NOT NULL, definition TEXT NOT NULL, description TEXT);
+CREATE TABLE gpkg_contents (table_name TEXT NOT NULL PRIMARY KEY, data_type TEXT NOT NULL, identifier TEXT UNIQUE, description TEXT DEFAULT '', last_change DATETIME NOT NULL DEFAULT (strftime('%Y-%m-%dT%H:%M:%fZ','now')), min_x DOUBLE, min_y DOUBLE, max_x DOUBLE, max_y DOUBLE, srs_id INTEGER, CONSTRAINT fk_gc_r_srs_id FOREIGN KEY (srs_id) REFERENCES gpkg_spatial_ref_sys(srs_id));
+CREATE TABLE gpkg_geometry_columns (table_name TEXT NOT NULL, column_name TEXT NOT NULL, geometry_type_name TEXT NOT NULL, srs_id INTEGER NOT NULL, z TINYINT NOT NULL, m TINYINT NOT NULL, CONSTRAINT pk_geom_cols PRIMARY KEY (table_name, column_name), CONSTRAINT uk_gc_table_name UNIQUE (table_name), CONSTRAINT fk_gc_tn FOREIGN KEY (table_name) REFERENCES gpkg_contents(table_name), CONSTRAINT fk_gc_srs FOREIGN KEY (srs_id) REFERENCES gpkg_spatial_ref_sys (srs_id));
+CREATE TABLE gpkg_extensions (table_name TEXT, column_name TEXT, extension_name TEXT NOT NULL, definition TEXT NOT NULL, scope TEXT NOT NULL, CONSTRAINT ge_tce UNIQUE (table_name, column_name, extension_name));
+CREATE TABLE gpkg_data_columns (table_name TEXT NOT NULL, column_name TEXT NOT NULL, name TEXT, title TEXT, description TEXT, mime_type TEXT, constraint_name TEXT, CONSTRAINT pk_gdc PRIMARY KEY (table_name, column_name), CONSTRAINT gdc_tn UNIQUE (table_name, name));
+CREATE TABLE gpkg_data_column_constraints (constraint_name TEXT NOT NULL, constraint_type TEXT NOT NULL, value TEXT, min NUMERIC, min_is_inclusive BOOLEAN, max NUMERIC, max_is_inclusive BOOLEAN, description TEXT, CONSTRAINT gdcc_ntv UNIQUE (constraint_name, constraint_type, value));
+INSERT INTO gpkg_extensions VALUES ('gpkg_data_columns', NULL, 'gpkg_schema', 'http://www.geopackage.org/spec121/#extension_schema', 'read-write');
+INSERT INTO gpkg_extensions VALUES ('gpkg_data_column_constraints', NULL, 'gpkg_schema', 'http://www.geopackage.org/spec121/#extension_schema', 'read-write');
+INSERT INTO gpkg_spatial_ref_sys VALUES ('Undefined cartesian SRS', -1, 'NONE', -1, 'undefined', 'undefined cartesian coordinate reference system');
+INSERT INTO gpkg_spatial_ref_sys VALUES ('Undefined geographic SRS', 0, 'NONE', 0, 'undefined', 'undefined geographic coordinate reference system');
+"#;
+
+#[derive(Debug, PartialEq)]
+pub struct GeoPackageError {
+    pub message: String,
+}
+
+impl fmt::Display for GeoPackageError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.message)
+    }
+}
+
+impl Error for GeoPackageError {}
+
+impl From<rusqlite::Error> for GeoPackageError {
+    fn from(error: rusqlite::Error) -> Self {
+        GeoPackageError { message: error.to_string() }
+    }
+}
+
+impl From<serde_json::Error> for GeoPackageError {
+    fn from(error: serde_json::Error) -> Self {
+        GeoPackageError { message: error.to_string() }
+    }
+}
+
+impl From<std::io::Error> for GeoPackageError {
+    fn from(error: std::io::Error) -> Self {
+        GeoPackageError { message: error.to_string() }
+    }
+}
+
+#[derive(PartialEq, Clone, Copy)]
+enum ColumnKind {
+    Integer,
+    Double,
+    Boolean,
+    Text,
+    /// Nested values, such as the multipliers of a pattern, stored as JSON text.
+    Json,
+}
+
+fn column_kind(values: &[&Value]) -> ColumnKind {
+    let values = values.iter().filter(|value| !value.is_null()).collect::<Vec<&&Value>>();
+    if values.iter().any(|value| value.is_array() || value.is_object()) {
+        ColumnKind::Json
+    } else if values.iter().all(|value| value.is_i64() || value.is_u64()) && !values.is_empty() {
+        ColumnKind::Integer
+    } else if values.iter().all(|value| value.is_number()) && !values.is_empty() {
+        ColumnKind::Double
+    } else if values.iter().all(|value| value.is_boolean()) && !values.is_empty() {
+        ColumnKind::Boolean
+    } else {
+        ColumnKind::Text
+    }
+}
+
+fn sql_value(kind: ColumnKind, value: Option<&Value>) -> SqlValue {
+    match (kind, value.unwrap_or(&Value::Null)) {
+        (_, Value::Null) => SqlValue::Null,
+        (ColumnKind::Json, value) => SqlValue::Text(value.to_string()),
+        (_, Value::Bool(value)) => SqlValue::Integer(*value as i64),
+        (ColumnKind::Integer, Value::Number(number)) => SqlValue::Integer(number.as_i64().unwrap_or_default()),
+        (_, Value::Number(number)) => SqlValue::Real(number.as_f64().unwrap_or_default()),
+        (_, Value::String(text)) => SqlValue::Text(text.clone()),
+        (_, value) => SqlValue::Text(value.to_string()),
+    }
+}
+
+fn bounds(points: &[Point]) -> Option<[f64; 4]> {
+    points.iter().fold(None, |bounds, &(x, y)| match bounds {
+        None => Some([x, y, x, y]),
+        Some([min_x, min_y, max_x, max_y]) => Some([min_x.min(x), min_y.min(y), max_x.max(x), max_y.max(y)]),
+    })
+}
+
+/// GeoPackage binary of a point, or of a line string when there are several points:
+/// a header with the SRS and the envelope, followed by little-endian WKB.
+pub fn geometry_blob(srs_id: i32, points: &[Point]) -> Vec<u8> {
+    let [min_x, min_y, max_x, max_y] = bounds(points).unwrap_or([0.0; 4]);
+    let mut blob = b"GP".to_vec();
+    blob.extend([0, 0b0000_0011]);
+    blob.extend(srs_id.to_le_bytes());
+    for value in [min_x, max_x, min_y, max_y] {
+        blob.extend(value.to_le_bytes());
+    }
+    blob.push(1);
+    if points.len() == 1 {
+        blob.extend(1_u32.to_le_bytes());
+    } else {
+        blob.extend(2_u32.to_le_bytes());
+        blob.extend((points.len() as u32).to_le_bytes());
+    }
+    for (x, y) in points.iter() {
+        blob.extend(x.to_le_bytes());
+        blob.extend(y.to_le_bytes());
+    }
+    blob
+}
+
+/// Points of a GeoPackage point or line string.
+fn read_geometry(blob: &[u8]) -> Result<Vec<Point>, GeoPackageError> {
+    let invalid = || GeoPackageError { message: "Invalid geometry".to_string() };
+    if blob.len() < 8 || &blob[..2] != b"GP" {
+        return Err(invalid());
+    }
+    let envelope = match (blob[3] >> 1) & 0b111 {
+        0 => 0,
+        1 => 32,
+        2 | 3 => 48,
+        4 => 64,
+        _ => return Err(invalid()),
+    };
+    let wkb = blob.get(8 + envelope..).ok_or_else(invalid)?;
+    let little_endian = *wkb.first().ok_or_else(invalid)? == 1;
+    let word = |at: usize| -> Result<u32, GeoPackageError> {
+        let bytes: [u8; 4] = wkb.get(at..at + 4).ok_or_else(invalid)?.try_into().unwrap();
+        Ok(if little_endian { u32::from_le_bytes(bytes) } else { u32::from_be_bytes(bytes) })
+    };
+    let number = |at: usize| -> Result<f64, GeoPackageError> {
+        let bytes: [u8; 8] = wkb.get(at..at + 8).ok_or_else(invalid)?.try_into().unwrap();
+        Ok(if little_endian { f64::from_le_bytes(bytes) } else { f64::from_be_bytes(bytes) })
+    };
+    let (count, start) = match word(1)? {
+        1 => (1, 5),
+        2 => (word(5)? as usize, 9),
+        _ => return Err(GeoPackageError { message: "Only points and line strings are supported".to_string() }),
+    };
+    (0..count).map(|i| Ok((number(start + 16 * i)?, number(start + 16 * i + 8)?))).collect()
+}
+
+/// Creates a table of records, a feature table when shapes are given.
+fn write_table(connection: &Connection, srs_id: i32, name: &str, records: &[Map<String, Value>], shapes: Option<(&str, Shapes)>) -> Result<(), GeoPackageError> {
+    let mut columns = records.iter().flat_map(|record| record.keys().cloned()).collect::<Vec<String>>();
+    columns.sort();
+    columns.dedup();
+    let kinds = columns.iter()
+        .map(|column| column_kind(&records.iter().filter_map(|record| record.get(column)).collect::<Vec<&Value>>()))
+        .collect::<Vec<ColumnKind>>();
+
+    let mut definitions = vec!["fid INTEGER PRIMARY KEY AUTOINCREMENT NOT NULL".to_string()];
+    if let Some((geometry_type, _)) = &shapes {
+        definitions.push(format!("geom {}", geometry_type));
+    }
+    for (column, kind) in columns.iter().zip(kinds.iter()) {
+        let sql_type = match kind {
+            ColumnKind::Integer => "INTEGER",
+            ColumnKind::Double => "DOUBLE",
+            ColumnKind::Boolean => "BOOLEAN",
+            ColumnKind::Text | ColumnKind::Json => "TEXT",
+        };
+        definitions.push(format!("\"{}\" {}", column, sql_type));
+    }
+    connection.execute(&format!("CREATE TABLE \"{}\" ({})", name, definitions.join(", ")), [])?;
+
+    match &shapes {
+        Some((geometry_type, shapes)) => {
+            let points = shapes.iter().flatten().flatten().copied().collect::<Vec<Point>>();
+            let [min_x, min_y, max_x, max_y] = bounds(&points).map(|bounds| bounds.map(Some)).unwrap_or([None; 4]);
+            connection.execute(
+                "INSERT INTO gpkg_contents (table_name, data_type, identifier, min_x, min_y, max_x, max_y, srs_id) VALUES (?1, 'features', ?1, ?2, ?3, ?4, ?5, ?6)",
+                params![name, min_x, min_y, max_x, max_y, srs_id],
+            )?;
+            connection.execute("INSERT INTO gpkg_geometry_columns VALUES (?1, 'geom', ?2, ?3, 0, 0)", params![name, geometry_type, srs_id])?;
+        },
+        None => {
+            connection.execute("INSERT INTO gpkg_contents (table_name, data_type, identifier) VALUES (?1, 'attributes', ?1)", params![name])?;
+        },
+    }
+    for (column, _) in columns.iter().zip(kinds.iter()).filter(|(_, &kind)| kind == ColumnKind::Json) {
+        connection.execute("INSERT INTO gpkg_data_columns (table_name, column_name, mime_type) VALUES (?1, ?2, ?3)", params![name, column, JSON_MIME_TYPE])?;
+    }
+
+    let mut names = columns.iter().map(|column| format!("\"{}\"", column)).collect::<Vec<String>>();
+    if shapes.is_some() {
+        names.insert(0, "geom".to_string());
+    }
+    if names.is_empty() {
+        for _ in records.iter() {
+            connection.execute(&format!("INSERT INTO \"{}\" DEFAULT VALUES", name), [])?;
+        }
+        return Ok(());
+    }
+    let placeholders = (1..=names.len()).map(|i| format!("?{}", i)).collect::<Vec<String>>();
+    let mut statement = connection.prepare(&format!("INSERT INTO \"{}\" ({}) VALUES ({})", name, names.join(", "), placeholders.join(", ")))?;
+    for (i, record) in records.iter().enumerate() {
+        let mut values = columns.iter().zip(kinds.iter())
+            .map(|(column, &kind)| sql_value(kind, record.get(column)))
+            .collect::<Vec<SqlValue>>();
+        if let Some((_, shapes)) = &shapes {
+            let geometry = shapes[i].as_ref().map(|points| SqlValue::Blob(geometry_blob(srs_id, points))).unwrap_or(SqlValue::Null);
+            values.insert(0, geometry);
+        }
+        statement.execute(params_from_iter(values))?;
+    }
+    Ok(())
+}
+
+/// Records of a table, with the points of their geometry. A missing table has no records.
+fn read_table<T: DeserializeOwned>(connection: &Connection, name: &str) -> Result<(Vec<T>, Shapes), GeoPackageError> {
+    let exists = connection.query_row("SELECT count(*) FROM sqlite_master WHERE type = 'table' AND name = ?1", params![name], |row| row.get::<_, i64>(0))? > 0;
+    if !exists {
+        return Ok((Vec::new(), Vec::new()));
+    }
+    let mut statement = connection.prepare("SELECT column_name FROM gpkg_data_columns WHERE table_name = ?1 AND mime_type = ?2")?;
+    let json_columns = statement.query_map(params![name, JSON_MIME_TYPE], |row| row.get::<_, String>(0))?
+        .collect::<Result<HashSet<String>, rusqlite::Error>>()?;
+    let mut statement = connection.prepare(&format!("PRAGMA table_info(\"{}\")", name))?;
+    let columns = statement.query_map([], |row| Ok((row.get::<_, String>(1)?, row.get::<_, String>(2)?)))?
+        .collect::<Result<Vec<(String, String)>, rusqlite::Error>>()?;
+
+    let mut statement = connection.prepare(&format!("SELECT * FROM \"{}\" ORDER BY fid", name))?;
+    let mut rows = statement.query([])?;
+    let mut items = Vec::new();
+    let mut shapes = Vec::new();
+    while let Some(row) = rows.next()? {
+        let mut record = Map::new();
+        let mut shape = None;
+        for (i, (column, sql_type)) in columns.iter().enumerate() {
+            let value = row.get_ref(i)?;
+            if column == "fid" {
+                continue;
+            }
+            if column == "geom" {
+                if let ValueRef::Blob(blob) = value {
+                    shape = Some(read_geometry(blob)?);
+                }
+                continue;
+            }
+            let value = match value {
+                ValueRef::Null => Value::Null,
+                ValueRef::Integer(value) if sql_type.eq_ignore_ascii_case("BOOLEAN") => Value::from(value != 0),
+                ValueRef::Integer(value) => Value::from(value),
+                ValueRef::Real(value) => Value::from(value),
+                ValueRef::Text(text) if json_columns.contains(column) => serde_json::from_slice(text)?,
+                ValueRef::Text(text) => Value::from(String::from_utf8_lossy(text).to_string()),
+                ValueRef::Blob(_) => Value::Null,
+            };
+            record.insert(column.clone(), value);
+        }
+        items.push(serde_json::from_value(Value::Object(record)).map_err(|error| GeoPackageError { message: format!("Invalid row of {}: {}", name, error) })?);
+        shapes.push(shape);
+    }
+    Ok((items, shapes))
+}
+
+/// Writes an INP as a GeoPackage: a feature table for each type of node and link, in
+/// the projection of the title, and an attribute table for every other section.
+pub fn write_geopackage(inp: &INP, path: &Path) -> Result<(), GeoPackageError> {
+    if path.exists() {
+        fs::remove_file(path)?;
+    }
+    let mut connection = Connection::open(path)?;
+    connection.pragma_update(None, "application_id", APPLICATION_ID)?;
+    connection.pragma_update(None, "user_version", USER_VERSION)?;
+    let transaction = connection.transaction()?;
+    transaction.execute_batch(SCHEMA)?;
+
+    let srs_id = match inp.projection().and_then(|projection| Some((projection, projection.split_once(':')?.1.trim().parse::<i32>().ok()?))) {
+        Some((projection, code)) => {
+            let definition = Crs::from_projection(projection).map(|crs| crs.wkt()).unwrap_or_else(|| "undefined".to_string());
+            let organization = projection.split(':').next().unwrap_or_default().to_uppercase();
+            transaction.execute("INSERT INTO gpkg_spatial_ref_sys VALUES (?1, ?2, ?3, ?2, ?4, NULL)", params![projection, code, organization, definition])?;
+            code
+        },
+        None => UNDEFINED_SRS,
+    };
+
+    let geometry = Geometry::new(inp);
+    let nodes = |ids: Vec<&String>| Some(("POINT", ids.into_iter().map(|id| geometry.node(id).map(|point| vec![point])).collect()));
+    let links = |ids: Vec<&String>| Some(("LINESTRING", ids.into_iter().map(|id| geometry.link(id)).collect()));
+    let mut title = Map::new();
+    title.insert("title".to_string(), Value::from(inp.title.clone()));
+
+    write_table(&transaction, srs_id, "junctions", &records(&inp.junctions), nodes(inp.junctions.iter().map(|junction| &junction.id).collect()))?;
+    write_table(&transaction, srs_id, "reservoirs", &records(&inp.reservoirs), nodes(inp.reservoirs.iter().map(|reservoir| &reservoir.id).collect()))?;
+    write_table(&transaction, srs_id, "tanks", &records(&inp.tanks), nodes(inp.tanks.iter().map(|tank| &tank.id).collect()))?;
+    write_table(&transaction, srs_id, "pipes", &records(&inp.pipes), links(inp.pipes.iter().map(|pipe| &pipe.id).collect()))?;
+    write_table(&transaction, srs_id, "pumps", &records(&inp.pumps), links(inp.pumps.iter().map(|pump| &pump.id).collect()))?;
+    write_table(&transaction, srs_id, "valves", &records(&inp.valves), links(inp.valves.iter().map(|valve| &valve.id).collect()))?;
+    write_table(&transaction, srs_id, "title", &[title], None)?;
+    write_table(&transaction, srs_id, "emitters", &records(&inp.emitters), None)?;
+    write_table(&transaction, srs_id, "demands", &records(&inp.demands), None)?;
+    write_table(&transaction, srs_id, "patterns", &records(&inp.patterns), None)?;
+    write_table(&transaction, srs_id, "curves", &records(&inp.curves), None)?;
+    write_table(&transaction, srs_id, "controls", &records(&inp.controls), None)?;
+    write_table(&transaction, srs_id, "rules", &records(&inp.rules), None)?;
+    write_table(&transaction, srs_id, "statuses", &records(&inp.statuses), None)?;
+    write_table(&transaction, srs_id, "tags", &records(&inp.tags), None)?;
+    write_table(&transaction, srs_id, "quality", &records(&inp.quality), None)?;
+    write_table(&transaction, srs_id, "sources", &records(&inp.sources), None)?;
+    write_table(&transaction, srs_id, "reactions", &records(&inp.reactions), None)?;
+    write_table(&transaction, srs_id, "mixing", &records(&inp.mixing), None)?;
+    write_table(&transaction, srs_id, "energy", &records(&inp.energy), None)?;
+    write_table(&transaction, srs_id, "options", &records(&inp.options), None)?;
+    write_table(&transaction, srs_id, "times", &records(&inp.times), None)?;
+    write_table(&transaction, srs_id, "coordinates", &records(&inp.coordinates), None)?;
+    write_table(&transaction, srs_id, "vertices", &records(&inp.vertices), None)?;
+    write_table(&transaction, srs_id, "labels", &records(&inp.labels), None)?;
+    write_table(&transaction, srs_id, "backdrop", &records(&inp.backdrop), None)?;
+    write_table(&transaction, srs_id, "comments", &records(&inp.comments), None)?;
+    write_table(&transaction, srs_id, "unknown_sections", &records(&inp.unknown_sections), None)?;
+    write_table(&transaction, srs_id, "errors", &records(&inp.errors), None)?;
+    transaction.commit()?;
+    Ok(())
+}
+
+/// Reads an INP from a GeoPackage written by `write_geopackage`. Nodes and links moved
+/// in a GIS keep their new position: [COORDINATES] and [VERTICES] follow the geometries.
+pub fn read_geopackage(path: &Path) -> Result<INP, GeoPackageError> {
+    let connection = Connection::open_with_flags(path, OpenFlags::SQLITE_OPEN_READ_ONLY)?;
+    let application_id = connection.pragma_query_value(None, "application_id", |row| row.get::<_, i32>(0))?;
+    if application_id != APPLICATION_ID {
+        return Err(GeoPackageError { message: "Not a GeoPackage".to_string() });
+    }
+
+    let mut inp = INP::read(String::new());
+    let (titles, _) = read_table::<Map<String, Value>>(&connection, "title")?;
+    inp.title = titles.first().and_then(|title| title.get("title")?.as_str().map(|title| title.to_string())).unwrap_or_default();
+
+    let mut node_points = HashMap::new();
+    let mut link_points = HashMap::new();
+    macro_rules! features {
+        ($field:ident, $table:expr, $points:expr) => {
+            let (items, shapes) = read_table(&connection, $table)?;
+            inp.$field = items;
+            for (item, shape) in inp.$field.iter().zip(shapes) {
+                if let Some(shape) = shape {
+                    $points.insert(item.id.clone(), shape);
+                }
+            }
+        };
+    }
+    features!(junctions, "junctions", node_points);
+    features!(reservoirs, "reservoirs", node_points);
+    features!(tanks, "tanks", node_points);
+    features!(pipes, "pipes", link_points);
+    features!(pumps, "pumps", link_points);
+    features!(valves, "valves", link_points);
+
+    inp.emitters = read_table(&connection, "emitters")?.0;
+    inp.demands = read_table(&connection, "demands")?.0;
+    inp.patterns = read_table(&connection, "patterns")?.0;
+    inp.curves = read_table(&connection, "curves")?.0;
+    inp.controls = read_table(&connection, "controls")?.0;
+    inp.rules = read_table(&connection, "rules")?.0;
+    inp.statuses = read_table(&connection, "statuses")?.0;
+    inp.tags = read_table(&connection, "tags")?.0;
+    inp.quality = read_table(&connection, "quality")?.0;
+    inp.sources = read_table(&connection, "sources")?.0;
+    inp.reactions = read_table(&connection, "reactions")?.0;
+    inp.mixing = read_table(&connection, "mixing")?.0;
+    inp.energy = read_table(&connection, "energy")?.0;
+    inp.options = read_table(&connection, "options")?.0;
+    inp.times = read_table(&connection, "times")?.0;
+    inp.labels = read_table(&connection, "labels")?.0;
+    inp.backdrop = read_table(&connection, "backdrop")?.0;
+    inp.comments = read_table(&connection, "comments")?.0;
+    inp.unknown_sections = read_table(&connection, "unknown_sections")?.0;
+    inp.errors = read_table(&connection, "errors")?.0;
+
+    // Empty points, stored as NaN coordinates, and empty line strings count as no geometry.
+    let location = |id: &str| node_points.get(id)
+        .and_then(|points: &Vec<Point>| points.first())
+        .filter(|(x, y)| !x.is_nan() && !y.is_nan())
+        .copied();
+    let coordinates: Vec<Coordinate> = read_table(&connection, "coordinates")?.0;
+    let mut placed = HashSet::new();
+    for mut coordinate in coordinates.into_iter() {
+        if let Some(point) = location(&coordinate.node_id) {
+            (coordinate.x, coordinate.y) = point;
+        }
+        placed.insert(coordinate.node_id.clone());
+        inp.coordinates.push(coordinate);
+    }
+    let node_ids = inp.junctions.iter().map(|junction| &junction.id)
+        .chain(inp.reservoirs.iter().map(|reservoir| &reservoir.id))
+        .chain(inp.tanks.iter().map(|tank| &tank.id));
+    for id in node_ids {
+        if let (false, Some((x, y))) = (placed.contains(id), location(id)) {
+            inp.coordinates.push(Coordinate { node_id: id.clone(), x, y, comment: None });
+        }
+    }
+
+    // The vertices of a link with a geometry are the interior points of the geometry.
+    let interior = |id: &str| link_points.get(id)
+        .filter(|points: &&Vec<Point>| points.len() >= 2)
+        .map(|points| &points[1..points.len() - 1]);
+    let vertices: Vec<Vertex> = read_table(&connection, "vertices")?.0;
+    let mut used: HashMap<String, usize> = HashMap::new();
+    for mut vertex in vertices.into_iter() {
+        if let Some(points) = interior(&vertex.link_id) {
+            let count = used.entry(vertex.link_id.clone()).or_insert(0);
+            match points.get(*count) {
+                Some(&point) => (vertex.x, vertex.y) = point,
+                None => continue,
+            }
+            *count += 1;
+        }
+        inp.vertices.push(vertex);
+    }
+    let link_ids = inp.pipes.iter().map(|pipe| &pipe.id)
+        .chain(inp.pumps.iter().map(|pump| &pump.id))
+        .chain(inp.valves.iter().map(|valve| &valve.id))
+        .cloned()
+        .collect::<Vec<String>>();
+    for id in link_ids {
+        if let Some(points) = interior(&id) {
+            for &(x, y) in points.iter().skip(used.get(&id).copied().unwrap_or(0)) {
+                inp.vertices.push(Vertex { link_id: id.clone(), x, y, comment: None });
+            }
+        }
+    }
+    Ok(inp)
+}
+
+#[cfg(test)]
+mod test {
+    use std::fs;
+    use std::path::PathBuf;
+    use rusqlite::{params, Connection};
+    use super::{geometry_blob, read_geopackage, write_geopackage};
+    use crate::INP;
+
+    fn a_path(name: &str) -> PathBuf {
+        std::env::temp_dir().join(format!("{}-{}.gpkg", name, std::process::id()))
+    }
+
+    fn a_model() -> INP {
+        INP::read(r#"
+[TITLE]
+A network
+Q_VAR_PROJECTION=EPSG:28355
+[JUNCTIONS]
+J1  10  5  Daily  ;corner
+J2  12
+[RESERVOIRS]
+R1  50
+[TANKS]
+T1  20  5  0  10  10  0  Volume  YES
+[PIPES]
+P1  R1  J1  100  300  130  0  Open
+P2  J1  J2  100  300  130
+[PUMPS]
+PU1  J1  T1  HEAD  Pump  SPEED  1.2
+[VALVES]
+V1  J2  T1  200  PRV  30  0
+[PATTERNS]
+Daily  1  1.2  0.8
+[CURVES]
+Pump  50  40
+Volume  0  0
+Volume  10  100
+[CONTROLS]
+LINK PU1 OPEN IF NODE T1 BELOW 2
+LINK P2 CLOSED AT TIME 4
+[RULES]
+RULE 1
+IF TANK T1 LEVEL ABOVE 8
+THEN PUMP PU1 STATUS IS CLOSED
+[STATUS]
+P2  Closed
+[OPTIONS]
+Units  LPS
+[TIMES]
+Duration  24:00
+[COORDINATES]
+R1  0  0
+J1  10  0
+T1  20  5
+J2  10  10
+[VERTICES]
+P1  5  2
+P1  7  1
+[BACKDROP]
+UNITS  None
+"#.to_string())
+    }
+
+    #[test]
+    fn export_and_import_a_model() {
+        let path = a_path("model");
+        let inp = a_model();
+
+        write_geopackage(&inp, &path).unwrap();
+        let imported = read_geopackage(&path).unwrap();
+
+        assert_eq!(imported, inp);
+        fs::remove_file(path).unwrap();
+    }
+
+    #[test]
+    fn write_geopackage_tables() {
+        let path = a_path("tables");
+        write_geopackage(&a_model(), &path).unwrap();
+
+        let connection = Connection::open(&path).unwrap();
+        let application_id = connection.pragma_query_value(None, "application_id", |row| row.get::<_, i32>(0)).unwrap();
+        assert_eq!(application_id, 0x4750_4B47);
+        let data_type = |table: &str| connection.query_row("SELECT data_type FROM gpkg_contents WHERE table_name = ?1", params![table], |row| row.get::<_, String>(0)).unwrap();
+        assert_eq!(data_type("junctions"), "features");
+        assert_eq!(data_type("patterns"), "attributes");
+        let geometry_type = connection.query_row("SELECT geometry_type_name, srs_id FROM gpkg_geometry_columns WHERE table_name = 'pipes'", [], |row| Ok((row.get::<_, String>(0)?, row.get::<_, i32>(1)?))).unwrap();
+        assert_eq!(geometry_type, ("LINESTRING".to_string(), 28355));
+        let definition = connection.query_row("SELECT definition FROM gpkg_spatial_ref_sys WHERE srs_id = 28355", [], |row| row.get::<_, String>(0)).unwrap();
+        assert!(definition.contains("GDA_1994_MGA_Zone_55"));
+        let max_x = connection.query_row("SELECT max_x FROM gpkg_contents WHERE table_name = 'junctions'", [], |row| row.get::<_, f64>(0)).unwrap();
+        assert_eq!(max_x, 10.0);
+        let multipliers = connection.query_row("SELECT multipliers FROM patterns", [], |row| row.get::<_, String>(0)).unwrap();
+        assert_eq!(multipliers, "[1.0,1.2,0.8]");
+        fs::remove_file(path).unwrap();
+    }
+
+    #[test]
+    fn follow_geometries_edited_in_a_gis() {
+        let path = a_path("edited");
+        write_geopackage(&a_model(), &path).unwrap();
+        let connection = Connection::open(&path).unwrap();
+        connection.execute("UPDATE junctions SET geom = ?1 WHERE id = 'J2'", params![geometry_blob(28355, &[(11.0, 12.0)])]).unwrap();
+        connection.execute("UPDATE pipes SET geom = ?1 WHERE id = 'P1'", params![geometry_blob(28355, &[(0.0, 0.0), (6.0, 3.0), (10.0, 0.0)])]).unwrap();
+        drop(connection);
+
+        let imported = read_geopackage(&path).unwrap();
+
+        let j2 = imported.coordinates.iter().find(|coordinate| coordinate.node_id == "J2").unwrap();
+        assert_eq!((j2.x, j2.y), (11.0, 12.0));
+        assert_eq!(imported.vertices.len(), 1);
+        assert_eq!((imported.vertices[0].x, imported.vertices[0].y), (6.0, 3.0));
+        fs::remove_file(path).unwrap();
+    }
+
+    #[test]
+    fn ignore_empty_geometries() {
+        let path = a_path("empty");
+        let inp = a_model();
+        write_geopackage(&inp, &path).unwrap();
+        let connection = Connection::open(&path).unwrap();
+        connection.execute("UPDATE junctions SET geom = ?1 WHERE id = 'J2'", params![geometry_blob(28355, &[(f64::NAN, f64::NAN)])]).unwrap();
+        connection.execute("UPDATE junctions SET geom = ?1 WHERE id = 'J1'", params![geometry_blob(28355, &[])]).unwrap();
+        connection.execute("UPDATE pipes SET geom = ?1 WHERE id = 'P1'", params![geometry_blob(28355, &[])]).unwrap();
+        drop(connection);
+
+        let imported = read_geopackage(&path).unwrap();
+
+        assert_eq!(imported.coordinates, inp.coordinates);
+        assert_eq!(imported.vertices, inp.vertices);
+        fs::remove_file(path).unwrap();
+    }
+
+    #[test]
+    fn export_and_import_magnetic_island() {
+        let path = a_path("magnetic-island");
+        let inp = INP::read(fs::read_to_string("tests/MagneticIslandEnhanced.inp").unwrap());
+
+        write_geopackage(&inp, &path).unwrap();
+
+        assert_eq!(read_geopackage(&path).unwrap(), inp);
+        fs::remove_file(path).unwrap();
+    }
+}
@@ -0,0 +1,226 @@
+use std::error::Error;
+use std::fmt;
+use serde::{Serialize, Deserialize};
+use crate::INP;
+use crate::sections::Setting;
+use crate::units::{HeadlossFormula, Quantity};
+
+const GRAVITY: f64 = 9.81;
+// Kinematic viscosity of water at 20ºC in m2/s, the one EPANET uses.
+pub const WATER_VISCOSITY: f64 = 1.1e-5 * 0.3048 * 0.3048;
+
+#[derive(Debug, PartialEq)]
+pub struct HeadlossError {
+    pub message: String,
+}
+
+impl fmt::Display for HeadlossError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.message)
+    }
+}
+
+impl Error for HeadlossError {}
+
+#[derive(Serialize, Deserialize, Debug, PartialEq)]
+pub struct RoughnessConversion {
+    pub pipe_id: String,
+    pub from: HeadlossFormula,
+    pub to: HeadlossFormula,
+    pub original_roughness: f64,
+    pub roughness: f64,
+    pub reference_velocity: f64,
+    pub reynolds_number: f64,
+    pub friction_factor: f64,
+    pub note: Option<String>,
+}
+
+/// Friction slope, in m/m, for a pipe of `diameter` metres at `velocity` m/s.
+/// D-W roughness must be given in metres.
+pub fn friction_slope(formula: HeadlossFormula, roughness: f64, diameter: f64, velocity: f64, viscosity: f64) -> f64 {
+    match formula {
+        HeadlossFormula::HazenWilliams => {
+            let flow = velocity * area(diameter);
+            10.67 * flow.powf(1.852) / (roughness.powf(1.852) * diameter.powf(4.871))
+        },
+        HeadlossFormula::DarcyWeisbach => {
+            let reynolds = velocity * diameter / viscosity;
+            swamee_jain(roughness, diameter, reynolds) * velocity * velocity / (2.0 * GRAVITY * diameter)
+        },
+        HeadlossFormula::ChezyManning => {
+            let hydraulic_radius = diameter / 4.0;
+            (roughness * velocity).powi(2) / hydraulic_radius.powf(4.0 / 3.0)
+        },
+    }
+}
+
+fn area(diameter: f64) -> f64 {
+    std::f64::consts::PI * diameter * diameter / 4.0
+}
+
+fn swamee_jain(roughness: f64, diameter: f64, reynolds: f64) -> f64 {
+    if reynolds < 2000.0 {
+        return 64.0 / reynolds;
+    }
+    0.25 / (roughness / (3.7 * diameter) + 5.74 / reynolds.powf(0.9)).log10().powi(2)
+}
+
+/// Roughness of `formula` that produces the friction `slope` in a pipe of `diameter`
+/// metres at `velocity` m/s, plus a note when the value had to be clamped.
+fn equivalent_roughness(formula: HeadlossFormula, slope: f64, diameter: f64, velocity: f64, viscosity: f64) -> (f64, Option<String>) {
+    match formula {
+        HeadlossFormula::HazenWilliams => {
+            let flow = velocity * area(diameter);
+            ((10.67 * flow.powf(1.852) / (slope * diameter.powf(4.871))).powf(1.0 / 1.852), None)
+        },
+        HeadlossFormula::DarcyWeisbach => {
+            let reynolds = velocity * diameter / viscosity;
+            if reynolds < 2000.0 {
+                return (0.0, Some(format!("Laminar flow at the reference velocity (Re {:.0}), headloss does not depend on roughness, roughness set to 0", reynolds)));
+            }
+            let friction_factor = slope * 2.0 * GRAVITY * diameter / (velocity * velocity);
+            let roughness = 3.7 * diameter * (10f64.powf(-0.5 / friction_factor.sqrt()) - 5.74 / reynolds.powf(0.9));
+            if roughness < 0.0 {
+                (0.0, Some("Friction below smooth pipe limit, roughness set to 0".to_string()))
+            } else {
+                (roughness, None)
+            }
+        },
+        HeadlossFormula::ChezyManning => {
+            let hydraulic_radius = diameter / 4.0;
+            ((slope * hydraulic_radius.powf(4.0 / 3.0)).sqrt() / velocity, None)
+        },
+    }
+}
+
+/// Converts the roughness of every pipe from the model headloss formula to `to`, so the
+/// headloss of each pipe is the same at `reference_velocity` (in the model velocity units).
+/// [OPTIONS] HEADLOSS is updated and a report of every pipe conversion is returned.
+/// Fails, leaving the model untouched, if the velocity, a pipe diameter or a H-W or C-M
+/// roughness is not positive, or a D-W roughness is negative.
+pub fn convert_headloss(inp: &mut INP, to: HeadlossFormula, reference_velocity: f64) -> Result<Vec<RoughnessConversion>, HeadlossError> {
+    if reference_velocity <= 0.0 {
+        return Err(HeadlossError { message: format!("Reference velocity must be positive, got {}", reference_velocity) });
+    }
+    if let Some(pipe) = inp.pipes.iter().find(|pipe| pipe.diameter <= 0.0) {
+        return Err(HeadlossError { message: format!("Pipe {} has a diameter of {}", pipe.id, pipe.diameter) });
+    }
+    let from = inp.headloss_formula();
+    // Smooth pipes have no D-W roughness, the coefficients of the other formulas divide.
+    let invalid_roughness = |roughness: f64| if from == HeadlossFormula::DarcyWeisbach { roughness < 0.0 } else { roughness <= 0.0 };
+    if let Some(pipe) = inp.pipes.iter().find(|pipe| invalid_roughness(pipe.roughness)) {
+        return Err(HeadlossError { message: format!("Pipe {} has a roughness of {}", pipe.id, pipe.roughness) });
+    }
+
+    let flow_units = inp.flow_units();
+    let velocity = reference_velocity * Quantity::Velocity.si_factor(flow_units, from);
+    let viscosity = WATER_VISCOSITY * inp.option_number("VISCOSITY").unwrap_or(1.0);
+    let diameter_factor = Quantity::Diameter.si_factor(flow_units, from);
+    let from_factor = Quantity::Roughness.si_factor(flow_units, from);
+    let to_factor = Quantity::Roughness.si_factor(flow_units, to);
+
+    let mut report = Vec::new();
+    for pipe in inp.pipes.iter_mut() {
+        let diameter = pipe.diameter * diameter_factor;
+        let slope = friction_slope(from, pipe.roughness * from_factor, diameter, velocity, viscosity);
+        let (roughness, note) = equivalent_roughness(to, slope, diameter, velocity, viscosity);
+        let reynolds_number = velocity * diameter / viscosity;
+
+        report.push(RoughnessConversion {
+            pipe_id: pipe.id.clone(),
+            from,
+            to,
+            original_roughness: pipe.roughness,
+            roughness: roughness / to_factor,
+            reference_velocity,
+            reynolds_number,
+            friction_factor: slope * 2.0 * GRAVITY * diameter / (velocity * velocity),
+            note,
+        });
+        pipe.roughness = roughness / to_factor;
+    }
+
+    match inp.options.iter_mut().find(|setting| setting.key == "HEADLOSS") {
+        Some(setting) => setting.value = to.name().to_string(),
+        None => inp.options.push(Setting { key: "HEADLOSS".to_string(), value: to.name().to_string(), comment: None }),
+    }
+
+    Ok(report)
+}
+
+#[cfg(test)]
+mod test {
+    use super::{convert_headloss, HeadlossError};
+    use crate::INP;
+    use crate::units::HeadlossFormula;
+
+    fn a_model(headloss: &str, roughness: &str) -> INP {
+        let input = format!(r#"
+[PIPES]
+P1  J1  J2  1000  300  {}
+[OPTIONS]
+Units  LPS
+Headloss  {}
+"#, roughness, headloss);
+        INP::read(input)
+    }
+
+    #[test]
+    fn convert_hazen_williams_to_darcy_weisbach() {
+        let mut inp = a_model("H-W", "130");
+
+        let report = convert_headloss(&mut inp, HeadlossFormula::DarcyWeisbach, 1.0).unwrap();
+
+        assert_eq!(inp.option("HEADLOSS"), Some("D-W"));
+        assert_eq!(report[0].pipe_id, "P1");
+        assert_eq!(report[0].original_roughness, 130.0);
+        assert!(inp.pipes[0].roughness > 0.05 && inp.pipes[0].roughness < 0.5, "{}", inp.pipes[0].roughness);
+    }
+
+    #[test]
+    fn conversions_are_reversible() {
+        let mut inp = a_model("C-M", "0.011");
+
+        convert_headloss(&mut inp, HeadlossFormula::HazenWilliams, 1.5).unwrap();
+        convert_headloss(&mut inp, HeadlossFormula::DarcyWeisbach, 1.5).unwrap();
+        convert_headloss(&mut inp, HeadlossFormula::ChezyManning, 1.5).unwrap();
+
+        assert!((inp.pipes[0].roughness - 0.011).abs() < 1e-6, "{}", inp.pipes[0].roughness);
+        assert_eq!(inp.option("HEADLOSS"), Some("C-M"));
+    }
+
+    #[test]
+    fn report_roughness_below_smooth_pipe_limit() {
+        let mut inp = a_model("H-W", "160");
+
+        let report = convert_headloss(&mut inp, HeadlossFormula::DarcyWeisbach, 0.1).unwrap();
+
+        assert_eq!(inp.pipes[0].roughness, 0.0);
+        assert!(report[0].note.is_some());
+    }
+
+    #[test]
+    fn report_laminar_flow() {
+        let mut inp = a_model("H-W", "130");
+
+        let report = convert_headloss(&mut inp, HeadlossFormula::DarcyWeisbach, 0.001).unwrap();
+
+        assert!(report[0].reynolds_number < 2000.0);
+        assert!(report[0].note.as_ref().unwrap().starts_with("Laminar flow"));
+    }
+
+    #[test]
+    fn reject_invalid_inputs() {
+        let mut inp = a_model("H-W", "130");
+
+        assert!(convert_headloss(&mut inp, HeadlossFormula::DarcyWeisbach, 0.0).is_err());
+        inp.pipes[0].diameter = 0.0;
+        assert_eq!(convert_headloss(&mut inp, HeadlossFormula::DarcyWeisbach, 1.0), Err(HeadlossError { message: "Pipe P1 has a diameter of 0".to_string() }));
+        inp.pipes[0].diameter = 300.0;
+        inp.pipes[0].roughness = 0.0;
+        assert_eq!(convert_headloss(&mut inp, HeadlossFormula::DarcyWeisbach, 1.0), Err(HeadlossError { message: "Pipe P1 has a roughness of 0".to_string() }));
+        assert_eq!(inp.option("HEADLOSS"), Some("H-W"));
+        let mut smooth = a_model("D-W", "0");
+        assert!(convert_headloss(&mut smooth, HeadlossFormula::HazenWilliams, 1.0).is_ok());
+    }
+}
@@ -0,0 +1,333 @@
+mod matrix;
+mod model;
+mod solver;
+mod controls;
+mod simulation;
+
+use std::error::Error;
+use std::fmt;
+use serde::{Serialize, Deserialize};
+use crate::{INP, ToJson};
+
+pub use model::{interpolate, DemandModel, HydraulicModel, LinkType, ModelLink, ModelTank, TimeOptions};
+pub use solver::{Convergence, Solver, State};
+pub use simulation::{is_report_time, run, simulate, Simulation, TimeStep};
+
+#[derive(Debug, PartialEq)]
+pub struct HydraulicError {
+    pub message: String,
+}
+
+impl fmt::Display for HydraulicError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.message)
+    }
+}
+
+impl Error for HydraulicError {}
+
+#[derive(Serialize, Deserialize, Debug, PartialEq, Clone, Copy)]
+pub enum LinkStatus {
+    Open,
+    Closed,
+    /// Valve controlling pressure or flow.
+    Active,
+}
+
+#[derive(Serialize, Deserialize, Debug, PartialEq, Clone)]
+pub struct NodeResult {
+    pub id: String,
+    /// Junction demand plus emitter outflow; net inflow of reservoirs and tanks.
+    pub demand: f64,
+    /// Demand of the junction at the current time, before any pressure deficit.
+    pub requested_demand: f64,
+    /// Demand actually received by the junction.
+    pub delivered_demand: f64,
+    pub emitter_flow: f64,
+    pub head: f64,
+    pub pressure: f64,
+}
+
+#[derive(Serialize, Deserialize, Debug, PartialEq, Clone)]
+pub struct LinkResult {
+    pub id: String,
+    pub flow: f64,
+    pub velocity: f64,
+    /// Head at the start node minus head at the end node.
+    pub headloss: f64,
+    pub status: LinkStatus,
+}
+
+/// Hydraulic state of the network, in the units of the INP.
+#[derive(Serialize, Deserialize, Debug, PartialEq, Clone)]
+pub struct HydraulicResults {
+    pub nodes: Vec<NodeResult>,
+    pub links: Vec<LinkResult>,
+    pub iterations: usize,
+    pub relative_error: f64,
+    pub converged: bool,
+}
+
+impl HydraulicResults {
+    pub fn node(&self, id: &str) -> Option<&NodeResult> {
+        self.nodes.iter().find(|node| node.id == id)
+    }
+
+    pub fn link(&self, id: &str) -> Option<&LinkResult> {
+        self.links.iter().find(|link| link.id == id)
+    }
+}
+
+impl ToJson for HydraulicResults {}
+
+/// Steady-state hydraulics at the start of the simulation.
+pub fn solve(inp: &INP) -> Result<HydraulicResults, HydraulicError> {
+    let model = HydraulicModel::new(inp)?;
+    let mut solver = Solver::new(&model);
+    let convergence = solver.run()?;
+
+    Ok(solver.results(&convergence))
+}
+
+#[cfg(test)]
+mod test {
+    use std::fs;
+    use super::{solve, LinkStatus};
+    use crate::INP;
+
+    #[test]
+    fn solve_a_single_pipe() {
+        let input = r#"
+[JUNCTIONS]
+J1  0  50
+[RESERVOIRS]
+R1  100
+[PIPES]
+P1  R1  J1  1000  300  130
+[OPTIONS]
+Units  LPS
+Headloss  H-W
+"#;
+        let results = solve(&INP::read(input.to_string())).unwrap();
+
+        let headloss = 10.67 * 1000.0 * 0.05_f64.powf(1.852) / (130_f64.powf(1.852) * 0.3_f64.powf(4.871));
+        assert!(results.converged);
+        assert!((results.node("J1").unwrap().head - (100.0 - headloss)).abs() < 1e-3);
+        assert!((results.link("P1").unwrap().flow - 50.0).abs() < 1e-3);
+        assert!((results.link("P1").unwrap().velocity - 0.05 / (std::f64::consts::PI * 0.0225)).abs() < 1e-4);
+        assert!((results.node("R1").unwrap().demand + 50.0).abs() < 1e-3);
+    }
+
+    #[test]
+    fn pumps_add_head() {
+        let input = r#"
+[JUNCTIONS]
+J1  0  50
+[RESERVOIRS]
+R1  0
+[PUMPS]
+PU1  R1  J1  HEAD C1
+[CURVES]
+C1  50  40
+[OPTIONS]
+Units  LPS
+"#;
+        let results = solve(&INP::read(input.to_string())).unwrap();
+
+        assert!((results.node("J1").unwrap().pressure - 40.0).abs() < 1e-3);
+    }
+
+    #[test]
+    fn pressure_reducing_valves_limit_the_downstream_pressure() {
+        let input = r#"
+[JUNCTIONS]
+J1  0  0
+J2  10  10
+[RESERVOIRS]
+R1  100
+[PIPES]
+P1  R1  J1  100  300  130
+[VALVES]
+V1  J1  J2  300  PRV  30  0
+[OPTIONS]
+Units  LPS
+"#;
+        let results = solve(&INP::read(input.to_string())).unwrap();
+
+        assert!((results.node("J2").unwrap().pressure - 30.0).abs() < 1e-3);
+        assert_eq!(results.link("V1").unwrap().status, LinkStatus::Active);
+    }
+
+    fn a_low_pressure_model() -> String {
+        r#"
+[JUNCTIONS]
+J1  0  50
+J2  0  5
+[RESERVOIRS]
+R1  20
+[PIPES]
+P1  R1  J1  1000  150  130
+P2  R1  J2  100  300  130
+[OPTIONS]
+Units  LPS
+"#.to_string()
+    }
+
+    #[test]
+    fn demand_driven_analysis_delivers_the_full_demand() {
+        let results = solve(&INP::read(a_low_pressure_model())).unwrap();
+
+        let junction = results.node("J1").unwrap();
+        assert!(junction.pressure < 0.0);
+        assert_eq!(junction.delivered_demand, junction.requested_demand);
+    }
+
+    #[test]
+    fn pressure_driven_analysis_reduces_demand_at_low_pressure() {
+        let input = a_low_pressure_model() + "Demand Model  PDA\nMinimum Pressure  0\nRequired Pressure  25\nPressure Exponent  0.5\n";
+        let results = solve(&INP::read(input.clone())).unwrap();
+
+        assert!(results.converged);
+        let junction = results.node("J1").unwrap();
+        assert!(junction.pressure > 0.0 && junction.pressure < 25.0);
+        let expected = 50.0 * (junction.pressure / 25.0).sqrt();
+        assert!((junction.delivered_demand - expected).abs() < 0.01, "{} {}", junction.delivered_demand, expected);
+        assert_eq!(junction.requested_demand, 50.0);
+        assert!((results.node("J2").unwrap().delivered_demand - 5.0 * (results.node("J2").unwrap().pressure / 25.0).sqrt()).abs() < 0.01);
+
+        let results = solve(&INP::read(input.replace("R1  20", "R1  100"))).unwrap();
+
+        assert!((results.node("J1").unwrap().delivered_demand - 50.0).abs() < 0.01);
+    }
+
+    #[test]
+    fn emitters_discharge_with_the_pressure() {
+        let input = a_low_pressure_model() + "[EMITTERS]\nJ2  2\n";
+        let results = solve(&INP::read(input)).unwrap();
+
+        let junction = results.node("J2").unwrap();
+        assert!((junction.emitter_flow - 2.0 * junction.pressure.sqrt()).abs() < 0.01);
+        assert!((junction.demand - junction.delivered_demand - junction.emitter_flow).abs() < 1e-9);
+    }
+
+    #[test]
+    fn check_valves_close_against_reverse_flow() {
+        let input = r#"
+[JUNCTIONS]
+J1  0  10
+[RESERVOIRS]
+R1  100
+R2  50
+[PIPES]
+P1  R1  J1  100  300  130
+P2  J1  R2  100  300  130  0  CV
+[OPTIONS]
+Units  LPS
+"#;
+        let results = solve(&INP::read(input.to_string())).unwrap();
+
+        assert_eq!(results.link("P2").unwrap().status, LinkStatus::Open);
+
+        let results = solve(&INP::read(input.replace("R2  50", "R2  150"))).unwrap();
+
+        assert_eq!(results.link("P2").unwrap().status, LinkStatus::Closed);
+        assert!((results.link("P1").unwrap().flow - 10.0).abs() < 1e-3);
+    }
+
+    /// Results for a pump, a PRV and two check valves worked out by hand from the equations
+    /// and internal constants of EPANET 2.2: Hazen-Williams headloss of
+    /// 4.727 L Q^1.852 / (C^1.852 D^4.871) in feet and cfs, and single point pump curves
+    /// fitted through 1.33334 times the head at no flow and twice the flow at no head.
+    #[test]
+    fn solve_a_pump_a_prv_and_check_valves() {
+        let input = r#"
+[JUNCTIONS]
+J1  0  0
+J2  0  20
+J3  5  30
+J4  0  10
+[RESERVOIRS]
+R1  100
+R2  50
+[PIPES]
+P1  J1  J2  1000  300  130
+P2  J3  R2  100   300  130  0  CV
+P3  J2  J4  100   150  130  0  CV
+[PUMPS]
+PU1  R1  J1  HEAD C1
+[VALVES]
+V1  J2  J3  300  PRV  40  0
+[CURVES]
+C1  50  40
+[OPTIONS]
+Units  LPS
+Headloss  H-W
+"#;
+        let results = solve(&INP::read(input.to_string())).unwrap();
+
+        let tolerance = 0.01;
+        for (id, head, pressure) in [("J1", 134.133, 134.133), ("J2", 131.638, 131.638), ("J3", 45.0, 40.0), ("J4", 131.374, 131.374)] {
+            let node = results.node(id).unwrap();
+            assert!((node.head - head).abs() < tolerance, "head of {}: {} != {}", id, node.head, head);
+            assert!((node.pressure - pressure).abs() < tolerance, "pressure of {}: {} != {}", id, node.pressure, pressure);
+        }
+        for (id, flow, status) in [("PU1", 60.0, LinkStatus::Open), ("P1", 60.0, LinkStatus::Open), ("V1", 30.0, LinkStatus::Active), ("P2", 0.0, LinkStatus::Closed), ("P3", 10.0, LinkStatus::Open)] {
+            let link = results.link(id).unwrap();
+            assert!((link.flow - flow).abs() < tolerance, "flow of {}: {} != {}", id, link.flow, flow);
+            assert_eq!(link.status, status, "status of {}", id);
+        }
+        assert!((results.link("PU1").unwrap().headloss + 34.133).abs() < tolerance);
+    }
+
+    #[test]
+    fn solve_magnetic_island() {
+        let inp = INP::read(fs::read_to_string("tests/MagneticIslandEnhanced.inp").unwrap());
+
+        let results = solve(&inp).unwrap();
+
+        assert!(results.converged, "relative error {}", results.relative_error);
+        let balance = results.nodes.iter().map(|node| node.demand).sum::<f64>();
+        let demand = results.nodes.iter().filter(|node| node.id.starts_with("J_")).map(|node| node.demand).sum::<f64>();
+        assert!(balance.abs() < 1e-3 * demand, "{} of {}", balance, demand);
+
+        // Flow into every junction matches its demand, as EPANET requires to converge.
+        let mut inflows = results.nodes.iter().map(|node| (node.id.as_str(), 0.0)).collect::<std::collections::HashMap<&str, f64>>();
+        let ends = inp.pipes.iter().map(|pipe| (&pipe.id, &pipe.node1, &pipe.node2))
+            .chain(inp.pumps.iter().map(|pump| (&pump.id, &pump.start_node, &pump.end_node)))
+            .chain(inp.valves.iter().map(|valve| (&valve.id, &valve.start_node, &valve.end_node)));
+        for (id, start, end) in ends {
+            let flow = results.link(id).unwrap().flow;
+            *inflows.get_mut(start.as_str()).unwrap() -= flow;
+            *inflows.get_mut(end.as_str()).unwrap() += flow;
+        }
+        for node in results.nodes.iter().filter(|node| node.id.starts_with("J_")) {
+            assert!((inflows[node.id.as_str()] - node.demand).abs() < 0.01, "{}: {} != {}", node.id, inflows[node.id.as_str()], node.demand);
+        }
+    }
+
+    /// Compares the heads and flows at time 0 with those of EPANET 2.2, read from
+    /// `tests/MagneticIslandEnhanced.epanet.csv`: `head,<node>,<m>` and `flow,<link>,<L/s>`
+    /// rows as `EN_HEAD` and `EN_FLOW` return them after the first hydraulic step.
+    #[test]
+    #[ignore = "needs tests/MagneticIslandEnhanced.epanet.csv written by EPANET 2.2"]
+    fn match_epanet_on_magnetic_island() {
+        let inp = INP::read(fs::read_to_string("tests/MagneticIslandEnhanced.inp").unwrap());
+        let reference = fs::read_to_string("tests/MagneticIslandEnhanced.epanet.csv").unwrap();
+
+        let results = solve(&inp).unwrap();
+
+        let mut compared = 0;
+        for line in reference.lines().filter(|line| !line.trim().is_empty()) {
+            let fields = line.split(',').map(str::trim).collect::<Vec<&str>>();
+            let expected = fields[2].parse::<f64>().unwrap();
+            let (value, tolerance) = match fields[0] {
+                "head" => (results.node(fields[1]).unwrap().head, 0.01),
+                "flow" => (results.link(fields[1]).unwrap().flow, 0.01 + 1e-3 * expected.abs()),
+                kind => panic!("Unknown reference {}", kind),
+            };
+            assert!((value - expected).abs() < tolerance, "{} of {}: {} != {}", fields[0], fields[1], value, expected);
+            compared += 1;
+        }
+        assert_eq!(compared, results.nodes.len() + results.links.len());
+    }
+}
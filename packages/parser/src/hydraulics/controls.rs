@@ -0,0 +1,106 @@
+use crate::controls::{compare, compare_clocktime, compare_time, parse_value, resolve_actions, rule_holds};
+use crate::sections::{ControlAction, Premise, Relation};
+use crate::units::Quantity;
+use super::model::ModelCondition;
+use super::solver::{Solver, State};
+
+/// Simulation clock: elapsed seconds, elapsed seconds at the previous step and time of day.
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub struct Clock {
+    pub time: u64,
+    pub previous: Option<u64>,
+    pub clocktime: u64,
+}
+
+/// Applies the [CONTROLS] whose condition holds. Returns whether any link changed.
+pub fn apply_controls(solver: &mut Solver, clock: &Clock) -> bool {
+    let mut changed = false;
+    for control in solver.model.controls.iter() {
+        let active = match control.condition {
+            ModelCondition::HeadAbove(node, head) => solver.heads[node] > head,
+            ModelCondition::HeadBelow(node, head) => solver.heads[node] < head,
+            ModelCondition::Time(time) => clock.time == time,
+            ModelCondition::ClockTime(time) => clock.clocktime == time % 86400,
+        };
+        if active {
+            changed |= solver.apply(control.link, &control.action);
+        }
+    }
+    changed
+}
+
+/// Whether a rule premise holds for the current state of the solver.
+pub fn evaluate_premise(solver: &Solver, clock: &Clock, premise: &Premise) -> bool {
+    let model = solver.model;
+    let factor = |quantity: Quantity| quantity.si_factor(model.flow_units, model.formula);
+    let id = premise.object_id.as_deref().unwrap_or("");
+    let outcome = match (premise.object.as_str(), premise.attribute.as_str()) {
+        ("SYSTEM", "TIME") => compare_time(clock.time, clock.previous, premise.relation, &premise.value),
+        ("SYSTEM", "CLOCKTIME") => {
+            let elapsed = clock.previous.map(|previous| clock.time - previous);
+            compare_clocktime(clock.clocktime, elapsed, premise.relation, &premise.value)
+        },
+        ("SYSTEM", "DEMAND") => parse_value(&premise.value).map(|target| {
+            compare(solver.demands.iter().sum::<f64>(), premise.relation, target * factor(Quantity::Flow))
+        }),
+        ("NODE", _) | ("JUNCTION", _) | ("RESERVOIR", _) | ("TANK", _) => model.network.node(id).and_then(|node| {
+            let target = parse_value(&premise.value)?;
+            let (value, quantity) = match premise.attribute.as_str() {
+                "HEAD" => (solver.heads[node], Quantity::Elevation),
+                "LEVEL" => (solver.heads[node] - model.elevations[node], Quantity::Length),
+                "PRESSURE" => (solver.heads[node] - model.elevations[node], Quantity::Pressure),
+                "DEMAND" if model.is_junction(node) => (solver.demands[node], Quantity::Flow),
+                "DEMAND" => (solver.inflows()[node], Quantity::Flow),
+                _ => return None,
+            };
+            Some(compare(value, premise.relation, target * factor(quantity)))
+        }),
+        ("LINK", _) | ("PIPE", _) | ("PUMP", _) | ("VALVE", _) => model.network.link(id).and_then(|link| {
+            match premise.attribute.as_str() {
+                "STATUS" => {
+                    let status = match solver.states[link] {
+                        State::Closed | State::TempClosed => "CLOSED",
+                        State::Active => "ACTIVE",
+                        State::Open | State::XFcv => "OPEN",
+                    };
+                    let equal = status == premise.value.to_uppercase();
+                    match premise.relation {
+                        Relation::Equal => Some(equal),
+                        Relation::NotEqual => Some(!equal),
+                        _ => None,
+                    }
+                },
+                "FLOW" => Some(compare(solver.flows[link], premise.relation, parse_value(&premise.value)? * factor(Quantity::Flow))),
+                "SETTING" => Some(compare(solver.settings[link]?, premise.relation, model.si_setting(link, parse_value(&premise.value)?))),
+                _ => None,
+            }
+        }),
+        _ => None,
+    };
+    outcome.unwrap_or(false)
+}
+
+/// Applies the THEN or ELSE actions of every rule. When several rules act on the
+/// same link, the one with the highest priority wins. Returns whether any link changed.
+pub fn apply_rules(solver: &mut Solver, clock: &Clock) -> bool {
+    let model = solver.model;
+    let mut actions = Vec::new();
+    for rule in model.rules.iter() {
+        let holds = rule_holds(&rule.premises, |premise| evaluate_premise(solver, clock, premise));
+        for action in if holds { &rule.then_actions } else { &rule.else_actions } {
+            if let Some(link) = model.network.link(&action.link_id) {
+                actions.push((rule.priority, link, &action.action));
+            }
+        }
+    }
+
+    let mut changed = false;
+    for (link, action) in resolve_actions(actions) {
+        let action = match action {
+            ControlAction::Setting(value) => ControlAction::Setting(model.si_setting(link, *value)),
+            action => action.clone(),
+        };
+        changed |= solver.apply(link, &action);
+    }
+    changed
+}
@@ -0,0 +1,231 @@
+use std::cmp::Reverse;
+use std::collections::{BTreeSet, BinaryHeap};
+
+/// Sparse symmetric positive definite matrix solved with an LDL' factorisation.
+/// Rows are reordered with a minimum degree heuristic to keep the fill-in low,
+/// and the symbolic structure is computed once so every solve only redoes the numbers.
+#[derive(Debug, Clone)]
+pub struct SparseMatrix {
+    order: Vec<usize>,
+    position: Vec<usize>,
+    column_start: Vec<usize>,
+    rows: Vec<usize>,
+    diagonal: Vec<f64>,
+    values: Vec<f64>,
+    // For every column, the slot updated by each pair of its entries (or usize::MAX for the diagonal).
+    updates: Vec<Vec<usize>>,
+    edge_slots: Vec<Option<usize>>,
+}
+
+impl SparseMatrix {
+    /// Builds the structure of a `size` x `size` matrix whose off-diagonal
+    /// entries are the given `edges`.
+    pub fn new(size: usize, edges: &[(usize, usize)]) -> SparseMatrix {
+        let mut adjacency = vec![BTreeSet::new(); size];
+        for &(a, b) in edges.iter() {
+            if a != b {
+                adjacency[a].insert(b);
+                adjacency[b].insert(a);
+            }
+        }
+
+        let mut eliminated = vec![false; size];
+        let mut heap = (0..size).map(|node| (Reverse(adjacency[node].len()), Reverse(node))).collect::<BinaryHeap<_>>();
+        let mut order = Vec::with_capacity(size);
+        let mut structure = vec![Vec::new(); size];
+        while let Some((Reverse(degree), Reverse(node))) = heap.pop() {
+            if eliminated[node] || degree != adjacency[node].len() {
+                continue;
+            }
+            eliminated[node] = true;
+            order.push(node);
+            let neighbours = std::mem::take(&mut adjacency[node]);
+            for &a in neighbours.iter() {
+                adjacency[a].remove(&node);
+                for &b in neighbours.iter() {
+                    if a != b {
+                        adjacency[a].insert(b);
+                    }
+                }
+                heap.push((Reverse(adjacency[a].len()), Reverse(a)));
+            }
+            structure[node] = neighbours.into_iter().collect();
+        }
+
+        let mut position = vec![0; size];
+        for (k, &node) in order.iter().enumerate() {
+            position[node] = k;
+        }
+        let mut column_start = vec![0];
+        let mut rows = Vec::new();
+        for &node in order.iter() {
+            let mut column = structure[node].iter().map(|&row| position[row]).collect::<Vec<usize>>();
+            column.sort_unstable();
+            rows.extend(column);
+            column_start.push(rows.len());
+        }
+
+        let slot = |column: usize, row: usize| -> usize {
+            let range = column_start[column]..column_start[column + 1];
+            range.start + rows[range].binary_search(&row).unwrap()
+        };
+        let updates = (0..size)
+            .map(|k| {
+                let entries = &rows[column_start[k]..column_start[k + 1]];
+                let mut targets = Vec::new();
+                for (s, &a) in entries.iter().enumerate() {
+                    for &b in entries[s..].iter() {
+                        targets.push(if a == b { usize::MAX } else { slot(a, b) });
+                    }
+                }
+                targets
+            })
+            .collect();
+        let edge_slots = edges.iter()
+            .map(|&(a, b)| {
+                if a == b {
+                    return None;
+                }
+                let (a, b) = (position[a].min(position[b]), position[a].max(position[b]));
+                Some(slot(a, b))
+            })
+            .collect();
+
+        SparseMatrix {
+            order,
+            position,
+            column_start,
+            diagonal: vec![0.0; size],
+            values: vec![0.0; rows.len()],
+            rows,
+            updates,
+            edge_slots,
+        }
+    }
+
+    pub fn size(&self) -> usize {
+        self.order.len()
+    }
+
+    pub fn clear(&mut self) {
+        self.diagonal.iter_mut().for_each(|value| *value = 0.0);
+        self.values.iter_mut().for_each(|value| *value = 0.0);
+    }
+
+    pub fn add_diagonal(&mut self, row: usize, value: f64) {
+        self.diagonal[self.position[row]] += value;
+    }
+
+    /// Adds `value` to the off-diagonal entry of the edge given to `new` at `edge`.
+    pub fn add_edge(&mut self, edge: usize, value: f64) {
+        if let Some(slot) = self.edge_slots[edge] {
+            self.values[slot] += value;
+        }
+    }
+
+    /// Factorises the matrix and solves it for `rhs`, which is replaced by the solution.
+    /// Fails with the row of the first non positive pivot.
+    pub fn solve(&mut self, rhs: &mut [f64]) -> Result<(), usize> {
+        let size = self.size();
+        for k in 0..size {
+            let pivot = self.diagonal[k];
+            if pivot <= 0.0 || !pivot.is_finite() {
+                return Err(self.order[k]);
+            }
+            let range = self.column_start[k]..self.column_start[k + 1];
+            for slot in range.clone() {
+                self.values[slot] /= pivot;
+            }
+            let mut update = 0;
+            for s in range.clone() {
+                let a = self.rows[s];
+                for t in s..range.end {
+                    let change = self.values[s] * self.values[t] * pivot;
+                    let target = self.updates[k][update];
+                    if target == usize::MAX {
+                        self.diagonal[a] -= change;
+                    } else {
+                        self.values[target] -= change;
+                    }
+                    update += 1;
+                }
+            }
+        }
+
+        let mut x = self.order.iter().map(|&row| rhs[row]).collect::<Vec<f64>>();
+        for k in 0..size {
+            for slot in self.column_start[k]..self.column_start[k + 1] {
+                x[self.rows[slot]] -= self.values[slot] * x[k];
+            }
+        }
+        for (value, pivot) in x.iter_mut().zip(self.diagonal.iter()) {
+            *value /= pivot;
+        }
+        for k in (0..size).rev() {
+            for slot in self.column_start[k]..self.column_start[k + 1] {
+                x[k] -= self.values[slot] * x[self.rows[slot]];
+            }
+        }
+        for (k, &row) in self.order.iter().enumerate() {
+            rhs[row] = x[k];
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::SparseMatrix;
+
+    #[test]
+    fn solve_a_symmetric_system() {
+        // | 4 -1  0 -1 |       |-2 |
+        // |-1  4 -1  0 | x  =  | 4 |
+        // | 0 -1  4 -1 |       | 6 |
+        // |-1  0 -1  4 |       |12 |
+        let edges = [(0, 1), (1, 2), (2, 3), (3, 0)];
+        let mut matrix = SparseMatrix::new(4, &edges);
+        for row in 0..4 {
+            matrix.add_diagonal(row, 4.0);
+        }
+        for edge in 0..4 {
+            matrix.add_edge(edge, -1.0);
+        }
+        let mut rhs = vec![-2.0, 4.0, 6.0, 12.0];
+
+        matrix.solve(&mut rhs).unwrap();
+
+        let expected = [1.0, 2.0, 3.0, 4.0];
+        for (value, expected) in rhs.iter().zip(expected.iter()) {
+            assert!((value - expected).abs() < 1e-12, "{:?}", rhs);
+        }
+    }
+
+    #[test]
+    fn refactorise_with_new_values() {
+        let mut matrix = SparseMatrix::new(2, &[(0, 1), (1, 0)]);
+        matrix.add_diagonal(0, 2.0);
+        matrix.add_diagonal(1, 2.0);
+        matrix.add_edge(0, -0.5);
+        matrix.add_edge(1, -0.5);
+        let mut rhs = vec![1.0, 1.0];
+        matrix.solve(&mut rhs).unwrap();
+
+        matrix.clear();
+        matrix.add_diagonal(0, 1.0);
+        matrix.add_diagonal(1, 1.0);
+        let mut rhs = vec![3.0, 4.0];
+        matrix.solve(&mut rhs).unwrap();
+
+        assert_eq!(rhs, vec![3.0, 4.0]);
+    }
+
+    #[test]
+    fn report_singular_rows() {
+        let mut matrix = SparseMatrix::new(2, &[(0, 1)]);
+        matrix.add_diagonal(0, 1.0);
+
+        assert_eq!(matrix.solve(&mut [1.0, 1.0]), Err(1));
+    }
+}
@@ -0,0 +1,580 @@
+use std::collections::HashMap;
+use crate::INP;
+use crate::network::{Network, LinkKind};
+use crate::sections::{ControlAction, ControlCondition, Rule, ValveType};
+use crate::units::{FlowUnits, HeadlossFormula, Quantity};
+use super::HydraulicError;
+
+pub const GRAVITY: f64 = 9.81;
+// Minor loss coefficient K turned into K / (2 g A^2) for a diameter of 1 m.
+pub const MINOR_LOSS_FACTOR: f64 = 8.0 / (GRAVITY * std::f64::consts::PI * std::f64::consts::PI);
+
+#[derive(Debug, PartialEq, Clone)]
+pub enum PumpCurve {
+    /// Head gain h0 - r Q^n.
+    Power { h0: f64, r: f64, n: f64 },
+    /// Head gain interpolated between the (flow, head) points.
+    Custom(Vec<(f64, f64)>),
+    /// Constant power, in kW.
+    ConstantPower(f64),
+}
+
+#[derive(Debug, PartialEq, Clone)]
+pub enum LinkType {
+    Pipe,
+    CheckValve,
+    Pump(PumpCurve),
+    Valve(ValveType),
+}
+
+#[derive(Debug, PartialEq, Clone)]
+pub struct ModelLink {
+    pub link_type: LinkType,
+    pub start: usize,
+    pub end: usize,
+    pub diameter: f64,
+    pub length: f64,
+    /// Resistance coefficient of H-W and C-M pipes, absolute roughness of D-W pipes.
+    pub resistance: f64,
+    pub minor_loss: f64,
+}
+
+#[derive(Debug, PartialEq, Clone)]
+pub struct ModelTank {
+    pub node: usize,
+    pub elevation: f64,
+    pub init_level: f64,
+    pub min_level: f64,
+    pub max_level: f64,
+    pub diameter: f64,
+    pub min_volume: f64,
+    pub volume_curve: Option<Vec<(f64, f64)>>,
+    pub overflow: bool,
+}
+
+/// Demand of a junction, in m3/s, and the pattern that modulates it.
+#[derive(Debug, PartialEq, Clone)]
+pub struct ModelDemand {
+    pub base: f64,
+    pub pattern: Option<usize>,
+}
+
+/// Control of the [CONTROLS] section with its node condition turned into a head.
+#[derive(Debug, PartialEq, Clone)]
+pub struct ModelControl {
+    pub link: usize,
+    pub action: ControlAction,
+    pub condition: ModelCondition,
+}
+
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum ModelCondition {
+    HeadAbove(usize, f64),
+    HeadBelow(usize, f64),
+    Time(u64),
+    ClockTime(u64),
+}
+
+/// Durations of the [TIMES] section, in seconds.
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub struct TimeOptions {
+    pub duration: u64,
+    pub hydraulic_step: u64,
+    pub pattern_step: u64,
+    pub pattern_start: u64,
+    pub report_step: u64,
+    pub report_start: u64,
+    pub start_clocktime: u64,
+}
+
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum DemandModel {
+    /// Demand-driven analysis: junctions always receive their full demand.
+    Dda,
+    /// Pressure-driven analysis: junctions receive their full demand above the required
+    /// pressure, nothing below the minimum pressure and a power of the pressure in between.
+    Pda { minimum_pressure: f64, required_pressure: f64, pressure_exponent: f64 },
+}
+
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub struct SolverOptions {
+    pub trials: usize,
+    pub accuracy: f64,
+    pub check_frequency: usize,
+    pub max_check: usize,
+    pub emitter_exponent: f64,
+    pub viscosity: f64,
+    pub demand_multiplier: f64,
+    pub demand_model: DemandModel,
+}
+
+/// INP network prepared for the hydraulic solver, with every value in SI units:
+/// metres, cubic metres per second and kilowatts.
+#[derive(Debug, Clone)]
+pub struct HydraulicModel {
+    pub network: Network,
+    pub junction_count: usize,
+    pub elevations: Vec<f64>,
+    pub links: Vec<ModelLink>,
+    pub demands: Vec<Vec<ModelDemand>>,
+    pub emitters: Vec<f64>,
+    pub reservoir_patterns: Vec<(usize, usize)>,
+    pub pump_patterns: Vec<(usize, usize)>,
+    pub tanks: Vec<ModelTank>,
+    pub patterns: Vec<Vec<f64>>,
+    pub pattern_ids: Vec<String>,
+    pub initial_settings: Vec<Option<f64>>,
+    pub initial_closed: Vec<bool>,
+    pub formula: HeadlossFormula,
+    pub flow_units: FlowUnits,
+    pub options: SolverOptions,
+    pub times: TimeOptions,
+    pub controls: Vec<ModelControl>,
+    pub rules: Vec<Rule>,
+}
+
+impl ModelLink {
+    /// Head gain at zero flow, for pumps.
+    pub fn shutoff_head(&self) -> f64 {
+        match &self.link_type {
+            LinkType::Pump(PumpCurve::Power { h0, .. }) => *h0,
+            LinkType::Pump(PumpCurve::Custom(points)) => curve_segment(points, 0.0).0,
+            _ => f64::INFINITY,
+        }
+    }
+
+    pub fn area(&self) -> f64 {
+        std::f64::consts::PI * self.diameter * self.diameter / 4.0
+    }
+}
+
+impl ModelTank {
+    pub fn area(&self) -> f64 {
+        std::f64::consts::PI * self.diameter * self.diameter / 4.0
+    }
+
+    /// Volume of water stored when the tank is filled up to `level`.
+    pub fn volume(&self, level: f64) -> f64 {
+        match &self.volume_curve {
+            Some(points) => interpolate(points, level),
+            None => {
+                let min_volume = if self.min_volume > 0.0 { self.min_volume } else { self.area() * self.min_level };
+                min_volume + self.area() * (level - self.min_level)
+            },
+        }
+    }
+
+    /// Level of the tank when it stores `volume`.
+    pub fn level(&self, volume: f64) -> f64 {
+        match &self.volume_curve {
+            Some(points) => interpolate(&points.iter().map(|&(level, volume)| (volume, level)).collect::<Vec<(f64, f64)>>(), volume),
+            None => self.min_level + (volume - self.volume(self.min_level)) / self.area(),
+        }
+    }
+}
+
+/// Intercept and slope of the curve segment that contains `x`, extended at both ends.
+pub fn curve_segment(points: &[(f64, f64)], x: f64) -> (f64, f64) {
+    if points.len() == 1 {
+        return (points[0].1, 0.0);
+    }
+    let mut i = 1;
+    while i < points.len() - 1 && x > points[i].0 {
+        i += 1;
+    }
+    let (x1, y1) = points[i - 1];
+    let (x2, y2) = points[i];
+    let slope = if x2 == x1 { 0.0 } else { (y2 - y1) / (x2 - x1) };
+    (y1 - slope * x1, slope)
+}
+
+pub fn interpolate(points: &[(f64, f64)], x: f64) -> f64 {
+    let (intercept, slope) = curve_segment(points, x);
+    intercept + slope * x
+}
+
+impl HydraulicModel {
+    pub fn new(inp: &INP) -> Result<HydraulicModel, HydraulicError> {
+        let network = Network::new(inp);
+        let flow_units = inp.flow_units();
+        let formula = inp.headloss_formula();
+        let factor = |quantity: Quantity| quantity.si_factor(flow_units, formula);
+        let junction_count = inp.junctions.iter().filter(|junction| network.node(&junction.id).is_some()).count();
+
+        let mut patterns = Vec::new();
+        let mut pattern_ids = Vec::new();
+        let mut pattern_index = HashMap::new();
+        for pattern in inp.patterns.iter() {
+            if !pattern_index.contains_key(&pattern.id) {
+                pattern_index.insert(pattern.id.clone(), patterns.len());
+                pattern_ids.push(pattern.id.clone());
+                patterns.push(inp.pattern(&pattern.id).unwrap_or_default());
+            }
+        }
+        let default_pattern = inp.option("PATTERN").unwrap_or("1");
+        let find_pattern = |id: Option<&String>| match id {
+            Some(id) => pattern_index.get(id).copied(),
+            None => pattern_index.get(default_pattern).copied(),
+        };
+
+        let mut elevations = vec![0.0; network.nodes.len()];
+        let mut demands = vec![Vec::new(); junction_count];
+        let mut emitters = vec![0.0; junction_count];
+        for junction in inp.junctions.iter() {
+            if let Some(node) = network.node(&junction.id) {
+                elevations[node] = junction.elevation * factor(Quantity::Elevation);
+                if let Some(base) = junction.base_demand_flow {
+                    demands[node] = vec![ModelDemand { base: base * factor(Quantity::Flow), pattern: find_pattern(junction.demand_pattern_id.as_ref()) }];
+                }
+            }
+        }
+        let mut replaced = vec![false; junction_count];
+        for demand in inp.demands.iter() {
+            if let Some(node) = network.node(&demand.junction_id).filter(|&node| node < junction_count) {
+                if !replaced[node] {
+                    demands[node].clear();
+                    replaced[node] = true;
+                }
+                demands[node].push(ModelDemand { base: demand.base_demand * factor(Quantity::Flow), pattern: find_pattern(demand.pattern_id.as_ref()) });
+            }
+        }
+
+        let emitter_exponent = inp.option_number("EMITTER EXPONENT").unwrap_or(0.5);
+        for emitter in inp.emitters.iter() {
+            if let Some(node) = network.node(&emitter.junction_id).filter(|&node| node < junction_count) {
+                let flow_factor = factor(Quantity::Flow);
+                let pressure_factor = factor(Quantity::Pressure);
+                emitters[node] = emitter.flow_coefficient * flow_factor / pressure_factor.powf(emitter_exponent);
+            }
+        }
+
+        let mut reservoir_patterns = Vec::new();
+        for reservoir in inp.reservoirs.iter() {
+            if let Some(node) = network.node(&reservoir.id) {
+                elevations[node] = reservoir.head * factor(Quantity::Elevation);
+                if let Some(pattern) = reservoir.pattern.as_ref().and_then(|id| pattern_index.get(id)) {
+                    reservoir_patterns.push((node, *pattern));
+                }
+            }
+        }
+
+        let curve = |id: &str| -> Vec<(f64, f64)> {
+            inp.curves.iter().filter(|point| point.id == id).map(|point| (point.x, point.y)).collect()
+        };
+        let mut tanks = Vec::new();
+        for tank in inp.tanks.iter() {
+            if let Some(node) = network.node(&tank.id) {
+                let length = factor(Quantity::Length);
+                elevations[node] = tank.elevation * factor(Quantity::Elevation);
+                let volume_curve = tank.volume_curve_id.as_ref()
+                    .map(|id| curve(id).into_iter().map(|(x, y)| (x * length, y * factor(Quantity::Volume))).collect::<Vec<(f64, f64)>>())
+                    .filter(|points| !points.is_empty());
+                tanks.push(ModelTank {
+                    node,
+                    elevation: elevations[node],
+                    init_level: tank.init_level * length,
+                    min_level: tank.min_level * length,
+                    max_level: tank.max_level * length,
+                    diameter: tank.diameter * length,
+                    min_volume: tank.min_volume * factor(Quantity::Volume),
+                    volume_curve,
+                    overflow: tank.overflow,
+                });
+            }
+        }
+
+        let pipes = inp.pipes.iter().map(|pipe| (pipe.id.as_str(), pipe)).collect::<HashMap<_, _>>();
+        let pumps = inp.pumps.iter().map(|pump| (pump.id.as_str(), pump)).collect::<HashMap<_, _>>();
+        let valves = inp.valves.iter().map(|valve| (valve.id.as_str(), valve)).collect::<HashMap<_, _>>();
+        let statuses = inp.statuses.iter().map(|status| (status.link_id.as_str(), &status.status)).collect::<HashMap<_, _>>();
+
+        let mut links = Vec::new();
+        let mut initial_settings = Vec::new();
+        let mut pump_patterns = Vec::new();
+        for link in network.links.iter() {
+            let mut model_link = ModelLink {
+                link_type: LinkType::Pipe,
+                start: link.start,
+                end: link.end,
+                diameter: 0.0,
+                length: 0.0,
+                resistance: 0.0,
+                minor_loss: 0.0,
+            };
+            let mut setting = None;
+            match link.kind {
+                LinkKind::Pipe => {
+                    let pipe = pipes[link.id.as_str()];
+                    let diameter = pipe.diameter * factor(Quantity::Diameter);
+                    let length = pipe.length * factor(Quantity::Length);
+                    model_link.link_type = if link.check_valve { LinkType::CheckValve } else { LinkType::Pipe };
+                    model_link.diameter = diameter;
+                    model_link.length = length;
+                    model_link.minor_loss = MINOR_LOSS_FACTOR * pipe.minor_loss / diameter.powi(4);
+                    model_link.resistance = match formula {
+                        HeadlossFormula::HazenWilliams => 10.67 * length / (pipe.roughness.powf(1.852) * diameter.powf(4.871)),
+                        HeadlossFormula::DarcyWeisbach => pipe.roughness * factor(Quantity::Roughness),
+                        HeadlossFormula::ChezyManning => 10.29 * pipe.roughness * pipe.roughness * length / diameter.powf(16.0 / 3.0),
+                    };
+                },
+                LinkKind::Pump => {
+                    let pump = pumps[link.id.as_str()];
+                    let pump_curve = match (&pump.head, pump.power) {
+                        (Some(id), _) => {
+                            let points = curve(id).into_iter()
+                                .map(|(x, y)| (x * factor(Quantity::Flow), y * factor(Quantity::Length)))
+                                .collect::<Vec<(f64, f64)>>();
+                            pump_curve(&link.id, points)?
+                        },
+                        (None, Some(power)) => PumpCurve::ConstantPower(power as f64 * factor(Quantity::Power)),
+                        (None, None) => return Err(HydraulicError { message: format!("Pump {} has no curve", link.id) }),
+                    };
+                    model_link.link_type = LinkType::Pump(pump_curve);
+                    if let Some(pattern) = pump.pattern.as_ref().and_then(|id| pattern_index.get(id)) {
+                        pump_patterns.push((links.len(), *pattern));
+                    }
+                    setting = Some(pump.speed.unwrap_or(1.0) as f64);
+                },
+                LinkKind::Valve => {
+                    let valve = valves[link.id.as_str()];
+                    let diameter = valve.diameter * factor(Quantity::Diameter);
+                    model_link.link_type = LinkType::Valve(valve.valve_type);
+                    model_link.diameter = diameter;
+                    model_link.minor_loss = MINOR_LOSS_FACTOR * valve.minor_loss_coefficient / diameter.powi(4);
+                    setting = Some(valve_setting(&valve.valve_type, valve.valve_setting, flow_units, formula, diameter));
+                },
+            }
+            match statuses.get(link.id.as_str()) {
+                Some(ControlAction::Setting(value)) => {
+                    setting = match &model_link.link_type {
+                        LinkType::Valve(valve_type) => Some(valve_setting(valve_type, *value, flow_units, formula, model_link.diameter)),
+                        _ => Some(*value),
+                    };
+                },
+                Some(_) if link.kind == LinkKind::Valve => setting = None,
+                _ => {},
+            }
+            links.push(model_link);
+            initial_settings.push(setting);
+        }
+
+        let rules = inp.rules().map_err(|error| HydraulicError { message: error.message })?;
+        let mut controls = Vec::new();
+        for control in inp.controls.iter() {
+            let link = match network.link(&control.link_id) {
+                Some(link) => link,
+                None => continue,
+            };
+            let node_head = |node_id: &str, value: f64| -> Option<(usize, f64)> {
+                let node = network.node(node_id)?;
+                let quantity = if tanks.iter().any(|tank| tank.node == node) { Quantity::Length } else { Quantity::Pressure };
+                Some((node, elevations[node] + value * factor(quantity)))
+            };
+            let condition = match &control.condition {
+                ControlCondition::Above { node_id, value } => match node_head(node_id, *value) {
+                    Some((node, head)) => ModelCondition::HeadAbove(node, head),
+                    None => continue,
+                },
+                ControlCondition::Below { node_id, value } => match node_head(node_id, *value) {
+                    Some((node, head)) => ModelCondition::HeadBelow(node, head),
+                    None => continue,
+                },
+                ControlCondition::Time(time) => ModelCondition::Time(*time),
+                ControlCondition::ClockTime(time) => ModelCondition::ClockTime(*time),
+            };
+            controls.push(ModelControl { link, action: control.action.clone(), condition });
+        }
+
+        let demand_model = match inp.option("DEMAND MODEL").map(|value| value.to_uppercase()) {
+            Some(value) if value.starts_with("PDA") => {
+                let minimum_pressure = inp.option_number("MINIMUM PRESSURE").unwrap_or(0.0) * factor(Quantity::Pressure);
+                let required_pressure = inp.option_number("REQUIRED PRESSURE").unwrap_or(0.1) * factor(Quantity::Pressure);
+                if required_pressure - minimum_pressure < 0.1 * factor(Quantity::Pressure) {
+                    return Err(HydraulicError { message: "REQUIRED PRESSURE must exceed MINIMUM PRESSURE".to_string() });
+                }
+                DemandModel::Pda { minimum_pressure, required_pressure, pressure_exponent: inp.option_number("PRESSURE EXPONENT").unwrap_or(0.5) }
+            },
+            _ => DemandModel::Dda,
+        };
+
+        let options = SolverOptions {
+            trials: inp.option_number("TRIALS").unwrap_or(200.0) as usize,
+            accuracy: inp.option_number("ACCURACY").unwrap_or(0.001),
+            check_frequency: inp.option_number("CHECKFREQ").unwrap_or(2.0) as usize,
+            max_check: inp.option_number("MAXCHECK").unwrap_or(10.0) as usize,
+            emitter_exponent,
+            viscosity: crate::headloss::WATER_VISCOSITY * inp.option_number("VISCOSITY").unwrap_or(1.0),
+            demand_multiplier: inp.option_number("DEMAND MULTIPLIER").unwrap_or(1.0),
+            demand_model,
+        };
+
+        let mut model = HydraulicModel {
+            initial_closed: network.links.iter().map(|link| link.initially_closed).collect(),
+            network,
+            junction_count,
+            elevations,
+            links,
+            demands,
+            emitters,
+            reservoir_patterns,
+            pump_patterns,
+            tanks,
+            patterns,
+            pattern_ids,
+            initial_settings,
+            formula,
+            flow_units,
+            options,
+            times: time_options(inp),
+            controls: Vec::new(),
+            rules,
+        };
+        for control in controls.iter_mut() {
+            if let ControlAction::Setting(value) = control.action {
+                control.action = ControlAction::Setting(model.si_setting(control.link, value));
+            }
+        }
+        model.controls = controls;
+
+        Ok(model)
+    }
+
+    /// Pattern period in effect `time` seconds after the start of the simulation.
+    pub fn period(&self, time: u64) -> usize {
+        ((time + self.times.pattern_start) / self.times.pattern_step) as usize
+    }
+
+    /// Setting of a link in SI units, from a setting in the units of the model.
+    pub fn si_setting(&self, link: usize, value: f64) -> f64 {
+        match &self.links[link].link_type {
+            LinkType::Valve(valve_type) => valve_setting(valve_type, value, self.flow_units, self.formula, self.links[link].diameter),
+            _ => value,
+        }
+    }
+
+    /// Multiplier of a pattern at the given pattern period.
+    pub fn multiplier(&self, pattern: usize, period: usize) -> f64 {
+        let multipliers = &self.patterns[pattern];
+        if multipliers.is_empty() { 1.0 } else { multipliers[period % multipliers.len()] }
+    }
+
+    /// Demand of every junction, in m3/s, during a pattern period.
+    pub fn junction_demands(&self, period: usize) -> Vec<f64> {
+        self.demands.iter()
+            .map(|demands| {
+                demands.iter()
+                    .map(|demand| demand.base * demand.pattern.map(|p| self.multiplier(p, period)).unwrap_or(1.0))
+                    .sum::<f64>() * self.options.demand_multiplier
+            })
+            .collect()
+    }
+
+    pub fn is_junction(&self, node: usize) -> bool {
+        node < self.junction_count
+    }
+}
+
+fn pump_curve(id: &str, points: Vec<(f64, f64)>) -> Result<PumpCurve, HydraulicError> {
+    match points.len() {
+        0 => Err(HydraulicError { message: format!("Pump {} has no curve", id) }),
+        1 => {
+            let (flow, head) = points[0];
+            let h0 = 4.0 / 3.0 * head;
+            Ok(PumpCurve::Power { h0, r: (h0 - head) / (flow * flow), n: 2.0 })
+        },
+        3 if points[0].0 == 0.0 => {
+            let (h0, (q1, h1), (q2, h2)) = (points[0].1, points[1], points[2]);
+            let n = ((h0 - h2) / (h0 - h1)).ln() / (q2 / q1).ln();
+            if !(n.is_finite() && n > 0.0 && h0 > h1 && h1 > h2) {
+                return Err(HydraulicError { message: format!("Pump {} has an invalid curve", id) });
+            }
+            Ok(PumpCurve::Power { h0, r: (h0 - h1) / q1.powf(n), n })
+        },
+        _ => Ok(PumpCurve::Custom(points)),
+    }
+}
+
+fn time_options(inp: &INP) -> TimeOptions {
+    let pattern_step = inp.time("PATTERN TIMESTEP").filter(|&step| step > 0).unwrap_or(3600);
+    let report_step = inp.time("REPORT TIMESTEP").filter(|&step| step > 0).unwrap_or(3600);
+    let hydraulic_step = inp.time("HYDRAULIC TIMESTEP").filter(|&step| step > 0).unwrap_or(3600);
+    TimeOptions {
+        duration: inp.time("DURATION").unwrap_or(0),
+        hydraulic_step: hydraulic_step.min(pattern_step).min(report_step),
+        pattern_step,
+        pattern_start: inp.time("PATTERN START").unwrap_or(0),
+        report_step,
+        report_start: inp.time("REPORT START").unwrap_or(0),
+        start_clocktime: inp.time("START CLOCKTIME").unwrap_or(0),
+    }
+}
+
+/// Valve setting in SI units: metres of pressure for PRV, PSV and PBV, m3/s for FCV
+/// and the minor loss K / (2 g A^2) for TCV.
+fn valve_setting(valve_type: &ValveType, setting: f64, flow_units: FlowUnits, formula: HeadlossFormula, diameter: f64) -> f64 {
+    match valve_type {
+        ValveType::Prv | ValveType::Psv | ValveType::Pbv => setting * Quantity::Pressure.si_factor(flow_units, formula),
+        ValveType::Fcv => setting * Quantity::Flow.si_factor(flow_units, formula),
+        ValveType::Tcv => MINOR_LOSS_FACTOR * setting / diameter.powi(4),
+        ValveType::Gpv => setting,
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::fs;
+    use super::{HydraulicModel, LinkType, PumpCurve, curve_segment};
+    use crate::INP;
+
+    #[test]
+    fn build_the_model_in_si_units() {
+        let input = r#"
+[JUNCTIONS]
+J1  100  10  Pat1
+[RESERVOIRS]
+R1  300
+[PIPES]
+P1  R1  J1  1000  12  100
+[PUMPS]
+PU1  R1  J1  HEAD C1
+[CURVES]
+C1  100  200
+[PATTERNS]
+Pat1  0.5  1.5
+[OPTIONS]
+Units  GPM
+"#;
+        let model = HydraulicModel::new(&INP::read(input.to_string())).unwrap();
+
+        assert_eq!(model.junction_count, 1);
+        assert!((model.elevations[0] - 30.48).abs() < 1e-9);
+        assert!((model.links[0].diameter - 0.3048).abs() < 1e-9);
+        assert!((model.junction_demands(1)[0] - 15.0 * 6.309e-5).abs() < 1e-7);
+        match &model.links[1].link_type {
+            LinkType::Pump(PumpCurve::Power { h0, n, .. }) => {
+                assert!((h0 - 200.0 * 0.3048 * 4.0 / 3.0).abs() < 1e-9);
+                assert_eq!(*n, 2.0);
+            },
+            other => panic!("{:?}", other),
+        }
+    }
+
+    #[test]
+    fn demands_section_replaces_junction_demand() {
+        let inp = INP::read(fs::read_to_string("tests/MagneticIslandEnhanced.inp").unwrap());
+        let model = HydraulicModel::new(&inp).unwrap();
+        let j2 = model.network.node("J_00002").unwrap();
+
+        assert_eq!(model.demands[j2].len(), 2);
+        assert!((model.demands[j2][1].base - 0.005498e-3).abs() < 1e-12);
+    }
+
+    #[test]
+    fn find_curve_segments() {
+        let points = [(0.0, 10.0), (2.0, 8.0), (4.0, 4.0)];
+
+        assert_eq!(curve_segment(&points, 1.0), (10.0, -1.0));
+        assert_eq!(curve_segment(&points, 3.0), (12.0, -2.0));
+        assert_eq!(curve_segment(&points, 5.0), (12.0, -2.0));
+    }
+}
@@ -0,0 +1,255 @@
+use serde::{Serialize, Deserialize};
+use crate::{INP, ToJson};
+use crate::sections::ControlAction;
+use super::controls::{apply_controls, apply_rules, Clock};
+use super::model::{HydraulicModel, ModelCondition, TimeOptions};
+use super::solver::{Convergence, Solver, QZERO};
+use super::{HydraulicError, HydraulicResults, LinkResult, NodeResult};
+
+#[derive(Serialize, Deserialize, Debug, PartialEq, Clone)]
+pub struct TimeStep {
+    /// Seconds since the start of the simulation.
+    pub time: u64,
+    pub results: HydraulicResults,
+}
+
+/// Results of an extended-period simulation at every reporting time.
+#[derive(Serialize, Deserialize, Debug, PartialEq, Clone)]
+pub struct Simulation {
+    pub steps: Vec<TimeStep>,
+}
+
+impl Simulation {
+    pub fn node_series(&self, id: &str) -> Vec<(u64, NodeResult)> {
+        self.steps.iter()
+            .filter_map(|step| Some((step.time, step.results.node(id)?.clone())))
+            .collect()
+    }
+
+    pub fn link_series(&self, id: &str) -> Vec<(u64, LinkResult)> {
+        self.steps.iter()
+            .filter_map(|step| Some((step.time, step.results.link(id)?.clone())))
+            .collect()
+    }
+}
+
+impl ToJson for Simulation {}
+
+fn time_to_volume(volume: f64, target: f64, inflow: f64) -> Option<f64> {
+    let time = (target - volume) / inflow;
+    if inflow.abs() > QZERO && time > 0.0 { Some(time) } else { None }
+}
+
+/// Length of the next hydraulic step: up to the next hydraulic, pattern or report time,
+/// a time control, or the moment a tank fills, drains or reaches a control level.
+fn next_step(solver: &Solver, clock: &Clock) -> u64 {
+    let model = solver.model;
+    let times = model.times;
+    let time = clock.time;
+    let mut step = (times.hydraulic_step - time % times.hydraulic_step)
+        .min(times.duration - time)
+        .min(times.pattern_step - (time + times.pattern_start) % times.pattern_step);
+    step = step.min(if time < times.report_start {
+        times.report_start - time
+    } else {
+        times.report_step - (time - times.report_start) % times.report_step
+    });
+
+    let inflows = solver.inflows();
+    let mut limit = f64::INFINITY;
+    for tank in model.tanks.iter() {
+        let volume = tank.volume(solver.heads[tank.node] - tank.elevation);
+        let inflow = inflows[tank.node];
+        let target = if inflow > 0.0 { tank.volume(tank.max_level) } else { tank.volume(tank.min_level) };
+        if !(inflow > 0.0 && tank.overflow) {
+            limit = limit.min(time_to_volume(volume, target, inflow).unwrap_or(f64::INFINITY));
+        }
+        for control in model.controls.iter() {
+            if let ModelCondition::HeadAbove(node, head) | ModelCondition::HeadBelow(node, head) = control.condition {
+                if node == tank.node {
+                    let target = tank.volume(head - tank.elevation);
+                    limit = limit.min(time_to_volume(volume, target, inflow).unwrap_or(f64::INFINITY));
+                }
+            }
+        }
+    }
+    for control in model.controls.iter() {
+        match control.condition {
+            ModelCondition::Time(at) if at > time => step = step.min(at - time),
+            ModelCondition::ClockTime(at) => {
+                let wait = (at % 86400 + 86400 - clock.clocktime) % 86400;
+                if wait > 0 {
+                    step = step.min(wait);
+                }
+            },
+            _ => {},
+        }
+    }
+    if limit.is_finite() {
+        step = step.min(limit.ceil() as u64);
+    }
+    step.max(1)
+}
+
+/// Moves the water of every tank according to its net inflow during `step` seconds.
+fn update_tanks(solver: &mut Solver, step: u64) {
+    let model = solver.model;
+    let inflows = solver.inflows();
+    for tank in model.tanks.iter() {
+        let volume = tank.volume(solver.heads[tank.node] - tank.elevation) + inflows[tank.node] * step as f64;
+        let volume = volume.max(tank.volume(tank.min_level)).min(tank.volume(tank.max_level));
+        solver.heads[tank.node] = tank.elevation + tank.level(volume);
+    }
+}
+
+/// Runs the extended-period simulation of a model over the [TIMES] duration, applying
+/// demand, reservoir and pump patterns, tank level changes, [CONTROLS] and [RULES].
+/// `observe` receives every hydraulic solution with its time and the length of the
+/// step that follows it, zero at the end of the simulation.
+pub fn run<F>(model: &HydraulicModel, mut observe: F) -> Result<(), HydraulicError>
+where
+    F: FnMut(&Solver, &Convergence, u64, u64),
+{
+    let times = model.times;
+    let mut solver = Solver::new(model);
+    let mut clock = Clock { time: 0, previous: None, clocktime: times.start_clocktime % 86400 };
+    let mut period = None;
+    loop {
+        let current = model.period(clock.time);
+        if period != Some(current) {
+            solver.set_period(current);
+            for &(link, pattern) in model.pump_patterns.iter() {
+                solver.apply(link, &ControlAction::Setting(model.multiplier(pattern, current)));
+            }
+            period = Some(current);
+        }
+        apply_controls(&mut solver, &clock);
+        apply_rules(&mut solver, &clock);
+
+        let convergence = solver.run()?;
+        if clock.time >= times.duration {
+            observe(&solver, &convergence, clock.time, 0);
+            return Ok(());
+        }
+
+        let step = next_step(&solver, &clock);
+        observe(&solver, &convergence, clock.time, step);
+        update_tanks(&mut solver, step);
+        clock = Clock {
+            time: clock.time + step,
+            previous: Some(clock.time),
+            clocktime: (clock.clocktime + step) % 86400,
+        };
+    }
+}
+
+/// Whether results are reported `time` seconds after the start of the simulation.
+pub fn is_report_time(times: &TimeOptions, time: u64) -> bool {
+    time >= times.report_start && (time - times.report_start).is_multiple_of(times.report_step)
+}
+
+/// Extended-period simulation, with the results at every reporting time.
+pub fn simulate(inp: &INP) -> Result<Simulation, HydraulicError> {
+    let model = HydraulicModel::new(inp)?;
+    let mut steps = Vec::new();
+    run(&model, |solver, convergence, time, _| {
+        if is_report_time(&model.times, time) {
+            steps.push(TimeStep { time, results: solver.results(convergence) });
+        }
+    })?;
+
+    Ok(Simulation { steps })
+}
+
+#[cfg(test)]
+mod test {
+    use std::fs;
+    use super::simulate;
+    use crate::INP;
+    use crate::hydraulics::LinkStatus;
+
+    fn a_model() -> String {
+        r#"
+[JUNCTIONS]
+J1  0  10  Pat1
+[RESERVOIRS]
+R1  50
+[TANKS]
+T1  10  2  0  10  10  0
+[PIPES]
+P1  J1  T1  100  300  130
+[PUMPS]
+PU1  R1  J1  HEAD C1
+[CURVES]
+C1  50  40
+[PATTERNS]
+Pat1  1  2
+[CONTROLS]
+LINK PU1 CLOSED IF NODE T1 ABOVE 3
+[TIMES]
+Duration  3:00
+Hydraulic Timestep  1:00
+Pattern Timestep  1:00
+Report Timestep  1:00
+[OPTIONS]
+Units  LPS
+"#.to_string()
+    }
+
+    #[test]
+    fn tanks_fill_with_their_net_inflow() {
+        let simulation = simulate(&INP::read(a_model().replace("ABOVE 3", "ABOVE 9"))).unwrap();
+
+        let tank = simulation.node_series("T1");
+        let pipe = simulation.link_series("P1");
+        assert_eq!(simulation.steps.iter().map(|step| step.time).collect::<Vec<u64>>(), vec![0, 3600, 7200, 10800]);
+        let area = std::f64::consts::PI * 25.0;
+        let expected = tank[0].1.pressure + pipe[0].1.flow / 1000.0 * 3600.0 / area;
+        assert!((tank[1].1.pressure - expected).abs() < 1e-6, "{} {}", tank[1].1.pressure, expected);
+    }
+
+    #[test]
+    fn patterns_change_the_demands() {
+        let simulation = simulate(&INP::read(a_model())).unwrap();
+
+        let junction = simulation.node_series("J1");
+        assert!((junction[0].1.demand - 10.0).abs() < 1e-9);
+        assert!((junction[1].1.demand - 20.0).abs() < 1e-9);
+    }
+
+    #[test]
+    fn controls_close_the_pump_when_the_tank_is_full() {
+        let simulation = simulate(&INP::read(a_model())).unwrap();
+
+        let pump = simulation.link_series("PU1");
+        assert_eq!(pump[0].1.status, LinkStatus::Open);
+        assert_eq!(pump.last().unwrap().1.status, LinkStatus::Closed);
+        let tank = simulation.node_series("T1");
+        assert!(tank.iter().all(|(_, tank)| tank.pressure >= 0.0 && tank.pressure <= 10.0));
+    }
+
+    #[test]
+    fn rules_act_on_links() {
+        let input = a_model()
+            .replace("[CONTROLS]\nLINK PU1 CLOSED IF NODE T1 ABOVE 3", "[RULES]\nRULE 1\nIF SYSTEM TIME >= 2\nTHEN PUMP PU1 STATUS IS CLOSED");
+        let simulation = simulate(&INP::read(input)).unwrap();
+
+        let pump = simulation.link_series("PU1");
+        assert_eq!(pump[1].1.status, LinkStatus::Open);
+        assert_eq!(pump[2].1.status, LinkStatus::Closed);
+    }
+
+    #[test]
+    fn simulate_magnetic_island() {
+        let inp = INP::read(fs::read_to_string("tests/MagneticIslandEnhanced.inp").unwrap());
+
+        let simulation = simulate(&inp).unwrap();
+
+        assert_eq!(simulation.steps.len(), 97);
+        assert!(simulation.steps.iter().all(|step| step.results.converged));
+        let tanks = inp.tanks.iter().map(|tank| simulation.node_series(&tank.id)).collect::<Vec<_>>();
+        for (tank, series) in inp.tanks.iter().zip(tanks.iter()) {
+            assert!(series.iter().all(|(_, node)| node.pressure >= tank.min_level - 1e-6 && node.pressure <= tank.max_level + 1e-6));
+        }
+    }
+}
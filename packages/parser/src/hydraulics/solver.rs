@@ -0,0 +1,703 @@
+use crate::sections::{ControlAction, ValveType};
+use crate::units::{HeadlossFormula, Quantity};
+use super::matrix::SparseMatrix;
+use super::model::{DemandModel, HydraulicModel, LinkType, PumpCurve, curve_segment, GRAVITY, MINOR_LOSS_FACTOR};
+use super::{HydraulicError, HydraulicResults, LinkResult, LinkStatus, NodeResult};
+
+// Tolerances of EPANET turned into metres and cubic metres per second.
+const CBIG: f64 = 1e8;
+const CSMALL: f64 = 1e-6;
+const RQTOL: f64 = 1.076e-6;
+const HTOL: f64 = 0.0001524;
+const QTOL: f64 = 2.83e-6;
+pub(super) const QZERO: f64 = 2.83e-8;
+const TINY: f64 = 1e-6;
+
+/// Status of a link while solving, finer than the reported `LinkStatus`.
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum State {
+    /// Closed by the user.
+    Closed,
+    /// Closed by the solver: reversed check valve, pump beyond its shutoff head,
+    /// pressure valve with reverse flow or link that would overflow or drain a tank.
+    TempClosed,
+    Open,
+    Active,
+    /// Flow control valve unable to deliver its setting, left fully open.
+    XFcv,
+}
+
+impl State {
+    fn is_closed(self) -> bool {
+        self == State::Closed || self == State::TempClosed
+    }
+}
+
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub struct Convergence {
+    pub iterations: usize,
+    pub relative_error: f64,
+    pub converged: bool,
+}
+
+/// Global gradient algorithm (Todini and Pilati) following the EPANET implementation.
+/// Heads of reservoirs and tanks are fixed, junction heads and link flows are unknown.
+pub struct Solver<'a> {
+    pub model: &'a HydraulicModel,
+    pub heads: Vec<f64>,
+    pub flows: Vec<f64>,
+    pub states: Vec<State>,
+    /// Pump speed or valve setting; `None` for valves fixed open or closed.
+    pub settings: Vec<Option<f64>>,
+    /// Requested demand of every junction.
+    pub demands: Vec<f64>,
+    /// Demand actually delivered to every junction, lower than requested under
+    /// pressure-driven analysis when the pressure is not enough.
+    pub demand_flows: Vec<f64>,
+    pub emitter_flows: Vec<f64>,
+    matrix: SparseMatrix,
+    p: Vec<f64>,
+    y: Vec<f64>,
+    xflow: Vec<f64>,
+    rhs: Vec<f64>,
+    tank_limits: Vec<Option<(f64, f64, bool)>>,
+}
+
+fn coefficients(flow: f64, hloss: f64, hgrad: f64) -> (f64, f64) {
+    if hgrad < RQTOL {
+        (1.0 / RQTOL, flow)
+    } else {
+        (1.0 / hgrad, hloss / hgrad)
+    }
+}
+
+fn valve_coefficients(minor_loss: f64, flow: f64) -> (f64, f64) {
+    coefficients(flow, minor_loss * flow * flow.abs(), 2.0 * minor_loss * flow.abs())
+}
+
+/// Swamee and Jain friction factor and its derivative with the Reynolds number.
+fn swamee_jain(reynolds: f64, relative_roughness: f64) -> (f64, f64) {
+    let w = relative_roughness / 3.7 + 5.74 * reynolds.powf(-0.9);
+    let log = w.log10();
+    let dfdw = -0.5 / (log * log * log * w * std::f64::consts::LN_10);
+    (0.25 / (log * log), dfdw * -0.9 * 5.74 * reynolds.powf(-1.9))
+}
+
+/// Turbulent friction factor and its derivative with the flow, interpolated
+/// between the laminar and turbulent values for transitional flow.
+fn friction_factor(reynolds: f64, relative_roughness: f64, flow: f64) -> (f64, f64) {
+    if reynolds >= 4000.0 {
+        let (f, dfdre) = swamee_jain(reynolds, relative_roughness);
+        (f, dfdre * reynolds / flow)
+    } else {
+        let laminar = 64.0 / 2000.0;
+        let slope = (swamee_jain(4000.0, relative_roughness).0 - laminar) / 2000.0;
+        (laminar + slope * (reynolds - 2000.0), slope * reynolds / flow)
+    }
+}
+
+fn check_valve_state(state: State, dh: f64, flow: f64) -> State {
+    if dh.abs() > HTOL {
+        if dh < -HTOL || flow < -QTOL { State::TempClosed } else { State::Open }
+    } else if flow < -QTOL {
+        State::TempClosed
+    } else {
+        state
+    }
+}
+
+fn prv_state(state: State, flow: f64, hset: f64, h1: f64, h2: f64, minor_headloss: f64) -> State {
+    match state {
+        State::Active if flow < -QTOL => State::TempClosed,
+        State::Active if h1 - minor_headloss < hset - HTOL => State::Open,
+        State::Open if flow < -QTOL => State::TempClosed,
+        State::Open if h2 >= hset + HTOL => State::Active,
+        State::TempClosed if h1 >= hset + HTOL && h2 < hset - HTOL => State::Active,
+        State::TempClosed if h1 < hset - HTOL && h1 > h2 + HTOL => State::Open,
+        _ => state,
+    }
+}
+
+fn psv_state(state: State, flow: f64, hset: f64, h1: f64, h2: f64, minor_headloss: f64) -> State {
+    match state {
+        State::Active if flow < -QTOL => State::TempClosed,
+        State::Active if h2 + minor_headloss > hset + HTOL => State::Open,
+        State::Open if flow < -QTOL => State::TempClosed,
+        State::Open if h1 < hset - HTOL => State::Active,
+        State::TempClosed if h2 > hset + HTOL && h1 > h2 + HTOL => State::Open,
+        State::TempClosed if h1 >= hset + HTOL && h1 > h2 + HTOL => State::Active,
+        _ => state,
+    }
+}
+
+/// Flow of a pump working at its design point.
+fn design_flow(curve: &PumpCurve) -> f64 {
+    match curve {
+        PumpCurve::Power { h0, r, n } => (0.25 * h0 / r).powf(1.0 / n),
+        PumpCurve::Custom(points) => (points[0].0 + points[points.len() - 1].0) / 2.0,
+        PumpCurve::ConstantPower(_) => 0.0283,
+    }
+}
+
+impl<'a> Solver<'a> {
+    pub fn new(model: &'a HydraulicModel) -> Solver<'a> {
+        let junctions = model.junction_count;
+        let edges = model.links.iter()
+            .map(|link| match (model.is_junction(link.start), model.is_junction(link.end)) {
+                (true, true) => (link.start, link.end),
+                (true, false) => (link.start, link.start),
+                (false, true) => (link.end, link.end),
+                (false, false) => (0, 0),
+            })
+            .collect::<Vec<(usize, usize)>>();
+
+        let settings = model.initial_settings.clone();
+        let states = model.links.iter().enumerate()
+            .map(|(k, link)| match (&link.link_type, settings[k]) {
+                _ if model.initial_closed[k] => State::Closed,
+                (LinkType::Valve(ValveType::Prv), Some(_)) | (LinkType::Valve(ValveType::Psv), Some(_)) | (LinkType::Valve(ValveType::Fcv), Some(_)) => State::Active,
+                _ => State::Open,
+            })
+            .collect::<Vec<State>>();
+        let flows = model.links.iter().enumerate()
+            .map(|(k, link)| match &link.link_type {
+                _ if states[k] == State::Closed => QZERO,
+                LinkType::Pump(curve) => settings[k].unwrap_or(1.0) * design_flow(curve),
+                _ => link.area() * 0.3048,
+            })
+            .collect();
+
+        let mut heads = model.elevations.clone();
+        let mut tank_limits = vec![None; model.network.nodes.len()];
+        for tank in model.tanks.iter() {
+            heads[tank.node] = tank.elevation + tank.init_level;
+            tank_limits[tank.node] = Some((tank.elevation + tank.min_level, tank.elevation + tank.max_level, tank.overflow));
+        }
+
+        let mut solver = Solver {
+            model,
+            heads,
+            flows,
+            states,
+            settings,
+            demands: Vec::new(),
+            demand_flows: Vec::new(),
+            emitter_flows: model.emitters.iter().map(|&emitter| if emitter > 0.0 { 0.0283 } else { 0.0 }).collect(),
+            matrix: SparseMatrix::new(junctions, &edges),
+            p: vec![0.0; model.links.len()],
+            y: vec![0.0; model.links.len()],
+            xflow: vec![0.0; model.network.nodes.len()],
+            rhs: vec![0.0; junctions],
+            tank_limits,
+        };
+        solver.set_period(0);
+        solver
+    }
+
+    /// Applies the demands and reservoir heads of a pattern period.
+    pub fn set_period(&mut self, period: usize) {
+        self.demands = self.model.junction_demands(period);
+        self.demand_flows = self.demands.clone();
+        for &(node, pattern) in self.model.reservoir_patterns.iter() {
+            self.heads[node] = self.model.elevations[node] * self.model.multiplier(pattern, period);
+        }
+    }
+
+    /// Iterates until the relative flow change drops below the accuracy and no link changes its status.
+    pub fn run(&mut self) -> Result<Convergence, HydraulicError> {
+        let options = self.model.options;
+        let junctions = self.model.junction_count;
+        let mut next_check = options.check_frequency;
+        let mut relative_error = f64::INFINITY;
+        for iteration in 1..=options.trials {
+            self.assemble();
+            if let Err(row) = self.matrix.solve(&mut self.rhs) {
+                return Err(HydraulicError {
+                    message: format!("Network is ill-conditioned at node {}", self.model.network.nodes[row].id),
+                });
+            }
+            self.heads[..junctions].copy_from_slice(&self.rhs);
+            relative_error = self.update_flows();
+            let valve_change = self.update_valve_states();
+
+            if relative_error <= options.accuracy {
+                let link_change = self.update_link_states();
+                if !valve_change && !link_change {
+                    return Ok(Convergence { iterations: iteration, relative_error, converged: true });
+                }
+                next_check = iteration + options.check_frequency;
+            } else if iteration <= options.max_check && iteration == next_check {
+                self.update_link_states();
+                next_check += options.check_frequency;
+            }
+        }
+        Ok(Convergence { iterations: options.trials, relative_error, converged: false })
+    }
+
+    fn is_control_valve(&self, k: usize) -> bool {
+        self.settings[k].is_some() && matches!(
+            self.model.links[k].link_type,
+            LinkType::Valve(ValveType::Prv) | LinkType::Valve(ValveType::Psv) | LinkType::Valve(ValveType::Fcv)
+        )
+    }
+
+    fn add_link(&mut self, k: usize, p: f64, y: f64) {
+        let link = &self.model.links[k];
+        let (start, end) = (self.model.is_junction(link.start), self.model.is_junction(link.end));
+        self.matrix.add_edge(k, -p);
+        if start {
+            self.matrix.add_diagonal(link.start, p);
+            self.rhs[link.start] += y;
+        } else if end {
+            self.rhs[link.end] += p * self.heads[link.start];
+        }
+        if end {
+            self.matrix.add_diagonal(link.end, p);
+            self.rhs[link.end] -= y;
+        } else if start {
+            self.rhs[link.start] += p * self.heads[link.end];
+        }
+    }
+
+    /// Forces the head of a junction to `head`.
+    fn fix_head(&mut self, node: usize, head: f64) {
+        if self.model.is_junction(node) {
+            self.matrix.add_diagonal(node, CBIG);
+            self.rhs[node] += head * CBIG;
+        }
+    }
+
+    fn assemble(&mut self) {
+        let model = self.model;
+        self.matrix.clear();
+        self.rhs.iter_mut().for_each(|value| *value = 0.0);
+        self.xflow.iter_mut().for_each(|value| *value = 0.0);
+
+        for (k, link) in model.links.iter().enumerate() {
+            if self.is_control_valve(k) {
+                continue;
+            }
+            let (p, y) = self.link_coefficients(k);
+            self.p[k] = p;
+            self.y[k] = y;
+            self.xflow[link.start] -= self.flows[k];
+            self.xflow[link.end] += self.flows[k];
+            self.add_link(k, p, y);
+        }
+
+        for node in 0..model.junction_count {
+            if model.emitters[node] > 0.0 {
+                let (hloss, hgrad) = self.emitter_headloss(node);
+                self.matrix.add_diagonal(node, 1.0 / hgrad);
+                self.rhs[node] += (hloss + model.elevations[node]) / hgrad;
+                self.xflow[node] -= self.emitter_flows[node];
+            }
+            if let Some((hloss, hgrad)) = self.demand_headloss(node) {
+                self.matrix.add_diagonal(node, 1.0 / hgrad);
+                self.rhs[node] += (hloss + model.elevations[node] + self.minimum_pressure()) / hgrad;
+            }
+            self.xflow[node] -= self.demand_flows[node];
+            self.rhs[node] += self.xflow[node];
+        }
+
+        for k in 0..model.links.len() {
+            if self.is_control_valve(k) {
+                self.control_valve_coefficients(k);
+            }
+        }
+    }
+
+    fn link_coefficients(&self, k: usize) -> (f64, f64) {
+        let link = &self.model.links[k];
+        let flow = self.flows[k];
+        if self.states[k].is_closed() {
+            return (1.0 / CBIG, flow);
+        }
+        match &link.link_type {
+            LinkType::Pipe | LinkType::CheckValve => self.pipe_coefficients(k),
+            LinkType::Pump(curve) => self.pump_coefficients(curve, flow, self.settings[k].unwrap_or(1.0)),
+            LinkType::Valve(ValveType::Tcv) => valve_coefficients(self.settings[k].unwrap_or(link.minor_loss), flow),
+            LinkType::Valve(ValveType::Pbv) => match self.settings[k] {
+                Some(setting) if setting != 0.0 && link.minor_loss * flow * flow <= setting => (CBIG, setting * CBIG),
+                _ => valve_coefficients(link.minor_loss, flow),
+            },
+            LinkType::Valve(_) => valve_coefficients(link.minor_loss, flow),
+        }
+    }
+
+    fn pipe_coefficients(&self, k: usize) -> (f64, f64) {
+        let link = &self.model.links[k];
+        let flow = self.flows[k];
+        let q = flow.abs();
+        let minor_loss = link.minor_loss;
+        let exponent = match self.model.formula {
+            HeadlossFormula::HazenWilliams => 1.852,
+            HeadlossFormula::ChezyManning => 2.0,
+            HeadlossFormula::DarcyWeisbach => {
+                let viscosity = self.model.options.viscosity;
+                let reynolds = q * link.diameter / (link.area() * viscosity);
+                let (hloss, hgrad) = if reynolds <= 2000.0 {
+                    let r = 128.0 * viscosity * link.length / (GRAVITY * std::f64::consts::PI * link.diameter.powi(4));
+                    (flow * (r + minor_loss * q), r + 2.0 * minor_loss * q)
+                } else {
+                    let r = MINOR_LOSS_FACTOR * link.length / link.diameter.powi(5);
+                    let (f, dfdq) = friction_factor(reynolds, link.resistance / link.diameter, q);
+                    let r1 = f * r + minor_loss;
+                    (r1 * q * flow, 2.0 * r1 * q + dfdq * r * q * q)
+                };
+                return coefficients(flow, hloss, hgrad);
+            },
+        };
+        if (link.resistance + minor_loss) * q < RQTOL {
+            return (1.0 / RQTOL, flow / exponent);
+        }
+        let friction = link.resistance * q.powf(exponent);
+        let minor = minor_loss * q * q;
+        let p = flow / (exponent * friction + 2.0 * minor);
+        (p.abs(), p * (friction + minor))
+    }
+
+    fn pump_coefficients(&self, curve: &PumpCurve, flow: f64, speed: f64) -> (f64, f64) {
+        if speed == 0.0 {
+            return (1.0 / CBIG, flow);
+        }
+        let q = flow.abs().max(TINY);
+        let (hloss, hgrad) = match curve {
+            PumpCurve::Power { h0, r, n } => {
+                let hgrad = (n * r * speed.powf(2.0 - n) * q.powf(n - 1.0)).max(RQTOL);
+                (-speed * speed * h0 + hgrad * q / n, hgrad)
+            },
+            PumpCurve::Custom(points) => {
+                let (intercept, slope) = curve_segment(points, q / speed);
+                let hgrad = (-slope * speed).max(RQTOL);
+                (-speed * speed * intercept + hgrad * flow, hgrad)
+            },
+            PumpCurve::ConstantPower(power) => {
+                let w = power * speed / GRAVITY;
+                (-w / q, w / (q * q))
+            },
+        };
+        (1.0 / hgrad, hloss / hgrad)
+    }
+
+    fn control_valve_coefficients(&mut self, k: usize) {
+        let link = &self.model.links[k];
+        let (i, j) = (link.start, link.end);
+        let setting = self.settings[k].unwrap_or(0.0);
+        let flow = self.flows[k];
+        match (&link.link_type, self.states[k]) {
+            (LinkType::Valve(ValveType::Prv), State::Active) => {
+                self.p[k] = 0.0;
+                self.y[k] = flow + self.xflow[j];
+                self.fix_head(j, self.model.elevations[j] + setting);
+                if self.xflow[j] < 0.0 && self.model.is_junction(i) {
+                    self.rhs[i] += self.xflow[j];
+                }
+            },
+            (LinkType::Valve(ValveType::Psv), State::Active) => {
+                self.p[k] = 0.0;
+                self.y[k] = flow - self.xflow[i];
+                self.fix_head(i, self.model.elevations[i] + setting);
+                if self.xflow[i] > 0.0 && self.model.is_junction(j) {
+                    self.rhs[j] += self.xflow[i];
+                }
+            },
+            (LinkType::Valve(ValveType::Fcv), State::Active) => {
+                self.xflow[i] -= setting;
+                self.xflow[j] += setting;
+                self.p[k] = 1.0 / CBIG;
+                self.y[k] = flow - setting;
+                self.add_link(k, 1.0 / CBIG, 0.0);
+                if self.model.is_junction(i) {
+                    self.rhs[i] -= setting;
+                }
+                if self.model.is_junction(j) {
+                    self.rhs[j] += setting;
+                }
+            },
+            (_, state) => {
+                let (p, y) = if state.is_closed() { (1.0 / CBIG, flow) } else { valve_coefficients(link.minor_loss, flow) };
+                self.p[k] = p;
+                self.y[k] = y;
+                self.add_link(k, p, y);
+                if self.model.is_junction(i) {
+                    self.rhs[i] -= flow;
+                }
+                if self.model.is_junction(j) {
+                    self.rhs[j] += flow;
+                }
+            },
+        }
+    }
+
+    fn emitter_headloss(&self, node: usize) -> (f64, f64) {
+        let exponent = 1.0 / self.model.options.emitter_exponent;
+        let coefficient = (1.0 / self.model.emitters[node].powf(exponent)).max(CSMALL);
+        let flow = self.emitter_flows[node];
+        let hgrad = exponent * coefficient * flow.abs().powf(exponent - 1.0);
+        if hgrad < RQTOL {
+            (RQTOL * flow, RQTOL)
+        } else {
+            (hgrad * flow / exponent, hgrad)
+        }
+    }
+
+    fn minimum_pressure(&self) -> f64 {
+        match self.model.options.demand_model {
+            DemandModel::Pda { minimum_pressure, .. } => minimum_pressure,
+            DemandModel::Dda => 0.0,
+        }
+    }
+
+    /// Head loss of the delivered demand, seen as a flow to a node at the minimum
+    /// pressure, and its gradient. `None` when the junction always gets its full demand.
+    fn demand_headloss(&self, node: usize) -> Option<(f64, f64)> {
+        let (minimum_pressure, required_pressure, exponent) = match self.model.options.demand_model {
+            DemandModel::Pda { minimum_pressure, required_pressure, pressure_exponent } => (minimum_pressure, required_pressure, 1.0 / pressure_exponent),
+            DemandModel::Dda => return None,
+        };
+        let requested = self.demands[node];
+        if requested <= 0.0 {
+            return None;
+        }
+        let delivered = self.demand_flows[node];
+        let span = required_pressure - minimum_pressure;
+        let ratio = delivered / requested;
+        Some(if ratio <= 0.0 {
+            (CBIG * delivered, CBIG)
+        } else if ratio < 1.0 {
+            let hgrad = exponent * span * ratio.powf(exponent - 1.0) / requested;
+            if hgrad < RQTOL { (RQTOL * delivered, RQTOL) } else { (hgrad * delivered / exponent, hgrad) }
+        } else {
+            (span + CBIG * (delivered - requested), CBIG)
+        })
+    }
+
+    /// Updates link and emitter flows from the new heads and returns the relative flow change.
+    fn update_flows(&mut self) -> f64 {
+        let model = self.model;
+        let mut flow_sum = 0.0;
+        let mut change_sum = 0.0;
+        for (k, link) in model.links.iter().enumerate() {
+            let dh = self.heads[link.start] - self.heads[link.end];
+            let mut change = self.y[k] - self.p[k] * dh;
+            if matches!(link.link_type, LinkType::Pump(PumpCurve::ConstantPower(_))) && change > self.flows[k] {
+                change = self.flows[k] / 2.0;
+            }
+            self.flows[k] -= change;
+            flow_sum += self.flows[k].abs();
+            change_sum += change.abs();
+        }
+        for node in 0..model.junction_count {
+            if model.emitters[node] > 0.0 {
+                let (hloss, hgrad) = self.emitter_headloss(node);
+                let change = (hloss - (self.heads[node] - model.elevations[node])) / hgrad;
+                self.emitter_flows[node] -= change;
+                flow_sum += self.emitter_flows[node].abs();
+                change_sum += change.abs();
+            }
+            if let Some((hloss, hgrad)) = self.demand_headloss(node) {
+                let mut change = (hloss - (self.heads[node] - model.elevations[node] - self.minimum_pressure())) / hgrad;
+                if change.abs() > self.demands[node] {
+                    change *= 0.5;
+                }
+                self.demand_flows[node] -= change;
+                flow_sum += self.demand_flows[node].abs();
+                change_sum += change.abs();
+            }
+        }
+        if flow_sum > model.options.accuracy { change_sum / flow_sum } else { change_sum }
+    }
+
+    /// Opens, closes or activates pressure reducing and sustaining valves.
+    fn update_valve_states(&mut self) -> bool {
+        let mut changed = false;
+        for (k, link) in self.model.links.iter().enumerate() {
+            let setting = match self.settings[k] {
+                Some(setting) if self.states[k] != State::Closed => setting,
+                _ => continue,
+            };
+            let flow = self.flows[k];
+            let (h1, h2) = (self.heads[link.start], self.heads[link.end]);
+            let minor_headloss = link.minor_loss * flow * flow;
+            let state = match link.link_type {
+                LinkType::Valve(ValveType::Prv) => prv_state(self.states[k], flow, self.model.elevations[link.end] + setting, h1, h2, minor_headloss),
+                LinkType::Valve(ValveType::Psv) => psv_state(self.states[k], flow, self.model.elevations[link.start] + setting, h1, h2, minor_headloss),
+                _ => continue,
+            };
+            if state != self.states[k] {
+                self.states[k] = state;
+                changed = true;
+            }
+        }
+        changed
+    }
+
+    /// Checks check valves, pumps, flow control valves and links connected to full or empty tanks.
+    fn update_link_states(&mut self) -> bool {
+        let mut changed = false;
+        for (k, link) in self.model.links.iter().enumerate() {
+            let original = self.states[k];
+            if original == State::Closed {
+                continue;
+            }
+            let pressure_valve = self.settings[k].is_some()
+                && matches!(link.link_type, LinkType::Valve(ValveType::Prv) | LinkType::Valve(ValveType::Psv));
+            let mut state = if original == State::TempClosed && !pressure_valve { State::Open } else { original };
+            let dh = self.heads[link.start] - self.heads[link.end];
+            let flow = self.flows[k];
+            match &link.link_type {
+                LinkType::CheckValve => state = check_valve_state(state, dh, flow),
+                LinkType::Pump(_) => {
+                    let speed = self.settings[k].unwrap_or(1.0);
+                    if state == State::Open && speed > 0.0 && -dh > speed * speed * link.shutoff_head() + HTOL {
+                        state = State::TempClosed;
+                    }
+                },
+                LinkType::Valve(ValveType::Fcv) => if let Some(setting) = self.settings[k] {
+                    state = if dh < -HTOL || flow < -QTOL {
+                        State::XFcv
+                    } else if original == State::XFcv && flow >= setting {
+                        State::Active
+                    } else {
+                        original
+                    };
+                },
+                _ => {},
+            }
+            state = self.tank_state(k, state);
+            if state != original {
+                self.states[k] = state;
+                changed = true;
+            }
+        }
+        changed
+    }
+
+    /// Closes links that would fill a full tank or drain an empty one.
+    fn tank_state(&self, k: usize, state: State) -> State {
+        let link = &self.model.links[k];
+        let (tank, other, flow) = match (self.tank_limits[link.start], self.tank_limits[link.end]) {
+            (Some(_), _) => (link.start, link.end, self.flows[k]),
+            (_, Some(_)) => (link.end, link.start, -self.flows[k]),
+            _ => return state,
+        };
+        let (min_head, max_head, overflow) = self.tank_limits[tank].unwrap();
+        let dh = self.heads[tank] - self.heads[other];
+        let pump = matches!(link.link_type, LinkType::Pump(_));
+        let full = self.heads[tank] >= max_head - HTOL && !overflow;
+        let empty = self.heads[tank] <= min_head + HTOL;
+        let filling = if pump { link.end == tank } else { check_valve_state(State::Open, dh, flow) == State::TempClosed };
+        let draining = if pump { link.start == tank } else { check_valve_state(State::TempClosed, dh, flow) == State::Open };
+        if full && filling || empty && draining {
+            return State::TempClosed;
+        }
+        state
+    }
+
+    /// Applies a control action, with its setting in SI units, to a link.
+    /// Returns whether the status or the setting of the link changed.
+    pub fn apply(&mut self, k: usize, action: &ControlAction) -> bool {
+        let (state, setting) = (self.states[k], self.settings[k]);
+        match (&self.model.links[k].link_type, action) {
+            (LinkType::Pump(_), ControlAction::Open) => {
+                if state == State::Closed {
+                    self.states[k] = State::Open;
+                }
+                if setting == Some(0.0) {
+                    self.settings[k] = Some(1.0);
+                }
+            },
+            (LinkType::Pump(_), ControlAction::Setting(speed)) => {
+                self.settings[k] = Some(*speed);
+                if *speed == 0.0 {
+                    self.states[k] = State::Closed;
+                } else if state == State::Closed {
+                    self.states[k] = State::Open;
+                }
+            },
+            (LinkType::Valve(_), ControlAction::Open) => {
+                self.settings[k] = None;
+                self.states[k] = State::Open;
+            },
+            (LinkType::Valve(valve_type), ControlAction::Setting(value)) => if setting != Some(*value) || state == State::Closed {
+                self.settings[k] = Some(*value);
+                self.states[k] = match valve_type {
+                    ValveType::Prv | ValveType::Psv | ValveType::Fcv => State::Active,
+                    _ => State::Open,
+                };
+            },
+            (_, ControlAction::Open) => if state == State::Closed {
+                self.states[k] = State::Open;
+            },
+            (LinkType::Valve(_), ControlAction::Closed) => {
+                self.settings[k] = None;
+                self.states[k] = State::Closed;
+            },
+            (_, ControlAction::Closed) => self.states[k] = State::Closed,
+            (_, ControlAction::Setting(_)) => {},
+        }
+        self.states[k] != state || self.settings[k] != setting
+    }
+
+    /// Net flow entering every node through its links.
+    pub fn inflows(&self) -> Vec<f64> {
+        let mut inflows = vec![0.0; self.model.network.nodes.len()];
+        for (k, link) in self.model.links.iter().enumerate() {
+            inflows[link.start] -= self.flows[k];
+            inflows[link.end] += self.flows[k];
+        }
+        inflows
+    }
+
+    /// Heads, pressures, demands, flows and velocities in the units of the model.
+    pub fn results(&self, convergence: &Convergence) -> HydraulicResults {
+        let model = self.model;
+        let factor = |quantity: Quantity| quantity.si_factor(model.flow_units, model.formula);
+        let inflows = self.inflows();
+        let nodes = model.network.nodes.iter().enumerate()
+            .map(|(i, node)| {
+                let junction = model.is_junction(i);
+                let demand = if junction { self.demand_flows[i] + self.emitter_flows[i] } else { inflows[i] };
+                let (requested, delivered, emitter) = if junction {
+                    (self.demands[i], self.demand_flows[i], self.emitter_flows[i])
+                } else {
+                    (0.0, 0.0, 0.0)
+                };
+                NodeResult {
+                    id: node.id.clone(),
+                    demand: demand / factor(Quantity::Flow),
+                    requested_demand: requested / factor(Quantity::Flow),
+                    delivered_demand: delivered / factor(Quantity::Flow),
+                    emitter_flow: emitter / factor(Quantity::Flow),
+                    head: self.heads[i] / factor(Quantity::Elevation),
+                    pressure: (self.heads[i] - model.elevations[i]) / factor(Quantity::Pressure),
+                }
+            })
+            .collect();
+        let links = model.links.iter().enumerate()
+            .map(|(k, link)| {
+                let velocity = if link.diameter > 0.0 { self.flows[k].abs() / link.area() } else { 0.0 };
+                LinkResult {
+                    id: model.network.links[k].id.clone(),
+                    flow: self.flows[k] / factor(Quantity::Flow),
+                    velocity: velocity / factor(Quantity::Velocity),
+                    headloss: (self.heads[link.start] - self.heads[link.end]) / factor(Quantity::Length),
+                    status: match self.states[k] {
+                        State::Closed | State::TempClosed => LinkStatus::Closed,
+                        State::Open | State::XFcv => LinkStatus::Open,
+                        State::Active => LinkStatus::Active,
+                    },
+                }
+            })
+            .collect();
+
+        HydraulicResults {
+            nodes,
+            links,
+            iterations: convergence.iterations,
+            relative_error: convergence.relative_error,
+            converged: convergence.converged,
+        }
+    }
+}
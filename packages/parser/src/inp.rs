@@ -1,25 +1,56 @@
 use serde::{Serialize, Deserialize};
 use crate::{Sectionable, SectionError};
-use crate::sections::{Source, Reservoir, Pipe, Unknown, Error, Junction, Tank, Pump, Valve, Emitter, Quality};
+use crate::units::{FlowUnits, HeadlossFormula};
+use crate::sections::{Source, Reservoir, Pipe, Unknown, Error, Junction, Tank, Pump, Valve, Emitter, Quality, Setting, Curve, Control, Status, Tag, Coordinate, Vertex, Demand, Pattern, Rule, RuleLine, Mixing, Label, Backdrop, Comment, ControlCondition};
+use crate::sections::rule::parse_rules;
+use crate::sections::time::parse_time;
+use crate::writer::column_header;
 
-#[derive(Serialize, Deserialize, Debug, PartialEq)]
+#[derive(Serialize, Deserialize, Debug, PartialEq, Clone)]
 pub struct INP {
-    title: String,
-    junctions: Vec<Junction>,
-    reservoirs: Vec<Reservoir>,
-    tanks: Vec<Tank>,
-    pipes: Vec<Pipe>,
-    pumps: Vec<Pump>,
-    valves: Vec<Valve>,
-    emitters: Vec<Emitter>,
+    pub title: String,
+    pub junctions: Vec<Junction>,
+    pub reservoirs: Vec<Reservoir>,
+    pub tanks: Vec<Tank>,
+    pub pipes: Vec<Pipe>,
+    pub pumps: Vec<Pump>,
+    pub valves: Vec<Valve>,
+    pub emitters: Vec<Emitter>,
+    pub demands: Vec<Demand>,
+    pub patterns: Vec<Pattern>,
+    pub curves: Vec<Curve>,
+    pub controls: Vec<Control>,
+    pub rules: Vec<RuleLine>,
+    pub statuses: Vec<Status>,
+    pub tags: Vec<Tag>,
     
-    quality: Vec<Quality>,
-    sources: Vec<Source>,
+    pub quality: Vec<Quality>,
+    pub sources: Vec<Source>,
+    pub reactions: Vec<Setting>,
+    pub mixing: Vec<Mixing>,
+    pub energy: Vec<Setting>,
+
+    pub options: Vec<Setting>,
+    pub times: Vec<Setting>,
+
+    pub coordinates: Vec<Coordinate>,
+    pub vertices: Vec<Vertex>,
+    pub labels: Vec<Label>,
+    pub backdrop: Vec<Backdrop>,
     
-    unknown_sections: Vec<Unknown>,
-    errors: Vec<Error>
+    pub unknown_sections: Vec<Unknown>,
+    /// Lines holding only a comment, in the known sections.
+    pub comments: Vec<Comment>,
+    pub errors: Vec<Error>
 }
 
+/// Sections read into the INP struct, the lines of any other section are unknown.
+pub const SECTIONS: [&str; 27] = [
+    "TITLE", "JUNCTIONS", "RESERVOIRS", "TANKS", "PIPES", "PUMPS", "VALVES", "EMITTERS", "SOURCES",
+    "QUALITY", "REACTIONS", "MIXING", "ENERGY", "DEMANDS", "PATTERNS", "CURVES", "CONTROLS", "STATUS",
+    "TAGS", "COORDINATES", "VERTICES", "LABELS", "BACKDROP", "OPTIONS", "TIMES", "RULES", "END",
+];
+
 struct LineData {
     content: String,
     number: i32
@@ -63,7 +94,7 @@ fn add<T: Sectionable<SelfType=T>>(line: LineData, items: &mut Vec<T>, errors: &
     
 }
 
-fn get_properties_and_comment<'a>(line: &'a str) -> (Vec<&str>, Option<String>) {
+fn get_properties_and_comment<'a>(line: &'a str) -> (Vec<&'a str>, Option<String>) {
     let mut parts = line.split(';');
     let properties = parts.next().unwrap_or("").split_whitespace().collect::<Vec<&'a str>>();
     let comment = parts.next().map(|s| s.to_string());
@@ -71,6 +102,33 @@ fn get_properties_and_comment<'a>(line: &'a str) -> (Vec<&str>, Option<String>)
     (properties, comment)
 }
 
+fn first_number(value: &str) -> Option<f64> {
+    value.split_whitespace().next()?.parse::<f64>().ok()
+}
+
+/// Renames the ID following one of the `objects` keywords in the premises and actions of rules.
+fn rename_rule_objects(rules: &mut [RuleLine], objects: &[&str], from: &str, to: &str) {
+    for line in rules.iter_mut().filter(|line| !line.keyword.eq_ignore_ascii_case("RULE")) {
+        for index in 1..line.words.len() {
+            if line.words[index] == from && objects.iter().any(|object| line.words[index - 1].eq_ignore_ascii_case(object)) {
+                line.words[index] = to.to_string();
+            }
+        }
+    }
+}
+
+/// Renames a word of the values of settings, at the position `word` gives for a key and
+/// the words of a value, e.g. the pipe of `BULK P1 -0.5`.
+fn rename_setting_words(settings: &mut [Setting], word: impl Fn(&str, &[&str]) -> Option<usize>, from: &str, to: &str) {
+    for setting in settings.iter_mut() {
+        let mut words = setting.value.split_whitespace().collect::<Vec<&str>>();
+        if let Some(index) = word(&setting.key, &words).filter(|index| words.get(*index) == Some(&from)) {
+            words[index] = to;
+            setting.value = words.join(" ");
+        }
+    }
+}
+
 impl INP {
     pub fn read(content: String) -> Self {
         let mut inp = INP { 
@@ -82,9 +140,26 @@ impl INP {
             pumps: Vec::new(),
             valves: Vec::new(),
             emitters: Vec::new(),
+            demands: Vec::new(),
+            patterns: Vec::new(),
+            curves: Vec::new(),
+            controls: Vec::new(),
+            rules: Vec::new(),
+            statuses: Vec::new(),
+            tags: Vec::new(),
             quality: Vec::new(),
             sources: Vec::new(), 
+            reactions: Vec::new(),
+            mixing: Vec::new(),
+            energy: Vec::new(),
+            options: Vec::new(),
+            times: Vec::new(),
+            coordinates: Vec::new(),
+            vertices: Vec::new(),
+            labels: Vec::new(),
+            backdrop: Vec::new(),
             unknown_sections: Vec::new(),
+            comments: Vec::new(),
             errors: Vec::new(),
         };
         let mut line_number = 1;
@@ -96,8 +171,25 @@ impl INP {
                 None => continue,
                 Some('[') => {
                     section = read_section(line.trim());
+                    // Unknown sections keep their header, so their lines can be written back.
+                    if !section.as_deref().is_some_and(|name| SECTIONS.contains(&name)) {
+                        inp.unknown_sections.push(Unknown { text: line.to_string() });
+                    }
                 }
-                Some(';') => continue,
+                Some(';') => {
+                    match section.as_deref() {
+                        Some(name) if !SECTIONS.contains(&name) => inp.unknown_sections.push(Unknown { text: line.to_string() }),
+                        name => {
+                            let name = name.unwrap_or("");
+                            let position = inp.rows(name);
+                            // The column headers `write_inp` adds are not comments of the model.
+                            if position > 0 || column_header(name).is_none_or(|header| line.trim() != format!(";{}", header)) {
+                                inp.comments.push(Comment { section: name.to_string(), position, text: line.trim_end().to_string() });
+                            }
+                        },
+                    }
+                    continue;
+                },
                 _ => match section.as_deref() {
                         Some("TITLE") => inp.set_title_line(read_title_line(line).as_str()),
                         Some("JUNCTIONS") => add::<Junction>(data, &mut inp.junctions, &mut inp.errors),
@@ -109,6 +201,22 @@ impl INP {
                         Some("EMITTERS") => add::<Emitter>(data, &mut inp.emitters, &mut inp.errors),
                         Some("SOURCES") => add::<Source>(data, &mut inp.sources, &mut inp.errors),
                         Some("QUALITY") => add::<Quality>(data, &mut inp.quality, &mut inp.errors),
+                        Some("REACTIONS") => add::<Setting>(data, &mut inp.reactions, &mut inp.errors),
+                        Some("MIXING") => add::<Mixing>(data, &mut inp.mixing, &mut inp.errors),
+                        Some("ENERGY") => add::<Setting>(data, &mut inp.energy, &mut inp.errors),
+                        Some("DEMANDS") => add::<Demand>(data, &mut inp.demands, &mut inp.errors),
+                        Some("PATTERNS") => add::<Pattern>(data, &mut inp.patterns, &mut inp.errors),
+                        Some("CURVES") => add::<Curve>(data, &mut inp.curves, &mut inp.errors),
+                        Some("CONTROLS") => add::<Control>(data, &mut inp.controls, &mut inp.errors),
+                        Some("STATUS") => add::<Status>(data, &mut inp.statuses, &mut inp.errors),
+                        Some("TAGS") => add::<Tag>(data, &mut inp.tags, &mut inp.errors),
+                        Some("COORDINATES") => add::<Coordinate>(data, &mut inp.coordinates, &mut inp.errors),
+                        Some("VERTICES") => add::<Vertex>(data, &mut inp.vertices, &mut inp.errors),
+                        Some("LABELS") => add::<Label>(data, &mut inp.labels, &mut inp.errors),
+                        Some("BACKDROP") => add::<Backdrop>(data, &mut inp.backdrop, &mut inp.errors),
+                        Some("OPTIONS") => add::<Setting>(data, &mut inp.options, &mut inp.errors),
+                        Some("TIMES") => add::<Setting>(data, &mut inp.times, &mut inp.errors),
+                        Some("RULES") => add::<RuleLine>(data, &mut inp.rules, &mut inp.errors),
                         _ => inp.unknown_sections.push(Unknown { text: line.to_string() })
                     }
             }
@@ -117,6 +225,190 @@ impl INP {
         inp
     }
 
+    /// Number of rows of a section read so far, as `write_inp` writes them.
+    fn rows(&self, section: &str) -> usize {
+        match section {
+            "TITLE" => usize::from(!self.title.is_empty()),
+            "JUNCTIONS" => self.junctions.len(),
+            "RESERVOIRS" => self.reservoirs.len(),
+            "TANKS" => self.tanks.len(),
+            "PIPES" => self.pipes.len(),
+            "PUMPS" => self.pumps.len(),
+            "VALVES" => self.valves.len(),
+            "EMITTERS" => self.emitters.len(),
+            "SOURCES" => self.sources.len(),
+            "QUALITY" => self.quality.len(),
+            "REACTIONS" => self.reactions.len(),
+            "MIXING" => self.mixing.len(),
+            "ENERGY" => self.energy.len(),
+            "DEMANDS" => self.demands.len(),
+            "PATTERNS" => self.patterns.len(),
+            "CURVES" => self.curves.len(),
+            "CONTROLS" => self.controls.len(),
+            "STATUS" => self.statuses.len(),
+            "TAGS" => self.tags.len(),
+            "COORDINATES" => self.coordinates.len(),
+            "VERTICES" => self.vertices.len(),
+            "LABELS" => self.labels.len(),
+            "BACKDROP" => self.backdrop.len(),
+            "OPTIONS" => self.options.len(),
+            "TIMES" => self.times.len(),
+            "RULES" => self.rules.len(),
+            _ => 0,
+        }
+    }
+
+    pub fn option(&self, key: &str) -> Option<&str> {
+        self.options.iter()
+            .find(|setting| setting.key == key.to_uppercase())
+            .map(|setting| setting.value.as_str())
+    }
+
+    /// First number of an [OPTIONS] setting, e.g. 0.5 for `EMITTER EXPONENT 0.5`.
+    pub fn option_number(&self, key: &str) -> Option<f64> {
+        first_number(self.option(key)?)
+    }
+
+    /// First number of an [ENERGY] setting, e.g. 0.1 for `GLOBAL PRICE 0.1`.
+    pub fn energy_number(&self, key: &str) -> Option<f64> {
+        let setting = self.energy.iter().find(|setting| setting.key == key.to_uppercase())?;
+        first_number(&setting.value)
+    }
+
+    /// Value of a [TIMES] setting, in seconds.
+    pub fn time(&self, key: &str) -> Option<u64> {
+        let value = self.times.iter().find(|setting| setting.key == key.to_uppercase())?;
+        let mut words = value.value.split_whitespace();
+        parse_time(words.next()?, words.next()).ok()
+    }
+
+    /// Value of a global [REACTIONS] setting, e.g. `ORDER BULK` or `GLOBAL WALL`.
+    pub fn reaction(&self, key: &str) -> Option<f64> {
+        self.reactions.iter()
+            .find(|setting| setting.key == key.to_uppercase())
+            .and_then(|setting| setting.value.parse::<f64>().ok())
+    }
+
+    /// Coordinate reference system of a `Q_VAR_PROJECTION=EPSG:xxxx` title line.
+    pub fn projection(&self) -> Option<&str> {
+        self.title.split_whitespace()
+            .find_map(|word| word.strip_prefix("Q_VAR_PROJECTION="))
+            .filter(|projection| !projection.is_empty())
+    }
+
+    /// Rules of the [RULES] section, grouped from its lines.
+    pub fn rules(&self) -> Result<Vec<Rule>, SectionError> {
+        parse_rules(&self.rules)
+    }
+
+    /// Multipliers of a pattern, joining every line of the pattern in [PATTERNS].
+    pub fn pattern(&self, id: &str) -> Option<Vec<f64>> {
+        let multipliers = self.patterns.iter()
+            .filter(|pattern| pattern.id == id)
+            .flat_map(|pattern| pattern.multipliers.iter().copied())
+            .collect::<Vec<f64>>();
+        if multipliers.is_empty() { None } else { Some(multipliers) }
+    }
+
+    pub fn flow_units(&self) -> FlowUnits {
+        self.option("UNITS")
+            .and_then(|value| value.parse::<FlowUnits>().ok())
+            .unwrap_or(FlowUnits::Gpm)
+    }
+
+    pub fn headloss_formula(&self) -> HeadlossFormula {
+        self.option("HEADLOSS")
+            .and_then(|value| value.parse::<HeadlossFormula>().ok())
+            .unwrap_or(HeadlossFormula::HazenWilliams)
+    }
+
+    /// Renames a junction, reservoir or tank and every reference to it: links, demands,
+    /// emitters, quality, sources, mixing, coordinates, labels, tags, controls, rules,
+    /// reactions and the trace node option.
+    pub fn rename_node(&mut self, from: &str, to: &str) {
+        let rename = |id: &mut String| if id == from { *id = to.to_string() };
+        self.junctions.iter_mut().for_each(|junction| rename(&mut junction.id));
+        self.reservoirs.iter_mut().for_each(|reservoir| rename(&mut reservoir.id));
+        self.tanks.iter_mut().for_each(|tank| rename(&mut tank.id));
+        for pipe in self.pipes.iter_mut() {
+            rename(&mut pipe.node1);
+            rename(&mut pipe.node2);
+        }
+        for pump in self.pumps.iter_mut() {
+            rename(&mut pump.start_node);
+            rename(&mut pump.end_node);
+        }
+        for valve in self.valves.iter_mut() {
+            rename(&mut valve.start_node);
+            rename(&mut valve.end_node);
+        }
+        self.demands.iter_mut().for_each(|demand| rename(&mut demand.junction_id));
+        self.emitters.iter_mut().for_each(|emitter| rename(&mut emitter.junction_id));
+        self.quality.iter_mut().for_each(|quality| rename(&mut quality.nodeid));
+        self.sources.iter_mut().for_each(|source| rename(&mut source.node));
+        self.mixing.iter_mut().for_each(|mixing| rename(&mut mixing.tank_id));
+        self.coordinates.iter_mut().for_each(|coordinate| rename(&mut coordinate.node_id));
+        self.labels.iter_mut().filter_map(|label| label.anchor_node_id.as_mut()).for_each(rename);
+        self.tags.iter_mut().filter(|tag| tag.object_type.eq_ignore_ascii_case("NODE")).for_each(|tag| rename(&mut tag.object_id));
+        for control in self.controls.iter_mut() {
+            if let ControlCondition::Above { node_id, .. } | ControlCondition::Below { node_id, .. } = &mut control.condition {
+                rename(node_id);
+            }
+        }
+        rename_rule_objects(&mut self.rules, &["NODE", "JUNCTION", "RESERVOIR", "TANK"], from, to);
+        rename_setting_words(&mut self.reactions, |key, _| (key == "TANK").then_some(0), from, to);
+        rename_setting_words(&mut self.options, |key, words| {
+            let trace = words.first().is_some_and(|word| word.eq_ignore_ascii_case("TRACE"));
+            (key == "QUALITY" && trace).then_some(1)
+        }, from, to);
+    }
+
+    /// Renames a pipe, pump or valve and every reference to it: status, vertices, tags,
+    /// controls, rules, reactions and energy.
+    pub fn rename_link(&mut self, from: &str, to: &str) {
+        let rename = |id: &mut String| if id == from { *id = to.to_string() };
+        self.pipes.iter_mut().for_each(|pipe| rename(&mut pipe.id));
+        self.pumps.iter_mut().for_each(|pump| rename(&mut pump.id));
+        self.valves.iter_mut().for_each(|valve| rename(&mut valve.id));
+        self.statuses.iter_mut().for_each(|status| rename(&mut status.link_id));
+        self.vertices.iter_mut().for_each(|vertex| rename(&mut vertex.link_id));
+        self.tags.iter_mut().filter(|tag| tag.object_type.eq_ignore_ascii_case("LINK")).for_each(|tag| rename(&mut tag.object_id));
+        self.controls.iter_mut().for_each(|control| rename(&mut control.link_id));
+        rename_rule_objects(&mut self.rules, &["LINK", "PIPE", "PUMP", "VALVE"], from, to);
+        rename_setting_words(&mut self.reactions, |key, _| (key == "BULK" || key == "WALL").then_some(0), from, to);
+        rename_setting_words(&mut self.energy, |key, _| (key == "PUMP").then_some(0), from, to);
+    }
+
+    /// Renames a time pattern and every reference to it: junctions, demands, reservoirs,
+    /// pumps, sources, the default pattern option and energy.
+    pub fn rename_pattern(&mut self, from: &str, to: &str) {
+        let rename = |id: &mut String| if id == from { *id = to.to_string() };
+        self.patterns.iter_mut().for_each(|pattern| rename(&mut pattern.id));
+        self.junctions.iter_mut().filter_map(|junction| junction.demand_pattern_id.as_mut()).for_each(rename);
+        self.demands.iter_mut().filter_map(|demand| demand.pattern_id.as_mut()).for_each(rename);
+        self.reservoirs.iter_mut().filter_map(|reservoir| reservoir.pattern.as_mut()).for_each(rename);
+        self.pumps.iter_mut().filter_map(|pump| pump.pattern.as_mut()).for_each(rename);
+        self.sources.iter_mut().filter_map(|source| source.pattern.as_mut()).for_each(rename);
+        rename_setting_words(&mut self.options, |key, _| (key == "PATTERN").then_some(0), from, to);
+        rename_setting_words(&mut self.energy, |key, words| match key {
+            "GLOBAL PATTERN" => Some(0),
+            "PUMP" if words.get(1).is_some_and(|word| word.eq_ignore_ascii_case("PATTERN")) => Some(2),
+            _ => None,
+        }, from, to);
+    }
+
+    /// Renames a curve and every reference to it: pump heads, tank volumes and pump efficiencies.
+    pub fn rename_curve(&mut self, from: &str, to: &str) {
+        let rename = |id: &mut String| if id == from { *id = to.to_string() };
+        self.curves.iter_mut().for_each(|curve| rename(&mut curve.id));
+        self.pumps.iter_mut().filter_map(|pump| pump.head.as_mut()).for_each(rename);
+        self.tanks.iter_mut().filter_map(|tank| tank.volume_curve_id.as_mut()).for_each(rename);
+        rename_setting_words(&mut self.energy, |key, words| {
+            let efficiency = words.get(1).is_some_and(|word| word.to_uppercase().starts_with("EFFIC"));
+            (key == "PUMP" && efficiency).then_some(2)
+        }, from, to);
+    }
+
     fn set_title_line(&mut self, s: &str) {
         if !self.title.is_empty() {
             self.title.push(' ');
@@ -141,6 +433,7 @@ mod test {
 
         let inp = INP::read(input);
         assert!(inp.title.contains("Magnetic Island"));
+        assert_eq!(inp.projection(), Some("EPSG:20255"));
         assert_eq!(inp.reservoirs.len(), 2);
         assert_eq!(inp.pipes.len(), 1648);
         assert_eq!(inp.junctions.len(), 2050);
@@ -148,11 +441,32 @@ mod test {
         assert_eq!(inp.pumps.len(), 5);
         assert_eq!(inp.valves.len(), 507);
         assert_eq!(inp.emitters.len(), 2020);
+        assert_eq!(inp.curves.len(), 18);
+        assert_eq!(inp.demands.len(), 2242);
+        assert_eq!(inp.pattern("Cust_Pattern_40").map(|multipliers| multipliers.len()), Some(96));
+        assert_eq!(inp.controls.len(), 8);
+        assert_eq!(inp.statuses.len(), 16);
+        assert_eq!(inp.coordinates.len(), 2056);
+        assert_eq!(inp.vertices.len(), 5210);
+        assert_eq!(inp.labels.len(), 0);
+        assert_eq!(inp.backdrop.len(), 4);
+        assert_eq!(inp.backdrop[0].values, vec!["479958.155", "7878685.517", "486656.440", "7886616.778"]);
+        assert_eq!(inp.option("units"), Some("LPS"));
+        assert_eq!(inp.option("Specific Gravity"), Some("1"));
+        assert_eq!(inp.time("Duration"), Some(86400));
+        assert_eq!(inp.time("Hydraulic Timestep"), Some(900));
+        assert_eq!(inp.time("Start ClockTime"), Some(0));
+        assert_eq!(inp.rules(), Ok(Vec::new()));
 
         assert_eq!(inp.quality.len(), 1);
         assert_eq!(inp.sources.len(), 0);
+        assert_eq!(inp.reaction("Order Wall"), Some(1.0));
+        assert_eq!(inp.reaction("Global Bulk"), Some(0.0));
+        assert!(inp.mixing.is_empty());
+        assert_eq!(inp.energy.len(), 3);
         
-        assert!(inp.unknown_sections.len() > 0);
+        assert!(!inp.unknown_sections.is_empty());
+        assert!(inp.errors.is_empty(), "{:?}", inp.errors);
     }
 
     #[test]
@@ -190,6 +504,9 @@ R2     120       Pat1    ;Head varies with time
         assert_eq!(
             inp.unknown_sections, 
             vec![
+                Unknown {
+                    text: "[[RESERVOIRS]".to_string(),
+                },
                 Unknown { 
                     text: "R1     Test               ;Head stays constant".to_string(), 
                 },
@@ -199,5 +516,50 @@ R2     120       Pat1    ;Head varies with time
             ]
         );
     }
-}
 
+    #[test]
+    fn rename_nodes_and_links() {
+        let input = r#"
+[JUNCTIONS]
+J1  10  1  Day
+[TANKS]
+T1  100  1  0  5  20  0
+[PATTERNS]
+Day  1  2
+[OPTIONS]
+Pattern  Day
+Quality  Trace  T1
+[PIPES]
+P1  T1  J1  100  100  100
+[COORDINATES]
+T1  0  0
+[CONTROLS]
+LINK P1 CLOSED IF NODE T1 BELOW 2
+[RULES]
+RULE 1
+IF TANK T1 LEVEL ABOVE 4
+THEN PIPE P1 STATUS IS OPEN
+[REACTIONS]
+Bulk  P1  -0.5
+Tank  T1  -1
+[TAGS]
+NODE  T1  Main
+"#;
+        let mut inp = INP::read(input.to_string());
+
+        inp.rename_node("T1", "Tower");
+        inp.rename_link("P1", "Main");
+        inp.rename_pattern("Day", "Week");
+
+        assert_eq!((inp.tanks[0].id.as_str(), inp.pipes[0].id.as_str(), inp.pipes[0].node1.as_str()), ("Tower", "Main", "Tower"));
+        assert_eq!(inp.coordinates[0].node_id, "Tower");
+        assert_eq!(inp.tags[0].object_id, "Tower");
+        assert_eq!(inp.controls[0].link_id, "Main");
+        assert_eq!(inp.rules[1].words, vec!["TANK", "Tower", "LEVEL", "ABOVE", "4"]);
+        assert_eq!(inp.rules[2].words, vec!["PIPE", "Main", "STATUS", "IS", "OPEN"]);
+        assert_eq!(inp.rules[0].words, vec!["1"]);
+        assert_eq!((inp.reactions[0].value.as_str(), inp.reactions[1].value.as_str()), ("Main -0.5", "Tower -1"));
+        assert_eq!((inp.patterns[0].id.as_str(), inp.junctions[0].demand_pattern_id.as_deref(), inp.option("pattern")), ("Week", Some("Week"), Some("Week")));
+        assert_eq!(inp.option("quality"), Some("Trace Tower"));
+    }
+}
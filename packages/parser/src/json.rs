@@ -0,0 +1,8 @@
+use serde::Serialize;
+
+/// Results that can be sent as JSON, e.g. to the browser through the WASM bindings.
+pub trait ToJson: Serialize {
+    fn to_json(&self) -> String {
+        serde_json::to_string(self).unwrap()
+    }
+}
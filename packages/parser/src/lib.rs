@@ -1,5 +1,34 @@
 mod inp;
-mod sections;
+mod json;
+pub mod writer;
+pub mod sections;
+pub mod units;
+pub mod headloss;
+pub mod network;
+pub mod connectivity;
+pub mod validation;
+pub mod diff;
+pub mod merge;
+pub mod patch;
+pub mod segments;
+pub mod geometry;
+pub mod geojson;
+pub mod crs;
+pub mod shapefile;
+#[cfg(feature = "geopackage")]
+pub mod geopackage;
+pub mod render;
+pub mod paths;
+pub mod hydraulics;
+pub mod quality;
+pub mod controls;
+pub mod energy;
+pub mod fireflow;
+pub mod criticality;
+pub mod output;
+pub mod report;
 
 pub use inp::INP;
+pub use json::ToJson;
 pub use sections::sectionable::{Sectionable, SectionError};
+pub use units::{convert_units, FlowUnits};
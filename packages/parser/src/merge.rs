@@ -0,0 +1,277 @@
+use std::error::Error;
+use std::fmt;
+use serde::{Serialize, Deserialize};
+use serde_json::{json, Value};
+use crate::diff::{build, diff, show, tables, ChangeKind, Record, Table};
+use crate::INP;
+
+#[derive(Debug, PartialEq)]
+pub struct MergeError {
+    pub message: String,
+}
+
+impl fmt::Display for MergeError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.message)
+    }
+}
+
+impl Error for MergeError {}
+
+impl From<String> for MergeError {
+    fn from(message: String) -> Self {
+        MergeError { message }
+    }
+}
+
+/// Change made on both sides in different ways. The merged model keeps our side.
+#[derive(Serialize, Deserialize, Debug, PartialEq, Clone)]
+pub struct Conflict {
+    /// Kind of element, as in the diff, or a section without IDs such as `tags`.
+    pub element: String,
+    pub id: String,
+    /// Field changed on both sides, none when one side removed the element.
+    pub field: Option<String>,
+    pub base: Value,
+    pub ours: Value,
+    pub theirs: Value,
+}
+
+#[derive(Debug)]
+pub struct Merge {
+    pub inp: INP,
+    pub conflicts: Vec<Conflict>,
+}
+
+fn state(value: &Value, base: &Value) -> &'static str {
+    match (value, base) {
+        (Value::Null, _) => "removed",
+        (_, Value::Null) => "added",
+        (value, base) if value == base => "unchanged",
+        _ => "modified",
+    }
+}
+
+impl Merge {
+    pub fn has_conflicts(&self) -> bool {
+        !self.conflicts.is_empty()
+    }
+
+    pub fn to_json(&self) -> Value {
+        json!({ "conflicts": self.conflicts })
+    }
+
+    /// One line per conflict, e.g. `pipe P1 diameter: 100 in base, 150 in ours, 200 in theirs`.
+    pub fn report(&self) -> String {
+        self.conflicts.iter()
+            .map(|conflict| match &conflict.field {
+                Some(field) => format!(
+                    "{} {} {}: {} in base, {} in ours, {} in theirs\n", conflict.element, conflict.id, field,
+                    show(&conflict.base), show(&conflict.ours), show(&conflict.theirs),
+                ),
+                None => format!(
+                    "{} {}: {} in ours, {} in theirs\n", conflict.element, conflict.id,
+                    state(&conflict.ours, &conflict.base), state(&conflict.theirs, &conflict.base),
+                ),
+            })
+            .collect()
+    }
+}
+
+/// Node and link renames of a side, from the old ID to the new one.
+fn renames(base: &INP, side: &INP) -> Vec<(String, String, String)> {
+    diff(base, side).changes.into_iter()
+        .filter(|change| change.kind == ChangeKind::Renamed)
+        .filter_map(|change| Some((change.element, change.id, change.new_id?)))
+        .collect()
+}
+
+fn rename(inp: &mut INP, element: &str, from: &str, to: &str) {
+    match element {
+        "junction" | "reservoir" | "tank" => inp.rename_node(from, to),
+        _ => inp.rename_link(from, to),
+    }
+}
+
+/// Merged record of an element on both sides, field by field.
+fn merge_fields(element: &str, id: &str, base: Option<&Record>, ours: &Record, theirs: &Record, conflicts: &mut Vec<Conflict>) -> Record {
+    let mut fields = ours.keys().collect::<Vec<&String>>();
+    fields.extend(theirs.keys().filter(|field| !ours.contains_key(*field)));
+    fields.extend(base.into_iter().flat_map(|base| base.keys()).filter(|field| !ours.contains_key(*field) && !theirs.contains_key(*field)));
+
+    let mut record = Record::new();
+    for field in fields {
+        let value = |record: Option<&Record>| record.and_then(|record| record.get(field)).cloned().unwrap_or(Value::Null);
+        let (before, mine, other) = (value(base), value(Some(ours)), value(Some(theirs)));
+        let merged = if mine == other || other == before {
+            mine
+        } else if mine == before {
+            other
+        } else {
+            conflicts.push(Conflict { element: element.to_string(), id: id.to_string(), field: Some(field.clone()), base: before, ours: mine.clone(), theirs: other });
+            mine
+        };
+        if !merged.is_null() {
+            record.insert(field.clone(), merged);
+        }
+    }
+    record
+}
+
+fn merge_table(element: &str, base: &Table, ours: &Table, theirs: &Table, conflicts: &mut Vec<Conflict>) -> Table {
+    let mut ids = ours.ids.clone();
+    ids.extend(theirs.ids.iter().filter(|id| !ours.records.contains_key(*id)).cloned());
+
+    let mut merged = Table::default();
+    for id in ids {
+        let record = match (base.records.get(&id), ours.records.get(&id), theirs.records.get(&id)) {
+            (base, Some(mine), Some(other)) => Some(merge_fields(element, &id, base, mine, other, conflicts)),
+            (None, mine, other) => mine.or(other).cloned(),
+            (Some(before), mine, other) => {
+                // Removed on one side, kept as it was or changed on the other.
+                let changed = mine.or(other).filter(|record| *record != before);
+                if changed.is_some() {
+                    let value = |record: Option<&Record>| record.cloned().map(Value::Object).unwrap_or(Value::Null);
+                    conflicts.push(Conflict { element: element.to_string(), id: id.clone(), field: None, base: Value::Object(before.clone()), ours: value(mine), theirs: value(other) });
+                }
+                mine.cloned().filter(|_| changed.is_some())
+            },
+        };
+        if let Some(record) = record {
+            merged.insert(id, record);
+        }
+    }
+    merged
+}
+
+/// Merged section without IDs, taken whole from the side that changed it.
+fn merge_section<T: Clone + PartialEq + Serialize>(name: &str, base: &[T], ours: &[T], theirs: &[T], conflicts: &mut Vec<Conflict>) -> Vec<T> {
+    if ours == theirs || theirs == base {
+        ours.to_vec()
+    } else if ours == base {
+        theirs.to_vec()
+    } else {
+        conflicts.push(Conflict { element: name.to_string(), id: String::new(), field: None, base: json!(base), ours: json!(ours), theirs: json!(theirs) });
+        ours.to_vec()
+    }
+}
+
+/// Three-way merge of two models edited from the same base. Elements are matched by ID,
+/// or by position and topology when renamed, and merged field by field. Fields changed
+/// differently on both sides are conflicts, for which the merged model keeps our side.
+pub fn merge(base: &INP, ours: &INP, theirs: &INP) -> Result<Merge, MergeError> {
+    let mut conflicts = Vec::new();
+    let (mut base, mut ours, mut theirs) = (base.clone(), ours.clone(), theirs.clone());
+
+    // Give the three models the same IDs, so renamed elements are merged with each other.
+    let our_renames = renames(&base, &ours);
+    let their_renames = renames(&base, &theirs);
+    for (element, from, to) in our_renames.iter() {
+        rename(&mut base, element, from, to);
+        match their_renames.iter().find(|(_, id, _)| id == from) {
+            Some((_, _, theirs_to)) if theirs_to != to => {
+                conflicts.push(Conflict { element: element.clone(), id: from.clone(), field: Some("id".to_string()), base: json!(from), ours: json!(to), theirs: json!(theirs_to) });
+                rename(&mut theirs, element, theirs_to, to);
+            },
+            Some(_) => {},
+            None => rename(&mut theirs, element, from, to),
+        }
+    }
+    for (element, from, to) in their_renames.iter().filter(|(_, from, _)| !our_renames.iter().any(|(_, id, _)| id == from)) {
+        rename(&mut base, element, from, to);
+        rename(&mut ours, element, from, to);
+    }
+
+    let mut merged = Vec::new();
+    for (((element, base), (_, ours)), (_, theirs)) in tables(&base).into_iter().zip(tables(&ours)).zip(tables(&theirs)) {
+        let table = merge_table(element, &base, &ours, &theirs, &mut conflicts);
+        merged.push((element, table));
+    }
+    let mut inp = build(&merged)?;
+    inp.tags = merge_section("tags", &base.tags, &ours.tags, &theirs.tags, &mut conflicts);
+    inp.labels = merge_section("labels", &base.labels, &ours.labels, &theirs.labels, &mut conflicts);
+    inp.backdrop = merge_section("backdrop", &base.backdrop, &ours.backdrop, &theirs.backdrop, &mut conflicts);
+    inp.comments = merge_section("comments", &base.comments, &ours.comments, &theirs.comments, &mut conflicts);
+    inp.unknown_sections = merge_section("unknown", &base.unknown_sections, &ours.unknown_sections, &theirs.unknown_sections, &mut conflicts);
+
+    Ok(Merge { inp, conflicts })
+}
+
+#[cfg(test)]
+mod test {
+    use std::fs;
+    use serde_json::json;
+    use super::{merge, Conflict};
+    use crate::diff::diff;
+    use crate::INP;
+
+    const BASE: &str = r#"
+[JUNCTIONS]
+J1  10  1
+J2  20  1
+[RESERVOIRS]
+R1  100
+[PIPES]
+P1  R1  J1  100  150  100
+P2  J1  J2  100  150  100
+[COORDINATES]
+J1  0  0
+J2  10  0
+R1  -10  0
+"#;
+
+    #[test]
+    fn merge_changes_of_both_sides() {
+        let base = INP::read(BASE.to_string());
+        let ours = INP::read(BASE.replace("J1  10  1", "J1  12  1"));
+        let theirs = INP::read(BASE.replace("P2  J1  J2  100  150", "P2  J1  J2  100  200").replace("[RESERVOIRS]", "J3  30\n[RESERVOIRS]"));
+
+        let merge = merge(&base, &ours, &theirs).unwrap();
+
+        assert!(!merge.has_conflicts());
+        assert_eq!(merge.inp.junctions.iter().map(|junction| (junction.id.as_str(), junction.elevation)).collect::<Vec<_>>(), vec![("J1", 12.0), ("J2", 20.0), ("J3", 30.0)]);
+        assert_eq!(merge.inp.pipes[1].diameter, 200.0);
+    }
+
+    #[test]
+    fn report_conflicts() {
+        let base = INP::read(BASE.to_string());
+        let ours = INP::read(BASE.replace("P2  J1  J2  100  150", "P2  J1  J2  100  200").replace("P1  R1  J1  100  150  100\n", ""));
+        let theirs = INP::read(BASE.replace("P2  J1  J2  100  150", "P2  J1  J2  100  250").replace("P1  R1  J1  100  150", "P1  R1  J1  120  150"));
+
+        let merge = merge(&base, &ours, &theirs).unwrap();
+
+        assert_eq!(merge.conflicts[0], Conflict {
+            element: "pipe".to_string(), id: "P2".to_string(), field: Some("diameter".to_string()),
+            base: json!(150.0), ours: json!(200.0), theirs: json!(250.0),
+        });
+        assert_eq!(merge.report(), "pipe P2 diameter: 150 in base, 200 in ours, 250 in theirs\npipe P1: removed in ours, modified in theirs\n");
+        assert_eq!(merge.inp.pipes.iter().map(|pipe| (pipe.id.as_str(), pipe.diameter)).collect::<Vec<_>>(), vec![("P2", 200.0)]);
+        assert_eq!(merge.to_json()["conflicts"][1]["field"], json!(null));
+    }
+
+    #[test]
+    fn merge_renamed_elements() {
+        let base = INP::read(BASE.to_string());
+        let ours = INP::read(BASE.replace("J2", "J2b"));
+        let theirs = INP::read(BASE.replace("J2  20  1", "J2  22  1").replace("P2  J1  J2  100", "P2  J1  J2  140"));
+
+        let merge = merge(&base, &ours, &theirs).unwrap();
+
+        assert!(!merge.has_conflicts());
+        assert_eq!((merge.inp.junctions[1].id.as_str(), merge.inp.junctions[1].elevation), ("J2b", 22.0));
+        assert_eq!((merge.inp.pipes[1].node2.as_str(), merge.inp.pipes[1].length), ("J2b", 140.0));
+        assert_eq!(merge.inp.coordinates[1].node_id, "J2b");
+    }
+
+    #[test]
+    fn merge_a_model_with_itself() {
+        let inp = INP::read(fs::read_to_string("tests/MagneticIslandEnhanced.inp").unwrap());
+
+        let merge = merge(&inp, &inp, &inp).unwrap();
+
+        assert!(!merge.has_conflicts());
+        assert!(diff(&inp, &merge.inp).is_empty(), "{}", diff(&inp, &merge.inp).report());
+        assert_eq!((merge.inp.backdrop.len(), merge.inp.unknown_sections.len()), (inp.backdrop.len(), inp.unknown_sections.len()));
+    }
+}
@@ -0,0 +1,288 @@
+use std::collections::{HashMap, VecDeque};
+use serde::{Serialize, Deserialize};
+use crate::INP;
+use crate::sections::ControlAction;
+
+#[derive(Serialize, Deserialize, Debug, PartialEq, Clone, Copy)]
+pub enum NodeKind {
+    Junction,
+    Reservoir,
+    Tank,
+}
+
+#[derive(Serialize, Deserialize, Debug, PartialEq, Clone, Copy)]
+pub enum LinkKind {
+    Pipe,
+    Pump,
+    Valve,
+}
+
+#[derive(Serialize, Deserialize, Debug, PartialEq, Clone)]
+pub struct NetworkNode {
+    pub id: String,
+    pub kind: NodeKind,
+}
+
+#[derive(Serialize, Deserialize, Debug, PartialEq, Clone)]
+pub struct NetworkLink {
+    pub id: String,
+    pub kind: LinkKind,
+    pub start: usize,
+    pub end: usize,
+    pub length: f64,
+    pub check_valve: bool,
+    pub initially_closed: bool,
+}
+
+/// Link left out of the network because some of its end nodes don't exist.
+#[derive(Serialize, Deserialize, Debug, PartialEq, Clone)]
+pub struct DanglingLink {
+    pub id: String,
+    pub kind: LinkKind,
+    pub missing_nodes: Vec<String>,
+}
+
+/// How links are walked when looking for the nodes reachable from another ones.
+#[derive(Debug, PartialEq, Clone, Copy, Default)]
+pub struct Traversal {
+    /// Skip links that are closed at the start of the simulation.
+    pub respect_status: bool,
+    /// Pumps and check valves only let the water flow from their start to their end node.
+    pub directed: bool,
+    /// Walk against the flow direction, looking for the nodes upstream.
+    pub upstream: bool,
+}
+
+/// Node and link topology of an INP, with elements addressed by index.
+#[derive(Debug, PartialEq, Clone)]
+pub struct Network {
+    pub nodes: Vec<NetworkNode>,
+    pub links: Vec<NetworkLink>,
+    pub dangling_links: Vec<DanglingLink>,
+    node_index: HashMap<String, usize>,
+    link_index: HashMap<String, usize>,
+    incident_links: Vec<Vec<usize>>,
+}
+
+impl NetworkLink {
+    pub fn is_directed(&self) -> bool {
+        self.kind == LinkKind::Pump || self.check_valve
+    }
+
+    pub fn other_end(&self, node: usize) -> usize {
+        if self.start == node { self.end } else { self.start }
+    }
+}
+
+impl Network {
+    pub fn new(inp: &INP) -> Network {
+        let mut network = Network {
+            nodes: Vec::new(),
+            links: Vec::new(),
+            dangling_links: Vec::new(),
+            node_index: HashMap::new(),
+            link_index: HashMap::new(),
+            incident_links: Vec::new(),
+        };
+
+        for junction in inp.junctions.iter() {
+            network.add_node(&junction.id, NodeKind::Junction);
+        }
+        for reservoir in inp.reservoirs.iter() {
+            network.add_node(&reservoir.id, NodeKind::Reservoir);
+        }
+        for tank in inp.tanks.iter() {
+            network.add_node(&tank.id, NodeKind::Tank);
+        }
+
+        let statuses = inp.statuses.iter()
+            .map(|status| (status.link_id.as_str(), &status.status))
+            .collect::<HashMap<&str, &ControlAction>>();
+        let closed = |id: &str, default: bool| match statuses.get(id) {
+            Some(ControlAction::Closed) => true,
+            Some(_) => false,
+            None => default,
+        };
+
+        for pipe in inp.pipes.iter() {
+            let status = pipe.status.to_uppercase();
+            if let Some(link) = network.add_link(&pipe.id, LinkKind::Pipe, &pipe.node1, &pipe.node2) {
+                link.length = pipe.length;
+                link.check_valve = status == "CV";
+                link.initially_closed = closed(&pipe.id, status == "CLOSED");
+            }
+        }
+        for pump in inp.pumps.iter() {
+            if let Some(link) = network.add_link(&pump.id, LinkKind::Pump, &pump.start_node, &pump.end_node) {
+                link.initially_closed = closed(&pump.id, false);
+            }
+        }
+        for valve in inp.valves.iter() {
+            if let Some(link) = network.add_link(&valve.id, LinkKind::Valve, &valve.start_node, &valve.end_node) {
+                link.initially_closed = closed(&valve.id, false);
+            }
+        }
+
+        network
+    }
+
+    fn add_node(&mut self, id: &str, kind: NodeKind) {
+        self.node_index.insert(id.to_string(), self.nodes.len());
+        self.nodes.push(NetworkNode { id: id.to_string(), kind });
+        self.incident_links.push(Vec::new());
+    }
+
+    fn add_link(&mut self, id: &str, kind: LinkKind, start: &str, end: &str) -> Option<&mut NetworkLink> {
+        let (start, end) = match (self.node(start), self.node(end)) {
+            (Some(start), Some(end)) => (start, end),
+            (start_index, end_index) => {
+                let missing_nodes = [(start, start_index), (end, end_index)].iter()
+                    .filter(|(_, index)| index.is_none())
+                    .map(|(node, _)| node.to_string())
+                    .collect();
+                self.dangling_links.push(DanglingLink { id: id.to_string(), kind, missing_nodes });
+                return None;
+            },
+        };
+        let index = self.links.len();
+        self.link_index.insert(id.to_string(), index);
+        self.links.push(NetworkLink {
+            id: id.to_string(),
+            kind,
+            start,
+            end,
+            length: 0.0,
+            check_valve: false,
+            initially_closed: false,
+        });
+        self.incident_links[start].push(index);
+        if end != start {
+            self.incident_links[end].push(index);
+        }
+        self.links.last_mut()
+    }
+
+    pub fn node(&self, id: &str) -> Option<usize> {
+        self.node_index.get(id).copied()
+    }
+
+    pub fn link(&self, id: &str) -> Option<usize> {
+        self.link_index.get(id).copied()
+    }
+
+    pub fn incident_links(&self, node: usize) -> &[usize] {
+        &self.incident_links[node]
+    }
+
+    pub fn is_source(&self, node: usize) -> bool {
+        self.nodes[node].kind != NodeKind::Junction
+    }
+
+    /// Links that can be walked from `node` and the node found on their other end.
+    pub fn neighbours(&self, node: usize, traversal: &Traversal) -> Vec<(usize, usize)> {
+        self.incident_links[node].iter()
+            .map(|&index| (index, &self.links[index]))
+            .filter(|(_, link)| !(traversal.respect_status && link.initially_closed))
+            .filter(|(_, link)| {
+                if !traversal.directed || !link.is_directed() {
+                    return true;
+                }
+                let from = if traversal.upstream { link.end } else { link.start };
+                from == node
+            })
+            .map(|(index, link)| (index, link.other_end(node)))
+            .collect()
+    }
+
+    /// Marks the nodes that can be reached from any of the `starts` nodes.
+    pub fn reachable(&self, starts: &[usize], traversal: &Traversal) -> Vec<bool> {
+        let mut visited = vec![false; self.nodes.len()];
+        let mut queue = VecDeque::new();
+        for &start in starts {
+            visited[start] = true;
+            queue.push_back(start);
+        }
+        while let Some(node) = queue.pop_front() {
+            for (_, next) in self.neighbours(node, traversal) {
+                if !visited[next] {
+                    visited[next] = true;
+                    queue.push_back(next);
+                }
+            }
+        }
+        visited
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{DanglingLink, LinkKind, Network, NodeKind, Traversal};
+    use crate::INP;
+
+    fn a_network() -> Network {
+        let input = r#"
+[JUNCTIONS]
+J1  10
+J2  10
+J3  10
+[RESERVOIRS]
+R1  100
+[PIPES]
+P1  R1  J1  100  100  100
+P2  J1  J2  100  100  100  0  CV
+[PUMPS]
+PU1  J2  J3  POWER 10
+[STATUS]
+PU1  Closed
+"#;
+        Network::new(&INP::read(input.to_string()))
+    }
+
+    #[test]
+    fn build_the_network_topology() {
+        let network = a_network();
+
+        assert_eq!(network.nodes.len(), 4);
+        assert_eq!(network.links.len(), 3);
+        assert_eq!(network.nodes[network.node("R1").unwrap()].kind, NodeKind::Reservoir);
+        assert!(network.links[network.link("P2").unwrap()].check_valve);
+        assert!(network.links[network.link("PU1").unwrap()].initially_closed);
+        assert_eq!(network.incident_links(network.node("J1").unwrap()).len(), 2);
+    }
+
+    #[test]
+    fn record_links_to_missing_nodes() {
+        let input = "[JUNCTIONS]\nJ1  10\n[PIPES]\nP1  J1  J9  100  100  100\nP2  J8  J9  100  100  100\n";
+
+        let network = Network::new(&INP::read(input.to_string()));
+
+        assert!(network.links.is_empty());
+        assert_eq!(network.dangling_links, vec![
+            DanglingLink { id: "P1".to_string(), kind: LinkKind::Pipe, missing_nodes: vec!["J9".to_string()] },
+            DanglingLink { id: "P2".to_string(), kind: LinkKind::Pipe, missing_nodes: vec!["J8".to_string(), "J9".to_string()] },
+        ]);
+    }
+
+    #[test]
+    fn walk_through_directed_links_only_forward() {
+        let network = a_network();
+        let j2 = network.node("J2").unwrap();
+        let directed = Traversal { directed: true, ..Traversal::default() };
+
+        let downstream = network.reachable(&[j2], &directed);
+        let upstream = network.reachable(&[j2], &Traversal { upstream: true, ..directed });
+
+        assert_eq!(downstream, vec![false, true, true, false]);
+        assert_eq!(upstream, vec![true, true, false, true]);
+    }
+
+    #[test]
+    fn skip_closed_links() {
+        let network = a_network();
+        let r1 = network.node("R1").unwrap();
+
+        let reachable = network.reachable(&[r1], &Traversal { respect_status: true, ..Traversal::default() });
+
+        assert_eq!(reachable, vec![true, true, false, true]);
+    }
+}
@@ -0,0 +1,569 @@
+use std::collections::HashMap;
+use std::error::Error;
+use std::fmt;
+use std::fs::File;
+use std::io::{BufReader, Read, Seek, SeekFrom};
+use std::path::Path;
+use serde::{Serialize, Deserialize};
+use crate::{INP, ToJson};
+
+const MAGIC_NUMBER: i32 = 516114521;
+// Lengths of the fixed-size strings of the prolog.
+const TITLE_LENGTH: usize = 80;
+const FILE_NAME_LENGTH: usize = 260;
+const ID_LENGTH: usize = 32;
+const EPILOG_SIZE: u64 = 28;
+
+#[derive(Debug, PartialEq)]
+pub struct OutError {
+    pub message: String,
+}
+
+impl fmt::Display for OutError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.message)
+    }
+}
+
+impl Error for OutError {}
+
+impl From<std::io::Error> for OutError {
+    fn from(error: std::io::Error) -> Self {
+        OutError { message: error.to_string() }
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, PartialEq, Clone, Copy)]
+pub enum OutLinkType {
+    CheckValvePipe,
+    Pipe,
+    Pump,
+    Prv,
+    Psv,
+    Pbv,
+    Fcv,
+    Tcv,
+    Gpv,
+}
+
+/// Description of the network and of the simulation, at the start of the file.
+#[derive(Serialize, Deserialize, Debug, PartialEq, Clone)]
+pub struct Prolog {
+    pub version: i32,
+    pub node_count: usize,
+    /// Number of reservoirs and tanks.
+    pub tank_count: usize,
+    pub link_count: usize,
+    pub pump_count: usize,
+    pub valve_count: usize,
+    /// 0 for none, 1 for a chemical, 2 for age and 3 for a trace.
+    pub quality_option: i32,
+    /// Index of the trace node, counted from 1.
+    pub trace_node: i32,
+    /// EPANET code of the flow units, 0 for CFS to 9 for CMD.
+    pub flow_units: i32,
+    /// 0 for psi, 1 for metres and 2 for kPa.
+    pub pressure_units: i32,
+    /// 0 for time series, 1 to 4 for averages, minima, maxima and ranges.
+    pub statistics: i32,
+    pub report_start: u64,
+    pub report_step: u64,
+    pub duration: u64,
+    pub title: Vec<String>,
+    pub input_file: String,
+    pub report_file: String,
+    pub chemical: String,
+    pub chemical_units: String,
+    pub node_ids: Vec<String>,
+    pub link_ids: Vec<String>,
+    /// Start and end node of every link, as indices into `node_ids`.
+    pub link_nodes: Vec<(usize, usize)>,
+    pub link_types: Vec<OutLinkType>,
+    /// Node index and cross-sectional area of every tank, zero for reservoirs.
+    pub tanks: Vec<(usize, f64)>,
+    pub elevations: Vec<f64>,
+    pub lengths: Vec<f64>,
+    pub diameters: Vec<f64>,
+}
+
+/// Row of the energy usage section.
+#[derive(Serialize, Deserialize, Debug, PartialEq, Clone)]
+pub struct PumpUsage {
+    pub link_id: String,
+    pub utilization: f64,
+    pub efficiency: f64,
+    pub energy_per_volume: f64,
+    pub average_power: f64,
+    pub peak_power: f64,
+    pub daily_cost: f64,
+}
+
+#[derive(Serialize, Deserialize, Debug, PartialEq, Clone)]
+pub struct EnergyUsage {
+    pub pumps: Vec<PumpUsage>,
+    pub demand_charge: f64,
+}
+
+/// Summary at the end of the file.
+#[derive(Serialize, Deserialize, Debug, PartialEq, Clone)]
+pub struct Epilog {
+    /// Average reaction rates, in mass per hour.
+    pub bulk_rate: f64,
+    pub wall_rate: f64,
+    pub tank_rate: f64,
+    pub source_rate: f64,
+    pub period_count: usize,
+    pub warning: bool,
+}
+
+#[derive(Serialize, Deserialize, Debug, PartialEq, Clone)]
+pub struct OutNodeResult {
+    pub id: String,
+    pub demand: f64,
+    pub head: f64,
+    pub pressure: f64,
+    pub quality: f64,
+}
+
+#[derive(Serialize, Deserialize, Debug, PartialEq, Clone)]
+pub struct OutLinkResult {
+    pub id: String,
+    pub flow: f64,
+    pub velocity: f64,
+    /// Headloss per 1000 length units for pipes, head gain for pumps.
+    pub headloss: f64,
+    pub quality: f64,
+    /// EPANET status code: 0 to 2 for closed, 3 for open, 4 for active, 5 to 7 for open
+    /// while a limit can't be met.
+    pub status: f64,
+    pub setting: f64,
+    pub reaction_rate: f64,
+    pub friction_factor: f64,
+}
+
+/// Results of every node and link at a reporting period.
+#[derive(Serialize, Deserialize, Debug, PartialEq, Clone)]
+pub struct PeriodResults {
+    pub period: usize,
+    /// Seconds since the start of the simulation.
+    pub time: u64,
+    pub nodes: Vec<OutNodeResult>,
+    pub links: Vec<OutLinkResult>,
+}
+
+impl PeriodResults {
+    pub fn node(&self, id: &str) -> Option<&OutNodeResult> {
+        self.nodes.iter().find(|node| node.id == id)
+    }
+
+    pub fn link(&self, id: &str) -> Option<&OutLinkResult> {
+        self.links.iter().find(|link| link.id == id)
+    }
+}
+
+impl ToJson for PeriodResults {}
+
+fn read_bytes<R: Read>(reader: &mut R, length: usize) -> Result<Vec<u8>, OutError> {
+    let mut buffer = vec![0; length];
+    reader.read_exact(&mut buffer)?;
+    Ok(buffer)
+}
+
+fn read_i32<R: Read>(reader: &mut R) -> Result<i32, OutError> {
+    let mut buffer = [0; 4];
+    reader.read_exact(&mut buffer)?;
+    Ok(i32::from_le_bytes(buffer))
+}
+
+fn read_f32s<R: Read>(reader: &mut R, count: usize) -> Result<Vec<f64>, OutError> {
+    Ok(read_bytes(reader, 4 * count)?
+        .chunks_exact(4)
+        .map(|bytes| f32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]) as f64)
+        .collect())
+}
+
+fn read_i32s<R: Read>(reader: &mut R, count: usize) -> Result<Vec<i32>, OutError> {
+    (0..count).map(|_| read_i32(reader)).collect()
+}
+
+/// Fixed-size string padded with null characters.
+fn read_string<R: Read>(reader: &mut R, length: usize) -> Result<String, OutError> {
+    let bytes = read_bytes(reader, length)?;
+    let end = bytes.iter().position(|&byte| byte == 0).unwrap_or(length);
+    Ok(String::from_utf8_lossy(&bytes[..end]).trim().to_string())
+}
+
+fn read_count<R: Read>(reader: &mut R, name: &str) -> Result<usize, OutError> {
+    let count = read_i32(reader)?;
+    if count < 0 {
+        return Err(OutError { message: format!("Invalid number of {}", name) });
+    }
+    Ok(count as usize)
+}
+
+fn read_index<R: Read>(reader: &mut R, count: usize) -> Result<usize, OutError> {
+    let index = read_i32(reader)?;
+    if index < 1 || index as usize > count {
+        return Err(OutError { message: format!("Invalid index {}", index) });
+    }
+    Ok(index as usize - 1)
+}
+
+fn link_type(code: i32) -> Result<OutLinkType, OutError> {
+    Ok(match code {
+        0 => OutLinkType::CheckValvePipe,
+        1 => OutLinkType::Pipe,
+        2 => OutLinkType::Pump,
+        3 => OutLinkType::Prv,
+        4 => OutLinkType::Psv,
+        5 => OutLinkType::Pbv,
+        6 => OutLinkType::Fcv,
+        7 => OutLinkType::Tcv,
+        8 => OutLinkType::Gpv,
+        _ => return Err(OutError { message: format!("Invalid link type {}", code) }),
+    })
+}
+
+fn read_prolog<R: Read>(reader: &mut R) -> Result<Prolog, OutError> {
+    if read_i32(reader)? != MAGIC_NUMBER {
+        return Err(OutError { message: "Not an EPANET binary output file".to_string() });
+    }
+    let version = read_i32(reader)?;
+    let node_count = read_count(reader, "nodes")?;
+    let tank_count = read_count(reader, "tanks")?;
+    let link_count = read_count(reader, "links")?;
+    let pump_count = read_count(reader, "pumps")?;
+    let valve_count = read_count(reader, "valves")?;
+    let options = read_i32s(reader, 5)?;
+    let times = read_i32s(reader, 3)?.into_iter().map(|time| time.max(0) as u64).collect::<Vec<u64>>();
+    let title = (0..3).map(|_| read_string(reader, TITLE_LENGTH)).collect::<Result<Vec<String>, OutError>>()?;
+    let input_file = read_string(reader, FILE_NAME_LENGTH)?;
+    let report_file = read_string(reader, FILE_NAME_LENGTH)?;
+    let chemical = read_string(reader, ID_LENGTH)?;
+    let chemical_units = read_string(reader, ID_LENGTH)?;
+    let node_ids = (0..node_count).map(|_| read_string(reader, ID_LENGTH)).collect::<Result<Vec<String>, OutError>>()?;
+    let link_ids = (0..link_count).map(|_| read_string(reader, ID_LENGTH)).collect::<Result<Vec<String>, OutError>>()?;
+    let starts = (0..link_count).map(|_| read_index(reader, node_count)).collect::<Result<Vec<usize>, OutError>>()?;
+    let ends = (0..link_count).map(|_| read_index(reader, node_count)).collect::<Result<Vec<usize>, OutError>>()?;
+    let link_types = read_i32s(reader, link_count)?.into_iter().map(link_type).collect::<Result<Vec<OutLinkType>, OutError>>()?;
+    let tank_nodes = (0..tank_count).map(|_| read_index(reader, node_count)).collect::<Result<Vec<usize>, OutError>>()?;
+    let areas = read_f32s(reader, tank_count)?;
+
+    Ok(Prolog {
+        version,
+        node_count,
+        tank_count,
+        link_count,
+        pump_count,
+        valve_count,
+        quality_option: options[0],
+        trace_node: options[1],
+        flow_units: options[2],
+        pressure_units: options[3],
+        statistics: options[4],
+        report_start: times[0],
+        report_step: times[1],
+        duration: times[2],
+        title,
+        input_file,
+        report_file,
+        chemical,
+        chemical_units,
+        node_ids,
+        link_ids,
+        link_nodes: starts.into_iter().zip(ends).collect(),
+        link_types,
+        tanks: tank_nodes.into_iter().zip(areas).collect(),
+        elevations: read_f32s(reader, node_count)?,
+        lengths: read_f32s(reader, link_count)?,
+        diameters: read_f32s(reader, link_count)?,
+    })
+}
+
+/// EPANET binary output file. The prolog, energy usage and epilog are read when the
+/// file is opened; the results of a period are only read when asked for.
+pub struct OutFile<R: Read + Seek> {
+    reader: R,
+    pub prolog: Prolog,
+    pub energy: EnergyUsage,
+    pub epilog: Epilog,
+    results_offset: u64,
+    node_index: HashMap<String, usize>,
+    link_index: HashMap<String, usize>,
+}
+
+impl OutFile<BufReader<File>> {
+    pub fn open<P: AsRef<Path>>(path: P) -> Result<Self, OutError> {
+        OutFile::new(BufReader::new(File::open(path)?))
+    }
+}
+
+impl<R: Read + Seek> OutFile<R> {
+    pub fn new(mut reader: R) -> Result<Self, OutError> {
+        reader.seek(SeekFrom::Start(0))?;
+        let prolog = read_prolog(&mut reader)?;
+
+        let mut pumps = Vec::new();
+        for _ in 0..prolog.pump_count {
+            let link = read_index(&mut reader, prolog.link_count)?;
+            let values = read_f32s(&mut reader, 6)?;
+            pumps.push(PumpUsage {
+                link_id: prolog.link_ids[link].clone(),
+                utilization: values[0],
+                efficiency: values[1],
+                energy_per_volume: values[2],
+                average_power: values[3],
+                peak_power: values[4],
+                daily_cost: values[5],
+            });
+        }
+        let demand_charge = read_f32s(&mut reader, 1)?[0];
+        let results_offset = reader.stream_position()?;
+
+        let end = reader.seek(SeekFrom::End(0))?;
+        if end < results_offset + EPILOG_SIZE {
+            return Err(OutError { message: "Binary output file is truncated".to_string() });
+        }
+        reader.seek(SeekFrom::End(-(EPILOG_SIZE as i64)))?;
+        let rates = read_f32s(&mut reader, 4)?;
+        let period_count = read_count(&mut reader, "periods")?;
+        let warning = read_i32(&mut reader)? != 0;
+        if read_i32(&mut reader)? != MAGIC_NUMBER {
+            return Err(OutError { message: "Binary output file is incomplete".to_string() });
+        }
+        let epilog = Epilog { bulk_rate: rates[0], wall_rate: rates[1], tank_rate: rates[2], source_rate: rates[3], period_count, warning };
+
+        let mut out = OutFile {
+            reader,
+            node_index: prolog.node_ids.iter().enumerate().map(|(i, id)| (id.clone(), i)).collect(),
+            link_index: prolog.link_ids.iter().enumerate().map(|(k, id)| (id.clone(), k)).collect(),
+            prolog,
+            energy: EnergyUsage { pumps, demand_charge },
+            epilog,
+            results_offset,
+        };
+        if out.results_offset + out.period_size() * period_count as u64 + EPILOG_SIZE > end {
+            return Err(OutError { message: "Binary output file is truncated".to_string() });
+        }
+        out.reader.seek(SeekFrom::Start(out.results_offset))?;
+        Ok(out)
+    }
+
+    fn period_size(&self) -> u64 {
+        (16 * self.prolog.node_count + 32 * self.prolog.link_count) as u64
+    }
+
+    pub fn period_count(&self) -> usize {
+        self.epilog.period_count
+    }
+
+    /// Seconds since the start of the simulation at a reporting period.
+    pub fn period_time(&self, period: usize) -> u64 {
+        self.prolog.report_start + period as u64 * self.prolog.report_step
+    }
+
+    pub fn node_index(&self, id: &str) -> Option<usize> {
+        self.node_index.get(id).copied()
+    }
+
+    pub fn link_index(&self, id: &str) -> Option<usize> {
+        self.link_index.get(id).copied()
+    }
+
+    /// Results of every node and link at a reporting period, read without the other periods.
+    pub fn period(&mut self, period: usize) -> Result<PeriodResults, OutError> {
+        if period >= self.period_count() {
+            return Err(OutError { message: format!("Period {} is out of {} periods", period, self.period_count()) });
+        }
+        let nodes = self.prolog.node_count;
+        let links = self.prolog.link_count;
+        self.reader.seek(SeekFrom::Start(self.results_offset + period as u64 * self.period_size()))?;
+        let node_values = read_f32s(&mut self.reader, 4 * nodes)?;
+        let link_values = read_f32s(&mut self.reader, 8 * links)?;
+
+        Ok(PeriodResults {
+            period,
+            time: self.period_time(period),
+            nodes: self.prolog.node_ids.iter().enumerate()
+                .map(|(i, id)| OutNodeResult {
+                    id: id.clone(),
+                    demand: node_values[i],
+                    head: node_values[nodes + i],
+                    pressure: node_values[2 * nodes + i],
+                    quality: node_values[3 * nodes + i],
+                })
+                .collect(),
+            links: self.prolog.link_ids.iter().enumerate()
+                .map(|(k, id)| {
+                    let value = |variable: usize| link_values[variable * links + k];
+                    OutLinkResult {
+                        id: id.clone(),
+                        flow: value(0),
+                        velocity: value(1),
+                        headloss: value(2),
+                        quality: value(3),
+                        status: value(4),
+                        setting: value(5),
+                        reaction_rate: value(6),
+                        friction_factor: value(7),
+                    }
+                })
+                .collect(),
+        })
+    }
+
+    /// Checks that the file holds results for the nodes and links of an INP.
+    pub fn check_inp(&self, inp: &INP) -> Result<(), OutError> {
+        let node_ids = inp.junctions.iter().map(|junction| &junction.id)
+            .chain(inp.reservoirs.iter().map(|reservoir| &reservoir.id))
+            .chain(inp.tanks.iter().map(|tank| &tank.id));
+        let link_ids = inp.pipes.iter().map(|pipe| &pipe.id)
+            .chain(inp.pumps.iter().map(|pump| &pump.id))
+            .chain(inp.valves.iter().map(|valve| &valve.id));
+        let missing = node_ids.filter(|id| !self.node_index.contains_key(*id))
+            .chain(link_ids.filter(|id| !self.link_index.contains_key(*id)))
+            .cloned()
+            .collect::<Vec<String>>();
+        if !missing.is_empty() {
+            return Err(OutError { message: format!("No results for {}", missing.join(", ")) });
+        }
+        let inp_nodes = inp.junctions.len() + inp.reservoirs.len() + inp.tanks.len();
+        let inp_links = inp.pipes.len() + inp.pumps.len() + inp.valves.len();
+        if inp_nodes != self.prolog.node_count || inp_links != self.prolog.link_count {
+            return Err(OutError {
+                message: format!("The INP has {} nodes and {} links, the results {} nodes and {} links", inp_nodes, inp_links, self.prolog.node_count, self.prolog.link_count),
+            });
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::io::Cursor;
+    use super::{OutFile, OutLinkType, MAGIC_NUMBER};
+    use crate::INP;
+
+    fn string(bytes: &mut Vec<u8>, value: &str, length: usize) {
+        let mut field = value.as_bytes().to_vec();
+        field.resize(length, 0);
+        bytes.extend(field);
+    }
+
+    fn ints(bytes: &mut Vec<u8>, values: &[i32]) {
+        for value in values {
+            bytes.extend(value.to_le_bytes());
+        }
+    }
+
+    fn floats(bytes: &mut Vec<u8>, values: &[f32]) {
+        for value in values {
+            bytes.extend(value.to_le_bytes());
+        }
+    }
+
+    /// Binary output of R1 -PU1-> J1 -P1-> T1 over two periods.
+    fn an_out_file() -> Vec<u8> {
+        let mut bytes = Vec::new();
+        ints(&mut bytes, &[MAGIC_NUMBER, 20012, 3, 2, 2, 1, 0, 0, 0, 5, 1, 0, 0, 3600, 3600]);
+        for line in ["A test network", "", ""] {
+            string(&mut bytes, line, 80);
+        }
+        string(&mut bytes, "test.inp", 260);
+        string(&mut bytes, "test.rpt", 260);
+        string(&mut bytes, "", 32);
+        string(&mut bytes, "mg/L", 32);
+        for id in ["J1", "R1", "T1"] {
+            string(&mut bytes, id, 32);
+        }
+        for id in ["P1", "PU1"] {
+            string(&mut bytes, id, 32);
+        }
+        ints(&mut bytes, &[1, 2, 3, 1, 1, 2]);
+        ints(&mut bytes, &[2, 3]);
+        floats(&mut bytes, &[0.0, 78.5]);
+        floats(&mut bytes, &[10.0, 0.0, 20.0]);
+        floats(&mut bytes, &[100.0, 0.0]);
+        floats(&mut bytes, &[300.0, 0.0]);
+        // Energy
+        ints(&mut bytes, &[2]);
+        floats(&mut bytes, &[100.0, 75.0, 0.15, 26.0, 26.5, 12.0, 26.5]);
+        // Two periods
+        for period in 0..2 {
+            let p = period as f32;
+            floats(&mut bytes, &[5.0, -15.0, 10.0]);
+            floats(&mut bytes, &[40.0 + p, 0.0, 25.0 + p]);
+            floats(&mut bytes, &[30.0 + p, 0.0, 5.0 + p]);
+            floats(&mut bytes, &[0.0, 0.0, 0.0]);
+            floats(&mut bytes, &[10.0, 15.0, 0.14, 0.0, 1.5, -40.0, 0.0, 0.0, 3.0, 3.0, 130.0, 1.0, 0.0, 0.0, 0.02, 0.0]);
+        }
+        floats(&mut bytes, &[0.0, 0.0, 0.0, 0.0]);
+        ints(&mut bytes, &[2, 0, MAGIC_NUMBER]);
+        bytes
+    }
+
+    #[test]
+    fn read_the_prolog_energy_and_epilog() {
+        let out = OutFile::new(Cursor::new(an_out_file())).unwrap();
+
+        assert_eq!(out.prolog.version, 20012);
+        assert_eq!(out.prolog.title[0], "A test network");
+        assert_eq!(out.prolog.node_ids, vec!["J1", "R1", "T1"]);
+        assert_eq!(out.prolog.link_nodes, vec![(0, 2), (1, 0)]);
+        assert_eq!(out.prolog.link_types, vec![OutLinkType::Pipe, OutLinkType::Pump]);
+        assert_eq!(out.prolog.tanks, vec![(1, 0.0), (2, 78.5)]);
+        assert_eq!(out.prolog.chemical_units, "mg/L");
+        assert_eq!(out.energy.pumps[0].link_id, "PU1");
+        assert_eq!(out.energy.pumps[0].average_power, 26.0);
+        assert_eq!(out.energy.demand_charge, 26.5);
+        assert_eq!(out.period_count(), 2);
+        assert!(!out.epilog.warning);
+    }
+
+    #[test]
+    fn read_a_single_period() {
+        let mut out = OutFile::new(Cursor::new(an_out_file())).unwrap();
+
+        let period = out.period(1).unwrap();
+
+        assert_eq!(period.time, 3600);
+        assert_eq!(period.node("J1").unwrap().head, 41.0);
+        assert_eq!(period.node("T1").unwrap().pressure, 6.0);
+        assert_eq!(period.link("PU1").unwrap().flow, 15.0);
+        assert_eq!(period.link("PU1").unwrap().headloss, -40.0);
+        assert_eq!(period.link("P1").unwrap().status, 3.0);
+        assert_eq!(out.period(0).unwrap().node("J1").unwrap().head, 40.0);
+        assert!(out.period(2).is_err());
+    }
+
+    #[test]
+    fn line_results_up_with_the_inp() {
+        let out = OutFile::new(Cursor::new(an_out_file())).unwrap();
+        let inp = INP::read(r#"
+[JUNCTIONS]
+J1  10  5
+[RESERVOIRS]
+R1  0
+[TANKS]
+T1  20  5  0  10  10  0
+[PIPES]
+P1  J1  T1  100  300  130
+[PUMPS]
+PU1  R1  J1  HEAD C1
+"#.to_string());
+
+        assert_eq!(out.check_inp(&inp), Ok(()));
+        assert_eq!(out.node_index("T1"), Some(2));
+
+        let other = INP::read("[JUNCTIONS]\nJ9  10\n".to_string());
+        assert!(out.check_inp(&other).is_err());
+    }
+
+    #[test]
+    fn reject_other_files() {
+        let mut bytes = an_out_file();
+        assert!(OutFile::new(Cursor::new(bytes[..1000].to_vec())).is_err());
+        bytes[0] = 0;
+        assert!(OutFile::new(Cursor::new(bytes)).is_err());
+    }
+}
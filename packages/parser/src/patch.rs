@@ -0,0 +1,386 @@
+use std::collections::HashSet;
+use std::error::Error;
+use std::fmt;
+use serde::{Serialize, Deserialize};
+use serde::de::DeserializeOwned;
+use serde_json::Value;
+use crate::diff::{setting_id, Record};
+use crate::sections::{Coordinate, Pattern, Vertex};
+use crate::shapefile::records;
+use crate::validation::validate;
+use crate::INP;
+
+#[derive(Debug, PartialEq)]
+pub struct PatchError {
+    pub message: String,
+}
+
+impl fmt::Display for PatchError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.message)
+    }
+}
+
+impl Error for PatchError {}
+
+impl From<serde_json::Error> for PatchError {
+    fn from(error: serde_json::Error) -> Self {
+        PatchError { message: error.to_string() }
+    }
+}
+
+/// Change of a patch, written in JSON with its name in `op`, e.g.
+/// `{"op": "set", "element": "pipe", "id": "P1", "field": "diameter", "value": 200}`.
+/// Elements are named as in the diff and their fields as in the JSON of the model.
+#[derive(Serialize, Deserialize, Debug, PartialEq, Clone)]
+#[serde(tag = "op", rename_all = "snake_case")]
+pub enum Operation {
+    /// Adds an element from its fields. Nodes may have `x` and `y`, links `vertices`.
+    Add { element: String, fields: Record },
+    /// Removes an element with its coordinates, vertices, demands, emitter, status,
+    /// quality, source, mixing and tags, and unanchors its labels. References to it
+    /// elsewhere, e.g. in controls, rules, energy or reactions, make the patch invalid.
+    Remove { element: String, id: String },
+    Set { element: String, id: String, field: String, value: Value },
+    /// Renames a node, link, pattern or curve and every reference to it.
+    Rename { element: String, id: String, new_id: String },
+    /// Replaces the multipliers of a pattern, adding the pattern when it doesn't exist.
+    ReplacePattern { id: String, multipliers: Vec<f64> },
+}
+
+#[derive(Serialize, Deserialize, Debug, PartialEq, Clone, Default)]
+pub struct Patch {
+    #[serde(default)]
+    pub description: String,
+    pub operations: Vec<Operation>,
+}
+
+impl Patch {
+    pub fn from_json(text: &str) -> Result<Patch, PatchError> {
+        Ok(serde_json::from_str(text)?)
+    }
+
+    pub fn to_json(&self) -> Value {
+        serde_json::to_value(self).unwrap()
+    }
+}
+
+const NODES: [&str; 3] = ["junction", "reservoir", "tank"];
+const LINKS: [&str; 3] = ["pipe", "pump", "valve"];
+
+enum Edit<'a> {
+    Add(&'a Record),
+    Remove(&'a str),
+    Set(&'a str, &'a str, &'a Value),
+}
+
+/// Field with the ID of the rows of a section. Settings are identified by `setting_id`,
+/// ignoring case as EPANET does.
+struct Key(&'static str);
+
+impl Key {
+    fn id(&self, record: &Record) -> String {
+        let text = |field: &str| record.get(field).and_then(Value::as_str).unwrap_or_default().to_string();
+        match self.0 {
+            "key" => setting_id(&text("key"), &text("value")),
+            field => text(field),
+        }
+    }
+
+    fn matches(&self, record: &Record, id: &str) -> bool {
+        match self.0 {
+            "key" => self.id(record).eq_ignore_ascii_case(id),
+            _ => self.id(record) == id,
+        }
+    }
+}
+
+/// Edits the rows of a section through their JSON form.
+fn edit_rows<T: Serialize + DeserializeOwned>(element: &str, items: &mut Vec<T>, key: Key, edit: &Edit) -> Result<(), String> {
+    let mut rows = records(items);
+    match edit {
+        Edit::Add(fields) => {
+            let id = key.id(fields);
+            if rows.iter().any(|row| key.matches(row, &id)) {
+                return Err(format!("{} {} already exists", element, id));
+            }
+            let mut row = (*fields).clone();
+            if let Some(Value::String(setting)) = row.get_mut("key") {
+                *setting = setting.to_uppercase();
+            }
+            rows.push(row);
+        },
+        Edit::Remove(id) => {
+            let count = rows.len();
+            rows.retain(|row| !key.matches(row, id));
+            if rows.len() == count {
+                return Err(format!("No {} {}", element, id));
+            }
+        },
+        Edit::Set(id, field, value) => {
+            let row = rows.iter_mut().find(|row| key.matches(row, id)).ok_or(format!("No {} {}", element, id))?;
+            if *field == key.0 {
+                return Err(format!("Rename {} {} instead of setting its {}", element, id, field));
+            }
+            if !row.contains_key(*field) {
+                return Err(format!("{} has no field {}", element, field));
+            }
+            row.insert(field.to_string(), (*value).clone());
+        },
+    }
+    *items = rows.into_iter()
+        .map(|row| serde_json::from_value(Value::Object(row)).map_err(|error| format!("Invalid {}: {}", element, error)))
+        .collect::<Result<Vec<T>, String>>()?;
+    Ok(())
+}
+
+fn change(inp: &mut INP, element: &str, edit: Edit) -> Result<(), String> {
+    match element {
+        "junction" => edit_rows(element, &mut inp.junctions, Key("id"), &edit)?,
+        "reservoir" => edit_rows(element, &mut inp.reservoirs, Key("id"), &edit)?,
+        "tank" => edit_rows(element, &mut inp.tanks, Key("id"), &edit)?,
+        "pipe" => edit_rows(element, &mut inp.pipes, Key("id"), &edit)?,
+        "pump" => edit_rows(element, &mut inp.pumps, Key("id"), &edit)?,
+        "valve" => edit_rows(element, &mut inp.valves, Key("id"), &edit)?,
+        "coordinate" => edit_rows(element, &mut inp.coordinates, Key("node_id"), &edit)?,
+        "status" => edit_rows(element, &mut inp.statuses, Key("link_id"), &edit)?,
+        "emitter" => edit_rows(element, &mut inp.emitters, Key("junction_id"), &edit)?,
+        "quality" => edit_rows(element, &mut inp.quality, Key("nodeid"), &edit)?,
+        "source" => edit_rows(element, &mut inp.sources, Key("node"), &edit)?,
+        "mixing" => edit_rows(element, &mut inp.mixing, Key("tank_id"), &edit)?,
+        "energy" => edit_rows(element, &mut inp.energy, Key("key"), &edit)?,
+        "reaction" => edit_rows(element, &mut inp.reactions, Key("key"), &edit)?,
+        "time" => edit_rows(element, &mut inp.times, Key("key"), &edit)?,
+        "option" => edit_rows(element, &mut inp.options, Key("key"), &edit)?,
+        "pattern" | "curve" if matches!(edit, Edit::Remove(_)) => {},
+        _ => return Err(format!("Unsupported element {}", element)),
+    }
+
+    match edit {
+        Edit::Add(fields) if NODES.contains(&element) => {
+            if let (Some(x), Some(y)) = (fields.get("x").and_then(Value::as_f64), fields.get("y").and_then(Value::as_f64)) {
+                inp.coordinates.push(Coordinate { node_id: Key("id").id(fields), x, y, comment: None });
+            }
+        },
+        Edit::Add(fields) if LINKS.contains(&element) => {
+            for vertex in fields.get("vertices").and_then(Value::as_array).into_iter().flatten() {
+                let (x, y) = (vertex[0].as_f64(), vertex[1].as_f64());
+                inp.vertices.push(Vertex { link_id: Key("id").id(fields), x: x.unwrap_or_default(), y: y.unwrap_or_default(), comment: None });
+            }
+        },
+        Edit::Remove(id) if NODES.contains(&element) => {
+            inp.coordinates.retain(|coordinate| coordinate.node_id != id);
+            inp.demands.retain(|demand| demand.junction_id != id);
+            inp.emitters.retain(|emitter| emitter.junction_id != id);
+            inp.quality.retain(|quality| quality.nodeid != id);
+            inp.sources.retain(|source| source.node != id);
+            inp.mixing.retain(|mixing| mixing.tank_id != id);
+            inp.tags.retain(|tag| !tag.object_type.eq_ignore_ascii_case("NODE") || tag.object_id != id);
+            inp.labels.iter_mut().filter(|label| label.anchor_node_id.as_deref() == Some(id)).for_each(|label| label.anchor_node_id = None);
+        },
+        Edit::Remove(id) if LINKS.contains(&element) => {
+            inp.vertices.retain(|vertex| vertex.link_id != id);
+            inp.statuses.retain(|status| status.link_id != id);
+            inp.tags.retain(|tag| !tag.object_type.eq_ignore_ascii_case("LINK") || tag.object_id != id);
+        },
+        Edit::Remove(id) if element == "pattern" => {
+            let count = inp.patterns.len();
+            inp.patterns.retain(|pattern| pattern.id != id);
+            if inp.patterns.len() == count {
+                return Err(format!("No pattern {}", id));
+            }
+        },
+        Edit::Remove(id) if element == "curve" => {
+            let count = inp.curves.len();
+            inp.curves.retain(|curve| curve.id != id);
+            if inp.curves.len() == count {
+                return Err(format!("No curve {}", id));
+            }
+        },
+        _ => {},
+    }
+    Ok(())
+}
+
+fn rename(inp: &mut INP, element: &str, id: &str, new_id: &str) -> Result<(), String> {
+    let nodes = || inp.junctions.iter().map(|junction| ("junction", &junction.id))
+        .chain(inp.reservoirs.iter().map(|reservoir| ("reservoir", &reservoir.id)))
+        .chain(inp.tanks.iter().map(|tank| ("tank", &tank.id)));
+    let links = || inp.pipes.iter().map(|pipe| ("pipe", &pipe.id))
+        .chain(inp.pumps.iter().map(|pump| ("pump", &pump.id)))
+        .chain(inp.valves.iter().map(|valve| ("valve", &valve.id)));
+    let ids = match element {
+        element if NODES.contains(&element) => nodes().collect::<Vec<_>>(),
+        element if LINKS.contains(&element) => links().collect(),
+        "pattern" => inp.patterns.iter().map(|pattern| ("pattern", &pattern.id)).collect(),
+        "curve" => inp.curves.iter().map(|curve| ("curve", &curve.id)).collect(),
+        _ => return Err(format!("Can't rename {} elements", element)),
+    };
+    if !ids.contains(&(element, &id.to_string())) {
+        return Err(format!("No {} {}", element, id));
+    }
+    if ids.iter().any(|(_, existing)| *existing == new_id) {
+        return Err(format!("{} is already used", new_id));
+    }
+    match element {
+        element if NODES.contains(&element) => inp.rename_node(id, new_id),
+        element if LINKS.contains(&element) => inp.rename_link(id, new_id),
+        "pattern" => inp.rename_pattern(id, new_id),
+        _ => inp.rename_curve(id, new_id),
+    }
+    Ok(())
+}
+
+fn replace_pattern(inp: &mut INP, id: &str, multipliers: &[f64]) -> Result<(), String> {
+    if multipliers.is_empty() {
+        return Err(format!("Pattern {} needs at least one multiplier", id));
+    }
+    let position = inp.patterns.iter().position(|pattern| pattern.id == id).unwrap_or(inp.patterns.len());
+    let comment = inp.patterns.get(position).and_then(|pattern| pattern.comment.clone());
+    inp.patterns.retain(|pattern| pattern.id != id);
+    // Six multipliers a line, as EPANET writes them.
+    let lines = multipliers.chunks(6).enumerate().map(|(index, chunk)| Pattern {
+        id: id.to_string(),
+        multipliers: chunk.to_vec(),
+        comment: if index == 0 { comment.clone() } else { None },
+    });
+    inp.patterns.splice(position..position, lines);
+    Ok(())
+}
+
+fn apply(inp: &mut INP, operation: &Operation) -> Result<(), String> {
+    match operation {
+        Operation::Add { element, fields } => change(inp, element, Edit::Add(fields)),
+        Operation::Remove { element, id } => change(inp, element, Edit::Remove(id)),
+        Operation::Set { element, id, field, value } => change(inp, element, Edit::Set(id, field, value)),
+        Operation::Rename { element, id, new_id } => rename(inp, element, id, new_id),
+        Operation::ReplacePattern { id, multipliers } => replace_pattern(inp, id, multipliers),
+    }
+}
+
+/// Applies the operations of a patch in order. The patched model must not have validation
+/// errors the model didn't have. When an operation fails or the patched model isn't valid
+/// the model is left unchanged.
+pub fn apply_patch(inp: &mut INP, patch: &Patch) -> Result<(), PatchError> {
+    let mut patched = inp.clone();
+    for (index, operation) in patch.operations.iter().enumerate() {
+        apply(&mut patched, operation).map_err(|message| PatchError { message: format!("Operation {}: {}", index + 1, message) })?;
+    }
+
+    let errors = validate(inp).errors().map(|diagnostic| diagnostic.message.clone()).collect::<HashSet<String>>();
+    let new_errors = validate(&patched).errors()
+        .filter(|diagnostic| !errors.contains(&diagnostic.message))
+        .map(|diagnostic| diagnostic.message.clone())
+        .collect::<Vec<String>>();
+    if !new_errors.is_empty() {
+        return Err(PatchError { message: format!("Patched model is invalid: {}", new_errors.join(", ")) });
+    }
+
+    *inp = patched;
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use serde_json::json;
+    use super::{apply_patch, Operation, Patch};
+    use crate::INP;
+
+    const MODEL: &str = r#"
+[JUNCTIONS]
+J1  10  1  Day
+J2  20  1
+[RESERVOIRS]
+R1  100
+[PIPES]
+P1  R1  J1  100  150  100
+P2  J1  J2  100  100  100
+[PATTERNS]
+Day  1  1.2
+[OPTIONS]
+Units  LPS
+[COORDINATES]
+J1  0  0
+J2  10  0
+R1  -10  0
+"#;
+
+    #[test]
+    fn apply_operations() {
+        let mut inp = INP::read(MODEL.to_string());
+        let patch = Patch::from_json(r#"{
+            "description": "Upsize P2 and add a PRV",
+            "operations": [
+                {"op": "set", "element": "pipe", "id": "P2", "field": "diameter", "value": 200},
+                {"op": "add", "element": "junction", "fields": {"id": "J3", "elevation": 15, "x": 20, "y": 0}},
+                {"op": "add", "element": "valve", "fields": {
+                    "id": "V1", "start_node": "J2", "end_node": "J3", "diameter": 100,
+                    "valve_type": "Prv", "valve_setting": 30, "minor_loss_coefficient": 0
+                }},
+                {"op": "set", "element": "option", "id": "units", "field": "value", "value": "GPM"},
+                {"op": "rename", "element": "junction", "id": "J2", "new_id": "J20"}
+            ]
+        }"#).unwrap();
+
+        apply_patch(&mut inp, &patch).unwrap();
+
+        assert_eq!(inp.pipes[1].diameter, 200.0);
+        assert_eq!((inp.pipes[1].node2.as_str(), inp.valves[0].start_node.as_str()), ("J20", "J20"));
+        assert_eq!(inp.junctions[2].id, "J3");
+        assert_eq!(inp.coordinates.last().map(|coordinate| (coordinate.node_id.as_str(), coordinate.x)), Some(("J3", 20.0)));
+        assert_eq!(inp.option("units"), Some("GPM"));
+    }
+
+    #[test]
+    fn leave_the_model_unchanged_when_a_patch_fails() {
+        let mut inp = INP::read(MODEL.to_string());
+        let set = |id: &str, field: &str, value| Operation::Set { element: "pipe".to_string(), id: id.to_string(), field: field.to_string(), value };
+
+        let missing = Patch { description: String::new(), operations: vec![set("P1", "diameter", json!(200)), set("P9", "diameter", json!(200))] };
+        let unknown_field = Patch { description: String::new(), operations: vec![set("P1", "colour", json!("blue"))] };
+        let wrong_type = Patch { description: String::new(), operations: vec![set("P1", "length", json!("long"))] };
+        let dangling = Patch { description: String::new(), operations: vec![Operation::Remove { element: "junction".to_string(), id: "J2".to_string() }] };
+
+        assert_eq!(apply_patch(&mut inp, &missing).unwrap_err().message, "Operation 2: No pipe P9");
+        assert_eq!(apply_patch(&mut inp, &unknown_field).unwrap_err().message, "Operation 1: pipe has no field colour");
+        assert!(apply_patch(&mut inp, &wrong_type).unwrap_err().message.starts_with("Operation 1: Invalid pipe: invalid type"));
+        assert_eq!(apply_patch(&mut inp, &dangling).unwrap_err().message, "Patched model is invalid: Link P2 refers to undefined node J2");
+        assert_eq!(inp, INP::read(MODEL.to_string()));
+    }
+
+    #[test]
+    fn replace_and_rename_patterns() {
+        let mut inp = INP::read(MODEL.to_string());
+        let patch = Patch {
+            description: "Hourly demand".to_string(),
+            operations: vec![
+                Operation::ReplacePattern { id: "Day".to_string(), multipliers: (0..24).map(|hour| 1.0 + hour as f64 / 8.0).collect() },
+                Operation::Rename { element: "pattern".to_string(), id: "Day".to_string(), new_id: "Hourly".to_string() },
+            ],
+        };
+
+        apply_patch(&mut inp, &patch).unwrap();
+
+        assert_eq!(inp.patterns.len(), 4);
+        assert_eq!(inp.pattern("Hourly").map(|multipliers| multipliers[23]), Some(3.875));
+        assert_eq!(inp.junctions[0].demand_pattern_id.as_deref(), Some("Hourly"));
+        assert_eq!(Patch::from_json(&patch.to_json().to_string()), Ok(patch));
+    }
+
+    #[test]
+    fn refuse_to_remove_referenced_elements() {
+        let input = format!("{}{}", MODEL, "[TAGS]\nNODE  J2  North\nLINK  P2  Cast\n[LABELS]\n5  5  \"End\"  J2\n[RULES]\nRULE 1\nIF JUNCTION J2 PRESSURE BELOW 20\nTHEN PIPE P2 STATUS IS CLOSED\n[REACTIONS]\nWall  P1  -0.1\n[OPTIONS]\nQuality  Trace  J1\n");
+        let mut inp = INP::read(input);
+        let remove = |element: &str, id: &str| Patch { description: String::new(), operations: vec![Operation::Remove { element: element.to_string(), id: id.to_string() }] };
+        let mut untagged = inp.clone();
+        untagged.rules.clear();
+        untagged.pipes.retain(|pipe| pipe.id != "P2");
+
+        assert_eq!(apply_patch(&mut inp, &remove("pipe", "P1")).unwrap_err().message, "Patched model is invalid: WALL refers to undefined pipe P1");
+        assert_eq!(apply_patch(&mut inp, &remove("pipe", "P2")).unwrap_err().message, "Patched model is invalid: Rule 1 refers to undefined link P2");
+        assert_eq!(apply_patch(&mut inp, &remove("junction", "J1")).unwrap_err().message, "Patched model is invalid: Link P1 refers to undefined node J1, Link P2 refers to undefined node J1, Trace node J1 is undefined");
+        apply_patch(&mut untagged, &remove("junction", "J2")).unwrap();
+        assert_eq!(untagged.tags.iter().map(|tag| tag.object_id.as_str()).collect::<Vec<&str>>(), vec!["P2"]);
+        assert_eq!(untagged.labels[0].anchor_node_id, None);
+    }
+}
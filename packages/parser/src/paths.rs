@@ -0,0 +1,287 @@
+use std::cmp::Ordering;
+use std::collections::BinaryHeap;
+use serde::{Serialize, Deserialize};
+use crate::INP;
+use crate::geometry::{Geometry, Point};
+use crate::headloss::{friction_slope, WATER_VISCOSITY};
+use crate::network::{Network, Traversal};
+use crate::units::Quantity;
+
+#[derive(Serialize, Deserialize, Debug, PartialEq, Clone, Copy)]
+pub enum PathWeight {
+    /// Pipe length, in model length units.
+    Length,
+    /// Headloss, in metres, of each pipe when the water flows at 1 m/s.
+    Resistance,
+}
+
+#[derive(Serialize, Deserialize, Debug, PartialEq, Clone)]
+pub struct Path {
+    pub nodes: Vec<String>,
+    pub links: Vec<String>,
+    pub cost: f64,
+}
+
+impl Path {
+    /// Polylines of the links of the path, ready to be highlighted on a map.
+    pub fn geometry(&self, geometry: &Geometry) -> Vec<Vec<Point>> {
+        self.links.iter().filter_map(|link| geometry.link(link)).collect()
+    }
+}
+
+/// Nodes and links the water can come through to reach a node.
+#[derive(Serialize, Deserialize, Debug, PartialEq, Clone, Default)]
+pub struct Upstream {
+    pub nodes: Vec<String>,
+    pub links: Vec<String>,
+}
+
+#[derive(PartialEq)]
+struct Visit {
+    cost: f64,
+    node: usize,
+}
+
+impl Eq for Visit {}
+
+impl Ord for Visit {
+    fn cmp(&self, other: &Self) -> Ordering {
+        other.cost.partial_cmp(&self.cost).unwrap_or(Ordering::Equal)
+    }
+}
+
+impl PartialOrd for Visit {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+// Water only flows forward through pumps and check valves, and never through closed links.
+const FLOW: Traversal = Traversal { respect_status: true, directed: true, upstream: false };
+
+fn link_weights(inp: &INP, network: &Network, weight: PathWeight) -> Vec<f64> {
+    let mut weights = vec![0.0; network.links.len()];
+    let flow_units = inp.flow_units();
+    let formula = inp.headloss_formula();
+    let viscosity = WATER_VISCOSITY * inp.option_number("VISCOSITY").unwrap_or(1.0);
+    for pipe in inp.pipes.iter() {
+        if let Some(index) = network.link(&pipe.id) {
+            weights[index] = match weight {
+                PathWeight::Length => pipe.length,
+                PathWeight::Resistance => {
+                    let diameter = pipe.diameter * Quantity::Diameter.si_factor(flow_units, formula);
+                    let roughness = pipe.roughness * Quantity::Roughness.si_factor(flow_units, formula);
+                    let length = pipe.length * Quantity::Length.si_factor(flow_units, formula);
+                    friction_slope(formula, roughness, diameter, 1.0, viscosity) * length
+                },
+            };
+        }
+    }
+    weights
+}
+
+/// Dijkstra from `start`, returning the cost to every node and the link used to reach it.
+fn search(network: &Network, start: usize, weights: &[f64], traversal: &Traversal) -> (Vec<f64>, Vec<Option<usize>>) {
+    let mut costs = vec![f64::INFINITY; network.nodes.len()];
+    let mut previous = vec![None; network.nodes.len()];
+    let mut heap = BinaryHeap::new();
+    costs[start] = 0.0;
+    heap.push(Visit { cost: 0.0, node: start });
+
+    while let Some(Visit { cost, node }) = heap.pop() {
+        if cost > costs[node] {
+            continue;
+        }
+        for (link, next) in network.neighbours(node, traversal) {
+            let next_cost = cost + weights[link];
+            if next_cost < costs[next] {
+                costs[next] = next_cost;
+                previous[next] = Some(link);
+                heap.push(Visit { cost: next_cost, node: next });
+            }
+        }
+    }
+
+    (costs, previous)
+}
+
+fn build_path(network: &Network, start: usize, end: usize, costs: &[f64], previous: &[Option<usize>]) -> Option<Path> {
+    if costs[end].is_infinite() {
+        return None;
+    }
+    let mut nodes = vec![network.nodes[end].id.clone()];
+    let mut links = Vec::new();
+    let mut node = end;
+    while node != start {
+        let link = previous[node]?;
+        links.push(network.links[link].id.clone());
+        node = network.links[link].other_end(node);
+        nodes.push(network.nodes[node].id.clone());
+    }
+    nodes.reverse();
+    links.reverse();
+
+    Some(Path { nodes, links, cost: costs[end] })
+}
+
+/// Cheapest path the water can follow from `from` to `to`.
+pub fn shortest_path(inp: &INP, from: &str, to: &str, weight: PathWeight) -> Option<Path> {
+    let network = Network::new(inp);
+    let start = network.node(from)?;
+    let end = network.node(to)?;
+    let (costs, previous) = search(&network, start, &link_weights(inp, &network, weight), &FLOW);
+
+    build_path(&network, start, end, &costs, &previous)
+}
+
+/// Every node the water can come from to reach `node_id`, and the links it flows through.
+pub fn upstream_nodes(inp: &INP, node_id: &str) -> Upstream {
+    let network = Network::new(inp);
+    let start = match network.node(node_id) {
+        Some(start) => start,
+        None => return Upstream::default(),
+    };
+    let traversal = Traversal { upstream: true, ..FLOW };
+    let upstream = network.reachable(&[start], &traversal);
+
+    let mut links = vec![false; network.links.len()];
+    for node in (0..network.nodes.len()).filter(|&i| upstream[i]) {
+        for (link, _) in network.neighbours(node, &traversal) {
+            links[link] = true;
+        }
+    }
+
+    Upstream {
+        nodes: network.nodes.iter().enumerate()
+            .filter(|(i, _)| upstream[*i] && *i != start)
+            .map(|(_, node)| node.id.clone())
+            .collect(),
+        links: network.links.iter().enumerate()
+            .filter(|(i, _)| links[*i])
+            .map(|(_, link)| link.id.clone())
+            .collect(),
+    }
+}
+
+/// Shortest path, by pipe length, from every reservoir able to supply `node_id`.
+pub fn trace_to_reservoirs(inp: &INP, node_id: &str) -> Vec<Path> {
+    let network = Network::new(inp);
+    let end = match network.node(node_id) {
+        Some(end) => end,
+        None => return Vec::new(),
+    };
+    let weights = link_weights(inp, &network, PathWeight::Length);
+    let reservoirs = inp.reservoirs.iter().filter_map(|reservoir| network.node(&reservoir.id)).collect::<Vec<usize>>();
+
+    let mut paths = reservoirs.iter()
+        .filter_map(|&start| {
+            let (costs, previous) = search(&network, start, &weights, &FLOW);
+            build_path(&network, start, end, &costs, &previous)
+        })
+        .collect::<Vec<Path>>();
+    paths.sort_by(|a, b| a.cost.partial_cmp(&b.cost).unwrap_or(Ordering::Equal));
+    paths
+}
+
+#[cfg(test)]
+mod test {
+    use std::fs;
+    use super::{shortest_path, trace_to_reservoirs, upstream_nodes, PathWeight};
+    use crate::INP;
+    use crate::geometry::Geometry;
+
+    fn a_model() -> INP {
+        let input = r#"
+[JUNCTIONS]
+J1  10
+J2  10
+J3  10
+J4  10
+[RESERVOIRS]
+R1  100
+R2  100
+[PIPES]
+P1  R1  J1  100  300  130
+P2  J1  J2  100  50   130
+P3  J1  J3  150  300  130
+P4  J3  J2  150  300  130
+P5  J4  J2  10   300  130  0  CV
+P6  R2  J4  500  300  130
+[COORDINATES]
+R1  0  0
+J1  10  0
+J2  20  0
+[VERTICES]
+P2  15  5
+"#;
+        INP::read(input.to_string())
+    }
+
+    #[test]
+    fn find_the_shortest_path_by_length() {
+        let path = shortest_path(&a_model(), "R1", "J2", PathWeight::Length).unwrap();
+
+        assert_eq!(path.nodes, vec!["R1", "J1", "J2"]);
+        assert_eq!(path.links, vec!["P1", "P2"]);
+        assert_eq!(path.cost, 200.0);
+        assert_eq!(path.geometry(&Geometry::new(&a_model())), vec![
+            vec![(0.0, 0.0), (10.0, 0.0)],
+            vec![(10.0, 0.0), (15.0, 5.0), (20.0, 0.0)],
+        ]);
+    }
+
+    #[test]
+    fn find_the_shortest_path_by_resistance() {
+        let path = shortest_path(&a_model(), "R1", "J2", PathWeight::Resistance).unwrap();
+
+        assert_eq!(path.links, vec!["P1", "P3", "P4"]);
+    }
+
+    #[test]
+    fn weigh_resistance_with_the_viscosity_of_the_model() {
+        let input = "[JUNCTIONS]\nJ1  0\n[RESERVOIRS]\nR1  100\n[PIPES]\nP1  R1  J1  100  50  0.01\n[OPTIONS]\nUnits  LPS\nHeadloss  D-W\n";
+        let cost = |options: &str| shortest_path(&INP::read(format!("{}{}", input, options)), "R1", "J1", PathWeight::Resistance).unwrap().cost;
+
+        assert!(cost("Viscosity  100\n") > 2.0 * cost(""));
+    }
+
+    #[test]
+    fn water_does_not_flow_backwards_through_check_valves() {
+        assert!(shortest_path(&a_model(), "J2", "J4", PathWeight::Length).is_none());
+    }
+
+    #[test]
+    fn find_upstream_nodes() {
+        let upstream = upstream_nodes(&a_model(), "J4");
+
+        assert_eq!(upstream.nodes, vec!["R2"]);
+        assert_eq!(upstream.links, vec!["P6"]);
+    }
+
+    #[test]
+    fn find_upstream_links() {
+        let upstream = upstream_nodes(&a_model(), "J2");
+
+        assert_eq!(upstream.nodes, vec!["J1", "J3", "J4", "R1", "R2"]);
+        assert_eq!(upstream.links, vec!["P1", "P2", "P3", "P4", "P5", "P6"]);
+    }
+
+    #[test]
+    fn trace_a_node_back_to_its_reservoirs() {
+        let paths = trace_to_reservoirs(&a_model(), "J2");
+
+        assert_eq!(paths.len(), 2);
+        assert_eq!(paths[0].nodes, vec!["R1", "J1", "J2"]);
+        assert_eq!(paths[1].links, vec!["P6", "P5"]);
+    }
+
+    #[test]
+    fn trace_magnetic_island_junctions() {
+        let inp = INP::read(fs::read_to_string("tests/MagneticIslandEnhanced.inp").unwrap());
+
+        let paths = trace_to_reservoirs(&inp, "J_00002");
+
+        assert!(!paths.is_empty());
+        assert_eq!(paths[0].nodes.last().unwrap(), "J_00002");
+    }
+}
@@ -0,0 +1,841 @@
+use std::collections::VecDeque;
+use std::error::Error;
+use std::fmt;
+use serde::{Serialize, Deserialize};
+use crate::{INP, ToJson};
+use crate::hydraulics::{self, is_report_time, HydraulicError, HydraulicModel, LinkType, Solver};
+use crate::sections::MixingModel;
+use crate::units::{HeadlossFormula, Quantity};
+
+// Flows below this value, in m3/s, do not carry water between nodes.
+const Q_STAGNANT: f64 = 3.15e-7;
+// Molecular diffusivity of chlorine in water, in m2/s, the reference of the DIFFUSIVITY option.
+const CHLORINE_DIFFUSIVITY: f64 = 1.208e-9;
+const SECONDS_PER_DAY: f64 = 86400.0;
+const LITRES_PER_CUBIC_METRE: f64 = 1000.0;
+
+#[derive(Debug, PartialEq)]
+pub struct QualityError {
+    pub message: String,
+}
+
+impl fmt::Display for QualityError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.message)
+    }
+}
+
+impl Error for QualityError {}
+
+impl From<HydraulicError> for QualityError {
+    fn from(error: HydraulicError) -> Self {
+        QualityError { message: error.message }
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, PartialEq, Clone)]
+pub enum QualityAnalysis {
+    /// Hours since the water entered the network.
+    Age,
+    /// Percentage of the water that comes from a node.
+    Trace(String),
+    /// Concentration of a single reactive chemical.
+    Chemical { name: String, units: String },
+}
+
+impl QualityAnalysis {
+    /// Analysis of the QUALITY option, `None` when the model has no water quality.
+    pub fn from_inp(inp: &INP) -> Option<QualityAnalysis> {
+        let mut words = inp.option("QUALITY")?.split_whitespace();
+        let kind = words.next()?;
+        match kind.to_uppercase().as_str() {
+            "NONE" => None,
+            "AGE" => Some(QualityAnalysis::Age),
+            "TRACE" => Some(QualityAnalysis::Trace(words.next()?.to_string())),
+            _ => Some(QualityAnalysis::Chemical {
+                name: kind.to_string(),
+                units: words.next().unwrap_or("mg/L").to_string(),
+            }),
+        }
+    }
+
+    pub fn units(&self) -> &str {
+        match self {
+            QualityAnalysis::Age => "hours",
+            QualityAnalysis::Trace(_) => "percent",
+            QualityAnalysis::Chemical { units, .. } => units,
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, PartialEq, Clone)]
+pub struct QualityStep {
+    /// Seconds since the start of the simulation.
+    pub time: u64,
+    /// Quality of every node, in the order of `QualityResults::node_ids`.
+    pub nodes: Vec<f64>,
+    /// Average quality of every link, in the order of `QualityResults::link_ids`.
+    pub links: Vec<f64>,
+}
+
+/// Water quality at every reporting time of an extended-period simulation.
+#[derive(Serialize, Deserialize, Debug, PartialEq, Clone)]
+pub struct QualityResults {
+    pub analysis: QualityAnalysis,
+    pub units: String,
+    pub node_ids: Vec<String>,
+    pub link_ids: Vec<String>,
+    pub steps: Vec<QualityStep>,
+}
+
+impl QualityResults {
+    pub fn node_series(&self, id: &str) -> Vec<(u64, f64)> {
+        match self.node_ids.iter().position(|node| node == id) {
+            Some(i) => self.steps.iter().map(|step| (step.time, step.nodes[i])).collect(),
+            None => Vec::new(),
+        }
+    }
+
+    pub fn link_series(&self, id: &str) -> Vec<(u64, f64)> {
+        match self.link_ids.iter().position(|link| link == id) {
+            Some(k) => self.steps.iter().map(|step| (step.time, step.links[k])).collect(),
+            None => Vec::new(),
+        }
+    }
+}
+
+impl ToJson for QualityResults {}
+
+#[derive(Debug, PartialEq, Clone, Copy)]
+enum SourceType {
+    /// Concentration of the external inflow of the node.
+    Concen,
+    /// Mass per minute added to the water leaving the node.
+    Mass,
+    /// Minimum concentration of the water leaving the node.
+    Setpoint,
+    /// Concentration added to the water leaving the node.
+    FlowPaced,
+}
+
+#[derive(Debug, Clone, Copy)]
+struct ModelSource {
+    source_type: SourceType,
+    strength: f64,
+    pattern: Option<usize>,
+}
+
+/// Parcel of water of uniform quality.
+#[derive(Debug, Clone, Copy)]
+struct Segment {
+    volume: f64,
+    quality: f64,
+}
+
+/// Removes `volume` of water from one end of a list of segments. Returns the mass
+/// removed and the volume that the segments could not provide.
+fn take(segments: &mut VecDeque<Segment>, volume: f64, front: bool) -> (f64, f64) {
+    let mut remaining = volume;
+    let mut mass = 0.0;
+    while remaining > 0.0 {
+        let segment = if front { segments.front_mut() } else { segments.back_mut() };
+        let Some(segment) = segment else { break };
+        let taken = segment.volume.min(remaining);
+        mass += taken * segment.quality;
+        remaining -= taken;
+        segment.volume -= taken;
+        if segment.volume <= 0.0 {
+            if front { segments.pop_front(); } else { segments.pop_back(); }
+        }
+    }
+    (mass, remaining)
+}
+
+/// Adds a segment at one end of a list, merging it with the segment already there
+/// when their qualities differ by less than `tolerance`.
+fn add(segments: &mut VecDeque<Segment>, volume: f64, quality: f64, back: bool, tolerance: f64) {
+    let last = if back { segments.back_mut() } else { segments.front_mut() };
+    match last {
+        Some(last) if (last.quality - quality).abs() < tolerance => {
+            last.quality = (last.quality * last.volume + quality * volume) / (last.volume + volume);
+            last.volume += volume;
+        },
+        _ if back => segments.push_back(Segment { volume, quality }),
+        _ => segments.push_front(Segment { volume, quality }),
+    }
+}
+
+/// Water of a tank. Mixed tanks hold a single segment and 2COMP tanks their mixing
+/// and stagnant zones; FIFO and LIFO tanks stack segments from the bottom (front).
+struct TankWater {
+    node: usize,
+    model: MixingModel,
+    mixing_volume: f64,
+    bulk: f64,
+    segments: VecDeque<Segment>,
+}
+
+impl TankWater {
+    fn quality(&self) -> f64 {
+        let segment = match self.model {
+            MixingModel::Lifo => self.segments.back(),
+            _ => self.segments.front(),
+        };
+        segment.map(|segment| segment.quality).unwrap_or(0.0)
+    }
+
+    /// Mixes `volume_in` of water carrying `mass_in` into the tank while `volume_out`
+    /// leaves it. Returns the quality of the water leaving the tank.
+    fn mix(&mut self, volume_in: f64, mass_in: f64, volume_out: f64, tolerance: f64) -> f64 {
+        let quality_in = if volume_in > 0.0 { mass_in / volume_in } else { 0.0 };
+        let net = volume_in - volume_out;
+        match self.model {
+            MixingModel::Mixed => {
+                let water = &mut self.segments[0];
+                if volume_in > 0.0 {
+                    water.quality = (water.quality * water.volume + mass_in) / (water.volume + volume_in);
+                }
+                water.volume = (water.volume + net).max(0.0);
+                water.quality
+            },
+            MixingModel::TwoCompartment => {
+                let (mut mixed, mut stagnant) = (self.segments[0], self.segments[1]);
+                if net > 0.0 {
+                    // The mixing zone overflows into the stagnant zone once full.
+                    let overflow = (mixed.volume + net - self.mixing_volume).max(0.0);
+                    if volume_in > 0.0 {
+                        mixed.quality = (mixed.quality * mixed.volume + mass_in) / (mixed.volume + volume_in);
+                    }
+                    if overflow > 0.0 {
+                        stagnant.quality = (stagnant.quality * stagnant.volume + mixed.quality * overflow) / (stagnant.volume + overflow);
+                    }
+                    mixed.volume += net - overflow;
+                    stagnant.volume += overflow;
+                } else {
+                    // The stagnant zone drains first into the mixing zone.
+                    let drawn = stagnant.volume.min(-net);
+                    if volume_in + drawn > 0.0 {
+                        mixed.quality = (mixed.quality * mixed.volume + mass_in + stagnant.quality * drawn) / (mixed.volume + volume_in + drawn);
+                    }
+                    stagnant.volume -= drawn;
+                    mixed.volume = (mixed.volume + net + drawn).max(0.0);
+                }
+                self.segments[0] = mixed;
+                self.segments[1] = stagnant;
+                mixed.quality
+            },
+            MixingModel::Fifo => {
+                if volume_in > 0.0 {
+                    add(&mut self.segments, volume_in, quality_in, true, tolerance);
+                }
+                let (mass, missing) = take(&mut self.segments, volume_out, true);
+                if volume_out > missing { mass / (volume_out - missing) } else { self.quality() }
+            },
+            MixingModel::Lifo => {
+                if net >= 0.0 {
+                    // Inflow passes through the top of the tank, the rest stays on top.
+                    if net > 0.0 {
+                        add(&mut self.segments, net, quality_in, true, tolerance);
+                    }
+                    if volume_in > 0.0 { quality_in } else { self.quality() }
+                } else {
+                    let (mass, missing) = take(&mut self.segments, -net, false);
+                    let volume = volume_in - net - missing;
+                    if volume > 0.0 { (mass_in + mass) / volume } else { self.quality() }
+                }
+            },
+        }
+    }
+
+    fn react(&mut self, dt: f64, rate: impl Fn(f64, f64) -> f64) {
+        for segment in self.segments.iter_mut() {
+            segment.quality = rate(segment.quality, self.bulk) * dt + segment.quality;
+            segment.quality = segment.quality.max(0.0);
+        }
+    }
+}
+
+/// Reaction rate in the bulk flow, in concentration per second, of order `order`
+/// towards the limiting concentration `limit`.
+fn bulk_rate(quality: f64, kb: f64, order: f64, limit: f64) -> f64 {
+    if kb == 0.0 {
+        return 0.0;
+    }
+    let c = if order == 0.0 {
+        1.0
+    } else if order < 0.0 {
+        // Michaelis-Menten kinetics
+        let mut c1 = limit + kb.signum() * quality;
+        if c1.abs() < 1e-6 {
+            c1 = c1.signum() * 1e-6;
+        }
+        quality / c1
+    } else {
+        let c1 = if limit == 0.0 { quality } else { (kb.signum() * (limit - quality)).max(0.0) };
+        if order == 1.0 { c1 } else { c1 * quality.max(0.0).powf(order - 1.0) }
+    };
+    kb * c.max(0.0)
+}
+
+/// Reaction rate at the pipe wall, in concentration per second, limited by the
+/// mass transfer coefficient `kf` from the bulk flow to the wall.
+fn wall_rate(quality: f64, diameter: f64, kw: f64, kf: f64, order: f64) -> f64 {
+    if kw == 0.0 || diameter == 0.0 {
+        return 0.0;
+    }
+    if order == 0.0 {
+        let transfer = if kf.is_finite() { quality * kf } else { f64::INFINITY };
+        kw.signum() * kw.abs().min(transfer) * 4.0 / diameter
+    } else {
+        let k = if kf.is_finite() { kw * kf / (kf + kw.abs()) } else { kw };
+        4.0 / diameter * k * quality
+    }
+}
+
+/// Mass transfer coefficient, in m/s, between the bulk flow and the wall of a pipe.
+fn mass_transfer(flow: f64, diameter: f64, length: f64, viscosity: f64, diffusivity: f64) -> f64 {
+    if diffusivity == 0.0 || diameter == 0.0 {
+        return f64::INFINITY;
+    }
+    let schmidt = viscosity / diffusivity;
+    let reynolds = flow.abs() / (std::f64::consts::PI * diameter * diameter / 4.0) * diameter / viscosity;
+    let sherwood = if reynolds < 1.0 {
+        2.0
+    } else if reynolds >= 2300.0 {
+        0.0149 * reynolds.powf(0.88) * schmidt.powf(1.0 / 3.0)
+    } else {
+        let y = diameter / length * reynolds * schmidt;
+        3.65 + 0.0668 * y / (1.0 + 0.04 * y.powf(2.0 / 3.0))
+    };
+    sherwood * diffusivity / diameter
+}
+
+/// Lagrangian transport of water quality: every link holds segments of water ordered
+/// from its end node (front) to its start node (back).
+struct Transport<'a> {
+    model: &'a HydraulicModel,
+    analysis: &'a QualityAnalysis,
+    trace_node: Option<usize>,
+    initial: Vec<f64>,
+    quality: Vec<f64>,
+    segments: Vec<VecDeque<Segment>>,
+    tanks: Vec<TankWater>,
+    tank_of: Vec<Option<usize>>,
+    sources: Vec<Option<ModelSource>>,
+    bulk: Vec<f64>,
+    wall: Vec<f64>,
+    bulk_order: f64,
+    wall_order: f64,
+    tank_order: f64,
+    limit: f64,
+    diffusivity: f64,
+    tolerance: f64,
+}
+
+/// Coefficients of the [REACTIONS] lines that name a pipe or tank, e.g. `BULK P1 -0.5`.
+fn object_coefficients(inp: &INP, key: &str) -> Vec<(String, f64)> {
+    inp.reactions.iter()
+        .filter(|setting| setting.key == key)
+        .filter_map(|setting| {
+            let mut words = setting.value.split_whitespace();
+            let id = words.next()?.to_string();
+            Some((id, words.next()?.parse::<f64>().ok()?))
+        })
+        .collect()
+}
+
+impl<'a> Transport<'a> {
+    fn new(inp: &INP, model: &'a HydraulicModel, analysis: &'a QualityAnalysis) -> Result<Transport<'a>, QualityError> {
+        let network = &model.network;
+        let trace_node = match analysis {
+            QualityAnalysis::Trace(id) => Some(network.node(id).ok_or_else(|| QualityError { message: format!("Trace node {} not found", id) })?),
+            _ => None,
+        };
+
+        let mut initial = vec![0.0; network.nodes.len()];
+        if trace_node.is_none() {
+            for quality in inp.quality.iter() {
+                if let Some(node) = network.node(&quality.nodeid) {
+                    initial[node] = quality.initqual;
+                }
+            }
+        }
+        if let Some(node) = trace_node {
+            initial[node] = 100.0;
+        }
+
+        let mut sources = vec![None; network.nodes.len()];
+        if let QualityAnalysis::Chemical { .. } = analysis {
+            for source in inp.sources.iter() {
+                let source_type = match source.source_type.to_uppercase().as_str() {
+                    "CONCEN" => SourceType::Concen,
+                    "MASS" => SourceType::Mass,
+                    "SETPOINT" => SourceType::Setpoint,
+                    "FLOWPACED" => SourceType::FlowPaced,
+                    _ => return Err(QualityError { message: format!("Invalid source type {}", source.source_type) }),
+                };
+                let pattern = match &source.pattern {
+                    Some(id) => Some(model.pattern_ids.iter().position(|pattern| pattern == id)
+                        .ok_or_else(|| QualityError { message: format!("Pattern {} not found", id) })?),
+                    None => None,
+                };
+                if let Some(node) = network.node(&source.node) {
+                    sources[node] = Some(ModelSource { source_type, strength: source.strength, pattern });
+                }
+            }
+        }
+
+        let bulk_order = inp.reaction("ORDER BULK").unwrap_or(1.0);
+        let wall_order = inp.reaction("ORDER WALL").unwrap_or(1.0);
+        let tank_order = inp.reaction("ORDER TANK").unwrap_or(1.0);
+        let global_bulk = inp.reaction("GLOBAL BULK").unwrap_or(0.0) / SECONDS_PER_DAY;
+        let global_wall = inp.reaction("GLOBAL WALL").unwrap_or(0.0);
+        let correlation = inp.reaction("ROUGHNESS CORRELATION").unwrap_or(0.0);
+        let length = Quantity::Length.si_factor(model.flow_units, model.formula);
+        // Wall coefficients are in length/day, or mass/area/day for zero-order reactions.
+        let wall_factor = if wall_order == 0.0 { 1.0 / (length * length * LITRES_PER_CUBIC_METRE) } else { length } / SECONDS_PER_DAY;
+
+        let mut bulk = vec![global_bulk; model.links.len()];
+        let mut wall = vec![global_wall * wall_factor; model.links.len()];
+        if correlation != 0.0 {
+            for pipe in inp.pipes.iter() {
+                let Some(k) = network.link(&pipe.id) else { continue };
+                let coefficient = match model.formula {
+                    HeadlossFormula::HazenWilliams => correlation / pipe.roughness,
+                    HeadlossFormula::DarcyWeisbach => {
+                        let roughness = pipe.roughness * Quantity::Roughness.si_factor(model.flow_units, model.formula);
+                        correlation / (roughness / model.links[k].diameter).ln().abs()
+                    },
+                    HeadlossFormula::ChezyManning => correlation * pipe.roughness,
+                };
+                wall[k] = coefficient * wall_factor;
+            }
+        }
+        for (id, value) in object_coefficients(inp, "BULK") {
+            if let Some(k) = network.link(&id) {
+                bulk[k] = value / SECONDS_PER_DAY;
+            }
+        }
+        for (id, value) in object_coefficients(inp, "WALL") {
+            if let Some(k) = network.link(&id) {
+                wall[k] = value * wall_factor;
+            }
+        }
+
+        let tank_bulk = object_coefficients(inp, "TANK");
+        let mut tank_of = vec![None; network.nodes.len()];
+        let mut tanks = Vec::new();
+        for (t, tank) in model.tanks.iter().enumerate() {
+            let id = &network.nodes[tank.node].id;
+            let mixing = inp.mixing.iter().find(|mixing| &mixing.tank_id == id);
+            let model = mixing.map(|mixing| mixing.model).unwrap_or(MixingModel::Mixed);
+            let volume = tank.volume(tank.init_level);
+            let mixing_volume = mixing.map(|mixing| mixing.fraction).unwrap_or(1.0) * tank.volume(tank.max_level);
+            let quality = initial[tank.node];
+            let mut segments = VecDeque::from([Segment { volume, quality }]);
+            if model == MixingModel::TwoCompartment {
+                segments = VecDeque::from([
+                    Segment { volume: volume.min(mixing_volume), quality },
+                    Segment { volume: (volume - mixing_volume).max(0.0), quality },
+                ]);
+            }
+            let bulk = tank_bulk.iter().find(|(tank, _)| tank == id).map(|(_, value)| value / SECONDS_PER_DAY).unwrap_or(global_bulk);
+            tank_of[tank.node] = Some(t);
+            tanks.push(TankWater { node: tank.node, model, mixing_volume, bulk, segments });
+        }
+
+        let segments = model.links.iter()
+            .map(|link| {
+                let volume = match link.link_type {
+                    LinkType::Pipe | LinkType::CheckValve => link.area() * link.length,
+                    _ => 0.0,
+                };
+                if volume > 0.0 { VecDeque::from([Segment { volume, quality: initial[link.end] }]) } else { VecDeque::new() }
+            })
+            .collect();
+
+        Ok(Transport {
+            model,
+            analysis,
+            trace_node,
+            quality: initial.clone(),
+            initial,
+            segments,
+            tanks,
+            tank_of,
+            sources,
+            bulk,
+            wall,
+            bulk_order,
+            wall_order,
+            tank_order,
+            limit: inp.reaction("LIMITING POTENTIAL").unwrap_or(0.0),
+            diffusivity: inp.option_number("DIFFUSIVITY").unwrap_or(1.0) * CHLORINE_DIFFUSIVITY,
+            tolerance: inp.option_number("TOLERANCE").unwrap_or(0.01),
+        })
+    }
+
+    fn link_quality(&self, k: usize) -> f64 {
+        let volume = self.segments[k].iter().map(|segment| segment.volume).sum::<f64>();
+        if volume > 0.0 {
+            self.segments[k].iter().map(|segment| segment.volume * segment.quality).sum::<f64>() / volume
+        } else {
+            let link = &self.model.links[k];
+            (self.quality[link.start] + self.quality[link.end]) / 2.0
+        }
+    }
+
+    fn step(&self, time: u64) -> QualityStep {
+        QualityStep {
+            time,
+            nodes: self.quality.clone(),
+            links: (0..self.segments.len()).map(|k| self.link_quality(k)).collect(),
+        }
+    }
+
+    fn source_strength(&self, source: &ModelSource, period: usize) -> f64 {
+        source.strength * source.pattern.map(|pattern| self.model.multiplier(pattern, period)).unwrap_or(1.0)
+    }
+
+    /// Quality of the water entering the network at a node: reservoirs and negative demands.
+    fn external_quality(&self, node: usize, period: usize) -> f64 {
+        match (self.analysis, &self.sources[node]) {
+            (QualityAnalysis::Chemical { .. }, Some(source)) if source.source_type == SourceType::Concen => self.source_strength(source, period),
+            (QualityAnalysis::Chemical { .. }, _) if !self.model.is_junction(node) => self.initial[node],
+            _ if Some(node) == self.trace_node => 100.0,
+            _ => 0.0,
+        }
+    }
+
+    /// Quality of the water leaving a node once its source, if any, has acted on it.
+    fn source_quality(&self, node: usize, quality: f64, volume_out: f64, dt: f64, period: usize) -> f64 {
+        let Some(source) = &self.sources[node] else { return quality };
+        let strength = self.source_strength(source, period);
+        match source.source_type {
+            SourceType::Concen => quality,
+            SourceType::Mass if volume_out > 0.0 => quality + strength * dt / 60.0 / (volume_out * LITRES_PER_CUBIC_METRE),
+            SourceType::Mass => quality,
+            SourceType::Setpoint => quality.max(strength),
+            SourceType::FlowPaced => quality + strength,
+        }
+    }
+
+    /// Nodes sorted from upstream to downstream, so that water crosses short links
+    /// within a single step. Loops of flow are broken at an arbitrary node.
+    fn flow_order(&self, flows: &[f64]) -> Vec<usize> {
+        let network = &self.model.network;
+        let links = &self.model.links;
+        let count = network.nodes.len();
+        let ends = |k: usize| if flows[k] > 0.0 { (links[k].start, links[k].end) } else { (links[k].end, links[k].start) };
+        let mut inflows = vec![0; count];
+        for k in 0..links.len() {
+            if flows[k] != 0.0 {
+                inflows[ends(k).1] += 1;
+            }
+        }
+        let mut queued = vec![false; count];
+        let mut queue = VecDeque::new();
+        for node in 0..count {
+            if inflows[node] == 0 {
+                queued[node] = true;
+                queue.push_back(node);
+            }
+        }
+        let mut order = Vec::with_capacity(count);
+        while order.len() < count {
+            if queue.is_empty() {
+                let node = (0..count).filter(|&node| !queued[node]).min_by_key(|&node| inflows[node]).unwrap();
+                queued[node] = true;
+                queue.push_back(node);
+            }
+            while let Some(node) = queue.pop_front() {
+                order.push(node);
+                for &k in network.incident_links(node) {
+                    let (upstream, downstream) = ends(k);
+                    if flows[k] != 0.0 && upstream == node && !queued[downstream] {
+                        inflows[downstream] -= 1;
+                        if inflows[downstream] == 0 {
+                            queued[downstream] = true;
+                            queue.push_back(downstream);
+                        }
+                    }
+                }
+            }
+        }
+        order
+    }
+
+    /// Moves water along the links for `dt` seconds, mixing it at the nodes.
+    fn transport(&mut self, flows: &[f64], outflows: &[f64], order: &[usize], dt: f64, period: usize) {
+        let model = self.model;
+        for &node in order {
+            let mut volume_in = 0.0;
+            let mut mass_in = 0.0;
+            let mut volume_out = outflows[node].max(0.0) * dt;
+            for &k in model.network.incident_links(node) {
+                let link = &model.links[k];
+                if flows[k] == 0.0 || link.start == link.end {
+                    continue;
+                }
+                let volume = flows[k].abs() * dt;
+                let forward = flows[k] > 0.0;
+                if (if forward { link.end } else { link.start }) == node {
+                    let upstream = if forward { link.start } else { link.end };
+                    let (mass, missing) = take(&mut self.segments[k], volume, forward);
+                    mass_in += mass + missing * self.quality[upstream];
+                    volume_in += volume;
+                } else {
+                    volume_out += volume;
+                }
+            }
+            if outflows[node] < 0.0 {
+                let volume = -outflows[node] * dt;
+                volume_in += volume;
+                mass_in += volume * self.external_quality(node, period);
+            }
+
+            let quality = match self.tank_of[node] {
+                Some(t) => self.tanks[t].mix(volume_in, mass_in, volume_out, self.tolerance),
+                None if !model.is_junction(node) => self.external_quality(node, period),
+                None if volume_in > 0.0 => mass_in / volume_in,
+                None => self.quality[node],
+            };
+            let quality = if Some(node) == self.trace_node { 100.0 } else { quality };
+            let quality = self.source_quality(node, quality, volume_out, dt, period);
+            self.quality[node] = quality;
+
+            for &k in model.network.incident_links(node) {
+                let link = &model.links[k];
+                let forward = flows[k] > 0.0;
+                if flows[k] != 0.0 && link.start != link.end && (if forward { link.start } else { link.end }) == node {
+                    add(&mut self.segments[k], flows[k].abs() * dt, quality, forward, self.tolerance);
+                }
+            }
+        }
+    }
+
+    /// Ages the water or lets the chemical react for `dt` seconds.
+    fn react(&mut self, dt: f64, transfer: &[f64]) {
+        match self.analysis {
+            QualityAnalysis::Age => {
+                let hours = dt / 3600.0;
+                let segments = self.segments.iter_mut().flatten().chain(self.tanks.iter_mut().flat_map(|tank| tank.segments.iter_mut()));
+                for segment in segments {
+                    segment.quality += hours;
+                }
+                for tank in self.tanks.iter() {
+                    self.quality[tank.node] = tank.quality();
+                }
+            },
+            QualityAnalysis::Trace(_) => {},
+            QualityAnalysis::Chemical { .. } => {
+                for (k, segments) in self.segments.iter_mut().enumerate() {
+                    let link = &self.model.links[k];
+                    for segment in segments.iter_mut() {
+                        let rate = bulk_rate(segment.quality, self.bulk[k], self.bulk_order, self.limit)
+                            + wall_rate(segment.quality, link.diameter, self.wall[k], transfer[k], self.wall_order);
+                        segment.quality = (segment.quality + rate * dt).max(0.0);
+                    }
+                }
+                let (order, limit) = (self.tank_order, self.limit);
+                for tank in self.tanks.iter_mut() {
+                    tank.react(dt, |quality, kb| bulk_rate(quality, kb, order, limit));
+                    self.quality[tank.node] = tank.quality();
+                }
+            },
+        }
+    }
+
+    /// Advances the water quality over a hydraulic step of `step` seconds starting at
+    /// `time`, in quality steps of at most `quality_step` seconds.
+    fn advance(&mut self, solver: &Solver, time: u64, step: u64, quality_step: u64) {
+        let model = self.model;
+        let flows = solver.flows.iter()
+            .map(|&flow| if flow.abs() < Q_STAGNANT { 0.0 } else { flow })
+            .collect::<Vec<f64>>();
+        let outflows = (0..model.network.nodes.len())
+            .map(|node| if model.is_junction(node) { solver.demand_flows[node] + solver.emitter_flows[node] } else { 0.0 })
+            .collect::<Vec<f64>>();
+        let transfer = model.links.iter().zip(flows.iter())
+            .map(|(link, &flow)| mass_transfer(flow, link.diameter, link.length, model.options.viscosity, self.diffusivity))
+            .collect::<Vec<f64>>();
+        let order = self.flow_order(&flows);
+        let period = model.period(time);
+
+        let mut elapsed = 0;
+        while elapsed < step {
+            let dt = quality_step.min(step - elapsed);
+            self.react(dt as f64, &transfer);
+            self.transport(&flows, &outflows, &order, dt as f64, period);
+            elapsed += dt;
+        }
+    }
+}
+
+/// Water quality over the extended-period simulation of an INP: water age, the
+/// percentage of water coming from a node, or the transport and reaction of a chemical.
+pub fn simulate_quality(inp: &INP, analysis: &QualityAnalysis) -> Result<QualityResults, QualityError> {
+    let model = HydraulicModel::new(inp)?;
+    let mut transport = Transport::new(inp, &model, analysis)?;
+    let quality_step = inp.time("QUALITY TIMESTEP").unwrap_or(300).min(model.times.hydraulic_step).max(1);
+    let mut steps = Vec::new();
+    hydraulics::run(&model, |solver, _, time, step| {
+        if is_report_time(&model.times, time) {
+            steps.push(transport.step(time));
+        }
+        transport.advance(solver, time, step, quality_step);
+    })?;
+
+    Ok(QualityResults {
+        analysis: analysis.clone(),
+        units: analysis.units().to_string(),
+        node_ids: model.network.nodes.iter().map(|node| node.id.clone()).collect(),
+        link_ids: model.network.links.iter().map(|link| link.id.clone()).collect(),
+        steps,
+    })
+}
+
+#[cfg(test)]
+mod test {
+    use std::fs;
+    use super::{simulate_quality, QualityAnalysis};
+    use crate::INP;
+
+    // 50 L/s through 1000 m of 300 mm pipe: a travel time of 1413.7 s.
+    fn a_model() -> String {
+        r#"
+[JUNCTIONS]
+J1  0  50
+[RESERVOIRS]
+R1  100
+[PIPES]
+P1  R1  J1  1000  300  130
+[TIMES]
+Duration  2:00
+Hydraulic Timestep  1:00
+Quality Timestep  0:01
+Report Timestep  1:00
+[OPTIONS]
+Units  LPS
+"#.to_string()
+    }
+
+    const TRAVEL_HOURS: f64 = 0.0706858 * 1000.0 / 0.05 / 3600.0;
+
+    fn chemical() -> QualityAnalysis {
+        QualityAnalysis::Chemical { name: "Chlorine".to_string(), units: "mg/L".to_string() }
+    }
+
+    #[test]
+    fn read_the_analysis_from_the_options() {
+        let analysis = |option: &str| QualityAnalysis::from_inp(&INP::read(format!("[OPTIONS]\nQuality  {}\n", option)));
+
+        assert_eq!(analysis("None mg/L"), None);
+        assert_eq!(analysis("Age"), Some(QualityAnalysis::Age));
+        assert_eq!(analysis("Trace R1"), Some(QualityAnalysis::Trace("R1".to_string())));
+        assert_eq!(analysis("Chlorine mg/L"), Some(chemical()));
+    }
+
+    #[test]
+    fn water_ages_with_the_travel_time() {
+        let results = simulate_quality(&INP::read(a_model()), &QualityAnalysis::Age).unwrap();
+
+        let age = results.node_series("J1");
+        assert_eq!(age.len(), 3);
+        assert_eq!(age[0].1, 0.0);
+        assert!((age[2].1 - TRAVEL_HOURS).abs() < 0.02, "{} {}", age[2].1, TRAVEL_HOURS);
+        assert_eq!(results.node_series("R1")[2].1, 0.0);
+    }
+
+    #[test]
+    fn trace_the_share_of_water_from_a_node() {
+        let input = a_model().replace("[PIPES]\n", "R2  100\n[PIPES]\nP2  R2  J1  500  200  100\n");
+        let inp = INP::read(input);
+        let results = simulate_quality(&inp, &QualityAnalysis::Trace("R1".to_string())).unwrap();
+
+        let flows = crate::hydraulics::solve(&inp).unwrap();
+        let share = 100.0 * flows.link("P1").unwrap().flow / 50.0;
+        assert!((results.node_series("J1")[2].1 - share).abs() < 0.1, "{} {}", results.node_series("J1")[2].1, share);
+        assert_eq!(results.node_series("R1")[2].1, 100.0);
+        assert!(simulate_quality(&inp, &QualityAnalysis::Trace("X".to_string())).is_err());
+    }
+
+    #[test]
+    fn first_order_bulk_decay() {
+        // Segments only merge within the tolerance, which would otherwise delay the decay.
+        let input = a_model() + "Tolerance  0.0001\n[QUALITY]\nR1  1\n[REACTIONS]\nGlobal Bulk  -1\n";
+        let results = simulate_quality(&INP::read(input), &chemical()).unwrap();
+
+        let expected = (-TRAVEL_HOURS / 24.0).exp();
+        assert!((results.node_series("J1")[2].1 - expected).abs() < 1e-3, "{} {}", results.node_series("J1")[2].1, expected);
+        assert!((results.link_series("P1")[2].1 - (1.0 + expected) / 2.0).abs() < 1e-3);
+    }
+
+    #[test]
+    fn sources_inject_the_chemical() {
+        let input = a_model() + "[SOURCES]\nR1  MASS  60\n";
+        let results = simulate_quality(&INP::read(input), &chemical()).unwrap();
+
+        // 60 mg/min in 50 L/s
+        assert!((results.node_series("J1")[2].1 - 0.02).abs() < 1e-6);
+
+        let input = a_model() + "[SOURCES]\nR1  CONCEN  2  Pat1\n[PATTERNS]\nPat1  0.5\n";
+        let results = simulate_quality(&INP::read(input), &chemical()).unwrap();
+
+        assert!((results.node_series("J1")[2].1 - 1.0).abs() < 1e-6);
+    }
+
+    fn a_tank_model(mixing: &str) -> String {
+        format!(r#"
+[RESERVOIRS]
+R1  20
+[TANKS]
+T1  0  5  0  20  10  0
+[PIPES]
+P1  R1  T1  10  300  130
+[QUALITY]
+R1  1
+[MIXING]
+T1  {}
+[TIMES]
+Duration  1:00
+Hydraulic Timestep  1:00
+Quality Timestep  0:01
+[OPTIONS]
+Units  LPS
+"#, mixing)
+    }
+
+    #[test]
+    fn tanks_mix_their_inflow() {
+        let inp = INP::read(a_tank_model("MIXED"));
+        let results = simulate_quality(&inp, &chemical()).unwrap();
+
+        let tank = results.node_series("T1");
+        let simulation = crate::hydraulics::simulate(&inp).unwrap();
+        let volume = |level: f64| std::f64::consts::PI * 25.0 * level;
+        let levels = simulation.node_series("T1");
+        let expected = 1.0 - volume(levels[0].1.pressure) / volume(levels[1].1.pressure);
+        assert!((tank[1].1 - expected).abs() < 0.01, "{} {}", tank[1].1, expected);
+
+        let results = simulate_quality(&INP::read(a_tank_model("FIFO")), &chemical()).unwrap();
+
+        assert_eq!(results.node_series("T1")[1].1, 0.0);
+
+        let results = simulate_quality(&INP::read(a_tank_model("LIFO")), &chemical()).unwrap();
+
+        assert!((results.node_series("T1")[1].1 - 1.0).abs() < 1e-2);
+    }
+
+    #[test]
+    fn simulate_the_age_of_magnetic_island() {
+        let inp = INP::read(fs::read_to_string("tests/MagneticIslandEnhanced.inp").unwrap());
+
+        let results = simulate_quality(&inp, &QualityAnalysis::Age).unwrap();
+
+        assert_eq!(results.steps.len(), 97);
+        let last = results.steps.last().unwrap();
+        assert!(last.nodes.iter().chain(last.links.iter()).all(|age| age.is_finite() && *age >= 0.0 && *age <= 24.0 + 1e-6));
+        assert!(last.nodes.iter().any(|age| *age > 1.0));
+    }
+}
@@ -0,0 +1,442 @@
+use std::collections::HashMap;
+use std::error::Error;
+use std::fmt;
+use serde_json::Value;
+use crate::geometry::{Geometry, Point};
+use crate::shapefile::records;
+use crate::INP;
+
+#[derive(Debug, PartialEq, Clone)]
+pub struct RenderError {
+    pub message: String,
+}
+
+impl fmt::Display for RenderError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.message)
+    }
+}
+
+impl Error for RenderError {}
+
+pub type Color = (u8, u8, u8);
+
+/// Colours of the legend classes, from the lowest values to the highest, as in EPANET.
+pub const CLASS_COLORS: [Color; 5] = [(0, 0, 255), (0, 255, 255), (0, 255, 0), (255, 255, 0), (255, 0, 0)];
+const LINK_COLOR: Color = (91, 103, 112);
+const NODE_COLOR: Color = (91, 103, 112);
+const OUTLINE_COLOR: Color = (33, 37, 41);
+const TEXT_COLOR: Color = (33, 37, 41);
+
+/// Numeric values of nodes or links split into the five classes of a legend at equal
+/// intervals between their minimum and maximum.
+#[derive(Debug, PartialEq, Clone)]
+pub struct ColorScale {
+    pub title: String,
+    pub values: HashMap<String, f64>,
+    pub breaks: [f64; 4],
+}
+
+impl ColorScale {
+    pub fn new(title: &str, values: HashMap<String, f64>) -> ColorScale {
+        let min = values.values().copied().fold(f64::INFINITY, f64::min);
+        let max = values.values().copied().fold(f64::NEG_INFINITY, f64::max);
+        let (min, max) = if min.is_finite() { (min, max) } else { (0.0, 0.0) };
+        let step = (max - min) / 5.0;
+        ColorScale {
+            title: title.to_string(),
+            values,
+            breaks: [min + step, min + 2.0 * step, min + 3.0 * step, min + 4.0 * step],
+        }
+    }
+
+    /// Colour of an element, None when it has no value.
+    pub fn color(&self, id: &str) -> Option<Color> {
+        let value = self.values.get(id)?;
+        let class = self.breaks.iter().filter(|&limit| value >= limit).count();
+        Some(CLASS_COLORS[class])
+    }
+
+    fn labels(&self) -> Vec<String> {
+        let [b1, b2, b3, b4] = self.breaks;
+        vec![
+            format!("< {}", format_value(b1)),
+            format!("{} - {}", format_value(b1), format_value(b2)),
+            format!("{} - {}", format_value(b2), format_value(b3)),
+            format!("{} - {}", format_value(b3), format_value(b4)),
+            format!(">= {}", format_value(b4)),
+        ]
+    }
+}
+
+fn format_value(value: f64) -> String {
+    if value.abs() >= 100.0 { format!("{:.0}", value) } else { format!("{:.2}", value) }
+}
+
+/// Values of a numeric property of the pipes, pumps and valves, e.g. `diameter`.
+pub fn link_attribute(inp: &INP, attribute: &str) -> HashMap<String, f64> {
+    let mut links = records(&inp.pipes);
+    links.extend(records(&inp.pumps));
+    links.extend(records(&inp.valves));
+    attribute_values(links, attribute)
+}
+
+/// Values of a numeric property of the junctions, reservoirs and tanks, e.g. `elevation`.
+pub fn node_attribute(inp: &INP, attribute: &str) -> HashMap<String, f64> {
+    let mut nodes = records(&inp.junctions);
+    nodes.extend(records(&inp.reservoirs));
+    nodes.extend(records(&inp.tanks));
+    attribute_values(nodes, attribute)
+}
+
+fn attribute_values(items: Vec<serde_json::Map<String, Value>>, attribute: &str) -> HashMap<String, f64> {
+    items.iter()
+        .filter_map(|item| Some((item.get("id")?.as_str()?.to_string(), item.get(attribute)?.as_f64()?)))
+        .collect()
+}
+
+#[derive(Debug, PartialEq, Clone)]
+pub struct RenderOptions {
+    pub width: u32,
+    pub height: u32,
+    /// Colours of the links, grey when None.
+    pub link_colors: Option<ColorScale>,
+    /// Colours of the junctions, which are only drawn when coloured.
+    pub node_colors: Option<ColorScale>,
+}
+
+impl Default for RenderOptions {
+    fn default() -> RenderOptions {
+        RenderOptions { width: 800, height: 600, link_colors: None, node_colors: None }
+    }
+}
+
+/// Something to draw, in pixels from the top left corner.
+#[derive(Debug, PartialEq, Clone)]
+enum Item {
+    Polyline { points: Vec<Point>, color: Color, width: f64 },
+    Polygon { points: Vec<Point>, fill: Color },
+    Circle { center: Point, radius: f64, fill: Color },
+    Text { at: Point, text: String, size: f64 },
+}
+
+/// The map as a list of items, so that every output draws the same thing.
+fn scene(inp: &INP, options: &RenderOptions) -> Result<Vec<Item>, RenderError> {
+    let geometry = Geometry::new(inp);
+    let (min_x, min_y, max_x, max_y) = geometry.bounds()
+        .ok_or_else(|| RenderError { message: "No coordinates to draw".to_string() })?;
+
+    let (width, height) = (options.width as f64, options.height as f64);
+    let margin = 0.05 * width.min(height);
+    let scale = ((width - 2.0 * margin) / (max_x - min_x).max(f64::EPSILON))
+        .min((height - 2.0 * margin) / (max_y - min_y).max(f64::EPSILON));
+    // Centre the network and flip the y axis, which points down in images.
+    let offset_x = (width - scale * (max_x - min_x)) / 2.0;
+    let offset_y = (height - scale * (max_y - min_y)) / 2.0;
+    let to_pixel = |(x, y): Point| (offset_x + scale * (x - min_x), height - offset_y - scale * (y - min_y));
+
+    let size = (width.min(height) / 120.0).max(3.0);
+    let line_width = (size / 3.0).max(1.0);
+    let mut items = Vec::new();
+
+    let link_ids = inp.pipes.iter().map(|pipe| &pipe.id)
+        .chain(inp.pumps.iter().map(|pump| &pump.id))
+        .chain(inp.valves.iter().map(|valve| &valve.id));
+    for id in link_ids {
+        if let Some(polyline) = geometry.link(id) {
+            let color = options.link_colors.as_ref().and_then(|scale| scale.color(id)).unwrap_or(LINK_COLOR);
+            items.push(Item::Polyline { points: polyline.into_iter().map(to_pixel).collect(), color, width: line_width });
+        }
+    }
+
+    if let Some(scale) = options.node_colors.as_ref() {
+        for junction in inp.junctions.iter() {
+            if let (Some(point), Some(fill)) = (geometry.node(&junction.id), scale.color(&junction.id)) {
+                items.push(Item::Circle { center: to_pixel(point), radius: size / 2.0, fill });
+            }
+        }
+    }
+    let node_color = |id: &str| options.node_colors.as_ref().and_then(|scale| scale.color(id)).unwrap_or(NODE_COLOR);
+
+    // Pumps are a circle with an arrow and valves a bow tie, at the middle of the link.
+    for pump in inp.pumps.iter() {
+        if let Some((center, angle)) = geometry.link(&pump.id).and_then(|polyline| middle(&polyline.into_iter().map(to_pixel).collect::<Vec<Point>>())) {
+            items.push(Item::Circle { center, radius: size, fill: (255, 255, 255) });
+            items.push(Item::Polygon { points: place(&[(-0.5, -0.6), (0.7, 0.0), (-0.5, 0.6)], center, angle, size), fill: OUTLINE_COLOR });
+        }
+    }
+    for valve in inp.valves.iter() {
+        if let Some((center, angle)) = geometry.link(&valve.id).and_then(|polyline| middle(&polyline.into_iter().map(to_pixel).collect::<Vec<Point>>())) {
+            items.push(Item::Polygon { points: place(&[(-0.8, -0.5), (0.8, 0.5), (0.8, -0.5), (-0.8, 0.5)], center, angle, size), fill: (255, 255, 255) });
+        }
+    }
+
+    // Reservoirs are a trapezoid and tanks a square.
+    for reservoir in inp.reservoirs.iter() {
+        if let Some(point) = geometry.node(&reservoir.id) {
+            let points = place(&[(-1.2, -1.0), (1.2, -1.0), (0.8, 1.0), (-0.8, 1.0)], to_pixel(point), 0.0, size);
+            items.push(Item::Polygon { points, fill: node_color(&reservoir.id) });
+        }
+    }
+    for tank in inp.tanks.iter() {
+        if let Some(point) = geometry.node(&tank.id) {
+            let points = place(&[(-1.0, -1.0), (1.0, -1.0), (1.0, 1.0), (-1.0, 1.0)], to_pixel(point), 0.0, size);
+            items.push(Item::Polygon { points, fill: node_color(&tank.id) });
+        }
+    }
+
+    let mut top = margin / 2.0;
+    for scale in options.link_colors.iter().chain(options.node_colors.iter()) {
+        legend(&mut items, scale, (margin / 2.0, top), 1.5 * size);
+        top += 7.0 * 1.5 * size;
+    }
+    Ok(items)
+}
+
+/// Point and direction in radians half way along a polyline.
+fn middle(polyline: &[Point]) -> Option<(Point, f64)> {
+    let length = |((x1, y1), (x2, y2)): (Point, Point)| (x2 - x1).hypot(y2 - y1);
+    let segments = polyline.iter().copied().zip(polyline.iter().copied().skip(1));
+    let mut remaining = segments.clone().map(length).sum::<f64>() / 2.0;
+    for segment in segments {
+        let ((x1, y1), (x2, y2)) = segment;
+        let segment_length = length(segment);
+        if remaining <= segment_length {
+            let t = if segment_length > 0.0 { remaining / segment_length } else { 0.0 };
+            return Some(((x1 + t * (x2 - x1), y1 + t * (y2 - y1)), (y2 - y1).atan2(x2 - x1)));
+        }
+        remaining -= segment_length;
+    }
+    None
+}
+
+/// Symbol outline scaled by `size`, rotated by `angle` and moved to `center`.
+fn place(outline: &[Point], (cx, cy): Point, angle: f64, size: f64) -> Vec<Point> {
+    let (sin, cos) = angle.sin_cos();
+    outline.iter().map(|&(x, y)| (cx + size * (x * cos - y * sin), cy + size * (x * sin + y * cos))).collect()
+}
+
+fn legend(items: &mut Vec<Item>, scale: &ColorScale, (left, top): Point, row: f64) {
+    items.push(Item::Text { at: (left, top + 0.8 * row), text: scale.title.clone(), size: 0.8 * row });
+    for (i, (color, label)) in CLASS_COLORS.iter().zip(scale.labels()).enumerate() {
+        let y = top + (i + 1) as f64 * row;
+        let swatch = vec![(left, y + 0.15 * row), (left + row, y + 0.15 * row), (left + row, y + 0.85 * row), (left, y + 0.85 * row)];
+        items.push(Item::Polygon { points: swatch, fill: *color });
+        items.push(Item::Text { at: (left + 1.4 * row, y + 0.75 * row), text: label, size: 0.7 * row });
+    }
+}
+
+fn hex((r, g, b): Color) -> String {
+    format!("#{:02x}{:02x}{:02x}", r, g, b)
+}
+
+fn escape(text: &str) -> String {
+    text.replace('&', "&amp;").replace('<', "&lt;").replace('>', "&gt;").replace('"', "&quot;")
+}
+
+fn svg_points(points: &[Point]) -> String {
+    points.iter().map(|(x, y)| format!("{:.1},{:.1}", x, y)).collect::<Vec<String>>().join(" ")
+}
+
+/// Map of the network as SVG, with the links drawn from [COORDINATES] and [VERTICES].
+pub fn render_svg(inp: &INP, options: &RenderOptions) -> Result<String, RenderError> {
+    let items = scene(inp, options)?;
+    let mut svg = format!(
+        "<svg xmlns=\"http://www.w3.org/2000/svg\" width=\"{0}\" height=\"{1}\" viewBox=\"0 0 {0} {1}\">\n<rect width=\"100%\" height=\"100%\" fill=\"#ffffff\"/>\n",
+        options.width, options.height,
+    );
+    for item in items.iter() {
+        let element = match item {
+            Item::Polyline { points, color, width } => format!(
+                "<polyline points=\"{}\" fill=\"none\" stroke=\"{}\" stroke-width=\"{:.1}\" stroke-linejoin=\"round\" stroke-linecap=\"round\"/>",
+                svg_points(points), hex(*color), width,
+            ),
+            Item::Polygon { points, fill } => format!(
+                "<polygon points=\"{}\" fill=\"{}\" stroke=\"{}\" stroke-width=\"1\"/>",
+                svg_points(points), hex(*fill), hex(OUTLINE_COLOR),
+            ),
+            Item::Circle { center: (x, y), radius, fill } => format!(
+                "<circle cx=\"{:.1}\" cy=\"{:.1}\" r=\"{:.1}\" fill=\"{}\" stroke=\"{}\" stroke-width=\"1\"/>",
+                x, y, radius, hex(*fill), hex(OUTLINE_COLOR),
+            ),
+            Item::Text { at: (x, y), text, size } => format!(
+                "<text x=\"{:.1}\" y=\"{:.1}\" font-family=\"sans-serif\" font-size=\"{:.1}\" fill=\"{}\">{}</text>",
+                x, y, size, hex(TEXT_COLOR), escape(text),
+            ),
+        };
+        svg.push_str(&element);
+        svg.push('\n');
+    }
+    svg.push_str("</svg>\n");
+    Ok(svg)
+}
+
+/// Draws a line of text from its baseline, with the embedded Hack font so that maps
+/// look the same wherever they are rendered.
+#[cfg(feature = "raster")]
+fn draw_text(pixmap: &mut tiny_skia::Pixmap, (x, y): Point, text: &str, size: f64) {
+    use ab_glyph::{point, Font, FontRef, ScaleFont};
+
+    let font = FontRef::try_from_slice(epaint_default_fonts::HACK_REGULAR).expect("embedded font");
+    let font = font.as_scaled(size as f32);
+    let (width, height) = (pixmap.width() as i64, pixmap.height() as i64);
+    let (r, g, b) = TEXT_COLOR;
+    let mut caret = x as f32;
+    let mut previous = None;
+    for c in text.chars() {
+        let id = font.glyph_id(c);
+        if let Some(previous) = previous {
+            caret += font.kern(previous, id);
+        }
+        previous = Some(id);
+        let glyph = id.with_scale_and_position(font.scale(), point(caret, y as f32));
+        caret += font.h_advance(id);
+        let Some(outline) = font.outline_glyph(glyph) else { continue };
+        let bounds = outline.px_bounds();
+        let pixels = pixmap.pixels_mut();
+        outline.draw(|gx, gy, coverage| {
+            let (px, py) = (bounds.min.x as i64 + gx as i64, bounds.min.y as i64 + gy as i64);
+            if px < 0 || py < 0 || px >= width || py >= height {
+                return;
+            }
+            let pixel = &mut pixels[(py * width + px) as usize];
+            let blend = |under: u8, over: u8| (under as f32 + (over as f32 - under as f32) * coverage.min(1.0)).round() as u8;
+            if let Some(color) = tiny_skia::PremultipliedColorU8::from_rgba(blend(pixel.red(), r), blend(pixel.green(), g), blend(pixel.blue(), b), 255) {
+                *pixel = color;
+            }
+        });
+    }
+}
+
+/// Map of the network as PNG, drawing the same items as the SVG map.
+#[cfg(feature = "raster")]
+pub fn render_png(inp: &INP, options: &RenderOptions) -> Result<Vec<u8>, RenderError> {
+    use tiny_skia::{FillRule, Paint, PathBuilder, Pixmap, Stroke, LineCap, LineJoin, Transform};
+
+    let items = scene(inp, options)?;
+    let mut pixmap = Pixmap::new(options.width, options.height)
+        .ok_or_else(|| RenderError { message: format!("Invalid image size {}x{}", options.width, options.height) })?;
+    pixmap.fill(tiny_skia::Color::WHITE);
+
+    let paint = |(r, g, b): Color| {
+        let mut paint = Paint::default();
+        paint.set_color_rgba8(r, g, b, 255);
+        paint.anti_alias = true;
+        paint
+    };
+    let polyline = |points: &[Point], close: bool| {
+        let mut builder = PathBuilder::new();
+        for (i, &(x, y)) in points.iter().enumerate() {
+            if i == 0 { builder.move_to(x as f32, y as f32) } else { builder.line_to(x as f32, y as f32) }
+        }
+        if close {
+            builder.close();
+        }
+        builder.finish()
+    };
+    let outline = Stroke { width: 1.0, ..Stroke::default() };
+
+    for item in items.iter() {
+        let (path, fill) = match item {
+            Item::Polyline { points, color, width } => {
+                if let Some(path) = polyline(points, false) {
+                    let stroke = Stroke { width: *width as f32, line_cap: LineCap::Round, line_join: LineJoin::Round, ..Stroke::default() };
+                    pixmap.stroke_path(&path, &paint(*color), &stroke, Transform::identity(), None);
+                }
+                continue;
+            },
+            Item::Polygon { points, fill } => (polyline(points, true), fill),
+            Item::Circle { center: (x, y), radius, fill } => (PathBuilder::from_circle(*x as f32, *y as f32, *radius as f32), fill),
+            Item::Text { at, text, size } => {
+                draw_text(&mut pixmap, *at, text, *size);
+                continue;
+            },
+        };
+        if let Some(path) = path {
+            pixmap.fill_path(&path, &paint(*fill), FillRule::Winding, Transform::identity(), None);
+            pixmap.stroke_path(&path, &paint(OUTLINE_COLOR), &outline, Transform::identity(), None);
+        }
+    }
+    pixmap.encode_png().map_err(|error| RenderError { message: error.to_string() })
+}
+
+#[cfg(test)]
+mod test {
+    use std::fs;
+    use super::{link_attribute, node_attribute, render_svg, ColorScale, RenderOptions, CLASS_COLORS};
+    use crate::INP;
+
+    fn magnetic_island() -> INP {
+        INP::read(fs::read_to_string("tests/MagneticIslandEnhanced.inp").unwrap())
+    }
+
+    #[test]
+    fn split_values_in_classes() {
+        let values = vec![("a", 0.0), ("b", 50.0), ("c", 100.0)].into_iter()
+            .map(|(id, value)| (id.to_string(), value))
+            .collect();
+
+        let scale = ColorScale::new("Pressure", values);
+
+        assert_eq!(scale.breaks, [20.0, 40.0, 60.0, 80.0]);
+        assert_eq!(scale.color("a"), Some(CLASS_COLORS[0]));
+        assert_eq!(scale.color("b"), Some(CLASS_COLORS[2]));
+        assert_eq!(scale.color("c"), Some(CLASS_COLORS[4]));
+        assert_eq!(scale.color("d"), None);
+    }
+
+    #[test]
+    fn render_network_to_svg() {
+        let inp = magnetic_island();
+        let options = RenderOptions {
+            link_colors: Some(ColorScale::new("Diameter", link_attribute(&inp, "diameter"))),
+            node_colors: Some(ColorScale::new("Elevation", node_attribute(&inp, "elevation"))),
+            ..RenderOptions::default()
+        };
+
+        let svg = render_svg(&inp, &options).unwrap();
+
+        assert!(svg.starts_with("<svg xmlns=\"http://www.w3.org/2000/svg\" width=\"800\" height=\"600\""));
+        assert_eq!(svg.matches("<polyline").count(), inp.pipes.len() + inp.pumps.len() + inp.valves.len());
+        assert!(svg.contains(">Diameter</text>") && svg.contains(">Elevation</text>"));
+        assert!(svg.contains("stroke=\"#ff0000\""));
+        assert!(svg.lines().filter_map(|line| line.strip_prefix("<circle cx=\"")).all(|line| {
+            let x = line.split('"').next().unwrap().parse::<f64>().unwrap();
+            (0.0..=800.0).contains(&x)
+        }));
+    }
+
+    #[test]
+    fn fail_without_coordinates() {
+        assert!(render_svg(&INP::read(String::new()), &RenderOptions::default()).is_err());
+    }
+
+    #[cfg(feature = "raster")]
+    #[test]
+    fn render_network_to_png() {
+        let options = RenderOptions { width: 200, height: 150, ..RenderOptions::default() };
+
+        let png = super::render_png(&magnetic_island(), &options).unwrap();
+
+        assert_eq!(&png[1..4], b"PNG");
+        assert_eq!(u32::from_be_bytes([png[16], png[17], png[18], png[19]]), 200);
+        assert_eq!(u32::from_be_bytes([png[20], png[21], png[22], png[23]]), 150);
+    }
+
+    #[cfg(feature = "raster")]
+    #[test]
+    fn write_legend_text_on_png() {
+        let inp = magnetic_island();
+        let scale = ColorScale::new("Diameter", link_attribute(&inp, "diameter"));
+        let options = RenderOptions { width: 1200, height: 900, link_colors: Some(scale), ..RenderOptions::default() };
+
+        let png = super::render_png(&inp, &options).unwrap();
+
+        // The title sits above the map, whose margin is 45 px, on a white background.
+        let pixmap = tiny_skia::Pixmap::decode_png(&png).unwrap();
+        let title = (20..33).flat_map(|y| (20..120).map(move |x| (x, y)))
+            .filter(|&(x, y)| pixmap.pixel(x, y).unwrap().red() < 128)
+            .count();
+        assert!(title > 20, "{} dark pixels", title);
+    }
+}
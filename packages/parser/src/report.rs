@@ -0,0 +1,464 @@
+use serde::{Serialize, Deserialize};
+use crate::energy::{EnergyReport, PumpEnergy};
+use crate::sections::time::parse_time;
+use crate::ToJson;
+
+/// Line of the input summary, e.g. `Number of Junctions.... 9`.
+#[derive(Serialize, Deserialize, Debug, PartialEq, Clone)]
+pub struct SummaryItem {
+    pub key: String,
+    pub value: String,
+}
+
+/// Error or warning, with its EPANET code when known.
+#[derive(Serialize, Deserialize, Debug, PartialEq, Clone)]
+pub struct ReportMessage {
+    pub code: Option<u32>,
+    /// Seconds since the start of the simulation, for messages of the status log.
+    pub time: Option<u64>,
+    pub message: String,
+}
+
+#[derive(Serialize, Deserialize, Debug, PartialEq, Clone)]
+pub enum StatusKind {
+    Balanced { trials: u32 },
+    Unbalanced,
+    /// Tank or reservoir filling, emptying, closed or open, with its level when given.
+    Storage { node_type: String, id: String, status: String, level: Option<f64> },
+    /// Status or setting change of a link, with its cause when given, e.g. `timer control`.
+    Link { link_type: String, id: String, from: Option<String>, to: Option<String>, cause: Option<String> },
+    Warning { code: Option<u32> },
+    Other,
+}
+
+/// Line of the hydraulic status log.
+#[derive(Serialize, Deserialize, Debug, PartialEq, Clone)]
+pub struct StatusEvent {
+    pub time: u64,
+    pub kind: StatusKind,
+    pub message: String,
+}
+
+#[derive(Serialize, Deserialize, Debug, PartialEq, Clone)]
+pub struct ResultRow {
+    pub id: String,
+    pub values: Vec<f64>,
+    /// Text after the values, e.g. `Tank` or a link status.
+    pub note: Option<String>,
+}
+
+/// Node or link results at a reporting time.
+#[derive(Serialize, Deserialize, Debug, PartialEq, Clone)]
+pub struct ResultTable {
+    /// `None` for the results of a single period.
+    pub time: Option<u64>,
+    pub columns: Vec<String>,
+    pub units: Vec<String>,
+    pub rows: Vec<ResultRow>,
+}
+
+impl ResultTable {
+    pub fn row(&self, id: &str) -> Option<&ResultRow> {
+        self.rows.iter().find(|row| row.id == id)
+    }
+
+    /// Value of a column, e.g. `Pressure`, for a node or a link.
+    pub fn value(&self, id: &str, column: &str) -> Option<f64> {
+        let index = self.columns.iter().position(|name| name.eq_ignore_ascii_case(column))?;
+        self.row(id)?.values.get(index).copied()
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, PartialEq, Clone, Default)]
+pub struct Report {
+    pub title: Vec<String>,
+    pub summary: Vec<SummaryItem>,
+    pub errors: Vec<ReportMessage>,
+    pub warnings: Vec<ReportMessage>,
+    pub status: Vec<StatusEvent>,
+    pub energy: Option<EnergyReport>,
+    pub node_results: Vec<ResultTable>,
+    pub link_results: Vec<ResultTable>,
+}
+
+#[derive(PartialEq, Clone, Copy)]
+enum Part {
+    Header,
+    Status,
+    Energy,
+    Nodes,
+    Links,
+}
+
+/// `Error 203: ...` anywhere in a line.
+fn error_code(line: &str) -> Option<(u32, String)> {
+    let start = line.find("Error ")?;
+    let rest = &line[start + 6..];
+    let (code, message) = rest.split_once(':')?;
+    Some((code.trim().parse().ok()?, message.trim().to_string()))
+}
+
+/// Code of a warning, given as `Warning 6:` or recognized from the messages of EPANET.
+fn warning_code(message: &str) -> Option<u32> {
+    let upper = message.to_uppercase();
+    if let Some(rest) = upper.strip_prefix("WARNING ") {
+        if let Some(code) = rest.split(':').next().and_then(|code| code.trim().parse().ok()) {
+            return Some(code);
+        }
+    }
+    if upper.contains("UNBALANCED") {
+        Some(1)
+    } else if upper.contains("UNSTABLE") {
+        Some(2)
+    } else if upper.contains("DISCONNECTED") {
+        Some(3)
+    } else if upper.contains("PUMP") && upper.contains("CANNOT DELIVER") {
+        Some(4)
+    } else if upper.contains("VALVE") && upper.contains("CANNOT DELIVER") {
+        Some(5)
+    } else if upper.contains("NEGATIVE PRESSURE") {
+        Some(6)
+    } else {
+        None
+    }
+}
+
+fn is_warning(line: &str) -> bool {
+    line.to_uppercase().starts_with("WARNING")
+}
+
+/// Splits `3:00:00: message` into its time and message.
+fn timed(line: &str) -> Option<(u64, &str)> {
+    let (time, message) = line.split_once(": ")?;
+    if !time.contains(':') {
+        return None;
+    }
+    Some((parse_time(time.trim(), None).ok()?, message.trim()))
+}
+
+fn status_kind(message: &str) -> StatusKind {
+    let words = message.split_whitespace().collect::<Vec<&str>>();
+    if is_warning(message) {
+        return StatusKind::Warning { code: warning_code(message) };
+    }
+    match words[..] {
+        ["Balanced", "after", trials, ..] => StatusKind::Balanced { trials: trials.parse().unwrap_or(0) },
+        ["Unbalanced", ..] => StatusKind::Unbalanced,
+        [node_type @ ("Tank" | "Reservoir"), id, "is", status, ..] => StatusKind::Storage {
+            node_type: node_type.to_string(),
+            id: id.to_string(),
+            status: status.to_string(),
+            level: words.iter().position(|&word| word == "at").and_then(|at| words.get(at + 1)?.parse().ok()),
+        },
+        [link_type, id, ..] if words.contains(&"changed") => {
+            let after = |keyword: &str, end: Option<&str>| -> Option<String> {
+                let start = words.iter().position(|&word| word == keyword)? + 1;
+                let stop = end.and_then(|end| words.iter().position(|&word| word == end)).unwrap_or(words.len());
+                if start < stop { Some(words[start..stop].join(" ")) } else { None }
+            };
+            StatusKind::Link {
+                link_type: link_type.to_string(),
+                id: id.to_string(),
+                from: after("from", Some("to")),
+                to: if words.contains(&"from") { after("to", None) } else { None },
+                cause: after("by", None),
+            }
+        },
+        _ => StatusKind::Other,
+    }
+}
+
+fn is_rule(line: &str) -> bool {
+    line.len() > 3 && line.chars().all(|c| c == '-')
+}
+
+fn parse_row(line: &str) -> ResultRow {
+    let mut words = line.split_whitespace();
+    let id = words.next().unwrap_or_default().to_string();
+    let mut values = Vec::new();
+    let mut note = Vec::new();
+    for word in words {
+        match word.parse::<f64>() {
+            Ok(value) if note.is_empty() => values.push(value),
+            _ => note.push(word),
+        }
+    }
+    ResultRow { id, values, note: if note.is_empty() { None } else { Some(note.join(" ")) } }
+}
+
+/// Time of a `Node Results at 1:00:00 hrs:` heading.
+fn heading_time(line: &str) -> Option<u64> {
+    let at = line.split_whitespace().skip_while(|&word| word != "at").nth(1)?;
+    parse_time(at.trim_end_matches(':'), None).ok()
+}
+
+impl Report {
+    /// Parses an EPANET text report. Lines that aren't recognized are skipped.
+    pub fn read(content: String) -> Self {
+        let mut report = Report::default();
+        let mut part = Part::Header;
+        let mut summary_started = false;
+        let mut rules = 0;
+        let mut headings: Vec<String> = Vec::new();
+
+        for raw in content.lines() {
+            let line = raw.trim();
+            if line.is_empty() || line.starts_with('*') || (line.starts_with("Page ") && line.split_whitespace().nth(1).is_some_and(|page| page.parse::<u32>().is_ok())) {
+                continue;
+            }
+            if let Some((code, message)) = error_code(line) {
+                report.errors.push(ReportMessage { code: Some(code), time: None, message });
+                continue;
+            }
+
+            let table = |report: &mut Report, nodes: bool, line: &str| {
+                let tables = if nodes { &mut report.node_results } else { &mut report.link_results };
+                if !line.contains("(continued)") || tables.is_empty() {
+                    tables.push(ResultTable { time: heading_time(line), columns: Vec::new(), units: Vec::new(), rows: Vec::new() });
+                }
+            };
+            if line.starts_with("Hydraulic Status:") {
+                part = Part::Status;
+                continue;
+            } else if line.starts_with("Energy Usage:") {
+                part = Part::Energy;
+                report.energy = Some(EnergyReport { pumps: Vec::new(), demand_charge: 0.0, total_cost: 0.0 });
+                (rules, headings) = (0, Vec::new());
+                continue;
+            } else if line.starts_with("Node Results") {
+                part = Part::Nodes;
+                table(&mut report, true, line);
+                (rules, headings) = (0, Vec::new());
+                continue;
+            } else if line.starts_with("Link Results") {
+                part = Part::Links;
+                table(&mut report, false, line);
+                (rules, headings) = (0, Vec::new());
+                continue;
+            } else if line.starts_with("Analysis begun") || line.starts_with("Analysis ended") {
+                continue;
+            }
+
+            match part {
+                Part::Header => {
+                    if is_warning(line) {
+                        report.warnings.push(ReportMessage { code: warning_code(line), time: None, message: line.to_string() });
+                    } else if let Some(dots) = line.find("..") {
+                        summary_started = true;
+                        report.summary.push(SummaryItem {
+                            key: line[..dots].trim().to_string(),
+                            value: line[dots..].trim_start_matches('.').trim().to_string(),
+                        });
+                    } else if !summary_started {
+                        report.title.push(line.to_string());
+                    }
+                },
+                Part::Status => {
+                    if is_rule(line) {
+                        continue;
+                    }
+                    let (time, message) = match timed(line) {
+                        Some((time, message)) => (Some(time), message),
+                        None => (None, line),
+                    };
+                    if is_warning(message) {
+                        report.warnings.push(ReportMessage { code: warning_code(message), time, message: message.to_string() });
+                    }
+                    if let Some(time) = time {
+                        report.status.push(StatusEvent { time, kind: status_kind(message), message: message.to_string() });
+                    }
+                },
+                Part::Energy => {
+                    let energy = report.energy.as_mut().unwrap();
+                    if is_rule(line) {
+                        rules += 1;
+                    } else if let Some(value) = line.strip_prefix("Demand Charge:") {
+                        energy.demand_charge = value.trim().parse().unwrap_or(0.0);
+                    } else if let Some(value) = line.strip_prefix("Total Cost:") {
+                        energy.total_cost = value.trim().parse().unwrap_or(0.0);
+                    } else if rules == 2 {
+                        let row = parse_row(line);
+                        if let [utilization, efficiency, energy_per_volume, average_power, peak_power, daily_cost] = row.values[..] {
+                            energy.pumps.push(PumpEnergy { id: row.id, utilization, efficiency, energy_per_volume, average_power, peak_power, daily_cost });
+                        }
+                    }
+                },
+                Part::Nodes | Part::Links => {
+                    let tables = if part == Part::Nodes { &mut report.node_results } else { &mut report.link_results };
+                    let table = tables.last_mut().unwrap();
+                    if is_rule(line) {
+                        rules += 1;
+                        // Columns are named on the first heading line, units follow the
+                        // element type on the last one.
+                        if rules == 2 && table.columns.is_empty() && !headings.is_empty() {
+                            table.columns = headings[0].split_whitespace().map(|word| word.to_string()).collect();
+                            table.units = headings[headings.len() - 1].split_whitespace().skip(1).map(|word| word.to_string()).collect();
+                        }
+                    } else if rules == 1 {
+                        headings.push(line.to_string());
+                    } else if rules >= 2 {
+                        table.rows.push(parse_row(line));
+                    }
+                },
+            }
+        }
+        report
+    }
+
+    /// Value of the input summary, e.g. `Number of Junctions`.
+    pub fn summary(&self, key: &str) -> Option<&str> {
+        self.summary.iter()
+            .find(|item| item.key.eq_ignore_ascii_case(key))
+            .map(|item| item.value.as_str())
+    }
+
+    pub fn node_results_at(&self, time: u64) -> Option<&ResultTable> {
+        self.node_results.iter().find(|table| table.time.unwrap_or(0) == time)
+    }
+
+    pub fn link_results_at(&self, time: u64) -> Option<&ResultTable> {
+        self.link_results.iter().find(|table| table.time.unwrap_or(0) == time)
+    }
+}
+
+impl ToJson for Report {}
+
+#[cfg(test)]
+mod test {
+    use super::{Report, StatusKind};
+
+    const REPORT: &str = r#"
+  Page 1                                    Mon Jan 01 00:00:00 2024
+
+  ******************************************************************
+  *                           E P A N E T                          *
+  *                   Hydraulic and Water Quality                  *
+  *                   Analysis for Pipe Networks                   *
+  *                         Version 2.2                            *
+  ******************************************************************
+
+  EPANET Example Network 1
+
+  Input Data File ................... Net1.inp
+  Number of Junctions................ 9
+  Number of Reservoirs............... 1
+  Headloss Formula .................. Hazen-Williams
+  Total Duration .................... 24.00 hrs
+  Reporting Criteria:
+     All Nodes
+     All Links
+
+  Hydraulic Status:
+  -----------------------------------------------------------------------
+     0:00:00: Balanced after 5 trials
+     0:00:00: Reservoir 9 is emptying
+     0:00:00: Tank 2 is filling at 120.00 ft
+     1:00:00: Pump 9 changed by timer control
+     1:00:00: Pump 9 changed from open to closed
+     2:00:00: WARNING: Negative pressures at 2:00:00 hrs.
+
+  Energy Usage:
+  ----------------------------------------------------------------
+                  Usage   Avg.     Kw-hr      Avg.      Peak      Cost
+  Pump           Factor Effic.     /Mgal        Kw        Kw      /day
+  ----------------------------------------------------------------
+  9              100.00  75.00    880.43     96.54     96.54     12.00
+  ----------------------------------------------------------------
+                                            Demand Charge:      5.00
+                                            Total Cost:        17.00
+
+  Node Results at 0:00:00 hrs:
+  --------------------------------------------------------
+                     Demand      Head  Pressure   Chlorine
+  Node                  gpm        ft       psi       mg/L
+  --------------------------------------------------------
+  10                   0.00   1004.35    127.54       0.50
+  11                 150.00    985.23    119.26       0.50
+
+  Page 2                                    Mon Jan 01 00:00:00 2024
+
+  Node Results at 0:00:00 hrs: (continued)
+  --------------------------------------------------------
+                     Demand      Head  Pressure   Chlorine
+  Node                  gpm        ft       psi       mg/L
+  --------------------------------------------------------
+  9                -1866.18    800.00      0.00       1.00  Reservoir
+  2                  766.18    970.00     52.00       1.00  Tank
+
+  Link Results at 1:00:00 hrs:
+  ----------------------------------------------------------------------
+                     Flow  Velocity  Headloss    Status
+  Link                gpm       fps    /1000ft
+  ----------------------------------------------------------------------
+  10              1866.18      2.11      1.31      Open
+  9                  0.00      0.00      0.00    Closed  Pump
+
+  Analysis ended Mon Jan 01 00:00:00 2024
+"#;
+
+    #[test]
+    fn read_the_summary_and_status_log() {
+        let report = Report::read(REPORT.to_string());
+
+        assert_eq!(report.title, vec!["EPANET Example Network 1"]);
+        assert_eq!(report.summary("Number of Junctions"), Some("9"));
+        assert_eq!(report.summary("Headloss Formula"), Some("Hazen-Williams"));
+        assert_eq!(report.status.len(), 6);
+        assert_eq!(report.status[0].kind, StatusKind::Balanced { trials: 5 });
+        assert_eq!(report.status[2].kind, StatusKind::Storage { node_type: "Tank".to_string(), id: "2".to_string(), status: "filling".to_string(), level: Some(120.0) });
+        assert_eq!(report.status[3].time, 3600);
+        assert_eq!(report.status[3].kind, StatusKind::Link { link_type: "Pump".to_string(), id: "9".to_string(), from: None, to: None, cause: Some("timer control".to_string()) });
+        assert_eq!(report.status[4].kind, StatusKind::Link { link_type: "Pump".to_string(), id: "9".to_string(), from: Some("open".to_string()), to: Some("closed".to_string()), cause: None });
+        assert_eq!(report.warnings.len(), 1);
+        assert_eq!(report.warnings[0].code, Some(6));
+        assert_eq!(report.warnings[0].time, Some(7200));
+        assert!(report.errors.is_empty());
+    }
+
+    #[test]
+    fn read_the_energy_and_result_tables() {
+        let report = Report::read(REPORT.to_string());
+
+        let energy = report.energy.as_ref().unwrap();
+        assert_eq!(energy.pumps.len(), 1);
+        assert_eq!(energy.pumps[0].id, "9");
+        assert_eq!(energy.pumps[0].energy_per_volume, 880.43);
+        assert_eq!(energy.demand_charge, 5.0);
+        assert_eq!(energy.total_cost, 17.0);
+
+        assert_eq!(report.node_results.len(), 1);
+        let nodes = report.node_results_at(0).unwrap();
+        assert_eq!(nodes.columns, vec!["Demand", "Head", "Pressure", "Chlorine"]);
+        assert_eq!(nodes.units, vec!["gpm", "ft", "psi", "mg/L"]);
+        assert_eq!(nodes.rows.len(), 4);
+        assert_eq!(nodes.value("11", "pressure"), Some(119.26));
+        assert_eq!(nodes.row("2").unwrap().note.as_deref(), Some("Tank"));
+
+        let links = report.link_results_at(3600).unwrap();
+        assert_eq!(links.value("10", "Flow"), Some(1866.18));
+        assert_eq!(links.row("9").unwrap().note.as_deref(), Some("Closed Pump"));
+    }
+
+    #[test]
+    fn read_errors_and_unbalanced_warnings() {
+        let report = Report::read(r#"
+  Error 213: illegal option value in [OPTIONS] section:
+  Units  XYZ
+  Error 200: one or more errors detected in input file.
+
+  Hydraulic Status:
+  ----------------------------------------
+    12:00:00: Balanced after 3 trials
+    13:00:00: WARNING: System unbalanced at 13:00:00 hrs.
+    13:00:00: Unbalanced after 40 trials (flow change = 0.012000)
+"#.to_string());
+
+        assert_eq!(report.errors.len(), 2);
+        assert_eq!(report.errors[0].code, Some(213));
+        assert_eq!(report.errors[0].message, "illegal option value in [OPTIONS] section:");
+        assert_eq!(report.warnings[0].code, Some(1));
+        assert_eq!(report.warnings[0].time, Some(46800));
+        assert_eq!(report.status[1].kind, StatusKind::Warning { code: Some(1) });
+        assert_eq!(report.status[2].kind, StatusKind::Unbalanced);
+    }
+}
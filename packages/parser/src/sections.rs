@@ -7,9 +7,24 @@ pub mod valve;
 pub mod emitter;
 pub mod quality;
 pub mod source;
+pub mod setting;
+pub mod curve;
+pub mod control;
+pub mod status;
+pub mod tag;
+pub mod coordinate;
+pub mod vertex;
+pub mod demand;
+pub mod pattern;
+pub mod rule;
+pub mod mixing;
+pub mod label;
+pub mod backdrop;
 
 pub mod sectionable;
+pub mod time;
 pub mod unknown;
+pub mod comment;
 pub mod error;
 
 pub use quality::Quality;
@@ -18,10 +33,22 @@ pub use reservoir::Reservoir;
 pub use junction::Junction;
 pub use pump::Pump;
 pub use tank::Tank;
-pub use valve::Valve;
+pub use valve::{Valve, ValveType};
 pub use emitter::Emitter;
 pub use source::Source;
+pub use setting::Setting;
+pub use curve::Curve;
+pub use control::{Control, ControlAction, ControlCondition};
+pub use status::Status;
+pub use tag::Tag;
+pub use coordinate::Coordinate;
+pub use vertex::Vertex;
+pub use demand::Demand;
+pub use pattern::Pattern;
+pub use rule::{Rule, RuleLine, RuleAction, Premise, Relation};
+pub use mixing::{Mixing, MixingModel};
+pub use label::Label;
+pub use backdrop::Backdrop;
 pub use unknown::Unknown;
+pub use comment::Comment;
 pub use error::Error;
-pub use sectionable::{Sectionable, SectionError};
-
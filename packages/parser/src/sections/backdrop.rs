@@ -0,0 +1,42 @@
+use super::sectionable::{Sectionable, SectionError};
+use serde::{Deserialize, Serialize};
+
+/// Line of [BACKDROP], e.g. `DIMENSIONS 0 0 100 100` or `FILE` without a file.
+#[derive(Serialize, Deserialize, Debug, PartialEq, Clone)]
+pub struct Backdrop {
+    pub key: String,
+    pub values: Vec<String>,
+    pub comment: Option<String>,
+}
+
+impl Sectionable for Backdrop {
+    type SelfType = Backdrop;
+
+    fn from_section(properties: Vec<&str>, comment: Option<String>) -> Result<Backdrop, SectionError> {
+        let key = properties.first()
+            .ok_or_else(|| SectionError { message: "Not enough properties to create BACKDROP section".to_string() })?
+            .to_uppercase();
+
+        Ok(Backdrop {
+            key,
+            values: properties[1..].iter().map(|value| value.to_string()).collect(),
+            comment,
+        })
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::Sectionable;
+    use super::Backdrop;
+
+    #[test]
+    fn create_backdrop_from_section() {
+        let dimensions = Backdrop::from_section(vec!["Dimensions", "0.0", "1.5", "100", "200"], None).unwrap();
+        let file = Backdrop::from_section(vec!["FILE"], None).unwrap();
+
+        assert_eq!(dimensions.key, "DIMENSIONS");
+        assert_eq!(dimensions.values, vec!["0.0", "1.5", "100", "200"]);
+        assert!(file.values.is_empty());
+    }
+}
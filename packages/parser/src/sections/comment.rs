@@ -0,0 +1,12 @@
+use serde::{Deserialize, Serialize};
+
+/// Line that only holds a comment, such as the column headers of a section.
+#[derive(Serialize, Deserialize, Debug, PartialEq, Clone)]
+pub struct Comment {
+    /// Section of the comment, empty before the first section.
+    pub section: String,
+    /// Number of rows of the section before the comment.
+    pub position: usize,
+    /// Whole line, starting with `;`.
+    pub text: String,
+}
@@ -0,0 +1,109 @@
+use super::sectionable::{Sectionable, SectionError};
+use super::time::parse_time;
+use serde::{Deserialize, Serialize};
+
+#[derive(Serialize, Deserialize, Debug, PartialEq, Clone)]
+pub struct Control {
+    pub link_id: String,
+    pub action: ControlAction,
+    pub condition: ControlCondition,
+    pub comment: Option<String>,
+}
+
+#[derive(Serialize, Deserialize, Debug, PartialEq, Clone)]
+pub enum ControlAction {
+    Open,
+    Closed,
+    Setting(f64),
+}
+
+#[derive(Serialize, Deserialize, Debug, PartialEq, Clone)]
+pub enum ControlCondition {
+    Above { node_id: String, value: f64 },
+    Below { node_id: String, value: f64 },
+    Time(u64),
+    ClockTime(u64),
+}
+
+impl Sectionable for Control {
+    type SelfType = Control;
+
+    fn from_section(properties: Vec<&str>, comment: Option<String>) -> Result<Control, SectionError> {
+        let words = properties.iter().map(|p| p.to_uppercase()).collect::<Vec<String>>();
+        if properties.len() < 6 || words[0] != "LINK" {
+            return Err(SectionError { message: "Not enough properties to create CONTROL section".to_string() });
+        }
+
+        let link_id = properties[1].to_string();
+        let action = match words[2].as_str() {
+            "OPEN" => ControlAction::Open,
+            "CLOSED" => ControlAction::Closed,
+            _ => ControlAction::Setting(properties[2].parse::<f64>()?),
+        };
+        let condition = match (words[3].as_str(), words[4].as_str()) {
+            ("IF", "NODE") if properties.len() >= 8 => {
+                let node_id = properties[5].to_string();
+                let value = properties[7].parse::<f64>()?;
+                match words[6].as_str() {
+                    "ABOVE" => ControlCondition::Above { node_id, value },
+                    "BELOW" => ControlCondition::Below { node_id, value },
+                    _ => return Err(SectionError { message: "Invalid control condition".to_string() })
+                }
+            },
+            ("AT", "TIME") => ControlCondition::Time(parse_time(properties[5], properties.get(6).copied())?),
+            ("AT", "CLOCKTIME") => ControlCondition::ClockTime(parse_time(properties[5], properties.get(6).copied())?),
+            _ => return Err(SectionError { message: "Invalid control condition".to_string() })
+        };
+
+        Ok(Control {
+            link_id,
+            action,
+            condition,
+            comment,
+        })
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::Sectionable;
+    use super::{Control, ControlAction, ControlCondition};
+
+    #[test]
+    fn create_node_control_from_section() {
+        let a_control = Control::from_section(
+            vec!["LINK", "P1", "CLOSED", "IF", "NODE", "T1", "ABOVE", "4.1"],
+            None,
+        );
+
+        assert_eq!(
+            a_control,
+            Ok(Control {
+                link_id: "P1".to_string(),
+                action: ControlAction::Closed,
+                condition: ControlCondition::Above { node_id: "T1".to_string(), value: 4.1 },
+                comment: None,
+            })
+        );
+    }
+
+    #[test]
+    fn create_time_controls_from_section() {
+        let at_time = Control::from_section(vec!["LINK", "PUMP2", "1.5", "AT", "TIME", "16"], None).unwrap();
+        let at_clocktime = Control::from_section(vec!["LINK", "PUMP2", "OPEN", "AT", "CLOCKTIME", "7", "AM"], None).unwrap();
+
+        assert_eq!(at_time.action, ControlAction::Setting(1.5));
+        assert_eq!(at_time.condition, ControlCondition::Time(57600));
+        assert_eq!(at_clocktime.condition, ControlCondition::ClockTime(25200));
+    }
+
+    #[test]
+    fn reject_unknown_conditions() {
+        let a_control = Control::from_section(
+            vec!["LINK", "P1", "CLOSED", "IF", "NODE", "T1", "EQUALS", "4.1"],
+            None,
+        );
+
+        assert!(a_control.is_err());
+    }
+}
@@ -0,0 +1,53 @@
+use super::sectionable::{Sectionable, SectionError};
+use serde::{Deserialize, Serialize};
+
+#[derive(Serialize, Deserialize, Debug, PartialEq, Clone)]
+pub struct Coordinate {
+    pub node_id: String,
+    pub x: f64,
+    pub y: f64,
+    pub comment: Option<String>,
+}
+
+impl Sectionable for Coordinate {
+    type SelfType = Coordinate;
+
+    fn from_section(properties: Vec<&str>, comment: Option<String>) -> Result<Coordinate, SectionError> {
+        if properties.len() < 3 {
+            return Err(SectionError { message: "Not enough properties to create COORDINATE section".to_string() });
+        }
+
+        Ok(Coordinate {
+            node_id: properties[0].to_string(),
+            x: properties[1].parse::<f64>()?,
+            y: properties[2].parse::<f64>()?,
+            comment,
+        })
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::Sectionable;
+    use super::Coordinate;
+
+    #[test]
+    fn create_coordinate_from_section() {
+        let a_coordinate = Coordinate::from_section(vec!["J1", "484140.562", "7885127.433"], None);
+
+        assert_eq!(
+            a_coordinate,
+            Ok(Coordinate {
+                node_id: "J1".to_string(),
+                x: 484140.562,
+                y: 7885127.433,
+                comment: None,
+            })
+        );
+    }
+
+    #[test]
+    fn x_and_y_are_compulsory() {
+        assert!(Coordinate::from_section(vec!["J1", "484140.562"], None).is_err());
+    }
+}
@@ -0,0 +1,59 @@
+use super::sectionable::{Sectionable, SectionError};
+use serde::{Deserialize, Serialize};
+
+#[derive(Serialize, Deserialize, Debug, PartialEq, Clone)]
+pub struct Curve {
+    pub id: String,
+    pub x: f64,
+    pub y: f64,
+    pub comment: Option<String>,
+}
+
+impl Sectionable for Curve {
+    type SelfType = Curve;
+
+    fn from_section(properties: Vec<&str>, comment: Option<String>) -> Result<Curve, SectionError> {
+        if properties.len() < 3 {
+            return Err(SectionError { message: "Not enough properties to create CURVE section".to_string() });
+        }
+
+        let id = properties[0].to_string();
+        let x = properties[1].parse::<f64>()?;
+        let y = properties[2].parse::<f64>()?;
+
+        Ok(Curve {
+            id,
+            x,
+            y,
+            comment,
+        })
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::Sectionable;
+    use super::Curve;
+
+    #[test]
+    fn create_curve_point_from_section() {
+        let a_curve = Curve::from_section(vec!["C1", "1500", "250"], None);
+
+        assert_eq!(
+            a_curve,
+            Ok(Curve {
+                id: "C1".to_string(),
+                x: 1500.0,
+                y: 250.0,
+                comment: None,
+            })
+        );
+    }
+
+    #[test]
+    fn id_x_and_y_are_compulsory() {
+        let a_curve = Curve::from_section(vec!["C1", "1500"], None);
+
+        assert!(a_curve.is_err());
+    }
+}
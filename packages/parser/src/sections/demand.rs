@@ -0,0 +1,53 @@
+use super::sectionable::{Sectionable, SectionError};
+use serde::{Deserialize, Serialize};
+
+#[derive(Serialize, Deserialize, Debug, PartialEq, Clone)]
+pub struct Demand {
+    pub junction_id: String,
+    pub base_demand: f64,
+    pub pattern_id: Option<String>,
+    pub comment: Option<String>,
+}
+
+impl Sectionable for Demand {
+    type SelfType = Demand;
+
+    fn from_section(properties: Vec<&str>, comment: Option<String>) -> Result<Demand, SectionError> {
+        if properties.len() < 2 {
+            return Err(SectionError { message: "Not enough properties to create DEMAND section".to_string() });
+        }
+
+        Ok(Demand {
+            junction_id: properties[0].to_string(),
+            base_demand: properties[1].parse::<f64>()?,
+            pattern_id: properties.get(2).map(|s| s.to_string()),
+            comment,
+        })
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::Sectionable;
+    use super::Demand;
+
+    #[test]
+    fn create_demand_from_section() {
+        let a_demand = Demand::from_section(vec!["J1", "0.005498", "Cust_Pattern_33"], Some("Residential".to_string()));
+
+        assert_eq!(
+            a_demand,
+            Ok(Demand {
+                junction_id: "J1".to_string(),
+                base_demand: 0.005498,
+                pattern_id: Some("Cust_Pattern_33".to_string()),
+                comment: Some("Residential".to_string()),
+            })
+        );
+    }
+
+    #[test]
+    fn junction_and_demand_are_compulsory() {
+        assert!(Demand::from_section(vec!["J1"], None).is_err());
+    }
+}
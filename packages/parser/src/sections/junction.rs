@@ -1,13 +1,13 @@
 use super::sectionable::{Sectionable, SectionError};
 use serde::{Deserialize, Serialize};
 
-#[derive(Serialize, Deserialize, Debug, PartialEq)]
+#[derive(Serialize, Deserialize, Debug, PartialEq, Clone)]
 pub struct Junction {
-    id: String,
-    elevation: f64,
-    base_demand_flow: Option<f64>,
-    demand_pattern_id: Option<String>,
-    comment: Option<String>,
+    pub id: String,
+    pub elevation: f64,
+    pub base_demand_flow: Option<f64>,
+    pub demand_pattern_id: Option<String>,
+    pub comment: Option<String>,
 }
 
 impl Sectionable for Junction {
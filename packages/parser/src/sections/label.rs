@@ -0,0 +1,71 @@
+use super::sectionable::{Sectionable, SectionError};
+use serde::{Deserialize, Serialize};
+
+#[derive(Serialize, Deserialize, Debug, PartialEq, Clone)]
+pub struct Label {
+    pub x: f64,
+    pub y: f64,
+    pub text: String,
+    pub anchor_node_id: Option<String>,
+    pub comment: Option<String>,
+}
+
+impl Sectionable for Label {
+    type SelfType = Label;
+
+    fn from_section(properties: Vec<&str>, comment: Option<String>) -> Result<Label, SectionError> {
+        if properties.len() < 3 {
+            return Err(SectionError { message: "Not enough properties to create LABEL section".to_string() });
+        }
+
+        // The text is quoted and can hold spaces, e.g. "Main Tank".
+        let rest = &properties[2..];
+        let words = if rest[0].starts_with('"') {
+            rest.iter().enumerate()
+                .position(|(i, word)| word.ends_with('"') && (i > 0 || word.len() > 1))
+                .map(|end| end + 1)
+                .unwrap_or(rest.len())
+        } else {
+            1
+        };
+
+        Ok(Label {
+            x: properties[0].parse::<f64>()?,
+            y: properties[1].parse::<f64>()?,
+            text: rest[..words].join(" ").trim_matches('"').to_string(),
+            anchor_node_id: rest.get(words).map(|id| id.to_string()),
+            comment,
+        })
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::Sectionable;
+    use super::Label;
+
+    #[test]
+    fn create_label_from_section() {
+        let a_label = Label::from_section(vec!["484140.5", "7885127.4", "\"Main", "Tank\"", "T1"], None);
+
+        assert_eq!(
+            a_label,
+            Ok(Label {
+                x: 484140.5,
+                y: 7885127.4,
+                text: "Main Tank".to_string(),
+                anchor_node_id: Some("T1".to_string()),
+                comment: None,
+            })
+        );
+    }
+
+    #[test]
+    fn create_label_of_one_word() {
+        let a_label = Label::from_section(vec!["1", "2", "\"Pumps\""], None).unwrap();
+
+        assert_eq!(a_label.text, "Pumps");
+        assert_eq!(a_label.anchor_node_id, None);
+        assert!(Label::from_section(vec!["1", "2"], None).is_err());
+    }
+}
@@ -0,0 +1,86 @@
+use super::sectionable::{Sectionable, SectionError};
+use serde::{Deserialize, Serialize};
+
+#[derive(Serialize, Deserialize, Debug, PartialEq, Clone, Copy)]
+pub enum MixingModel {
+    /// Completely mixed.
+    Mixed,
+    /// Two compartments: a mixed inlet/outlet zone and a stagnant zone.
+    TwoCompartment,
+    /// Plug flow, first in first out.
+    Fifo,
+    /// Stacked plug flow, last in first out.
+    Lifo,
+}
+
+#[derive(Serialize, Deserialize, Debug, PartialEq, Clone)]
+pub struct Mixing {
+    pub tank_id: String,
+    pub model: MixingModel,
+    /// Fraction of the tank volume taken by the mixing zone of a 2COMP tank.
+    pub fraction: f64,
+    pub comment: Option<String>,
+}
+
+impl Sectionable for Mixing {
+    type SelfType = Mixing;
+
+    fn from_section(properties: Vec<&str>, comment: Option<String>) -> Result<Mixing, SectionError> {
+        if properties.len() < 2 {
+            return Err(SectionError { message: "Not enough properties to create MIXING section".to_string() });
+        }
+
+        let model = match properties[1].to_uppercase().as_str() {
+            "MIXED" => MixingModel::Mixed,
+            "2COMP" => MixingModel::TwoCompartment,
+            "FIFO" => MixingModel::Fifo,
+            "LIFO" => MixingModel::Lifo,
+            _ => return Err(SectionError { message: format!("Invalid mixing model {}", properties[1]) }),
+        };
+        let fraction = match properties.get(2) {
+            Some(value) => value.parse::<f64>()?,
+            None => 1.0,
+        };
+
+        Ok(Mixing {
+            tank_id: properties[0].to_string(),
+            model,
+            fraction,
+            comment,
+        })
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::Sectionable;
+    use super::{Mixing, MixingModel};
+
+    #[test]
+    fn create_mixing_from_section() {
+        let a_mixing = Mixing::from_section(vec!["T1", "2COMP", "0.2"], None);
+
+        assert_eq!(
+            a_mixing,
+            Ok(Mixing {
+                tank_id: "T1".to_string(),
+                model: MixingModel::TwoCompartment,
+                fraction: 0.2,
+                comment: None,
+            })
+        );
+    }
+
+    #[test]
+    fn the_whole_tank_mixes_by_default() {
+        let a_mixing = Mixing::from_section(vec!["T1", "fifo"], None).unwrap();
+
+        assert_eq!(a_mixing.model, MixingModel::Fifo);
+        assert_eq!(a_mixing.fraction, 1.0);
+    }
+
+    #[test]
+    fn return_error_with_an_unknown_model() {
+        assert!(Mixing::from_section(vec!["T1", "STIRRED"], None).is_err());
+    }
+}
@@ -0,0 +1,60 @@
+use super::sectionable::{Sectionable, SectionError};
+use serde::{Deserialize, Serialize};
+
+#[derive(Serialize, Deserialize, Debug, PartialEq, Clone)]
+pub struct Pattern {
+    pub id: String,
+    pub multipliers: Vec<f64>,
+    pub comment: Option<String>,
+}
+
+impl Sectionable for Pattern {
+    type SelfType = Pattern;
+
+    fn from_section(properties: Vec<&str>, comment: Option<String>) -> Result<Pattern, SectionError> {
+        if properties.len() < 2 {
+            return Err(SectionError { message: "Not enough properties to create PATTERN section".to_string() });
+        }
+
+        let id = properties[0].to_string();
+        let multipliers = properties[1..].iter()
+            .map(|multiplier| multiplier.parse::<f64>())
+            .collect::<Result<Vec<f64>, _>>()?;
+
+        Ok(Pattern {
+            id,
+            multipliers,
+            comment,
+        })
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::Sectionable;
+    use super::Pattern;
+
+    #[test]
+    fn create_pattern_from_section() {
+        let a_pattern = Pattern::from_section(vec!["Pat1", "0.5", "1.3", "1"], None);
+
+        assert_eq!(
+            a_pattern,
+            Ok(Pattern {
+                id: "Pat1".to_string(),
+                multipliers: vec![0.5, 1.3, 1.0],
+                comment: None,
+            })
+        );
+    }
+
+    #[test]
+    fn at_least_one_multiplier_is_compulsory() {
+        assert!(Pattern::from_section(vec!["Pat1"], None).is_err());
+    }
+
+    #[test]
+    fn multipliers_must_be_numbers() {
+        assert!(Pattern::from_section(vec!["Pat1", "0.5", "high"], None).is_err());
+    }
+}
@@ -1,17 +1,17 @@
 use super::sectionable::{Sectionable, SectionError};
 use serde::{Deserialize, Serialize};
 
-#[derive(Serialize, Deserialize, Debug, PartialEq)]
+#[derive(Serialize, Deserialize, Debug, PartialEq, Clone)]
 pub struct Pipe {
-    id: String,
-    node1: String,
-    node2: String,
-    length: f64,
-    diameter: f64,
-    roughness: f64,
-    minor_loss: f64,
-    status: String,
-    comment: Option<String>,
+    pub id: String,
+    pub node1: String,
+    pub node2: String,
+    pub length: f64,
+    pub diameter: f64,
+    pub roughness: f64,
+    pub minor_loss: f64,
+    pub status: String,
+    pub comment: Option<String>,
 }
 
 impl Sectionable for Pipe {
@@ -1,7 +1,7 @@
 use super::sectionable::{Sectionable, SectionError};
 use serde::{Deserialize, Serialize};
 
-#[derive(Serialize, Deserialize, Debug, PartialEq)]
+#[derive(Serialize, Deserialize, Debug, PartialEq, Clone)]
 pub struct Pump {
     pub id: String,
     pub start_node: String,
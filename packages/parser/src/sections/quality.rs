@@ -1,11 +1,11 @@
 use super::sectionable::{Sectionable, SectionError};
 use serde::{Deserialize, Serialize};
 
-#[derive(Serialize, Deserialize, Debug, PartialEq)]
+#[derive(Serialize, Deserialize, Debug, PartialEq, Clone)]
 pub struct Quality {
-    nodeid: String,
-    initqual: f64,
-    comment: Option<String>,
+    pub nodeid: String,
+    pub initqual: f64,
+    pub comment: Option<String>,
 }
 
 impl Sectionable for Quality {
@@ -1,12 +1,12 @@
 use super::sectionable::{Sectionable, SectionError};
 use serde::{Deserialize, Serialize};
 
-#[derive(Serialize, Deserialize, Debug, PartialEq)]
+#[derive(Serialize, Deserialize, Debug, PartialEq, Clone)]
 pub struct Reservoir {
-    id: String,
-    head: f64,
-    pattern: Option<String>,
-    comment: Option<String>,
+    pub id: String,
+    pub head: f64,
+    pub pattern: Option<String>,
+    pub comment: Option<String>,
 }
 
 impl Sectionable for Reservoir {
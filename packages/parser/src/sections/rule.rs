@@ -0,0 +1,205 @@
+use super::sectionable::{Sectionable, SectionError};
+use super::control::ControlAction;
+use serde::{Deserialize, Serialize};
+
+/// Line of the [RULES] section. Rules span several lines, so they are grouped
+/// into `Rule`s once the whole section has been read.
+#[derive(Serialize, Deserialize, Debug, PartialEq, Clone)]
+pub struct RuleLine {
+    pub keyword: String,
+    pub words: Vec<String>,
+    pub comment: Option<String>,
+}
+
+#[derive(Serialize, Deserialize, Debug, PartialEq, Clone, Copy)]
+pub enum Relation {
+    Equal,
+    NotEqual,
+    Below,
+    Above,
+    BelowOrEqual,
+    AboveOrEqual,
+}
+
+/// Condition of a rule, e.g. `TANK T1 LEVEL ABOVE 12`.
+#[derive(Serialize, Deserialize, Debug, PartialEq, Clone)]
+pub struct Premise {
+    /// Joined to the previous premises with OR instead of AND.
+    pub or: bool,
+    pub object: String,
+    pub object_id: Option<String>,
+    pub attribute: String,
+    pub relation: Relation,
+    pub value: String,
+}
+
+#[derive(Serialize, Deserialize, Debug, PartialEq, Clone)]
+pub struct RuleAction {
+    pub link_id: String,
+    pub action: ControlAction,
+}
+
+#[derive(Serialize, Deserialize, Debug, PartialEq, Clone)]
+pub struct Rule {
+    pub id: String,
+    pub premises: Vec<Premise>,
+    pub then_actions: Vec<RuleAction>,
+    pub else_actions: Vec<RuleAction>,
+    pub priority: f64,
+}
+
+impl Sectionable for RuleLine {
+    type SelfType = RuleLine;
+
+    fn from_section(properties: Vec<&str>, comment: Option<String>) -> Result<RuleLine, SectionError> {
+        if properties.len() < 2 {
+            return Err(SectionError { message: "Not enough properties to create RULES section".to_string() });
+        }
+
+        Ok(RuleLine {
+            keyword: properties[0].to_uppercase(),
+            words: properties[1..].iter().map(|word| word.to_string()).collect(),
+            comment,
+        })
+    }
+}
+
+fn parse_relation(word: &str) -> Result<Relation, SectionError> {
+    match word.to_uppercase().as_str() {
+        "=" | "IS" => Ok(Relation::Equal),
+        "<>" | "NOT" => Ok(Relation::NotEqual),
+        "<" | "BELOW" => Ok(Relation::Below),
+        ">" | "ABOVE" => Ok(Relation::Above),
+        "<=" => Ok(Relation::BelowOrEqual),
+        ">=" => Ok(Relation::AboveOrEqual),
+        _ => Err(SectionError { message: format!("Invalid rule relation {}", word) }),
+    }
+}
+
+fn parse_premise(or: bool, words: &[String]) -> Result<Premise, SectionError> {
+    let object = words.first().map(|word| word.to_uppercase()).unwrap_or_default();
+    let (object_id, rest) = if object == "SYSTEM" { (None, &words[1..]) } else { (words.get(1).cloned(), &words[2.min(words.len())..]) };
+    if rest.len() < 3 {
+        return Err(SectionError { message: "Not enough properties to create a rule premise".to_string() });
+    }
+
+    Ok(Premise {
+        or,
+        object,
+        object_id,
+        attribute: rest[0].to_uppercase(),
+        relation: parse_relation(&rest[1])?,
+        value: rest[2..].join(" "),
+    })
+}
+
+fn parse_action(words: &[String]) -> Result<RuleAction, SectionError> {
+    if words.len() < 5 || !(words[3] == "=" || words[3].to_uppercase() == "IS") {
+        return Err(SectionError { message: "Not enough properties to create a rule action".to_string() });
+    }
+    let action = match (words[2].to_uppercase().as_str(), words[4].to_uppercase().as_str()) {
+        ("STATUS", "OPEN") => ControlAction::Open,
+        ("STATUS", "CLOSED") => ControlAction::Closed,
+        ("SETTING", _) => ControlAction::Setting(words[4].parse::<f64>()?),
+        _ => return Err(SectionError { message: format!("Invalid rule action {}", words.join(" ")) }),
+    };
+
+    Ok(RuleAction { link_id: words[1].to_string(), action })
+}
+
+/// Groups the lines of the [RULES] section into rules.
+pub fn parse_rules(lines: &[RuleLine]) -> Result<Vec<Rule>, SectionError> {
+    let mut rules: Vec<Rule> = Vec::new();
+    let mut clause = "";
+    for line in lines.iter() {
+        if line.keyword == "RULE" {
+            rules.push(Rule { id: line.words[0].clone(), premises: Vec::new(), then_actions: Vec::new(), else_actions: Vec::new(), priority: 0.0 });
+            clause = "IF";
+            continue;
+        }
+        let rule = rules.last_mut().ok_or_else(|| SectionError { message: format!("{} outside of a rule", line.keyword) })?;
+        match (line.keyword.as_str(), clause) {
+            ("IF", _) | ("AND", "IF") => rule.premises.push(parse_premise(false, &line.words)?),
+            ("OR", "IF") => rule.premises.push(parse_premise(true, &line.words)?),
+            ("THEN", _) | ("AND", "THEN") => {
+                rule.then_actions.push(parse_action(&line.words)?);
+                clause = "THEN";
+            },
+            ("ELSE", _) | ("AND", "ELSE") => {
+                rule.else_actions.push(parse_action(&line.words)?);
+                clause = "ELSE";
+            },
+            ("PRIORITY", _) => rule.priority = line.words[0].parse::<f64>()?,
+            _ => return Err(SectionError { message: format!("Invalid rule clause {}", line.keyword) }),
+        }
+    }
+
+    Ok(rules)
+}
+
+#[cfg(test)]
+mod test {
+    use super::Sectionable;
+    use super::{parse_rules, ControlAction, Premise, Relation, RuleAction, RuleLine};
+
+    fn lines(text: &str) -> Vec<RuleLine> {
+        text.lines()
+            .filter(|line| !line.trim().is_empty())
+            .map(|line| RuleLine::from_section(line.split_whitespace().collect(), None).unwrap())
+            .collect()
+    }
+
+    #[test]
+    fn create_rule_line_from_section() {
+        let a_line = RuleLine::from_section(vec!["if", "TANK", "T1", "LEVEL", ">", "12"], None);
+
+        assert_eq!(
+            a_line,
+            Ok(RuleLine {
+                keyword: "IF".to_string(),
+                words: vec!["TANK".to_string(), "T1".to_string(), "LEVEL".to_string(), ">".to_string(), "12".to_string()],
+                comment: None,
+            })
+        );
+    }
+
+    #[test]
+    fn group_lines_into_rules() {
+        let rules = parse_rules(&lines(r#"
+RULE 1
+IF TANK T1 LEVEL ABOVE 12
+AND SYSTEM CLOCKTIME >= 8 AM
+OR LINK P1 STATUS IS CLOSED
+THEN PUMP PU1 STATUS IS OPEN
+AND VALVE V1 SETTING = 30
+ELSE PUMP PU1 STATUS = CLOSED
+PRIORITY 2
+RULE 2
+IF JUNCTION J1 PRESSURE < 20
+THEN PIPE P2 STATUS = CLOSED
+"#)).unwrap();
+
+        assert_eq!(rules.len(), 2);
+        assert_eq!(rules[0].premises[1], Premise {
+            or: false,
+            object: "SYSTEM".to_string(),
+            object_id: None,
+            attribute: "CLOCKTIME".to_string(),
+            relation: Relation::AboveOrEqual,
+            value: "8 AM".to_string(),
+        });
+        assert!(rules[0].premises[2].or);
+        assert_eq!(rules[0].then_actions, vec![
+            RuleAction { link_id: "PU1".to_string(), action: ControlAction::Open },
+            RuleAction { link_id: "V1".to_string(), action: ControlAction::Setting(30.0) },
+        ]);
+        assert_eq!(rules[0].else_actions.len(), 1);
+        assert_eq!(rules[0].priority, 2.0);
+        assert_eq!(rules[1].premises[0].relation, Relation::Below);
+    }
+
+    #[test]
+    fn reject_clauses_outside_rules() {
+        assert!(parse_rules(&lines("IF TANK T1 LEVEL ABOVE 12")).is_err());
+    }
+}
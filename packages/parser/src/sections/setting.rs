@@ -0,0 +1,81 @@
+use super::sectionable::{Sectionable, SectionError};
+use serde::{Deserialize, Serialize};
+
+// Second words that turn the first word of a line into a two words key,
+// e.g. "Specific Gravity 1" or "Hydraulic Timestep 0:15".
+const COMPOUND_KEYS: [&str; 17] = [
+    "GRAVITY", "MULTIPLIER", "MODEL", "CHARGE", "EXPONENT", "PRESSURE", "TIMESTEP",
+    "START", "CLOCKTIME", "EFFICIENCY", "PRICE", "PATTERN", "BULK", "WALL", "TANK",
+    "POTENTIAL", "CORRELATION",
+];
+
+#[derive(Serialize, Deserialize, Debug, PartialEq, Clone)]
+pub struct Setting {
+    pub key: String,
+    pub value: String,
+    pub comment: Option<String>,
+}
+
+impl Sectionable for Setting {
+    type SelfType = Setting;
+
+    fn from_section(properties: Vec<&str>, comment: Option<String>) -> Result<Setting, SectionError> {
+        if properties.len() < 2 {
+            return Err(SectionError { message: "Not enough properties to create a setting".to_string() });
+        }
+
+        let compound = properties.len() > 2 && COMPOUND_KEYS.contains(&properties[1].to_uppercase().as_str());
+        let key_length = if compound { 2 } else { 1 };
+        let key = properties[..key_length].join(" ").to_uppercase();
+        let value = properties[key_length..].join(" ");
+
+        Ok(Setting {
+            key,
+            value,
+            comment,
+        })
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::Sectionable;
+    use super::Setting;
+
+    #[test]
+    fn create_setting_from_section() {
+        let a_setting = Setting::from_section(vec!["Units", "LPS"], None);
+
+        assert_eq!(
+            a_setting,
+            Ok(Setting {
+                key: "UNITS".to_string(),
+                value: "LPS".to_string(),
+                comment: None,
+            })
+        );
+    }
+
+    #[test]
+    fn create_setting_with_a_two_words_key() {
+        let a_setting = Setting::from_section(vec!["Specific", "Gravity", "1"], None).unwrap();
+
+        assert_eq!(a_setting.key, "SPECIFIC GRAVITY");
+        assert_eq!(a_setting.value, "1");
+    }
+
+    #[test]
+    fn keep_every_word_after_the_key_as_value() {
+        let a_setting = Setting::from_section(vec!["Unbalanced", "Continue", "10"], None).unwrap();
+
+        assert_eq!(a_setting.key, "UNBALANCED");
+        assert_eq!(a_setting.value, "Continue 10");
+    }
+
+    #[test]
+    fn a_value_is_compulsory() {
+        let a_setting = Setting::from_section(vec!["Units"], None);
+
+        assert!(a_setting.is_err());
+    }
+}
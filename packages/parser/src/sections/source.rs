@@ -1,13 +1,13 @@
 use super::sectionable::{Sectionable, SectionError};
 use serde::{Deserialize, Serialize};
 
-#[derive(Serialize, Deserialize, Debug, PartialEq)]
+#[derive(Serialize, Deserialize, Debug, PartialEq, Clone)]
 pub struct Source {
-    node: String,
-    source_type: String,
-    strength: f64,
-    pattern: Option<String>,
-    comment: Option<String>,
+    pub node: String,
+    pub source_type: String,
+    pub strength: f64,
+    pub pattern: Option<String>,
+    pub comment: Option<String>,
 }
 
 impl Sectionable for Source {
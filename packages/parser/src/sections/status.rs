@@ -0,0 +1,65 @@
+use super::sectionable::{Sectionable, SectionError};
+use super::control::ControlAction;
+use serde::{Deserialize, Serialize};
+
+#[derive(Serialize, Deserialize, Debug, PartialEq, Clone)]
+pub struct Status {
+    pub link_id: String,
+    pub status: ControlAction,
+    pub comment: Option<String>,
+}
+
+impl Sectionable for Status {
+    type SelfType = Status;
+
+    fn from_section(properties: Vec<&str>, comment: Option<String>) -> Result<Status, SectionError> {
+        if properties.len() < 2 {
+            return Err(SectionError { message: "Not enough properties to create STATUS section".to_string() });
+        }
+
+        let link_id = properties[0].to_string();
+        let status = match properties[1].to_uppercase().as_str() {
+            "OPEN" => ControlAction::Open,
+            "CLOSED" => ControlAction::Closed,
+            _ => ControlAction::Setting(properties[1].parse::<f64>()?),
+        };
+
+        Ok(Status {
+            link_id,
+            status,
+            comment,
+        })
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::Sectionable;
+    use super::{Status, ControlAction};
+
+    #[test]
+    fn create_status_from_section() {
+        let a_status = Status::from_section(vec!["V1", "Closed"], None);
+
+        assert_eq!(
+            a_status,
+            Ok(Status {
+                link_id: "V1".to_string(),
+                status: ControlAction::Closed,
+                comment: None,
+            })
+        );
+    }
+
+    #[test]
+    fn create_setting_status_from_section() {
+        let a_status = Status::from_section(vec!["PRV1", "35"], None).unwrap();
+
+        assert_eq!(a_status.status, ControlAction::Setting(35.0));
+    }
+
+    #[test]
+    fn status_is_compulsory() {
+        assert!(Status::from_section(vec!["V1"], None).is_err());
+    }
+}
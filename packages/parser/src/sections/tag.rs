@@ -0,0 +1,58 @@
+use super::sectionable::{Sectionable, SectionError};
+use serde::{Deserialize, Serialize};
+
+#[derive(Serialize, Deserialize, Debug, PartialEq, Clone)]
+pub struct Tag {
+    pub object_type: String,
+    pub object_id: String,
+    pub tag: String,
+    pub comment: Option<String>,
+}
+
+impl Sectionable for Tag {
+    type SelfType = Tag;
+
+    fn from_section(properties: Vec<&str>, comment: Option<String>) -> Result<Tag, SectionError> {
+        if properties.len() < 3 {
+            return Err(SectionError { message: "Not enough properties to create TAG section".to_string() });
+        }
+
+        let object_type = properties[0].to_uppercase();
+        if object_type != "NODE" && object_type != "LINK" {
+            return Err(SectionError { message: format!("Invalid tag object type {}", properties[0]) });
+        }
+
+        Ok(Tag {
+            object_type,
+            object_id: properties[1].to_string(),
+            tag: properties[2].to_string(),
+            comment,
+        })
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::Sectionable;
+    use super::Tag;
+
+    #[test]
+    fn create_tag_from_section() {
+        let a_tag = Tag::from_section(vec!["LINK", "P1", "ISOLATION"], None);
+
+        assert_eq!(
+            a_tag,
+            Ok(Tag {
+                object_type: "LINK".to_string(),
+                object_id: "P1".to_string(),
+                tag: "ISOLATION".to_string(),
+                comment: None,
+            })
+        );
+    }
+
+    #[test]
+    fn object_type_must_be_node_or_link() {
+        assert!(Tag::from_section(vec!["PIPE", "P1", "ISOLATION"], None).is_err());
+    }
+}
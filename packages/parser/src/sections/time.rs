@@ -0,0 +1,71 @@
+use super::sectionable::SectionError;
+
+/// Converts an EPANET time value into seconds. The value can be expressed as
+/// decimal hours or as hours:minutes[:seconds], optionally followed by a unit
+/// (SEC, MIN, HOURS, DAYS) or by AM/PM for clock times.
+pub fn parse_time(value: &str, unit: Option<&str>) -> Result<u64, SectionError> {
+    let seconds = if value.contains(':') {
+        let mut seconds = 0.0;
+        for (i, part) in value.split(':').enumerate() {
+            if i > 2 {
+                return Err(SectionError { message: format!("Invalid time {}", value) });
+            }
+            seconds += part.parse::<f64>()? * 3600.0 / 60f64.powi(i as i32);
+        }
+        seconds
+    } else {
+        let number = value.parse::<f64>()?;
+        match unit.map(|u| u.to_uppercase()).as_deref() {
+            Some(u) if u.starts_with("SEC") => number,
+            Some(u) if u.starts_with("MIN") => number * 60.0,
+            Some(u) if u.starts_with("DAY") => number * 86400.0,
+            _ => number * 3600.0,
+        }
+    };
+
+    let seconds = match unit.map(|u| u.to_uppercase()).as_deref() {
+        Some("AM") if seconds >= 43200.0 => seconds - 43200.0,
+        Some("PM") if seconds < 43200.0 => seconds + 43200.0,
+        _ => seconds,
+    };
+
+    if seconds < 0.0 {
+        return Err(SectionError { message: format!("Invalid time {}", value) });
+    }
+
+    Ok(seconds.round() as u64)
+}
+
+#[cfg(test)]
+mod test {
+    use super::parse_time;
+
+    #[test]
+    fn parse_decimal_hours() {
+        assert_eq!(parse_time("1.5", None), Ok(5400));
+    }
+
+    #[test]
+    fn parse_hours_and_minutes() {
+        assert_eq!(parse_time("0:15", None), Ok(900));
+        assert_eq!(parse_time("24:00:30", None), Ok(86430));
+    }
+
+    #[test]
+    fn parse_time_with_units() {
+        assert_eq!(parse_time("0", Some("SEC")), Ok(0));
+        assert_eq!(parse_time("90", Some("min")), Ok(5400));
+        assert_eq!(parse_time("2", Some("DAYS")), Ok(172800));
+    }
+
+    #[test]
+    fn parse_clock_times() {
+        assert_eq!(parse_time("12", Some("AM")), Ok(0));
+        assert_eq!(parse_time("8:30", Some("PM")), Ok(73800));
+    }
+
+    #[test]
+    fn reject_invalid_times() {
+        assert!(parse_time("noon", None).is_err());
+    }
+}
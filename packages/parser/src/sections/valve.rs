@@ -1,19 +1,19 @@
 use super::sectionable::{Sectionable, SectionError};
 use serde::{Deserialize, Serialize};
 
-#[derive(Serialize, Deserialize, Debug, PartialEq)]
+#[derive(Serialize, Deserialize, Debug, PartialEq, Clone)]
 pub struct Valve {
-    id: String,
-    start_node: String,
-    end_node: String,
-    diameter: f64,
-    valve_type: ValveType,
-    valve_setting: f64,
-    minor_loss_coefficient: f64,
-    comment: Option<String>,
+    pub id: String,
+    pub start_node: String,
+    pub end_node: String,
+    pub diameter: f64,
+    pub valve_type: ValveType,
+    pub valve_setting: f64,
+    pub minor_loss_coefficient: f64,
+    pub comment: Option<String>,
 }
 
-#[derive(Serialize, Deserialize, Debug, PartialEq)]
+#[derive(Serialize, Deserialize, Debug, PartialEq, Clone, Copy)]
 pub enum ValveType {
     Prv,
     Psv,
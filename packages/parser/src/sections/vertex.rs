@@ -0,0 +1,53 @@
+use super::sectionable::{Sectionable, SectionError};
+use serde::{Deserialize, Serialize};
+
+#[derive(Serialize, Deserialize, Debug, PartialEq, Clone)]
+pub struct Vertex {
+    pub link_id: String,
+    pub x: f64,
+    pub y: f64,
+    pub comment: Option<String>,
+}
+
+impl Sectionable for Vertex {
+    type SelfType = Vertex;
+
+    fn from_section(properties: Vec<&str>, comment: Option<String>) -> Result<Vertex, SectionError> {
+        if properties.len() < 3 {
+            return Err(SectionError { message: "Not enough properties to create VERTEX section".to_string() });
+        }
+
+        Ok(Vertex {
+            link_id: properties[0].to_string(),
+            x: properties[1].parse::<f64>()?,
+            y: properties[2].parse::<f64>()?,
+            comment,
+        })
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::Sectionable;
+    use super::Vertex;
+
+    #[test]
+    fn create_vertex_from_section() {
+        let a_vertex = Vertex::from_section(vec!["P1", "484232.933", "7881427.830"], None);
+
+        assert_eq!(
+            a_vertex,
+            Ok(Vertex {
+                link_id: "P1".to_string(),
+                x: 484232.933,
+                y: 7881427.830,
+                comment: None,
+            })
+        );
+    }
+
+    #[test]
+    fn x_and_y_are_compulsory() {
+        assert!(Vertex::from_section(vec!["P1"], None).is_err());
+    }
+}
@@ -0,0 +1,306 @@
+use std::collections::{BTreeSet, HashMap, HashSet};
+use serde::{Serialize, Deserialize};
+use crate::{INP, ToJson};
+use crate::network::{Network, LinkKind, NodeKind};
+
+/// Isolation valve placed on a link, next to one of its end nodes.
+#[derive(Serialize, Deserialize, Debug, PartialEq, Clone)]
+pub struct IsolationValve {
+    pub link_id: String,
+    pub node_id: String,
+}
+
+#[derive(Serialize, Deserialize, Debug, PartialEq)]
+pub struct Segment {
+    pub index: usize,
+    pub nodes: Vec<String>,
+    pub links: Vec<String>,
+    pub pipe_length: f64,
+    pub demand: f64,
+    /// Valves that have to be closed to isolate the segment.
+    pub valves: Vec<String>,
+}
+
+/// Valve of the segment-valve graph and the two segments it separates.
+#[derive(Serialize, Deserialize, Debug, PartialEq)]
+pub struct SegmentBoundary {
+    pub valve: String,
+    pub segments: (usize, usize),
+}
+
+#[derive(Serialize, Deserialize, Debug, PartialEq)]
+pub struct SegmentReport {
+    pub segments: Vec<Segment>,
+    pub boundaries: Vec<SegmentBoundary>,
+}
+
+impl ToJson for SegmentReport {}
+
+/// End of a tagged link where its isolation valve is placed.
+#[derive(Serialize, Deserialize, Debug, PartialEq, Clone, Copy, Default)]
+pub enum ValvePosition {
+    #[default]
+    Start,
+    End,
+    Both,
+}
+
+/// Isolation valves of the pipes and pumps tagged with `tag` in [TAGS] or whose comment
+/// has it as a whole word, placed at the `position` end of each link. [VALVES] are left
+/// out, as they already are segment boundaries.
+pub fn isolation_valves_from_tags(inp: &INP, tag: &str, position: ValvePosition) -> Vec<IsolationValve> {
+    let tag = tag.to_uppercase();
+    let tagged = inp.tags.iter()
+        .filter(|t| t.object_type == "LINK" && t.tag.to_uppercase() == tag)
+        .map(|t| t.object_id.as_str())
+        .collect::<HashSet<&str>>();
+    let has_tag = |id: &str, comment: &Option<String>| {
+        tagged.contains(id) || comment.as_ref().is_some_and(|comment| {
+            comment.to_uppercase()
+                .split(|c: char| !(c.is_alphanumeric() || c == '_' || c == '-'))
+                .any(|word| word == tag)
+        })
+    };
+
+    let links = inp.pipes.iter().map(|pipe| (&pipe.id, &pipe.node1, &pipe.node2, &pipe.comment))
+        .chain(inp.pumps.iter().map(|pump| (&pump.id, &pump.start_node, &pump.end_node, &pump.comment)));
+    let mut valves = Vec::new();
+    for (id, start, end, _) in links.filter(|(id, _, _, comment)| has_tag(id, comment)) {
+        let nodes = match position {
+            ValvePosition::Start => vec![start],
+            ValvePosition::End => vec![end],
+            ValvePosition::Both => vec![start, end],
+        };
+        valves.extend(nodes.into_iter().map(|node| IsolationValve { link_id: id.clone(), node_id: node.clone() }));
+    }
+    valves
+}
+
+fn find(parents: &mut [usize], element: usize) -> usize {
+    let mut root = element;
+    while parents[root] != root {
+        root = parents[root];
+    }
+    let mut element = element;
+    while parents[element] != root {
+        let next = parents[element];
+        parents[element] = root;
+        element = next;
+    }
+    root
+}
+
+fn union(parents: &mut [usize], a: usize, b: usize) {
+    let a = find(parents, a);
+    let b = find(parents, b);
+    if a != b {
+        parents[b] = a;
+    }
+}
+
+/// Splits the network in the segments left between the links of `INP::valves`
+/// and the given isolation valves. Isolation valves on [VALVES] links are ignored.
+pub fn find_segments(inp: &INP, isolation_valves: &[IsolationValve]) -> SegmentReport {
+    let network = Network::new(inp);
+    let node_count = network.nodes.len();
+    let element_count = node_count + network.links.len();
+    let isolation_valves = isolation_valves.iter()
+        .filter_map(|valve| Some((valve, network.link(&valve.link_id)?, network.node(&valve.node_id)?)))
+        .filter(|(_, link, _)| network.links[*link].kind != LinkKind::Valve)
+        .collect::<Vec<_>>();
+    let cuts = isolation_valves.iter()
+        .map(|(_, link, node)| (*link, *node))
+        .collect::<HashSet<(usize, usize)>>();
+
+    let mut parents = (0..element_count).collect::<Vec<usize>>();
+    for (index, link) in network.links.iter().enumerate() {
+        if link.kind == LinkKind::Valve {
+            continue;
+        }
+        for node in [link.start, link.end] {
+            if !cuts.contains(&(index, node)) {
+                union(&mut parents, node_count + index, node);
+            }
+        }
+    }
+
+    let mut segment_of_root = HashMap::new();
+    let mut segment_of = vec![0; element_count];
+    for (element, segment) in segment_of.iter_mut().enumerate() {
+        if element >= node_count && network.links[element - node_count].kind == LinkKind::Valve {
+            continue;
+        }
+        let root = find(&mut parents, element);
+        let next = segment_of_root.len();
+        *segment = *segment_of_root.entry(root).or_insert(next);
+    }
+
+    let mut segments = (0..segment_of_root.len())
+        .map(|index| Segment { index, nodes: Vec::new(), links: Vec::new(), pipe_length: 0.0, demand: 0.0, valves: Vec::new() })
+        .collect::<Vec<Segment>>();
+    // As in the simulation, [DEMANDS] replace the base demand of their junctions.
+    let mut demands = inp.junctions.iter()
+        .map(|junction| (junction.id.as_str(), junction.base_demand_flow.unwrap_or(0.0)))
+        .collect::<HashMap<&str, f64>>();
+    let mut replaced = HashSet::new();
+    for demand in inp.demands.iter() {
+        if let Some(total) = demands.get_mut(demand.junction_id.as_str()) {
+            if replaced.insert(demand.junction_id.as_str()) {
+                *total = 0.0;
+            }
+            *total += demand.base_demand;
+        }
+    }
+    for (index, node) in network.nodes.iter().enumerate() {
+        let segment = &mut segments[segment_of[index]];
+        segment.nodes.push(node.id.clone());
+        if node.kind == NodeKind::Junction {
+            segment.demand += demands.get(node.id.as_str()).copied().unwrap_or(0.0);
+        }
+    }
+
+    let mut boundaries = Vec::new();
+    for (index, link) in network.links.iter().enumerate() {
+        if link.kind == LinkKind::Valve {
+            boundaries.push(SegmentBoundary {
+                valve: link.id.clone(),
+                segments: (segment_of[link.start], segment_of[link.end]),
+            });
+            continue;
+        }
+        let segment = &mut segments[segment_of[node_count + index]];
+        segment.links.push(link.id.clone());
+        if link.kind == LinkKind::Pipe {
+            segment.pipe_length += link.length;
+        }
+    }
+    for (valve, link, node) in isolation_valves {
+        boundaries.push(SegmentBoundary {
+            valve: format!("{}@{}", valve.link_id, valve.node_id),
+            segments: (segment_of[node_count + link], segment_of[node]),
+        });
+    }
+
+    for (index, segment) in segments.iter_mut().enumerate() {
+        segment.valves = boundaries.iter()
+            .filter(|boundary| boundary.segments.0 != boundary.segments.1)
+            .filter(|boundary| boundary.segments.0 == index || boundary.segments.1 == index)
+            .map(|boundary| boundary.valve.clone())
+            .collect::<BTreeSet<String>>()
+            .into_iter()
+            .collect();
+    }
+
+    SegmentReport {
+        segments,
+        boundaries,
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::fs;
+    use super::{find_segments, isolation_valves_from_tags, IsolationValve, ValvePosition};
+    use crate::{INP, ToJson};
+
+    fn a_model() -> INP {
+        let input = r#"
+[JUNCTIONS]
+J1  10  1
+J2  10  2
+J3  10  3
+J4  10  4
+[RESERVOIRS]
+R1  100
+[PIPES]
+P1  R1  J1  100  100  100
+P2  J1  J2  200  100  100
+P3  J3  J4  300  100  100  0  OPEN  ;ISOLATION
+[VALVES]
+V1  J2  J3  100  TCV  0  0
+[DEMANDS]
+J4  3
+J4  2
+[TAGS]
+LINK  P1  Isolation
+"#;
+        INP::read(input.to_string())
+    }
+
+    #[test]
+    fn valves_split_the_network_in_segments() {
+        let report = find_segments(&a_model(), &[]);
+
+        assert_eq!(report.segments.len(), 2);
+        assert_eq!(report.segments[0].nodes, vec!["J1", "J2", "R1"]);
+        assert_eq!(report.segments[0].pipe_length, 300.0);
+        assert_eq!(report.segments[0].demand, 3.0);
+        assert_eq!(report.segments[0].valves, vec!["V1"]);
+        assert_eq!(report.segments[1].links, vec!["P3"]);
+        assert_eq!(report.segments[1].demand, 8.0);
+    }
+
+    #[test]
+    fn read_isolation_valves_from_tags_and_comments() {
+        let valves = isolation_valves_from_tags(&a_model(), "isolation", ValvePosition::Start);
+
+        assert_eq!(valves, vec![
+            IsolationValve { link_id: "P1".to_string(), node_id: "R1".to_string() },
+            IsolationValve { link_id: "P3".to_string(), node_id: "J3".to_string() },
+        ]);
+    }
+
+    #[test]
+    fn place_isolation_valves_at_the_chosen_end() {
+        let input = r#"
+[PIPES]
+P1  J1  J2  100  100  100  0  OPEN  ;No isolation-valve
+[PUMPS]
+PU1  J1  J2  POWER 10  ;Pump with isolation valves
+"#;
+        let inp = INP::read(input.to_string());
+
+        let at_end = isolation_valves_from_tags(&inp, "valves", ValvePosition::End);
+        let at_both = isolation_valves_from_tags(&inp, "valves", ValvePosition::Both);
+
+        assert_eq!(at_end, vec![IsolationValve { link_id: "PU1".to_string(), node_id: "J2".to_string() }]);
+        assert_eq!(at_both.iter().map(|valve| valve.node_id.as_str()).collect::<Vec<_>>(), vec!["J1", "J2"]);
+    }
+
+    #[test]
+    fn isolation_valves_split_segments() {
+        let inp = a_model();
+        let report = find_segments(&inp, &isolation_valves_from_tags(&inp, "ISOLATION", ValvePosition::Start));
+
+        assert_eq!(report.segments.len(), 4);
+        assert_eq!(report.segments[1].nodes, vec!["J3"]);
+        assert_eq!(report.segments[1].valves, vec!["P3@J3", "V1"]);
+        assert!(report.to_json().contains("\"valve\":\"P1@R1\""));
+    }
+
+    #[test]
+    fn leave_tagged_valves_as_they_are() {
+        let mut inp = a_model();
+        inp.valves[0].comment = Some("ISOLATION".to_string());
+        let tagged_valve = IsolationValve { link_id: "V1".to_string(), node_id: "J3".to_string() };
+
+        let valves = isolation_valves_from_tags(&inp, "ISOLATION", ValvePosition::Both);
+        let report = find_segments(&inp, &[tagged_valve]);
+
+        assert!(valves.iter().all(|valve| valve.link_id != "V1"));
+        assert_eq!(report, find_segments(&inp, &[]));
+        assert_eq!(report.segments[0].valves, vec!["V1"]);
+    }
+
+    #[test]
+    fn every_valve_of_magnetic_island_is_a_boundary() {
+        let inp = INP::read(fs::read_to_string("tests/MagneticIslandEnhanced.inp").unwrap());
+
+        let report = find_segments(&inp, &[]);
+
+        assert_eq!(report.boundaries.len(), inp.valves.len());
+        let total_length = report.segments.iter().map(|segment| segment.pipe_length).sum::<f64>();
+        let pipes_length = inp.pipes.iter().map(|pipe| pipe.length).sum::<f64>();
+        assert!((total_length - pipes_length).abs() < 1e-6);
+    }
+}
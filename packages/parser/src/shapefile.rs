@@ -0,0 +1,388 @@
+use std::fs;
+use std::io;
+use std::path::Path;
+use serde::Serialize;
+use serde_json::{Map, Value};
+use crate::INP;
+use crate::crs::Crs;
+use crate::geometry::{Geometry, Point};
+
+const MAX_FIELD_NAME: usize = 10;
+const NUMERIC_WIDTH: usize = 19;
+const NUMERIC_DECIMALS: usize = 8;
+const MAX_CHARACTER_WIDTH: usize = 254;
+
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum FieldKind {
+    Character,
+    Numeric,
+    Logical,
+}
+
+/// Column of a DBF table.
+#[derive(Debug, PartialEq, Clone)]
+pub struct Field {
+    /// Attribute of the section struct.
+    pub attribute: String,
+    /// Name in the DBF, at most ten characters.
+    pub name: String,
+    pub kind: FieldKind,
+    pub width: usize,
+    pub decimals: usize,
+}
+
+#[derive(Debug, PartialEq, Clone, Copy)]
+enum ShapeType {
+    Point = 1,
+    PolyLine = 3,
+}
+
+/// The .shp, .shx, .dbf, .prj and .cpg files of a layer.
+#[derive(Debug, PartialEq, Clone)]
+pub struct ShapefileLayer {
+    /// File name without extension, e.g. `junctions`.
+    pub name: String,
+    pub fields: Vec<Field>,
+    pub shp: Vec<u8>,
+    pub shx: Vec<u8>,
+    pub dbf: Vec<u8>,
+    /// Only written for the projections of `Crs`.
+    pub prj: Option<String>,
+    pub cpg: String,
+}
+
+/// DBF names of attributes: each name is cut to ten characters, and a name already
+/// taken is cut further to end with `_` and the lowest free number, e.g. `base_dem_1`.
+pub fn field_names(attributes: &[String]) -> Vec<String> {
+    let mut names: Vec<String> = Vec::new();
+    for attribute in attributes.iter() {
+        let mut name = attribute.chars().take(MAX_FIELD_NAME).collect::<String>();
+        let mut counter = 0;
+        while names.iter().any(|taken| taken.eq_ignore_ascii_case(&name)) {
+            counter += 1;
+            let suffix = format!("_{}", counter);
+            name = attribute.chars().take(MAX_FIELD_NAME - suffix.len()).collect::<String>() + &suffix;
+        }
+        names.push(name);
+    }
+    names
+}
+
+/// Attributes of every item, from its section struct.
+pub(crate) fn records<T: Serialize>(items: &[T]) -> Vec<Map<String, Value>> {
+    items.iter()
+        .map(|item| match serde_json::to_value(item).unwrap() {
+            Value::Object(attributes) => attributes,
+            _ => Map::new(),
+        })
+        .collect()
+}
+
+/// Columns of the records, `id` first and the others in alphabetical order.
+fn fields(records: &[Map<String, Value>]) -> Vec<Field> {
+    let mut attributes = records.iter().flat_map(|record| record.keys().cloned()).collect::<Vec<String>>();
+    attributes.sort_by_key(|attribute| (attribute != "id", attribute.clone()));
+    attributes.dedup();
+    let names = field_names(&attributes);
+
+    attributes.into_iter().zip(names)
+        .map(|(attribute, name)| {
+            let values = records.iter().filter_map(|record| record.get(&attribute)).filter(|value| !value.is_null());
+            let kind = match values.clone().next() {
+                Some(Value::Number(_)) => FieldKind::Numeric,
+                Some(Value::Bool(_)) => FieldKind::Logical,
+                _ => FieldKind::Character,
+            };
+            let (width, decimals) = match kind {
+                FieldKind::Numeric => (NUMERIC_WIDTH, NUMERIC_DECIMALS),
+                FieldKind::Logical => (1, 0),
+                FieldKind::Character => {
+                    let longest = values.map(|value| text(value).len()).max().unwrap_or(0);
+                    (longest.clamp(1, MAX_CHARACTER_WIDTH), 0)
+                },
+            };
+            Field { attribute, name, kind, width, decimals }
+        })
+        .collect()
+}
+
+fn text(value: &Value) -> String {
+    match value {
+        Value::String(text) => text.clone(),
+        Value::Null => String::new(),
+        value => value.to_string(),
+    }
+}
+
+/// Value of a field, padded to its width.
+fn field_value(field: &Field, value: Option<&Value>) -> Vec<u8> {
+    let value = value.unwrap_or(&Value::Null);
+    let formatted = match (field.kind, value) {
+        (_, Value::Null) => String::new(),
+        (FieldKind::Numeric, Value::Number(number)) => {
+            let number = number.as_f64().unwrap_or(0.0);
+            let fixed = format!("{:>width$.decimals$}", number, width = field.width, decimals = field.decimals);
+            if fixed.len() > field.width { format!("{:>width$e}", number, width = field.width) } else { fixed }
+        },
+        (FieldKind::Logical, Value::Bool(value)) => (if *value { "T" } else { "F" }).to_string(),
+        (_, value) => text(value),
+    };
+    let mut bytes = formatted.into_bytes();
+    bytes.truncate(field.width);
+    // Text is aligned to the left, numbers to the right.
+    let padding = vec![b' '; field.width - bytes.len()];
+    match field.kind {
+        FieldKind::Numeric => [padding, bytes].concat(),
+        _ => [bytes, padding].concat(),
+    }
+}
+
+fn dbf(fields: &[Field], records: &[Map<String, Value>]) -> Vec<u8> {
+    let header_length = 32 + 32 * fields.len() + 1;
+    let record_length = 1 + fields.iter().map(|field| field.width).sum::<usize>();
+    // dBASE III, with a fixed date of last update so that exports are reproducible.
+    let mut bytes = vec![0x03, 70, 1, 1];
+    bytes.extend((records.len() as u32).to_le_bytes());
+    bytes.extend((header_length as u16).to_le_bytes());
+    bytes.extend((record_length as u16).to_le_bytes());
+    bytes.extend([0; 20]);
+    for field in fields.iter() {
+        let mut name = field.name.as_bytes().to_vec();
+        name.resize(11, 0);
+        bytes.extend(name);
+        bytes.push(match field.kind {
+            FieldKind::Character => b'C',
+            FieldKind::Numeric => b'N',
+            FieldKind::Logical => b'L',
+        });
+        bytes.extend([0; 4]);
+        bytes.push(field.width as u8);
+        bytes.push(field.decimals as u8);
+        bytes.extend([0; 14]);
+    }
+    bytes.push(0x0D);
+    for record in records.iter() {
+        bytes.push(b' ');
+        for field in fields.iter() {
+            bytes.extend(field_value(field, record.get(&field.attribute)));
+        }
+    }
+    bytes.push(0x1A);
+    bytes
+}
+
+fn bounds(points: &[Point]) -> [f64; 4] {
+    points.iter().fold(None, |bounds: Option<[f64; 4]>, &(x, y)| match bounds {
+        None => Some([x, y, x, y]),
+        Some([min_x, min_y, max_x, max_y]) => Some([min_x.min(x), min_y.min(y), max_x.max(x), max_y.max(y)]),
+    }).unwrap_or([0.0; 4])
+}
+
+/// Header of the .shp and .shx files, with their length in bytes.
+fn shape_header(shape_type: ShapeType, length: usize, bounds: [f64; 4]) -> Vec<u8> {
+    let mut bytes = Vec::with_capacity(100);
+    bytes.extend(9994_i32.to_be_bytes());
+    bytes.extend([0; 20]);
+    bytes.extend(((length / 2) as i32).to_be_bytes());
+    bytes.extend(1000_i32.to_le_bytes());
+    bytes.extend((shape_type as i32).to_le_bytes());
+    for value in bounds.iter().chain([0.0; 4].iter()) {
+        bytes.extend(value.to_le_bytes());
+    }
+    bytes
+}
+
+/// Record contents of a shape, a null shape without geometry.
+fn shape_content(shape_type: ShapeType, points: Option<&[Point]>) -> Vec<u8> {
+    let mut bytes = Vec::new();
+    match points {
+        None => bytes.extend(0_i32.to_le_bytes()),
+        Some(points) => {
+            bytes.extend((shape_type as i32).to_le_bytes());
+            if shape_type == ShapeType::PolyLine {
+                for value in bounds(points).iter() {
+                    bytes.extend(value.to_le_bytes());
+                }
+                bytes.extend(1_i32.to_le_bytes());
+                bytes.extend((points.len() as i32).to_le_bytes());
+                bytes.extend(0_i32.to_le_bytes());
+            }
+            for (x, y) in points.iter() {
+                bytes.extend(x.to_le_bytes());
+                bytes.extend(y.to_le_bytes());
+            }
+        },
+    }
+    bytes
+}
+
+fn layer(name: &str, shape_type: ShapeType, shapes: Vec<Option<Vec<Point>>>, records: Vec<Map<String, Value>>, prj: Option<String>) -> ShapefileLayer {
+    let all_points = shapes.iter().flatten().flatten().copied().collect::<Vec<Point>>();
+    let mut contents = Vec::new();
+    let mut index = Vec::new();
+    let mut offset = 100;
+    for (number, shape) in shapes.iter().enumerate() {
+        let content = shape_content(shape_type, shape.as_deref());
+        index.extend(((offset / 2) as i32).to_be_bytes());
+        index.extend(((content.len() / 2) as i32).to_be_bytes());
+        contents.extend((number as i32 + 1).to_be_bytes());
+        contents.extend(((content.len() / 2) as i32).to_be_bytes());
+        offset += 8 + content.len();
+        contents.extend(content);
+    }
+    let bounds = bounds(&all_points);
+    let fields = fields(&records);
+
+    ShapefileLayer {
+        name: name.to_string(),
+        dbf: dbf(&fields, &records),
+        fields,
+        shp: [shape_header(shape_type, 100 + contents.len(), bounds), contents].concat(),
+        shx: [shape_header(shape_type, 100 + index.len(), bounds), index].concat(),
+        prj,
+        cpg: "UTF-8".to_string(),
+    }
+}
+
+/// Layers of junctions, reservoirs, tanks, pipes, pumps and valves, with a column for
+/// every attribute of their section. Elements without coordinates get a null shape.
+pub fn shapefile_layers(inp: &INP) -> Vec<ShapefileLayer> {
+    let geometry = Geometry::new(inp);
+    let prj = inp.projection().and_then(Crs::from_projection).map(|crs| crs.wkt());
+    let nodes = |ids: Vec<&String>| ids.into_iter().map(|id| geometry.node(id).map(|point| vec![point])).collect::<Vec<Option<Vec<Point>>>>();
+    let links = |ids: Vec<&String>| ids.into_iter().map(|id| geometry.link(id)).collect::<Vec<Option<Vec<Point>>>>();
+
+    vec![
+        layer("junctions", ShapeType::Point, nodes(inp.junctions.iter().map(|junction| &junction.id).collect()), records(&inp.junctions), prj.clone()),
+        layer("reservoirs", ShapeType::Point, nodes(inp.reservoirs.iter().map(|reservoir| &reservoir.id).collect()), records(&inp.reservoirs), prj.clone()),
+        layer("tanks", ShapeType::Point, nodes(inp.tanks.iter().map(|tank| &tank.id).collect()), records(&inp.tanks), prj.clone()),
+        layer("pipes", ShapeType::PolyLine, links(inp.pipes.iter().map(|pipe| &pipe.id).collect()), records(&inp.pipes), prj.clone()),
+        layer("pumps", ShapeType::PolyLine, links(inp.pumps.iter().map(|pump| &pump.id).collect()), records(&inp.pumps), prj.clone()),
+        layer("valves", ShapeType::PolyLine, links(inp.valves.iter().map(|valve| &valve.id).collect()), records(&inp.valves), prj),
+    ]
+}
+
+/// Writes the files of every layer into a directory, e.g. `pipes.shp`.
+pub fn write_shapefiles(inp: &INP, directory: &Path) -> io::Result<()> {
+    fs::create_dir_all(directory)?;
+    for layer in shapefile_layers(inp) {
+        let path = |extension: &str| directory.join(format!("{}.{}", layer.name, extension));
+        fs::write(path("shp"), &layer.shp)?;
+        fs::write(path("shx"), &layer.shx)?;
+        fs::write(path("dbf"), &layer.dbf)?;
+        fs::write(path("cpg"), &layer.cpg)?;
+        if let Some(prj) = &layer.prj {
+            fs::write(path("prj"), prj)?;
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use std::fs;
+    use super::{field_names, shapefile_layers, write_shapefiles, FieldKind};
+    use crate::INP;
+
+    fn a_model() -> INP {
+        INP::read(r#"
+[TITLE]
+Q_VAR_PROJECTION=EPSG:28355
+[JUNCTIONS]
+J1  10  5  ;corner
+J2  12
+[TANKS]
+T1  20  5  0  10  10  0
+[PIPES]
+P1  T1  J1  100  300  130
+P2  J1  J2  100  300  130
+[COORDINATES]
+T1  0  0
+J1  10  0
+[VERTICES]
+P1  5  2
+"#.to_string())
+    }
+
+    fn i32_be(bytes: &[u8], at: usize) -> i32 {
+        i32::from_be_bytes([bytes[at], bytes[at + 1], bytes[at + 2], bytes[at + 3]])
+    }
+
+    fn i32_le(bytes: &[u8], at: usize) -> i32 {
+        i32::from_le_bytes([bytes[at], bytes[at + 1], bytes[at + 2], bytes[at + 3]])
+    }
+
+    fn f64_le(bytes: &[u8], at: usize) -> f64 {
+        let mut value = [0; 8];
+        value.copy_from_slice(&bytes[at..at + 8]);
+        f64::from_le_bytes(value)
+    }
+
+    #[test]
+    fn cut_field_names_predictably() {
+        let attributes = ["id", "base_demand_flow", "base_demand_pattern", "base_demand_x", "valve_setting"].iter().map(|name| name.to_string()).collect::<Vec<String>>();
+
+        assert_eq!(field_names(&attributes), vec!["id", "base_deman", "base_dem_1", "base_dem_2", "valve_sett"]);
+    }
+
+    #[test]
+    fn write_points_with_attributes() {
+        let layers = shapefile_layers(&a_model());
+
+        assert_eq!(layers.iter().map(|layer| layer.name.as_str()).collect::<Vec<&str>>(), vec!["junctions", "reservoirs", "tanks", "pipes", "pumps", "valves"]);
+        let junctions = &layers[0];
+        assert_eq!(i32_be(&junctions.shp, 0), 9994);
+        assert_eq!(i32_be(&junctions.shp, 24) as usize * 2, junctions.shp.len());
+        assert_eq!(i32_le(&junctions.shp, 32), 1);
+        // J1, then J2 without coordinates.
+        assert_eq!(i32_le(&junctions.shp, 108), 1);
+        assert_eq!(f64_le(&junctions.shp, 112), 10.0);
+        assert_eq!(i32_le(&junctions.shp, 136), 0);
+        assert_eq!(junctions.shx.len(), 100 + 2 * 8);
+        assert_eq!(i32_be(&junctions.shx, 108), (100 + 28) / 2);
+
+        let names = junctions.fields.iter().map(|field| field.name.as_str()).collect::<Vec<&str>>();
+        assert_eq!(names, vec!["id", "base_deman", "comment", "demand_pat", "elevation"]);
+        assert_eq!(junctions.fields[1].kind, FieldKind::Numeric);
+        assert_eq!(junctions.fields[2].kind, FieldKind::Character);
+        let dbf = &junctions.dbf;
+        assert_eq!(u32::from_le_bytes([dbf[4], dbf[5], dbf[6], dbf[7]]), 2);
+        assert_eq!(&dbf[32..34], b"id");
+        let header = 32 + 32 * 5 + 1;
+        let record = String::from_utf8_lossy(&dbf[header..header + 1 + 2 + 19 + 6 + 1 + 19]);
+        assert!(record.starts_with(" J1         5.00000000corner"), "{}", record);
+        assert_eq!(layers[2].fields.iter().find(|field| field.attribute == "overflow").unwrap().kind, FieldKind::Logical);
+        assert!(junctions.prj.as_deref().unwrap().contains("GDA_1994_MGA_Zone_55"));
+    }
+
+    #[test]
+    fn write_polylines_through_vertices() {
+        let layers = shapefile_layers(&a_model());
+
+        let pipes = &layers[3];
+        assert_eq!(i32_le(&pipes.shp, 32), 3);
+        // P1 from T1 through its vertex to J1, then P2 without coordinates for J2.
+        assert_eq!(i32_le(&pipes.shp, 108), 3);
+        assert_eq!(i32_le(&pipes.shp, 144), 1);
+        assert_eq!(i32_le(&pipes.shp, 148), 3);
+        assert_eq!(f64_le(&pipes.shp, 156 + 16), 5.0);
+        assert_eq!(f64_le(&pipes.shp, 156 + 24), 2.0);
+        assert_eq!(i32_be(&pipes.shp, 104) as usize * 2, 4 + 32 + 8 + 4 + 48);
+        assert_eq!(f64_le(&pipes.shp, 36), 0.0);
+        assert_eq!(f64_le(&pipes.shp, 52), 10.0);
+    }
+
+    #[test]
+    fn write_files_into_a_directory() {
+        let directory = std::env::temp_dir().join(format!("shapefiles-{}", std::process::id()));
+
+        write_shapefiles(&a_model(), &directory).unwrap();
+
+        for name in ["junctions", "reservoirs", "tanks", "pipes", "pumps", "valves"] {
+            for extension in ["shp", "shx", "dbf", "prj", "cpg"] {
+                assert!(directory.join(format!("{}.{}", name, extension)).exists());
+            }
+        }
+        fs::remove_dir_all(directory).unwrap();
+    }
+}
@@ -0,0 +1,527 @@
+use std::collections::HashSet;
+use std::str::FromStr;
+use serde::{Serialize, Deserialize};
+use serde::de::DeserializeOwned;
+use serde_json::Value;
+use crate::{INP, SectionError};
+use crate::sections::{Junction, Reservoir, Tank, Pipe, Pump, Valve, ValveType, Emitter, Demand, Setting, ControlAction, ControlCondition};
+
+#[derive(Serialize, Deserialize, Debug, PartialEq, Clone, Copy)]
+pub enum FlowUnits {
+    Cfs,
+    Gpm,
+    Mgd,
+    Imgd,
+    Afd,
+    Lps,
+    Lpm,
+    Mld,
+    Cmh,
+    Cmd,
+}
+
+#[derive(Serialize, Deserialize, Debug, PartialEq, Clone, Copy)]
+pub enum UnitSystem {
+    Us,
+    Si,
+}
+
+#[derive(Serialize, Deserialize, Debug, PartialEq, Clone, Copy)]
+pub enum HeadlossFormula {
+    HazenWilliams,
+    DarcyWeisbach,
+    ChezyManning,
+}
+
+#[derive(Serialize, Deserialize, Debug, PartialEq, Clone, Copy)]
+pub enum Quantity {
+    Length,
+    Diameter,
+    Elevation,
+    Flow,
+    Pressure,
+    Power,
+    Roughness,
+    Volume,
+    Velocity,
+    EmitterCoefficient,
+}
+
+/// Numeric fields of a section and the quantity each one is expressed in.
+pub trait Measured {
+    const QUANTITIES: &'static [(&'static str, Quantity)];
+}
+
+impl FlowUnits {
+    pub fn name(&self) -> &'static str {
+        match self {
+            FlowUnits::Cfs => "CFS",
+            FlowUnits::Gpm => "GPM",
+            FlowUnits::Mgd => "MGD",
+            FlowUnits::Imgd => "IMGD",
+            FlowUnits::Afd => "AFD",
+            FlowUnits::Lps => "LPS",
+            FlowUnits::Lpm => "LPM",
+            FlowUnits::Mld => "MLD",
+            FlowUnits::Cmh => "CMH",
+            FlowUnits::Cmd => "CMD",
+        }
+    }
+
+    pub fn system(&self) -> UnitSystem {
+        match self {
+            FlowUnits::Cfs | FlowUnits::Gpm | FlowUnits::Mgd | FlowUnits::Imgd | FlowUnits::Afd => UnitSystem::Us,
+            _ => UnitSystem::Si,
+        }
+    }
+
+    fn cubic_metres_per_second(&self) -> f64 {
+        match self {
+            FlowUnits::Cfs => 0.028316847,
+            FlowUnits::Gpm => 0.028316847 / 448.831,
+            FlowUnits::Mgd => 0.028316847 / 0.64632,
+            FlowUnits::Imgd => 0.028316847 / 0.5382,
+            FlowUnits::Afd => 0.028316847 / 1.9837,
+            FlowUnits::Lps => 0.001,
+            FlowUnits::Lpm => 0.001 / 60.0,
+            FlowUnits::Mld => 1000.0 / 86400.0,
+            FlowUnits::Cmh => 1.0 / 3600.0,
+            FlowUnits::Cmd => 1.0 / 86400.0,
+        }
+    }
+}
+
+impl FromStr for FlowUnits {
+    type Err = SectionError;
+
+    fn from_str(s: &str) -> Result<FlowUnits, SectionError> {
+        match s.to_uppercase().as_str() {
+            "CFS" => Ok(FlowUnits::Cfs),
+            "GPM" => Ok(FlowUnits::Gpm),
+            "MGD" => Ok(FlowUnits::Mgd),
+            "IMGD" => Ok(FlowUnits::Imgd),
+            "AFD" => Ok(FlowUnits::Afd),
+            "LPS" => Ok(FlowUnits::Lps),
+            "LPM" => Ok(FlowUnits::Lpm),
+            "MLD" => Ok(FlowUnits::Mld),
+            "CMH" => Ok(FlowUnits::Cmh),
+            "CMD" => Ok(FlowUnits::Cmd),
+            _ => Err(SectionError { message: format!("Unknown flow units {}", s) }),
+        }
+    }
+}
+
+impl HeadlossFormula {
+    pub fn name(&self) -> &'static str {
+        match self {
+            HeadlossFormula::HazenWilliams => "H-W",
+            HeadlossFormula::DarcyWeisbach => "D-W",
+            HeadlossFormula::ChezyManning => "C-M",
+        }
+    }
+}
+
+impl FromStr for HeadlossFormula {
+    type Err = SectionError;
+
+    fn from_str(s: &str) -> Result<HeadlossFormula, SectionError> {
+        match s.to_uppercase().as_str() {
+            "H-W" => Ok(HeadlossFormula::HazenWilliams),
+            "D-W" => Ok(HeadlossFormula::DarcyWeisbach),
+            "C-M" => Ok(HeadlossFormula::ChezyManning),
+            _ => Err(SectionError { message: format!("Unknown headloss formula {}", s) }),
+        }
+    }
+}
+
+impl Quantity {
+    /// Unit in which the quantity is written in an INP file using the given flow units.
+    pub fn unit(&self, flow_units: FlowUnits, formula: HeadlossFormula) -> &'static str {
+        let us = flow_units.system() == UnitSystem::Us;
+        match self {
+            Quantity::Length | Quantity::Elevation => if us { "ft" } else { "m" },
+            Quantity::Diameter => if us { "in" } else { "mm" },
+            Quantity::Flow => flow_units.name(),
+            Quantity::Pressure => if us { "psi" } else { "m" },
+            Quantity::Power => if us { "hp" } else { "kW" },
+            Quantity::Roughness => match formula {
+                HeadlossFormula::DarcyWeisbach => if us { "mft" } else { "mm" },
+                _ => "",
+            },
+            Quantity::Volume => if us { "ft3" } else { "m3" },
+            Quantity::Velocity => if us { "ft/s" } else { "m/s" },
+            Quantity::EmitterCoefficient => if us { "flow/psi^0.5" } else { "flow/m^0.5" },
+        }
+    }
+
+    /// Factor that turns a value expressed in the given flow units into SI base units.
+    pub fn si_factor(&self, flow_units: FlowUnits, formula: HeadlossFormula) -> f64 {
+        let us = flow_units.system() == UnitSystem::Us;
+        match self {
+            Quantity::Length | Quantity::Elevation | Quantity::Velocity => if us { 0.3048 } else { 1.0 },
+            Quantity::Diameter => if us { 0.0254 } else { 0.001 },
+            Quantity::Flow => flow_units.cubic_metres_per_second(),
+            Quantity::Pressure => if us { 0.70307 } else { 1.0 },
+            Quantity::Power => if us { 0.745699872 } else { 1.0 },
+            Quantity::Roughness => match formula {
+                HeadlossFormula::DarcyWeisbach => if us { 0.0003048 } else { 0.001 },
+                _ => 1.0,
+            },
+            Quantity::Volume => if us { 0.028316847 } else { 1.0 },
+            Quantity::EmitterCoefficient => {
+                Quantity::Flow.si_factor(flow_units, formula) / Quantity::Pressure.si_factor(flow_units, formula).sqrt()
+            },
+        }
+    }
+
+    fn ratio(&self, from: FlowUnits, to: FlowUnits, formula: HeadlossFormula) -> f64 {
+        self.si_factor(from, formula) / self.si_factor(to, formula)
+    }
+}
+
+impl Measured for Junction {
+    const QUANTITIES: &'static [(&'static str, Quantity)] = &[
+        ("elevation", Quantity::Elevation),
+        ("base_demand_flow", Quantity::Flow),
+    ];
+}
+
+impl Measured for Reservoir {
+    const QUANTITIES: &'static [(&'static str, Quantity)] = &[
+        ("head", Quantity::Elevation),
+    ];
+}
+
+impl Measured for Tank {
+    const QUANTITIES: &'static [(&'static str, Quantity)] = &[
+        ("elevation", Quantity::Elevation),
+        ("init_level", Quantity::Length),
+        ("min_level", Quantity::Length),
+        ("max_level", Quantity::Length),
+        ("diameter", Quantity::Length),
+        ("min_volume", Quantity::Volume),
+    ];
+}
+
+impl Measured for Pipe {
+    const QUANTITIES: &'static [(&'static str, Quantity)] = &[
+        ("length", Quantity::Length),
+        ("diameter", Quantity::Diameter),
+        ("roughness", Quantity::Roughness),
+    ];
+}
+
+impl Measured for Pump {
+    const QUANTITIES: &'static [(&'static str, Quantity)] = &[
+        ("power", Quantity::Power),
+    ];
+}
+
+impl Measured for Valve {
+    const QUANTITIES: &'static [(&'static str, Quantity)] = &[
+        ("diameter", Quantity::Diameter),
+    ];
+}
+
+impl Measured for Emitter {
+    const QUANTITIES: &'static [(&'static str, Quantity)] = &[
+        ("flow_coefficient", Quantity::EmitterCoefficient),
+    ];
+}
+
+impl Measured for Demand {
+    const QUANTITIES: &'static [(&'static str, Quantity)] = &[
+        ("base_demand", Quantity::Flow),
+    ];
+}
+
+/// Quantity of a valve setting, which depends on the valve type.
+pub fn valve_setting_quantity(valve_type: &ValveType) -> Option<Quantity> {
+    match valve_type {
+        ValveType::Prv | ValveType::Psv | ValveType::Pbv => Some(Quantity::Pressure),
+        ValveType::Fcv => Some(Quantity::Flow),
+        ValveType::Tcv | ValveType::Gpv => None,
+    }
+}
+
+/// Multiplies the fields of `T::QUANTITIES` by the ratio of their quantity.
+fn rescale<T: Measured + Serialize + DeserializeOwned>(items: &mut [T], ratio: &impl Fn(Quantity) -> f64) {
+    for item in items.iter_mut() {
+        let mut value = serde_json::to_value(&*item).unwrap();
+        for (field, quantity) in T::QUANTITIES.iter() {
+            if let Some(number) = value[*field].as_f64() {
+                value[*field] = Value::from(number * ratio(*quantity));
+            }
+        }
+        *item = serde_json::from_value(value).unwrap();
+    }
+}
+
+/// Quantity of a rule premise or action attribute, e.g. `PRESSURE` or the `SETTING` of a valve.
+fn rule_quantity(attribute: &str, link: Option<&Valve>) -> Option<Quantity> {
+    match attribute {
+        "DEMAND" | "FLOW" => Some(Quantity::Flow),
+        "HEAD" | "GRADE" => Some(Quantity::Elevation),
+        "LEVEL" => Some(Quantity::Length),
+        "PRESSURE" => Some(Quantity::Pressure),
+        "SETTING" => link.and_then(|valve| valve_setting_quantity(&valve.valve_type)),
+        _ => None,
+    }
+}
+
+/// Rescales every value of the model from its current flow units to `to`, switching
+/// between US customary and SI units when needed. Pattern multipliers are left untouched.
+pub fn convert_units(inp: &mut INP, to: FlowUnits) {
+    let from = inp.flow_units();
+    let formula = inp.headloss_formula();
+    let emitter_exponent = inp.option_number("EMITTER EXPONENT").unwrap_or(0.5);
+    let ratio = |quantity: Quantity| match quantity {
+        Quantity::EmitterCoefficient => Quantity::Flow.ratio(from, to, formula) / Quantity::Pressure.ratio(from, to, formula).powf(emitter_exponent),
+        quantity => quantity.ratio(from, to, formula),
+    };
+
+    rescale(&mut inp.junctions, &ratio);
+    rescale(&mut inp.reservoirs, &ratio);
+    rescale(&mut inp.tanks, &ratio);
+    rescale(&mut inp.pipes, &ratio);
+    rescale(&mut inp.pumps, &ratio);
+    rescale(&mut inp.valves, &ratio);
+    rescale(&mut inp.emitters, &ratio);
+    rescale(&mut inp.demands, &ratio);
+    // GPV settings are the ID of their headloss curve.
+    for valve in inp.valves.iter_mut() {
+        if let Some(quantity) = valve_setting_quantity(&valve.valve_type) {
+            valve.valve_setting *= ratio(quantity);
+        }
+    }
+
+    // Pump and GPV headloss curves give the head for a flow, efficiency curves the
+    // efficiency in percent for a flow and volume curves the volume for a level.
+    let mut head_curves = inp.pumps.iter().filter_map(|pump| pump.head.clone()).collect::<HashSet<String>>();
+    head_curves.extend(inp.valves.iter().filter(|valve| valve.valve_type == ValveType::Gpv).map(|valve| valve.valve_setting.to_string()));
+    let efficiency_curves = inp.energy.iter()
+        .filter(|setting| setting.key == "PUMP")
+        .filter_map(|setting| match setting.value.split_whitespace().collect::<Vec<&str>>()[..] {
+            [_, parameter, curve] if parameter.to_uppercase().starts_with("EFFIC") => Some(curve.to_string()),
+            _ => None,
+        })
+        .collect::<HashSet<String>>();
+    let volume_curves = inp.tanks.iter().filter_map(|tank| tank.volume_curve_id.clone()).collect::<HashSet<String>>();
+    for point in inp.curves.iter_mut() {
+        if head_curves.contains(&point.id) {
+            point.x *= ratio(Quantity::Flow);
+            point.y *= ratio(Quantity::Length);
+        } else if efficiency_curves.contains(&point.id) {
+            point.x *= ratio(Quantity::Flow);
+        } else if volume_curves.contains(&point.id) {
+            point.x *= ratio(Quantity::Length);
+            point.y *= ratio(Quantity::Volume);
+        }
+    }
+
+    let tanks = inp.tanks.iter().map(|tank| tank.id.as_str()).collect::<HashSet<&str>>();
+    for control in inp.controls.iter_mut() {
+        match &mut control.condition {
+            ControlCondition::Above { node_id, value } | ControlCondition::Below { node_id, value } => {
+                let quantity = if tanks.contains(node_id.as_str()) { Quantity::Length } else { Quantity::Pressure };
+                *value *= ratio(quantity);
+            },
+            _ => {}
+        }
+        let quantity = inp.valves.iter()
+            .find(|valve| valve.id == control.link_id)
+            .and_then(|valve| valve_setting_quantity(&valve.valve_type));
+        if let ControlAction::Setting(setting) = &mut control.action {
+            if let Some(quantity) = quantity {
+                *setting *= ratio(quantity);
+            }
+        }
+    }
+
+    // Premises are `OBJECT ID ATTRIBUTE RELATION VALUE`, or `SYSTEM ATTRIBUTE RELATION VALUE`,
+    // and actions `OBJECT ID SETTING IS VALUE`.
+    for line in inp.rules.iter_mut().filter(|line| ["IF", "AND", "OR", "THEN", "ELSE"].contains(&line.keyword.as_str())) {
+        let system = line.words.first().is_some_and(|object| object.eq_ignore_ascii_case("SYSTEM"));
+        let attribute = if system { 1 } else { 2 };
+        let valve = if system { None } else { inp.valves.iter().find(|valve| Some(&valve.id) == line.words.get(1)) };
+        let quantity = line.words.get(attribute).and_then(|name| rule_quantity(&name.to_uppercase(), valve));
+        if let (Some(quantity), Some(word)) = (quantity, line.words.get_mut(attribute + 2)) {
+            if let Ok(value) = word.parse::<f64>() {
+                *word = (value * ratio(quantity)).to_string();
+            }
+        }
+    }
+
+    // Wall coefficients are in length a day for first order reactions and in mass by
+    // area a day for zero order ones. Bulk coefficients don't depend on length.
+    let wall_ratio = match inp.reaction("ORDER WALL") {
+        Some(0.0) => 1.0 / ratio(Quantity::Length).powi(2),
+        _ => ratio(Quantity::Length),
+    };
+    for setting in inp.reactions.iter_mut() {
+        let coefficient = match setting.key.as_str() {
+            "GLOBAL WALL" => 0,
+            "WALL" => 1,
+            _ => continue,
+        };
+        let mut words = setting.value.split_whitespace().map(str::to_string).collect::<Vec<String>>();
+        if let Some(word) = words.get_mut(coefficient) {
+            if let Ok(value) = word.parse::<f64>() {
+                *word = (value * wall_ratio).to_string();
+                setting.value = words.join(" ");
+            }
+        }
+    }
+
+    for setting in inp.options.iter_mut() {
+        if ["MINIMUM PRESSURE", "REQUIRED PRESSURE"].contains(&setting.key.as_str()) {
+            if let Ok(pressure) = setting.value.parse::<f64>() {
+                setting.value = (pressure * ratio(Quantity::Pressure)).to_string();
+            }
+        }
+    }
+    match inp.options.iter_mut().find(|setting| setting.key == "UNITS") {
+        Some(setting) => setting.value = to.name().to_string(),
+        None => inp.options.push(Setting { key: "UNITS".to_string(), value: to.name().to_string(), comment: None }),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::fs;
+    use super::{convert_units, FlowUnits, HeadlossFormula, Quantity, Measured};
+    use crate::INP;
+    use crate::sections::{Pipe, ControlCondition};
+
+    fn assert_close(actual: f64, expected: f64) {
+        assert!((actual - expected).abs() < 1e-3 * expected.abs().max(1.0), "{} != {}", actual, expected);
+    }
+
+    #[test]
+    fn describe_the_units_of_a_field() {
+        let (field, quantity) = Pipe::QUANTITIES[1];
+
+        assert_eq!(field, "diameter");
+        assert_eq!(quantity.unit(FlowUnits::Gpm, HeadlossFormula::HazenWilliams), "in");
+        assert_eq!(quantity.unit(FlowUnits::Lps, HeadlossFormula::HazenWilliams), "mm");
+        assert_eq!(Quantity::Roughness.unit(FlowUnits::Lps, HeadlossFormula::DarcyWeisbach), "mm");
+        assert_eq!(Quantity::Roughness.unit(FlowUnits::Lps, HeadlossFormula::HazenWilliams), "");
+    }
+
+    #[test]
+    fn convert_us_model_to_si() {
+        let input = r#"
+[JUNCTIONS]
+J1  700  150
+[RESERVOIRS]
+R1  800
+[TANKS]
+T1  850  10  0  20  50  0
+[PIPES]
+P1  R1  J1  1000  12  0.5
+[PUMPS]
+PU1  J1  T1  HEAD C1
+[VALVES]
+V1  J1  T1  8  PRV  50  0
+[CURVES]
+C1  1000  100
+[CONTROLS]
+LINK PU1 CLOSED IF NODE T1 ABOVE 15
+LINK V1 40 IF NODE J1 BELOW 30
+[OPTIONS]
+Units  GPM
+Headloss  D-W
+"#;
+        let mut inp = INP::read(input.to_string());
+
+        convert_units(&mut inp, FlowUnits::Lps);
+
+        assert_close(inp.junctions[0].elevation, 213.36);
+        assert_close(inp.junctions[0].base_demand_flow.unwrap(), 9.4635);
+        assert_close(inp.reservoirs[0].head, 243.84);
+        assert_close(inp.tanks[0].diameter, 15.24);
+        assert_close(inp.pipes[0].length, 304.8);
+        assert_close(inp.pipes[0].diameter, 304.8);
+        assert_close(inp.pipes[0].roughness, 0.1524);
+        assert_close(inp.valves[0].valve_setting, 35.1535);
+        assert_close(inp.curves[0].x, 63.09);
+        assert_close(inp.curves[0].y, 30.48);
+        assert_eq!(inp.controls[0].condition, ControlCondition::Above { node_id: "T1".to_string(), value: 15.0 * 0.3048 });
+        assert_eq!(inp.option("UNITS"), Some("LPS"));
+    }
+
+    #[test]
+    fn convert_demands_curves_and_rules() {
+        let input = r#"
+[JUNCTIONS]
+J1  700  150
+[TANKS]
+T1  850  10  0  20  50  0
+[PIPES]
+P1  J1  T1  1000  12  100
+[PUMPS]
+PU1  J1  T1  HEAD C1
+[VALVES]
+V1  J1  T1  8  PRV  50  0
+V2  T1  J1  8  GPV  3  0
+[DEMANDS]
+J1  100  Day
+[ENERGY]
+Pump PU1 Efficiency E1
+[REACTIONS]
+Global Wall  -1
+Wall  P1  -0.5
+[CURVES]
+C1  1000  100
+E1  1000  75
+3  1000  10
+[RULES]
+RULE 1
+IF TANK T1 LEVEL ABOVE 15
+AND SYSTEM DEMAND > 1000
+THEN VALVE V1 SETTING = 40
+ELSE PUMP PU1 SETTING = 1.2
+[OPTIONS]
+Units  GPM
+"#;
+        let mut inp = INP::read(input.to_string());
+
+        convert_units(&mut inp, FlowUnits::Lps);
+
+        assert_close(inp.demands[0].base_demand, 6.309);
+        assert_eq!(inp.valves[1].valve_setting, 3.0);
+        assert_close(inp.curves[1].x, 63.09);
+        assert_close(inp.curves[1].y, 75.0);
+        assert_close(inp.curves[2].x, 63.09);
+        assert_close(inp.curves[2].y, 3.048);
+        let value = |line: usize, word: usize| inp.rules[line].words[word].parse::<f64>().unwrap();
+        assert_close(value(1, 4), 4.572);
+        assert_close(value(2, 3), 63.09);
+        assert_close(value(3, 4), 28.1228);
+        assert_close(value(4, 4), 1.2);
+        assert_close(inp.reaction("GLOBAL WALL").unwrap(), -0.3048);
+        assert_eq!(inp.reactions[1].value, format!("P1 {}", -0.5 * 0.3048));
+
+        // Zero order wall coefficients are by area.
+        let mut inp = INP::read(format!("{}[REACTIONS]\nOrder Wall  0\n", input));
+        convert_units(&mut inp, FlowUnits::Lps);
+        assert_close(inp.reaction("GLOBAL WALL").unwrap(), -1.0 / (0.3048 * 0.3048));
+    }
+
+    #[test]
+    fn convert_back_and_forth() {
+        let input = fs::read_to_string("tests/MagneticIslandEnhanced.inp").unwrap();
+        let original = INP::read(input.clone());
+        let mut inp = INP::read(input);
+
+        convert_units(&mut inp, FlowUnits::Gpm);
+        assert_eq!(inp.flow_units(), FlowUnits::Gpm);
+        assert_close(inp.pipes[0].diameter, 150.0 / 25.4);
+
+        convert_units(&mut inp, FlowUnits::Lps);
+        for (converted, pipe) in inp.pipes.iter().zip(original.pipes.iter()) {
+            assert_close(converted.length, pipe.length);
+            assert_close(converted.roughness, pipe.roughness);
+        }
+    }
+}
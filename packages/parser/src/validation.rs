@@ -0,0 +1,276 @@
+use std::collections::HashSet;
+use serde::{Serialize, Deserialize};
+use serde_json::Value;
+use crate::connectivity::{analyze_connectivity, ConnectivityOptions};
+use crate::sections::ControlCondition;
+use crate::INP;
+
+#[derive(Serialize, Deserialize, Debug, PartialEq, Eq, PartialOrd, Ord, Clone, Copy)]
+pub enum Severity {
+    Error,
+    Warning,
+}
+
+#[derive(Serialize, Deserialize, Debug, PartialEq, Clone)]
+pub struct Diagnostic {
+    pub severity: Severity,
+    pub section: String,
+    /// Element the diagnostic is about, if any.
+    pub id: Option<String>,
+    pub message: String,
+    /// Line of the file, for lines that couldn't be read.
+    pub line: Option<i32>,
+}
+
+impl Diagnostic {
+    fn error(section: &str, id: &str, message: String) -> Diagnostic {
+        Diagnostic { severity: Severity::Error, section: section.to_string(), id: Some(id.to_string()), message, line: None }
+    }
+
+    fn warning(section: &str, id: &str, message: String) -> Diagnostic {
+        Diagnostic { severity: Severity::Warning, section: section.to_string(), id: Some(id.to_string()), message, line: None }
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, PartialEq)]
+pub struct Validation {
+    pub diagnostics: Vec<Diagnostic>,
+}
+
+impl Validation {
+    pub fn has_errors(&self) -> bool {
+        self.diagnostics.iter().any(|diagnostic| diagnostic.severity == Severity::Error)
+    }
+
+    pub fn errors(&self) -> impl Iterator<Item = &Diagnostic> {
+        self.diagnostics.iter().filter(|diagnostic| diagnostic.severity == Severity::Error)
+    }
+
+    pub fn warnings(&self) -> impl Iterator<Item = &Diagnostic> {
+        self.diagnostics.iter().filter(|diagnostic| diagnostic.severity == Severity::Warning)
+    }
+
+    pub fn to_json(&self) -> Value {
+        serde_json::to_value(self).unwrap()
+    }
+}
+
+/// Checks a model for the mistakes EPANET would refuse to run: unreadable lines,
+/// duplicated IDs, references to missing nodes, links, patterns or curves, including
+/// those of rules, energy, reactions and the trace node, and invalid values. Nodes
+/// without supply are reported as warnings.
+pub fn validate(inp: &INP) -> Validation {
+    let mut diagnostics = Vec::new();
+
+    for error in inp.errors.iter() {
+        diagnostics.push(Diagnostic {
+            severity: Severity::Error,
+            section: String::new(),
+            id: None,
+            message: format!("{}: {}", error.message, error.line.trim()),
+            line: Some(error.line_number),
+        });
+    }
+
+    let mut nodes = HashSet::new();
+    let node_ids = inp.junctions.iter().map(|junction| ("JUNCTIONS", &junction.id))
+        .chain(inp.reservoirs.iter().map(|reservoir| ("RESERVOIRS", &reservoir.id)))
+        .chain(inp.tanks.iter().map(|tank| ("TANKS", &tank.id)));
+    for (section, id) in node_ids {
+        if !nodes.insert(id.as_str()) {
+            diagnostics.push(Diagnostic::error(section, id, format!("Duplicate node ID {}", id)));
+        }
+    }
+    let mut links = HashSet::new();
+    let link_ends = inp.pipes.iter().map(|pipe| ("PIPES", &pipe.id, &pipe.node1, &pipe.node2))
+        .chain(inp.pumps.iter().map(|pump| ("PUMPS", &pump.id, &pump.start_node, &pump.end_node)))
+        .chain(inp.valves.iter().map(|valve| ("VALVES", &valve.id, &valve.start_node, &valve.end_node)));
+    for (section, id, start, end) in link_ends {
+        if !links.insert(id.as_str()) {
+            diagnostics.push(Diagnostic::error(section, id, format!("Duplicate link ID {}", id)));
+        }
+        for node in [start, end] {
+            if !nodes.contains(node.as_str()) {
+                diagnostics.push(Diagnostic::error(section, id, format!("Link {} refers to undefined node {}", id, node)));
+            }
+        }
+        if start == end {
+            diagnostics.push(Diagnostic::error(section, id, format!("Link {} starts and ends at node {}", id, start)));
+        }
+    }
+    if inp.reservoirs.is_empty() && inp.tanks.is_empty() && !nodes.is_empty() {
+        diagnostics.push(Diagnostic { severity: Severity::Error, section: "RESERVOIRS".to_string(), id: None, message: "No reservoirs or tanks".to_string(), line: None });
+    }
+
+    let patterns = inp.patterns.iter().map(|pattern| pattern.id.as_str()).collect::<HashSet<&str>>();
+    let pattern_references = inp.junctions.iter().map(|junction| ("JUNCTIONS", &junction.id, &junction.demand_pattern_id))
+        .chain(inp.demands.iter().map(|demand| ("DEMANDS", &demand.junction_id, &demand.pattern_id)))
+        .chain(inp.reservoirs.iter().map(|reservoir| ("RESERVOIRS", &reservoir.id, &reservoir.pattern)))
+        .chain(inp.pumps.iter().map(|pump| ("PUMPS", &pump.id, &pump.pattern)))
+        .chain(inp.sources.iter().map(|source| ("SOURCES", &source.node, &source.pattern)));
+    for (section, id, pattern) in pattern_references {
+        if let Some(pattern) = pattern.as_deref().filter(|pattern| !patterns.contains(pattern)) {
+            diagnostics.push(Diagnostic::error(section, id, format!("{} refers to undefined pattern {}", id, pattern)));
+        }
+    }
+
+    let curves = inp.curves.iter().map(|curve| curve.id.as_str()).collect::<HashSet<&str>>();
+    let curve_references = inp.pumps.iter().map(|pump| ("PUMPS", &pump.id, &pump.head))
+        .chain(inp.tanks.iter().map(|tank| ("TANKS", &tank.id, &tank.volume_curve_id)));
+    for (section, id, curve) in curve_references {
+        if let Some(curve) = curve.as_deref().filter(|curve| *curve != "*" && !curves.contains(curve)) {
+            diagnostics.push(Diagnostic::error(section, id, format!("{} refers to undefined curve {}", id, curve)));
+        }
+    }
+
+    let undefined_links = inp.controls.iter().map(|control| ("CONTROLS", &control.link_id))
+        .chain(inp.statuses.iter().map(|status| ("STATUS", &status.link_id)));
+    for (section, id) in undefined_links.filter(|(_, id)| !links.contains(id.as_str())) {
+        diagnostics.push(Diagnostic::error(section, id, format!("Undefined link {}", id)));
+    }
+    for control in inp.controls.iter() {
+        if let ControlCondition::Above { node_id, .. } | ControlCondition::Below { node_id, .. } = &control.condition {
+            if !nodes.contains(node_id.as_str()) {
+                diagnostics.push(Diagnostic::error("CONTROLS", &control.link_id, format!("Control of {} refers to undefined node {}", control.link_id, node_id)));
+            }
+        }
+    }
+    if let Ok(rules) = inp.rules() {
+        for rule in rules.iter() {
+            for premise in rule.premises.iter() {
+                let (kind, defined) = match premise.object.as_str() {
+                    "NODE" | "JUNCTION" | "RESERVOIR" | "TANK" => ("node", &nodes),
+                    "LINK" | "PIPE" | "PUMP" | "VALVE" => ("link", &links),
+                    _ => continue,
+                };
+                if let Some(id) = premise.object_id.as_deref().filter(|id| !defined.contains(id)) {
+                    diagnostics.push(Diagnostic::error("RULES", &rule.id, format!("Rule {} refers to undefined {} {}", rule.id, kind, id)));
+                }
+            }
+            for action in rule.then_actions.iter().chain(rule.else_actions.iter()).filter(|action| !links.contains(action.link_id.as_str())) {
+                diagnostics.push(Diagnostic::error("RULES", &rule.id, format!("Rule {} refers to undefined link {}", rule.id, action.link_id)));
+            }
+        }
+    }
+    // Settings naming an element by their first word, e.g. `BULK P1 -0.5`.
+    let pumps = inp.pumps.iter().map(|pump| pump.id.as_str()).collect::<HashSet<&str>>();
+    let pipes = inp.pipes.iter().map(|pipe| pipe.id.as_str()).collect::<HashSet<&str>>();
+    let tanks = inp.tanks.iter().map(|tank| tank.id.as_str()).collect::<HashSet<&str>>();
+    let setting_references = inp.energy.iter().filter(|setting| setting.key == "PUMP").map(|setting| ("ENERGY", "pump", &pumps, setting))
+        .chain(inp.reactions.iter().filter(|setting| setting.key == "BULK" || setting.key == "WALL").map(|setting| ("REACTIONS", "pipe", &pipes, setting)))
+        .chain(inp.reactions.iter().filter(|setting| setting.key == "TANK").map(|setting| ("REACTIONS", "tank", &tanks, setting)));
+    for (section, kind, defined, setting) in setting_references {
+        if let Some(id) = setting.value.split_whitespace().next().filter(|id| !defined.contains(id)) {
+            diagnostics.push(Diagnostic::error(section, id, format!("{} refers to undefined {} {}", setting.key, kind, id)));
+        }
+    }
+    let trace = inp.option("QUALITY").map(|value| value.split_whitespace().collect::<Vec<&str>>()).unwrap_or_default();
+    if let [mode, id, ..] = trace.as_slice() {
+        if mode.eq_ignore_ascii_case("TRACE") && !nodes.contains(id) {
+            diagnostics.push(Diagnostic::error("OPTIONS", id, format!("Trace node {} is undefined", id)));
+        }
+    }
+    let node_references = inp.emitters.iter().map(|emitter| ("EMITTERS", &emitter.junction_id))
+        .chain(inp.demands.iter().map(|demand| ("DEMANDS", &demand.junction_id)))
+        .chain(inp.sources.iter().map(|source| ("SOURCES", &source.node)));
+    for (section, id) in node_references.filter(|(_, id)| !nodes.contains(id.as_str())) {
+        diagnostics.push(Diagnostic::error(section, id, format!("Undefined node {}", id)));
+    }
+    // EPANET skips the initial quality of undefined nodes.
+    for quality in inp.quality.iter().filter(|quality| !nodes.contains(quality.nodeid.as_str())) {
+        diagnostics.push(Diagnostic::warning("QUALITY", &quality.nodeid, format!("Initial quality of undefined node {} is ignored", quality.nodeid)));
+    }
+
+    for pipe in inp.pipes.iter() {
+        if pipe.length <= 0.0 || pipe.diameter <= 0.0 || pipe.roughness <= 0.0 {
+            diagnostics.push(Diagnostic::error("PIPES", &pipe.id, format!("Pipe {} needs a positive length, diameter and roughness", pipe.id)));
+        }
+    }
+    for tank in inp.tanks.iter() {
+        if tank.min_level > tank.max_level || tank.init_level < tank.min_level || tank.init_level > tank.max_level {
+            diagnostics.push(Diagnostic::error("TANKS", &tank.id, format!("Initial level of tank {} is not between its minimum and maximum levels", tank.id)));
+        }
+    }
+
+    // Supply only makes sense once the network itself is consistent.
+    if !diagnostics.iter().any(|diagnostic| diagnostic.severity == Severity::Error) {
+        for id in analyze_connectivity(inp, &ConnectivityOptions::default()).unsupplied_nodes {
+            diagnostics.push(Diagnostic::warning("JUNCTIONS", &id, format!("Node {} is not connected to any reservoir or tank", id)));
+        }
+    }
+
+    Validation { diagnostics }
+}
+
+#[cfg(test)]
+mod test {
+    use std::fs;
+    use super::{validate, Severity};
+    use crate::INP;
+
+    #[test]
+    fn validate_a_valid_model() {
+        let inp = INP::read(fs::read_to_string("tests/MagneticIslandEnhanced.inp").unwrap());
+
+        let validation = validate(&inp);
+
+        assert!(!validation.has_errors(), "{:?}", validation.errors().collect::<Vec<_>>());
+        assert_eq!(validation.warnings().next().map(|diagnostic| diagnostic.section.as_str()), Some("QUALITY"));
+    }
+
+    #[test]
+    fn report_references_and_values() {
+        let input = r#"
+[JUNCTIONS]
+J1  10  1  Missing
+J1  12
+J2  10
+[RESERVOIRS]
+R1  100
+[PIPES]
+P1  R1  J1  100  100  100
+P2  J1  J3  -5  100  100
+[STATUS]
+P9  CLOSED
+[EMITTERS]
+J1  not-a-number
+"#;
+        let validation = validate(&INP::read(input.to_string()));
+
+        let messages = validation.errors().map(|diagnostic| diagnostic.message.as_str()).collect::<Vec<&str>>();
+        assert_eq!(validation.diagnostics[0].line, Some(13));
+        assert_eq!(&messages[1..], [
+            "Duplicate node ID J1",
+            "Link P2 refers to undefined node J3",
+            "J1 refers to undefined pattern Missing",
+            "Undefined link P9",
+            "Pipe P2 needs a positive length, diameter and roughness",
+        ]);
+    }
+
+    #[test]
+    fn report_references_of_rules_and_settings() {
+        let input = "[JUNCTIONS]\nJ1 10\n[RESERVOIRS]\nR1 100\n[PIPES]\nP1 R1 J1 100 100 100\n[RULES]\nRULE 1\nIF TANK T1 LEVEL ABOVE 4\nTHEN PUMP PU1 STATUS IS OPEN\n[ENERGY]\nPump P1 Price 0.1\n[REACTIONS]\nBulk P1 -0.5\nTank T1 -1\n[OPTIONS]\nQuality Trace J9\n";
+
+        let validation = validate(&INP::read(input.to_string()));
+
+        assert_eq!(validation.errors().map(|diagnostic| diagnostic.message.as_str()).collect::<Vec<&str>>(), [
+            "Rule 1 refers to undefined node T1",
+            "Rule 1 refers to undefined link PU1",
+            "PUMP refers to undefined pump P1",
+            "TANK refers to undefined tank T1",
+            "Trace node J9 is undefined",
+        ]);
+    }
+
+    #[test]
+    fn warn_about_unsupplied_nodes() {
+        let input = "[JUNCTIONS]\nJ1 10\nJ2 10\n[RESERVOIRS]\nR1 100\n[PIPES]\nP1 J1 J2 100 100 100\n";
+
+        let validation = validate(&INP::read(input.to_string()));
+
+        assert!(!validation.has_errors());
+        assert_eq!(validation.warnings().map(|diagnostic| diagnostic.id.as_deref()).collect::<Vec<_>>(), vec![Some("J1"), Some("J2")]);
+        assert_eq!(validation.diagnostics[0].severity, Severity::Warning);
+    }
+}
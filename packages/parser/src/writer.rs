@@ -0,0 +1,343 @@
+use crate::sections::{Control, ControlAction, ControlCondition, MixingModel, Setting, ValveType};
+use crate::INP;
+
+/// Line of a section with the fields in columns, as EPANET writes them.
+fn row(fields: Vec<String>, comment: &Option<String>) -> String {
+    let mut row = String::from(" ");
+    for field in fields.iter() {
+        row.push_str(&format!("{:<16}\t", field));
+    }
+    match comment {
+        Some(comment) => format!("{};{}", row, comment),
+        None => row.trim_end().to_string(),
+    }
+}
+
+fn number<T: ToString>(value: T) -> String {
+    value.to_string()
+}
+
+fn action(action: &ControlAction) -> String {
+    match action {
+        ControlAction::Open => "OPEN".to_string(),
+        ControlAction::Closed => "CLOSED".to_string(),
+        ControlAction::Setting(setting) => number(setting),
+    }
+}
+
+fn clock(seconds: u64) -> String {
+    format!("{}:{:02}:{:02}", seconds / 3600, seconds / 60 % 60, seconds % 60)
+}
+
+/// Text of a control, e.g. `LINK P1 CLOSED AT TIME 6:00:00`.
+pub fn control_line(control: &Control) -> String {
+    let condition = match &control.condition {
+        ControlCondition::Above { node_id, value } => format!("IF NODE {} ABOVE {}", node_id, value),
+        ControlCondition::Below { node_id, value } => format!("IF NODE {} BELOW {}", node_id, value),
+        ControlCondition::Time(seconds) => format!("AT TIME {}", clock(*seconds)),
+        ControlCondition::ClockTime(seconds) => format!("AT CLOCKTIME {}", clock(*seconds)),
+    };
+    format!("LINK {} {} {}", control.link_id, action(&control.action), condition)
+}
+
+fn settings(settings: &[Setting]) -> Vec<String> {
+    settings.iter()
+        .map(|setting| row(vec![format!("{:<20}", setting.key), setting.value.clone()], &setting.comment))
+        .collect()
+}
+
+/// Column headers written at the top of a section when the model has no comment there.
+pub(crate) fn column_header(section: &str) -> Option<&'static str> {
+    Some(match section {
+        "JUNCTIONS" => "ID\tElevation\tDemand\tPattern",
+        "RESERVOIRS" => "ID\tHead\tPattern",
+        "TANKS" => "ID\tElevation\tInitLevel\tMinLevel\tMaxLevel\tDiameter\tMinVol\tVolCurve\tOverflow",
+        "PIPES" => "ID\tNode1\tNode2\tLength\tDiameter\tRoughness\tMinorLoss\tStatus",
+        "PUMPS" => "ID\tNode1\tNode2\tParameters",
+        "VALVES" => "ID\tNode1\tNode2\tDiameter\tType\tSetting\tMinorLoss",
+        "DEMANDS" => "Junction\tDemand\tPattern",
+        "STATUS" => "ID\tStatus/Setting",
+        "PATTERNS" => "ID\tMultipliers",
+        "CURVES" => "ID\tX-Value\tY-Value",
+        "EMITTERS" => "Junction\tCoefficient",
+        "QUALITY" => "Node\tInitQual",
+        "SOURCES" => "Node\tType\tQuality\tPattern",
+        "MIXING" => "Tank\tModel\tFraction",
+        "COORDINATES" => "Node\tX-Coord\tY-Coord",
+        "VERTICES" => "Link\tX-Coord\tY-Coord",
+        "LABELS" => "X-Coord\tY-Coord\tLabel & Anchor Node",
+        _ => return None,
+    })
+}
+
+/// Writes an INP back to the text of an EPANET input file. Reading the text gives the
+/// same INP, apart from the lines with errors which are not written.
+pub fn write_inp(inp: &INP) -> String {
+    let comments = |text: &mut String, name: &str, rows: std::ops::Range<usize>| {
+        for comment in inp.comments.iter().filter(|comment| comment.section == name && rows.contains(&comment.position)) {
+            text.push_str(&comment.text);
+            text.push('\n');
+        }
+    };
+    let mut text = String::new();
+    comments(&mut text, "", 0..usize::MAX);
+    let section = |text: &mut String, name: &str, rows: Vec<String>| {
+        if !text.is_empty() {
+            text.push('\n');
+        }
+        text.push_str(&format!("[{}]\n", name));
+        let commented = inp.comments.iter().any(|comment| comment.section == name && comment.position == 0);
+        if let (false, Some(header)) = (commented, column_header(name)) {
+            text.push_str(&format!(";{}\n", header));
+        }
+        let count = rows.len();
+        for (i, row) in rows.into_iter().enumerate() {
+            comments(text, name, i..i + 1);
+            text.push_str(&row);
+            text.push('\n');
+        }
+        comments(text, name, count..usize::MAX);
+    };
+
+    section(&mut text, "TITLE", if inp.title.is_empty() { vec![] } else { vec![inp.title.clone()] });
+
+    section(&mut text, "JUNCTIONS", inp.junctions.iter().map(|junction| {
+        let mut fields = vec![junction.id.clone(), number(junction.elevation)];
+        if let Some(demand) = junction.base_demand_flow {
+            fields.push(number(demand));
+            fields.extend(junction.demand_pattern_id.clone());
+        }
+        row(fields, &junction.comment)
+    }).collect());
+
+    section(&mut text, "RESERVOIRS", inp.reservoirs.iter().map(|reservoir| {
+        let mut fields = vec![reservoir.id.clone(), number(reservoir.head)];
+        fields.extend(reservoir.pattern.clone());
+        row(fields, &reservoir.comment)
+    }).collect());
+
+    section(&mut text, "TANKS", inp.tanks.iter().map(|tank| {
+        let mut fields = vec![
+            tank.id.clone(), number(tank.elevation), number(tank.init_level), number(tank.min_level),
+            number(tank.max_level), number(tank.diameter), number(tank.min_volume),
+        ];
+        match (&tank.volume_curve_id, tank.overflow) {
+            (Some(curve), overflow) => {
+                fields.push(curve.clone());
+                if overflow {
+                    fields.push("YES".to_string());
+                }
+            },
+            (None, true) => fields.extend(["*".to_string(), "YES".to_string()]),
+            (None, false) => {},
+        }
+        row(fields, &tank.comment)
+    }).collect());
+
+    section(&mut text, "PIPES", inp.pipes.iter().map(|pipe| {
+        row(vec![
+            pipe.id.clone(), pipe.node1.clone(), pipe.node2.clone(), number(pipe.length),
+            number(pipe.diameter), number(pipe.roughness), number(pipe.minor_loss), pipe.status.clone(),
+        ], &pipe.comment)
+    }).collect());
+
+    section(&mut text, "PUMPS", inp.pumps.iter().map(|pump| {
+        let mut fields = vec![pump.id.clone(), pump.start_node.clone(), pump.end_node.clone()];
+        if let Some(head) = &pump.head {
+            fields.extend(["HEAD".to_string(), head.clone()]);
+        }
+        if let Some(power) = pump.power {
+            fields.extend(["POWER".to_string(), number(power)]);
+        }
+        if let Some(speed) = pump.speed {
+            fields.extend(["SPEED".to_string(), number(speed)]);
+        }
+        if let Some(pattern) = &pump.pattern {
+            fields.extend(["PATTERN".to_string(), pattern.clone()]);
+        }
+        row(fields, &pump.comment)
+    }).collect());
+
+    section(&mut text, "VALVES", inp.valves.iter().map(|valve| {
+        let valve_type = match valve.valve_type {
+            ValveType::Prv => "PRV",
+            ValveType::Psv => "PSV",
+            ValveType::Pbv => "PBV",
+            ValveType::Fcv => "FCV",
+            ValveType::Tcv => "TCV",
+            ValveType::Gpv => "GPV",
+        };
+        row(vec![
+            valve.id.clone(), valve.start_node.clone(), valve.end_node.clone(), number(valve.diameter),
+            valve_type.to_string(), number(valve.valve_setting), number(valve.minor_loss_coefficient),
+        ], &valve.comment)
+    }).collect());
+
+    section(&mut text, "TAGS", inp.tags.iter().map(|tag| {
+        row(vec![tag.object_type.clone(), tag.object_id.clone(), tag.tag.clone()], &tag.comment)
+    }).collect());
+
+    section(&mut text, "DEMANDS", inp.demands.iter().map(|demand| {
+        let mut fields = vec![demand.junction_id.clone(), number(demand.base_demand)];
+        fields.extend(demand.pattern_id.clone());
+        row(fields, &demand.comment)
+    }).collect());
+
+    section(&mut text, "STATUS", inp.statuses.iter().map(|status| {
+        row(vec![status.link_id.clone(), action(&status.status)], &status.comment)
+    }).collect());
+
+    section(&mut text, "PATTERNS", inp.patterns.iter().map(|pattern| {
+        let mut fields = vec![pattern.id.clone()];
+        fields.extend(pattern.multipliers.iter().map(number));
+        row(fields, &pattern.comment)
+    }).collect());
+
+    section(&mut text, "CURVES", inp.curves.iter().map(|curve| {
+        row(vec![curve.id.clone(), number(curve.x), number(curve.y)], &curve.comment)
+    }).collect());
+
+    section(&mut text, "CONTROLS", inp.controls.iter().map(|control| {
+        let line = format!(" {}", control_line(control));
+        match &control.comment {
+            Some(comment) => format!("{}\t;{}", line, comment),
+            None => line,
+        }
+    }).collect());
+
+    section(&mut text, "RULES", inp.rules.iter().map(|rule| {
+        let line = format!("{} {}", rule.keyword, rule.words.join(" "));
+        match &rule.comment {
+            Some(comment) => format!("{}\t;{}", line, comment),
+            None => line,
+        }
+    }).collect());
+
+    section(&mut text, "ENERGY", settings(&inp.energy));
+
+    section(&mut text, "EMITTERS", inp.emitters.iter().map(|emitter| {
+        row(vec![emitter.junction_id.clone(), number(emitter.flow_coefficient)], &emitter.comment)
+    }).collect());
+
+    section(&mut text, "QUALITY", inp.quality.iter().map(|quality| {
+        row(vec![quality.nodeid.clone(), number(quality.initqual)], &quality.comment)
+    }).collect());
+
+    section(&mut text, "SOURCES", inp.sources.iter().map(|source| {
+        let mut fields = vec![source.node.clone(), source.source_type.clone(), number(source.strength)];
+        fields.extend(source.pattern.clone());
+        row(fields, &source.comment)
+    }).collect());
+
+    section(&mut text, "REACTIONS", settings(&inp.reactions));
+
+    section(&mut text, "MIXING", inp.mixing.iter().map(|mixing| {
+        let model = match mixing.model {
+            MixingModel::Mixed => "MIXED",
+            MixingModel::TwoCompartment => "2COMP",
+            MixingModel::Fifo => "FIFO",
+            MixingModel::Lifo => "LIFO",
+        };
+        row(vec![mixing.tank_id.clone(), model.to_string(), number(mixing.fraction)], &mixing.comment)
+    }).collect());
+
+    section(&mut text, "TIMES", settings(&inp.times));
+
+    section(&mut text, "OPTIONS", settings(&inp.options));
+
+    section(&mut text, "COORDINATES", inp.coordinates.iter().map(|coordinate| {
+        row(vec![coordinate.node_id.clone(), number(coordinate.x), number(coordinate.y)], &coordinate.comment)
+    }).collect());
+
+    section(&mut text, "VERTICES", inp.vertices.iter().map(|vertex| {
+        row(vec![vertex.link_id.clone(), number(vertex.x), number(vertex.y)], &vertex.comment)
+    }).collect());
+
+    section(&mut text, "LABELS", inp.labels.iter().map(|label| {
+        let mut fields = vec![number(label.x), number(label.y), format!("\"{}\"", label.text)];
+        fields.extend(label.anchor_node_id.clone());
+        row(fields, &label.comment)
+    }).collect());
+
+    section(&mut text, "BACKDROP", inp.backdrop.iter().map(|backdrop| {
+        let mut fields = vec![backdrop.key.clone()];
+        fields.extend(backdrop.values.iter().cloned());
+        row(fields, &backdrop.comment)
+    }).collect());
+
+    // Sections the parser doesn't know, each line after the header of its section.
+    if !inp.unknown_sections.is_empty() {
+        text.push('\n');
+        for unknown in inp.unknown_sections.iter() {
+            text.push_str(&unknown.text);
+            text.push('\n');
+        }
+    }
+
+    text.push_str("\n[END]\n");
+    comments(&mut text, "END", 0..usize::MAX);
+    text
+}
+
+#[cfg(test)]
+mod test {
+    use std::fs;
+    use super::write_inp;
+    use crate::INP;
+
+    #[test]
+    fn write_what_is_read() {
+        let inp = INP::read(fs::read_to_string("tests/MagneticIslandEnhanced.inp").unwrap());
+
+        let text = write_inp(&inp);
+
+        assert!(inp.errors.is_empty());
+        assert_eq!(INP::read(text.clone()), inp);
+        assert_eq!(write_inp(&INP::read(text.clone())), text);
+        assert!(text.contains("\n[REPORT]\n"));
+    }
+
+    #[test]
+    fn write_sections() {
+        let input = r#"
+[TITLE]
+Two tanks
+
+[TANKS]
+T1 10 1 0 5 20 0 VolCurve YES ;Main
+
+[CONTROLS]
+LINK P1 CLOSED AT CLOCKTIME 6:30 PM
+
+[LABELS]
+1 2 "Water Tower" T1
+"#;
+        let inp = INP::read(input.to_string());
+
+        let text = write_inp(&inp);
+
+        assert!(text.starts_with("[TITLE]\nTwo tanks\n\n[JUNCTIONS]\n"));
+        assert!(text.contains(" T1              \t10              \t1               \t0               \t5               \t20              \t0               \tVolCurve        \tYES             \t;Main\n"));
+        assert!(text.contains(" LINK P1 CLOSED AT CLOCKTIME 18:30:00\n"));
+        assert!(text.contains(" 1               \t2               \t\"Water Tower\"   \tT1\n"));
+        assert!(text.ends_with("\n[END]\n"));
+        assert_eq!(INP::read(text), inp);
+    }
+
+    #[test]
+    fn keep_comments() {
+        let input = "; Exported for the 2024 review\n[TITLE]\nTwo pipes\n[JUNCTIONS]\n;Junction  Elevation\nJ1 10\n; Raised in 2019\nJ2 12\n; Add J3 after the works\n[RESERVOIRS]\nR1 100\n[PIPES]\nP1 R1 J1 100 100 100\nP2 J1 J2 100 100 100\n[OPTIONS]\n; Litres per second\nUNITS LPS\n[END]\n; Nothing is read after the end\n";
+        let inp = INP::read(input.to_string());
+
+        let text = write_inp(&inp);
+
+        assert!(text.starts_with("; Exported for the 2024 review\n\n[TITLE]\n"));
+        assert!(text.contains("[JUNCTIONS]\n;Junction  Elevation\n J1 "));
+        assert!(text.contains("\n; Raised in 2019\n J2 "));
+        assert!(text.contains("\n; Add J3 after the works\n\n[RESERVOIRS]\n;ID\tHead\tPattern\n"));
+        assert!(text.contains("[OPTIONS]\n; Litres per second\n UNITS "));
+        assert!(text.ends_with("\n[END]\n; Nothing is read after the end\n"));
+        assert_eq!(write_inp(&INP::read(text.clone())), text);
+        assert_eq!(INP::read(text), inp);
+    }
+}